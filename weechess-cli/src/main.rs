@@ -1,5 +1,9 @@
+// The `ArrayMap` bounds on core's types need this in every crate that uses them
+#![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
+
 use std::{
-    io::stdin,
+    io::{stdin, Write},
     sync::mpsc::{self},
     thread,
 };
@@ -7,21 +11,144 @@ use std::{
 use anyhow::Context;
 use clap::{Parser, Subcommand};
 use colored::Colorize;
+use rand::{seq::SliceRandom, SeedableRng};
 use weechess_core::{
-    notation::{into_notation, try_from_notation, Fen, Peg},
-    State,
+    notation::{into_notation, lan::Lan, try_from_notation, Fen, MoveNotation, Peg, San},
+    pawns::{self, PawnStructure},
+    pgn::Pgn,
+    reference, BitBoard, Color, DiagramParseError, GameRecordReader, GameRecordWriter, GameStatus,
+    MoveGenerator, MoveQuery, MoveResult, State,
+};
+use weechess_engine::{
+    adjudication::AdjudicationPolicy,
+    book::{BookLearning, OpeningBook},
+    cache::AnalysisCache,
+    config::EngineConfig,
+    crash,
+    database::GameDatabase,
+    eval,
+    mate::{MateOutcome, MateSearch},
+    profiles::{self, BookProfiles, EcoRange, ImportFilter},
+    searcher, uci,
+    version::EngineVersion,
 };
-use weechess_engine::{eval, searcher, uci, version::EngineVersion};
+
+/// The file crash reports are appended to if the engine panics, kept in the user's
+/// data directory (or the working directory if there isn't one)
+const CRASH_FILE: &str = "weechess-crash.log";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// The notation to write moves in, instead of the one each command picks
+    #[arg(long, global = true, value_parser = ["san", "lan", "uci", "peg"])]
+    notation: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Analyze a game from a PGN file and write it back out with evaluations and annotations
+    Analyze {
+        /// The PGN file containing the game
+        file: String,
+
+        /// Maximum depth to search each position to
+        #[arg(short, long, default_value = "4")]
+        max_depth: usize,
+
+        /// File to write the annotated game to, instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Read commands from stdin, one per line, and write one line with the result of each to stdout.
+    /// The commands are `eval <fen>`, `perft <fen> <depth>` and `bestmove <fen> <milliseconds>`.
+    Batch {
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Create, fill and remove named opening book profiles, kept in the user's data directory
+    BookProfile {
+        #[command(subcommand)]
+        action: BookProfileAction,
+    },
+    /// Measure how well searches scale with more threads, and recommend a thread count for this machine
+    Calibrate {
+        /// Thread counts to try, defaults to powers of two up to the default maximum
+        #[arg(long, value_delimiter = ',')]
+        threads: Vec<usize>,
+
+        /// Time to search each position with each thread count, in milliseconds
+        #[arg(short, long, default_value = "3000")]
+        time: u64,
+
+        /// Config file to write the recommended thread count to
+        #[arg(long)]
+        config: Option<String>,
+
+        /// Transposition table size for each search, in MB
+        #[arg(long)]
+        hash: Option<usize>,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Search a position with two engine configurations and compare the results
+    Compare {
+        /// Starting position in FEN notation
+        #[arg(short, long)]
+        fen: Option<String>,
+
+        /// The first configuration, as comma separated `key=value` pairs (ex. `handicap=20,seed=1`)
+        #[arg(long, default_value = "")]
+        config_a: compare::Config,
+
+        /// The second configuration, in the same format as the first
+        #[arg(long, default_value = "")]
+        config_b: compare::Config,
+
+        /// Maximum depth to search to
+        #[arg(short, long)]
+        max_depth: Option<usize>,
+
+        /// Time to give each configuration, in milliseconds
+        #[arg(short, long, default_value = "5000")]
+        time: u64,
+    },
+    /// Index the games in a PGN database, and show the moves played from a position and the games reaching it.
+    /// The index is saved next to the games and reused until the games change.
+    Db {
+        /// The PGN file with the games
+        file: String,
+
+        /// File to save the index to, defaults to the PGN file with `.index` added
+        #[arg(long)]
+        index: Option<String>,
+
+        /// Index the games again even if a saved index is up to date
+        #[arg(long)]
+        reindex: bool,
+
+        /// Position to look up in FEN notation, defaults to the starting position
+        #[arg(short, long)]
+        fen: Option<String>,
+
+        /// Only index this many plies of each game, which is all that opening statistics need
+        #[arg(long)]
+        max_plies: Option<usize>,
+
+        /// Most games reaching the position to list
+        #[arg(short, long, default_value = "10")]
+        games: usize,
+    },
     /// Print out the board in a human-readable format
     Display {
         /// Starting position in FEN notation
@@ -41,6 +168,133 @@ enum Commands {
         /// Random number seed to use
         #[arg(long)]
         seed: Option<u64>,
+
+        /// Percentage by which the engine undervalues the pieces of the side to move
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        handicap: Option<u8>,
+
+        /// Let the transposition tables grow up to this many megabytes as they fill up
+        #[arg(long)]
+        max_table_size: Option<usize>,
+
+        /// How often to report progress within a depth, in milliseconds
+        #[arg(long)]
+        progress_interval: Option<u64>,
+
+        /// Also report progress within a depth every time this many more nodes are searched
+        #[arg(long)]
+        progress_nodes: Option<usize>,
+
+        /// Also show this many of the next best lines at every depth
+        #[arg(long, default_value_t = 0)]
+        alternatives: usize,
+
+        /// When to search promotions to rooks and bishops
+        #[arg(long, value_parser = ["all", "deferred"])]
+        underpromotions: Option<String>,
+
+        /// Periodically save the search tables and progress to this file, so that
+        /// a long evaluation can be picked back up with `--resume`
+        #[arg(long)]
+        checkpoint: Option<String>,
+
+        /// How often to save a checkpoint, in seconds
+        #[arg(long, default_value_t = 60, requires = "checkpoint")]
+        checkpoint_interval: u64,
+
+        /// Continue the evaluation saved in a checkpoint file, instead of starting a new one
+        #[arg(long, conflicts_with_all = ["fen", "max_depth", "seed"])]
+        resume: Option<String>,
+    },
+    /// Read positions in FEN notation from stdin and print the ones matching the given filters
+    Filter {
+        /// Only include positions with this material signature (ex. `KRPkr`)
+        #[arg(short, long)]
+        signature: Option<String>,
+
+        /// Only include positions in this phase of the game
+        #[arg(short, long, value_parser = ["opening", "middlegame", "endgame"])]
+        phase: Option<String>,
+
+        /// Leave out positions that were already printed, counting color-flipped positions as the same
+        #[arg(short, long)]
+        unique: bool,
+    },
+    /// Learn book move weights from the results of the games in a PGN file, like `uci --learn`
+    /// does from the results a GUI reports. Only the moves each game played from the book count.
+    Learn {
+        /// The PGN file with the games
+        file: String,
+
+        /// File with the learned weights to update, as given to `uci --learn`
+        #[arg(long, required_unless_present = "book_profile")]
+        weights: Option<String>,
+
+        /// Update the learned weights of this book profile instead, using its book
+        #[arg(long, conflicts_with = "weights")]
+        book_profile: Option<String>,
+
+        /// Additional opening book files (`path[:depth]`), in priority order
+        #[arg(long = "book", value_parser = common::parse_book_source)]
+        books: Vec<(String, usize)>,
+
+        /// Don't fall back to the built-in opening book
+        #[arg(long)]
+        no_default_book: bool,
+    },
+    /// Play games between two engine configurations, giving the first one time or node odds
+    Match {
+        /// The first configuration, as comma separated `key=value` pairs (ex. `handicap=20,seed=1`)
+        #[arg(long, default_value = "")]
+        config_a: compare::Config,
+
+        /// The second configuration, in the same format as the first
+        #[arg(long, default_value = "")]
+        config_b: compare::Config,
+
+        /// Odds to give the first configuration, each played as its own match (ex. `time=2,nodes=50000`). Use
+        /// `time=N` for N times the second one's time, and `nodes=N` or `nodes=N/M` for fixed node budgets.
+        #[arg(long, value_delimiter = ',', default_value = "time=1")]
+        odds: Vec<match_play::Odds>,
+
+        /// Number of games to play for each of the odds, in pairs with the colors swapped
+        #[arg(short, long, default_value = "10")]
+        games: usize,
+
+        /// Time the second configuration gets for each move, in milliseconds
+        #[arg(short, long, default_value = "100")]
+        time: u64,
+
+        /// Number of random moves played from the starting position to vary the openings
+        #[arg(long, default_value = "4")]
+        opening_plies: usize,
+
+        /// When to end games early (ex. `resign=600/4,draw=10/8,max-moves=150`)
+        #[arg(long, default_value = "resign=1000/3,max-moves=150")]
+        adjudication: AdjudicationPolicy,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Write every game with the score of each move to this file, in the compact binary
+        /// format that `records` reads
+        #[arg(long)]
+        record: Option<String>,
+    },
+    /// Find out whether the side to move can force a mate, with a proof-number search
+    Mate {
+        /// Starting position in FEN notation
+        #[arg(short, long)]
+        fen: Option<String>,
+
+        /// Only look for mates within this many plies (a mate in 2 takes 3)
+        #[arg(long, default_value = "5")]
+        max_ply: usize,
+
+        /// Give up after searching this many nodes
+        #[arg(long, default_value_t = MateSearch::DEFAULT_MAX_NODES)]
+        max_nodes: usize,
     },
     /// Walk the move generation tree of strictly legal moves to count all the leaf nodes of a certain depth
     Perft {
@@ -52,39 +306,387 @@ enum Commands {
         #[arg(short, long, default_value = "6")]
         depth: usize,
     },
+    /// Find tactical puzzles in games, where a blunder leaves exactly one winning move, and
+    /// write out one per line as the FEN of the position and the solution in long algebraic notation
+    Puzzles {
+        /// The PGN file with the games to look for puzzles in
+        #[arg(long)]
+        pgn: String,
+
+        /// Maximum depth to search each position of the games to
+        #[arg(short, long, default_value = "4")]
+        max_depth: usize,
+
+        /// Depth to verify puzzles to, which defaults to two more than the maximum depth
+        #[arg(long)]
+        verify_depth: Option<usize>,
+
+        /// File to write the puzzles to, instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Print the games in a file of binary game records (see `match --record`) as PGN
+    Records {
+        /// The file of game records
+        file: String,
+
+        /// Only print this many games
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
     /// Start an interactive REPL session with the engine
     Repl {
         /// Starting position in FEN notation
         #[arg(short, long)]
         fen: Option<String>,
     },
+    /// Search every position in a file of FEN lines, writing out the best move and score of each as CSV
+    Score {
+        /// File with one position in FEN notation per line
+        #[arg(short, long)]
+        input: String,
+
+        /// File to write the results to, defaults to stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Depth to search each position to
+        #[arg(short, long, default_value = "8")]
+        depth: usize,
+
+        /// Most time to spend on each position, in milliseconds
+        #[arg(short, long)]
+        time: Option<u64>,
+
+        /// Number of positions to search at the same time, defaults to the number of CPUs
+        #[arg(short, long)]
+        workers: Option<usize>,
+
+        /// Transposition table size in MB, split evenly between the workers
+        #[arg(long, default_value_t = searcher::DEFAULT_TRANSPOSITION_TABLE_SIZE_MB)]
+        hash: usize,
+
+        /// Random number seed to use for every search
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Serve information about positions as JSON over HTTP, for GUIs to draw from. `GET /pressure?fen=<fen>`
+    /// returns the number of white and black pieces attacking each square, from a1 to h8.
+    Serve {
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    /// Tune the search's pruning parameters with SPSA, playing games between slightly different settings
+    Spsa {
+        /// Comma separated names of the parameters to tune, defaults to all of them
+        #[arg(short, long, value_delimiter = ',')]
+        params: Vec<String>,
+
+        /// Number of iterations to run, each playing a pair of games
+        #[arg(short, long, default_value = "100")]
+        iterations: usize,
+
+        /// Depth to search each move to
+        #[arg(short, long, default_value = "4")]
+        depth: usize,
+
+        /// Number of random moves played from the starting position to vary the openings
+        #[arg(long, default_value = "4")]
+        opening_plies: usize,
+
+        /// When to end games early (ex. `resign=600/4,draw=10/8,max-moves=150`)
+        #[arg(long, default_value = "resign=1000/3,max-moves=150")]
+        adjudication: AdjudicationPolicy,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Print a report of the pawn structure of a position
+    Structure {
+        /// Position to report on in FEN notation
+        #[arg(short, long)]
+        fen: Option<String>,
+    },
     /// Start a UCI client
-    Uci,
+    Uci {
+        /// Additional opening book files (`path[:depth]`), in priority order
+        #[arg(long = "book", value_parser = common::parse_book_source)]
+        books: Vec<(String, usize)>,
+
+        /// Don't fall back to the built-in opening book
+        #[arg(long)]
+        no_default_book: bool,
+
+        /// Percentage by which the engine undervalues its own pieces
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        handicap: Option<u8>,
+
+        /// Learn book move weights from the game results reported with the `result` command
+        #[arg(long)]
+        learn: Option<String>,
+
+        /// Use the learned book move weights without updating them
+        #[arg(long, requires = "learn")]
+        freeze_book: bool,
+
+        /// Remember the results of deep searches in this file, and reuse them in later sessions
+        #[arg(long)]
+        analysis_cache: Option<String>,
+
+        /// Only remember searches that reach at least this depth
+        #[arg(long, requires = "analysis_cache", default_value_t = AnalysisCache::DEFAULT_MIN_DEPTH)]
+        cache_depth: usize,
+
+        /// Config file with settings found by `calibrate`
+        #[arg(long)]
+        config: Option<String>,
+
+        /// Book profile to start with, which can be changed with the BookProfile option
+        #[arg(long)]
+        book_profile: Option<String>,
+    },
+    /// Compare the move generator against a slow reference implementation using random games
+    VerifyMovegen {
+        /// Number of random games to play out
+        #[arg(short, long, default_value = "1000")]
+        games: usize,
+
+        /// Maximum number of plies to play in each game
+        #[arg(short, long, default_value = "200")]
+        plies: usize,
+
+        /// Random number seed to use
+        #[arg(long)]
+        seed: Option<u64>,
+    },
     /// Print out the version of the engine
-    Version,
+    Version {
+        /// Also print out how and from what the engine was built
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BookProfileAction {
+    /// List the profiles
+    List,
+    /// Create an empty profile
+    Create { name: String },
+    /// Add the games from a PGN file to a profile's book
+    Import {
+        name: String,
+
+        /// The PGN file with the games
+        file: String,
+
+        /// Only import games in these ECO ranges (ex. `B20-B99`, `C42` or `E`)
+        #[arg(long, value_delimiter = ',')]
+        eco: Vec<EcoRange>,
+
+        /// Only import games played by one of these players, as either color
+        #[arg(long)]
+        player: Vec<String>,
+    },
+    /// Delete a profile, along with its games and learned weights
+    Remove { name: String },
 }
 
 fn run() -> Result<(), anyhow::Error> {
     let cli = Cli::parse();
 
+    crash::install_panic_hook(
+        profiles::user_data_dir()
+            .map(|dir| dir.join(CRASH_FILE))
+            .unwrap_or_else(|| CRASH_FILE.into()),
+    );
+
+    let notation = match &cli.notation {
+        Some(notation) => Some(
+            notation
+                .parse::<MoveNotation>()
+                .map_err(|_| anyhow::anyhow!("Invalid notation: {}", notation))?,
+        ),
+        None => None,
+    };
+
     match cli.command {
-        Some(Commands::Display { fen }) => {
-            let game_state = {
-                if let Some(fen) = &fen {
-                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
-                } else {
-                    State::default()
+        Some(Commands::Analyze {
+            file,
+            max_depth,
+            output,
+            seed,
+        }) => {
+            let contents = std::fs::read_to_string(&file)
+                .with_context(|| format!("while reading {}", file))?;
+
+            let mut pgn =
+                Pgn::parse(&contents).with_context(|| format!("while parsing {}", file))?;
+            let rng_seed = seed.unwrap_or_else(rand::random);
+
+            // Ctrl+C stops the analysis, and the moves analyzed up to then are still written
+            let ctrl_c =
+                interrupt::CtrlC::install().context("while installing the Ctrl+C handler")?;
+
+            let (states, evaluations) =
+                analysis::evaluate_game(&pgn, max_depth, rng_seed, Some(&ctrl_c), |i, count| {
+                    eprintln!("Analyzing position {}/{}...", i + 1, count);
+                })?;
+
+            if ctrl_c.is_interrupted() {
+                eprintln!(
+                    "Interrupted, annotating the first {} moves",
+                    evaluations.len().saturating_sub(1)
+                );
+            }
+
+            analysis::annotate(&mut pgn, &states, &evaluations);
+
+            // Keep whatever the game was already classified as
+            if let Some(opening) = pgn.opening() {
+                for (name, value) in [("ECO", &opening.eco), ("Opening", &opening.name)] {
+                    if !pgn.tags.iter().any(|(n, _)| n == name) {
+                        pgn.tags.push((name.to_string(), value.clone()));
+                    }
+                }
+            }
+
+            pgn.tags.retain(|(name, _)| name != "Annotator");
+            pgn.tags.push((
+                String::from("Annotator"),
+                EngineVersion::CURRENT.to_string(),
+            ));
+
+            match output {
+                Some(output) => std::fs::write(&output, pgn.to_string())
+                    .with_context(|| format!("while writing {}", output))?,
+                None => print!("{}", pgn),
+            }
+
+            if ctrl_c.is_interrupted() {
+                std::io::stdout().flush()?;
+                std::process::exit(interrupt::EXIT_CODE);
+            }
+
+            Ok(())
+        }
+        Some(Commands::Batch { seed }) => {
+            let mut batch = batch::Batch::new(seed.unwrap_or_else(rand::random));
+            batch.run(std::io::stdin().lock(), std::io::stdout().lock())?;
+            Ok(())
+        }
+        Some(Commands::BookProfile { action }) => {
+            let profiles = BookProfiles::in_user_data_dir()
+                .context("No user data directory to keep book profiles in")?;
+
+            match action {
+                BookProfileAction::List => {
+                    for name in profiles.names()? {
+                        println!("{}", name);
+                    }
+                }
+                BookProfileAction::Create { name } => {
+                    profiles.create(&name)?;
+                    eprintln!("Created profile {} in {}", name, profiles.dir().display());
+                }
+                BookProfileAction::Import {
+                    name,
+                    file,
+                    eco,
+                    player,
+                } => {
+                    let profile = profiles.get(&name)?;
+                    let contents = std::fs::read_to_string(&file)
+                        .with_context(|| format!("while reading {}", file))?;
+
+                    let filter = ImportFilter {
+                        eco,
+                        players: player,
+                    };
+
+                    let imported = profile.import(&contents, &filter)?;
+                    eprintln!("Imported {} games into profile {}", imported, name);
+                }
+                BookProfileAction::Remove { name } => {
+                    profiles.remove(&name)?;
+                    eprintln!("Removed profile {}", name);
                 }
+            }
+
+            Ok(())
+        }
+        Some(Commands::Calibrate {
+            threads,
+            time,
+            config,
+            hash,
+            seed,
+        }) => {
+            let threads = if threads.is_empty() {
+                std::iter::successors(Some(1), |n| Some(n * 2))
+                    .take_while(|n| *n <= searcher::DEFAULT_MAX_THREAD_COUNT)
+                    .collect()
+            } else {
+                threads
             };
 
-            println!("{}", game_state.pretty());
+            if threads.contains(&0) {
+                anyhow::bail!("thread counts must be at least 1");
+            }
+
+            if hash == Some(0) {
+                anyhow::bail!("the hash size must be at least 1 MB");
+            }
+
+            let budget = std::time::Duration::from_millis(time);
+            let seed = seed.unwrap_or_else(rand::random);
+
+            println!("{:>8} {:>8} {:>12}", "Threads", "Depth", "NPS");
+
+            let mut results = vec![];
+            for count in threads {
+                let result = calibrate::measure(count, budget, hash, seed);
+                println!(
+                    "{:>8} {:>8.2} {:>12.0}",
+                    count, result.mean_depth, result.nodes_per_second
+                );
+
+                results.push(result);
+            }
+
+            let best = calibrate::recommend(&results).context("no thread counts were tried")?;
+            println!();
+            println!("Recommended thread count: {}", best.threads);
+
+            match config {
+                Some(path) => {
+                    let mut engine_config = EngineConfig::load(&path)
+                        .with_context(|| format!("while loading config {}", path))?;
+
+                    engine_config.set(EngineConfig::THREADS, best.threads);
+                    engine_config
+                        .save(&path)
+                        .with_context(|| format!("while writing config {}", path))?;
+
+                    println!("Wrote it to {}", path);
+                }
+                None => println!("Pass --config to save it for the UCI client"),
+            }
 
             Ok(())
         }
-        Some(Commands::Evaluate {
+        Some(Commands::Compare {
             fen,
+            config_a,
+            config_b,
             max_depth,
-            seed,
+            time,
         }) => {
             let game_state = {
                 if let Some(fen) = &fen {
@@ -94,209 +696,3628 @@ fn run() -> Result<(), anyhow::Error> {
                 }
             };
 
-            let rng_seed = seed.unwrap_or_else(rand::random);
+            let budget = std::time::Duration::from_millis(time);
+            let mut results = vec![];
+            for (name, config) in [("A", &config_a), ("B", &config_b)] {
+                eprintln!("Searching with configuration {}...", name);
+                results.push((
+                    name,
+                    compare::run(&game_state, config, max_depth, Some(budget)),
+                ));
+            }
+
+            println!(
+                "{:<8} {:>6} {:>12} {:>10} {:>8}",
+                "Config", "Depth", "Nodes", "Best Move", "Score"
+            );
+
+            for (name, result) in results.iter() {
+                println!(
+                    "{:<8} {:>6} {:>12} {:>10} {:>8}",
+                    name,
+                    result.depth,
+                    result.nodes_searched,
+                    result
+                        .best_move
+                        .map(|m| notation.unwrap_or(MoveNotation::Peg).write(&game_state, &m))
+                        .unwrap_or_else(|| String::from("-")),
+                    result
+                        .evaluation
+                        .map(|e| e.to_string())
+                        .unwrap_or_else(|| String::from("-")),
+                );
+            }
+
+            Ok(())
+        }
+        Some(Commands::Db {
+            file,
+            index,
+            reindex,
+            fen,
+            max_plies,
+            games,
+        }) => {
+            let game_state = match &fen {
+                Some(fen) => {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                }
+                None => State::default(),
+            };
+
+            let index = index.unwrap_or_else(|| format!("{}.index", file));
+            let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            let is_fresh = matches!(
+                (modified(&index), modified(&file)),
+                (Some(index), Some(file)) if index >= file
+            );
+
+            let saved = match is_fresh && !reindex {
+                true => match GameDatabase::load(&index) {
+                    Ok(database) if database.max_plies() == max_plies => Some(database),
+                    Ok(_) => None,
+                    Err(e) => {
+                        eprintln!("{} Ignoring {}: {}", "[Warning]".yellow(), index, e);
+                        None
+                    }
+                },
+                false => None,
+            };
+
+            let database = match saved {
+                Some(database) => {
+                    eprintln!(
+                        "Loaded the index of {} games ({} positions) from {}",
+                        database.games().len(),
+                        database.position_count(),
+                        index
+                    );
+
+                    database
+                }
+                None => {
+                    let contents = std::fs::read_to_string(&file)
+                        .with_context(|| format!("while reading {}", file))?;
+
+                    let start_time = std::time::Instant::now();
+                    let mut database = GameDatabase::new(max_plies);
+                    let mut skipped = 0;
+                    for (i, pgn) in Pgn::parse_all(&contents).enumerate() {
+                        let result = pgn
+                            .map_err(anyhow::Error::from)
+                            .and_then(|pgn| Ok(database.add_game(&pgn)?));
+
+                        if let Err(e) = result {
+                            eprintln!("{} Skipping game {}: {}", "[Warning]".yellow(), i + 1, e);
+                            skipped += 1;
+                        }
+                    }
+
+                    eprintln!(
+                        "Indexed {} games ({} positions, {} skipped) in {:.2}s",
+                        database.games().len(),
+                        database.position_count(),
+                        skipped,
+                        start_time.elapsed().as_secs_f64()
+                    );
+
+                    // The index only saves time, so the lookup goes ahead without it
+                    if let Err(e) = database.save(&index) {
+                        eprintln!("{} Unable to save {}: {}", "[Warning]".yellow(), index, e);
+                    }
+
+                    database
+                }
+            };
+
+            let occurrences = database.lookup(&game_state);
+            println!("{}", game_state.pretty());
+            println!("Reached in {} games", occurrences.len());
+
+            let stats = database.move_stats(&game_state);
+            if !stats.is_empty() {
+                println!();
+                println!(
+                    "{:<8} {:>6} {:>6} {:>6} {:>6} {:>7}",
+                    "Move", "Games", "White", "Draw", "Black", "Score"
+                );
+
+                for s in stats {
+                    let score = s
+                        .score(game_state.turn_to_move())
+                        .map(|score| format!("{:.1}%", score * 100.0))
+                        .unwrap_or_else(|| String::from("-"));
+
+                    println!(
+                        "{:<8} {:>6} {:>6} {:>6} {:>6} {:>7}",
+                        notation
+                            .unwrap_or(MoveNotation::San)
+                            .write(&game_state, &s.mv),
+                        s.games,
+                        s.white_wins,
+                        s.draws,
+                        s.black_wins,
+                        score
+                    );
+                }
+            }
+
+            if games > 0 && !occurrences.is_empty() {
+                println!();
+                for occurrence in occurrences.iter().take(games) {
+                    let game = &database.games()[occurrence.game];
+                    let result = match game.result {
+                        Some(GameStatus::Won(Color::White)) => "1-0",
+                        Some(GameStatus::Won(Color::Black)) => "0-1",
+                        Some(GameStatus::Drawn) => "1/2-1/2",
+                        Some(GameStatus::Ongoing) | None => "*",
+                    };
+
+                    println!(
+                        "{} - {} ({}), {}, at ply {} of {}",
+                        game.tag("White").unwrap_or("?"),
+                        game.tag("Black").unwrap_or("?"),
+                        game.tag("Event").unwrap_or("?"),
+                        result,
+                        occurrence.ply,
+                        game.length
+                    );
+                }
+
+                if occurrences.len() > games {
+                    println!("... and {} more", occurrences.len() - games);
+                }
+            }
+
+            Ok(())
+        }
+        Some(Commands::Display { fen }) => {
+            let game_state = {
+                if let Some(fen) = &fen {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                } else {
+                    State::default()
+                }
+            };
+
+            println!("{}", game_state.pretty());
+            println!(
+                "Phase: {}, Material: {}",
+                game_state.phase(),
+                game_state.material_signature()
+            );
+
+            Ok(())
+        }
+        Some(Commands::Filter {
+            signature,
+            phase,
+            unique,
+        }) => {
+            let mut seen = std::collections::HashSet::new();
+            for line in stdin().lines() {
+                let line = line?;
+                let fen = line.trim();
+                if fen.is_empty() {
+                    continue;
+                }
+
+                let Ok(game_state) = try_from_notation::<_, Fen>(fen) else {
+                    eprintln!("{} Invalid fen: {}", "[Error]".red(), fen);
+                    continue;
+                };
+
+                if let Some(signature) = &signature {
+                    if game_state.material_signature() != *signature {
+                        continue;
+                    }
+                }
+
+                if let Some(phase) = &phase {
+                    if game_state.phase().to_string() != *phase {
+                        continue;
+                    }
+                }
+
+                if unique && !seen.insert(game_state.canonical_key()) {
+                    continue;
+                }
+
+                println!("{}", fen);
+            }
+
+            Ok(())
+        }
+        Some(Commands::Evaluate {
+            fen,
+            max_depth,
+            seed,
+            handicap,
+            max_table_size,
+            progress_interval,
+            progress_nodes,
+            alternatives,
+            underpromotions,
+            checkpoint: checkpoint_file,
+            checkpoint_interval,
+            resume,
+        }) => {
+            let mut checkpoint = match &resume {
+                Some(path) => checkpoint::Checkpoint::open(path)
+                    .with_context(|| format!("while opening {}", path))?,
+                None => {
+                    let game_state = match &fen {
+                        Some(fen) => try_from_notation::<_, Fen>(fen)
+                            .map_err(|_| anyhow::anyhow!("Invalid fen"))?,
+                        None => State::default(),
+                    };
+
+                    checkpoint::Checkpoint::new(
+                        &game_state,
+                        seed.unwrap_or_else(rand::random),
+                        max_depth,
+                    )
+                }
+            };
+
+            let game_state = try_from_notation::<_, Fen>(&checkpoint.fen)
+                .map_err(|_| anyhow::anyhow!("Invalid fen: {}", checkpoint.fen))?;
+
+            if resume.is_some() {
+                println!(
+                    "Resuming from depth {} after {:.3}s",
+                    checkpoint.depth,
+                    checkpoint.elapsed.as_secs_f64()
+                );
+            }
+
+            let options = searcher::SearchOptions {
+                max_depth: checkpoint.max_depth,
+                handicap: handicap.map(|h| h as f32 / 100.0),
+                max_table_size_mb: max_table_size,
+                progress_interval: progress_interval.map(std::time::Duration::from_millis),
+                progress_nodes,
+                multi_pv: Some(alternatives + 1),
+                underpromotions: match &underpromotions {
+                    Some(policy) => policy
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("Invalid underpromotions: {}", policy))?,
+                    None => Default::default(),
+                },
+                // The searcher snapshots its tables every so often without stopping, and
+                // each one is saved along with the deepest depth that finished before it
+                snapshot_interval: checkpoint_file
+                    .as_ref()
+                    .map(|_| std::time::Duration::from_secs(checkpoint_interval)),
+                max_snapshot_entries: Some(checkpoint::MAX_ENTRIES),
+                ..Default::default()
+            };
+
+            // Time spent (and nodes searched) in earlier runs counts towards the reported totals
+            let previous_elapsed = checkpoint.elapsed;
+            let previous_nodes = checkpoint.nodes_searched;
+            let start_time = std::time::Instant::now()
+                .checked_sub(previous_elapsed)
+                .unwrap_or_else(std::time::Instant::now);
+
+            let ctrl_c =
+                interrupt::CtrlC::install().context("while installing the Ctrl+C handler")?;
+
+            // Nothing was actually played, the root was just searched before
+            let artifact = checkpoint.artifact.take().map(|snapshot| {
+                let mut artifact = searcher::SearchArtifact::from(snapshot);
+                artifact.load_history(std::iter::empty());
+                artifact
+            });
+
+            let searcher = searcher::Searcher::new();
+            let evaluator = eval::Evaluator::default();
+            let (search_handle, send, recv) = searcher.analyze(
+                game_state.clone(),
+                checkpoint.seed,
+                evaluator,
+                options,
+                artifact,
+            );
+
+            ctrl_c.watch(send);
+
+            // Depths that earlier runs already reported aren't worth printing again
+            let reported_depth = checkpoint.depth;
+            let printed_state = game_state.clone();
+            let saved_file = checkpoint_file.clone();
+            let print_handle = thread::spawn(move || {
+                let mut pv = None;
+                let mut nodes_searched = 0;
+                while let Ok(e) = recv.recv() {
+                    let depth = match &e {
+                        searcher::StatusEvent::PvUpdate { depth, .. }
+                        | searcher::StatusEvent::IterationComplete { depth, .. } => *depth,
+                        _ => u32::MAX,
+                    };
+
+                    if depth > reported_depth {
+                        common::print_search_event(
+                            &e,
+                            start_time,
+                            &printed_state,
+                            notation.unwrap_or(MoveNotation::Peg),
+                        );
+                    }
+
+                    match e {
+                        searcher::StatusEvent::PvUpdate {
+                            depth,
+                            line,
+                            evaluation,
+                        } => pv = Some((depth, line.to_vec(), evaluation)),
+                        searcher::StatusEvent::IterationComplete { depth, stats } => {
+                            nodes_searched = stats.nodes_searched;
+                            if let Some((_, line, evaluation)) =
+                                pv.take().filter(|(pv_depth, _, _)| *pv_depth == depth)
+                            {
+                                checkpoint.record_depth(depth, line, evaluation);
+                            }
+                        }
+                        searcher::StatusEvent::Progress {
+                            nodes_searched: nodes,
+                            ..
+                        } => nodes_searched = nodes,
+                        searcher::StatusEvent::Snapshot { snapshot, .. } => {
+                            checkpoint.record_snapshot(
+                                *snapshot,
+                                start_time.elapsed(),
+                                previous_nodes + nodes_searched,
+                            );
+                            if let Some(path) = &saved_file {
+                                if let Err(e) = checkpoint.save(path) {
+                                    eprintln!("Unable to save {}: {:#}", path, e);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                checkpoint
+            });
+
+            let (artifact, result) = search_handle.join().unwrap();
+            let mut checkpoint = print_handle.join().unwrap();
+
+            checkpoint.record_depth(result.depth, result.line.to_vec(), result.evaluation);
+            checkpoint.record_snapshot(
+                artifact.snapshot(Some(checkpoint::MAX_ENTRIES)),
+                previous_elapsed + result.elapsed,
+                previous_nodes + result.stats.nodes_searched,
+            );
+
+            if let Some(path) = &checkpoint_file {
+                checkpoint
+                    .save(path)
+                    .with_context(|| format!("while saving {}", path))?;
+            }
+
+            println!(
+                "\nSearch ended ({}) at depth {} after {:.3}s",
+                if ctrl_c.is_interrupted() {
+                    String::from("interrupted")
+                } else {
+                    result.reason.to_string()
+                },
+                checkpoint.depth,
+                checkpoint.elapsed.as_secs_f64()
+            );
+
+            // The search's own result only covers this run, while the checkpoint has the
+            // best line from every run so far
+            let line = notation
+                .unwrap_or(MoveNotation::Peg)
+                .write_line(&game_state, &checkpoint.line);
+
+            println!(
+                "Best move: {}",
+                line.first().map(String::as_str).unwrap_or("(none)")
+            );
+            match checkpoint.evaluation {
+                Some(evaluation) => println!("Best line: ({}) {}", evaluation, line.join(" ")),
+                None => println!("Best line: (none)"),
+            }
+
+            // Like the best line, these count every run so far
+            println!(
+                "Nodes: {} ({:.0} per second)",
+                checkpoint.nodes_searched,
+                checkpoint.nodes_searched as f64 / checkpoint.elapsed.as_secs_f64().max(1e-3)
+            );
+
+            if ctrl_c.is_interrupted() {
+                std::io::stdout().flush()?;
+                std::process::exit(interrupt::EXIT_CODE);
+            }
+
+            Ok(())
+        }
+        Some(Commands::Learn {
+            file,
+            weights,
+            book_profile,
+            books,
+            no_default_book,
+        }) => {
+            let (mut book, path) = match &book_profile {
+                Some(name) => {
+                    let profile = BookProfiles::in_user_data_dir()
+                        .context("No user data directory to find book profiles in")?
+                        .get(name)?;
+
+                    (
+                        profile.book(OpeningBook::DEFAULT_DEPTH)?,
+                        profile.learning_path(),
+                    )
+                }
+                None => (
+                    OpeningBook::empty(),
+                    weights.context("No weights file to learn into")?.into(),
+                ),
+            };
+
+            for (path, depth) in books.iter() {
+                book.merge(
+                    OpeningBook::load(path, *depth)
+                        .with_context(|| format!("while loading book {}", path))?,
+                )?;
+            }
+
+            if !no_default_book {
+                book.merge(OpeningBook::try_default().context("while loading the default book")?)?;
+            }
+
+            let mut learning = BookLearning::load(&path)
+                .with_context(|| format!("while loading book learning {}", path.display()))?;
+
+            let contents = std::fs::read_to_string(&file)
+                .with_context(|| format!("while reading {}", file))?;
+
+            let mut learned = 0;
+            let mut total = 0;
+            for (i, pgn) in Pgn::parse_all(&contents).enumerate() {
+                let pgn = match pgn {
+                    Ok(pgn) => pgn,
+                    Err(e) => {
+                        eprintln!("{} Skipping game {}: {}", "[Warning]".yellow(), i + 1, e);
+                        continue;
+                    }
+                };
+
+                total += 1;
+
+                // Games without a result have nothing to teach
+                let Some(result) = pgn
+                    .declared_result
+                    .as_deref()
+                    .and_then(weechess_engine::book::parse_result)
+                else {
+                    continue;
+                };
+
+                if learning.learn(&book.book_moves(&pgn), result) {
+                    learned += 1;
+                }
+            }
+
+            if learned > 0 {
+                learning
+                    .save(&path)
+                    .with_context(|| format!("while saving book learning {}", path.display()))?;
+            }
+
+            eprintln!(
+                "Learned from {} of {} games into {}",
+                learned,
+                total,
+                path.display()
+            );
+
+            Ok(())
+        }
+        Some(Commands::Match {
+            config_a,
+            config_b,
+            odds,
+            games,
+            time,
+            opening_plies,
+            adjudication,
+            seed,
+            record,
+        }) => {
+            let mut writer = match &record {
+                Some(path) => Some(GameRecordWriter::new(std::io::BufWriter::new(
+                    std::fs::File::create(path)
+                        .with_context(|| format!("while creating {}", path))?,
+                ))?),
+                None => None,
+            };
+
+            let config = match_play::Config {
+                a: config_a,
+                b: config_b,
+                games,
+                time: std::time::Duration::from_millis(time),
+                opening_plies,
+                adjudication,
+                seed: seed.unwrap_or_else(rand::random),
+            };
+
+            let mut results = vec![];
+            for odds in odds {
+                eprintln!("Playing with odds of {}...", odds);
+                let mut record_error = None;
+                let result = match_play::play_match(&config, odds, |game, result, record| {
+                    eprintln!("[{}/{}] {}", game + 1, games, result);
+                    if let Some(writer) = &mut writer {
+                        if let Err(e) = writer.write(record) {
+                            record_error.get_or_insert(e);
+                        }
+                    }
+                });
+
+                if let Some(e) = record_error {
+                    return Err(e).context("while recording games");
+                }
+
+                results.push((odds, result));
+            }
+
+            if let Some(writer) = &mut writer {
+                writer.flush()?;
+            }
+
+            println!(
+                "{:<16} {:>6} {:>6} {:>6} {:>8} {:>8}",
+                "Odds", "Wins", "Draws", "Losses", "Score", "Elo"
+            );
+
+            for (odds, result) in results.iter() {
+                println!(
+                    "{:<16} {:>6} {:>6} {:>6} {:>7.1}% {:>8}",
+                    odds.to_string(),
+                    result.wins,
+                    result.draws,
+                    result.losses,
+                    result.score() * 100.0,
+                    result
+                        .elo_difference()
+                        .map(|elo| format!("{:+.0}", elo))
+                        .unwrap_or_else(|| String::from("-")),
+                );
+            }
+
+            Ok(())
+        }
+        Some(Commands::Mate {
+            fen,
+            max_ply,
+            max_nodes,
+        }) => {
+            let game_state = match &fen {
+                Some(fen) => {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                }
+                None => State::default(),
+            };
+
+            let result = MateSearch::new(max_ply)
+                .with_max_nodes(max_nodes)
+                .run(&game_state);
+
+            match result.outcome {
+                MateOutcome::Mate(line) => {
+                    let moves = notation
+                        .unwrap_or(MoveNotation::San)
+                        .write_line(&game_state, &line);
+
+                    println!("Mate in {}: {}", line.len().div_ceil(2), moves.join(" "));
+                }
+                MateOutcome::NoMate => println!("No mate within {} plies", max_ply),
+                MateOutcome::Unknown => println!("Unknown, ran out of nodes"),
+            }
+
+            eprintln!(
+                "Searched {} nodes in {:.2}s",
+                result.nodes,
+                result.elapsed.as_secs_f64()
+            );
+
+            Ok(())
+        }
+        Some(Commands::Perft { fen, depth }) => {
+            let game_state = {
+                if let Some(fen) = &fen {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                } else {
+                    State::default()
+                }
+            };
+
+            let start_time = std::time::Instant::now();
+            let count = MoveGenerator::perft(&game_state, depth, |gs, mv, depth, count| {
+                if depth == 1 {
+                    println!(
+                        "{}: {} [{}]",
+                        into_notation::<_, Peg>(mv),
+                        count,
+                        into_notation::<_, Fen>(gs)
+                    );
+                }
+            });
+
+            let elapsed = start_time.elapsed().as_secs_f64();
+            let nodes_per_second = count as f64 / elapsed;
+
+            println!(
+                "\nTotal nodes: {} ({:.2}s : {:.0}nps)",
+                count, elapsed, nodes_per_second
+            );
+
+            Ok(())
+        }
+        Some(Commands::VerifyMovegen { games, plies, seed }) => {
+            let rng_seed = seed.unwrap_or_else(rand::random);
+            let mut rng = rand::rngs::StdRng::seed_from_u64(rng_seed);
+            let mut positions = 0;
+
+            println!("Verifying move generation (seed: {})...", rng_seed);
+
+            for _ in 0..games {
+                let mut game_state = State::default();
+                for _ in 0..plies {
+                    if let Err(e) = reference::verify(&game_state) {
+                        let shrunk_state = reference::shrink(&game_state);
+                        eprintln!(
+                            "{} Move generation mismatch: {}",
+                            "[Error]".red(),
+                            into_notation::<_, Fen>(&game_state)
+                        );
+                        eprintln!("  {}", e);
+
+                        if let Err(e) = reference::verify(&shrunk_state) {
+                            eprintln!(
+                                "{} Shrunk position: {}",
+                                "[Error]".red(),
+                                into_notation::<_, Fen>(&shrunk_state)
+                            );
+                            eprintln!("  {}", e);
+                        }
+
+                        return Err(anyhow::anyhow!("Move generation mismatch"));
+                    }
+
+                    positions += 1;
+
+                    let legal_moves = MoveGenerator::compute_legal_moves(&game_state);
+                    let Some(MoveResult(_, next_state)) = legal_moves.moves().choose(&mut rng)
+                    else {
+                        break;
+                    };
+
+                    game_state = next_state.clone();
+                }
+            }
+
+            println!("Verified {} positions across {} games", positions, games);
+
+            Ok(())
+        }
+        Some(Commands::Puzzles {
+            pgn,
+            max_depth,
+            verify_depth,
+            output,
+            seed,
+        }) => {
+            let contents =
+                std::fs::read_to_string(&pgn).with_context(|| format!("while reading {}", pgn))?;
+
+            let rng_seed = seed.unwrap_or_else(rand::random);
+            let verify_depth = verify_depth.unwrap_or(max_depth + 2);
+
+            let mut lines = String::new();
+            let mut count = 0;
+            for (i, game) in Pgn::parse_all(&contents).enumerate() {
+                let game = match game {
+                    Ok(game) => game,
+                    Err(e) => {
+                        eprintln!("{} Skipping game {}: {}", "[Warning]".yellow(), i + 1, e);
+                        continue;
+                    }
+                };
+
+                eprintln!("Analyzing game {}...", i + 1);
+                let (states, evaluations) =
+                    analysis::evaluate_game(&game, max_depth, rng_seed, None, |_, _| {})?;
+
+                for puzzle in puzzles::find(&states, &evaluations, verify_depth, rng_seed) {
+                    let solution: Vec<String> = puzzle
+                        .solution
+                        .iter()
+                        .map(|mv| into_notation::<_, Lan>(mv).to_string())
+                        .collect();
+
+                    lines.push_str(&format!(
+                        "{}\t{}\n",
+                        into_notation::<_, Fen>(&puzzle.state),
+                        solution.join(" ")
+                    ));
+
+                    count += 1;
+                }
+            }
+
+            eprintln!("Found {} puzzles", count);
+            match output {
+                Some(output) => std::fs::write(&output, lines)
+                    .with_context(|| format!("while writing {}", output))?,
+                None => print!("{}", lines),
+            }
+
+            Ok(())
+        }
+        Some(Commands::Records { file, limit }) => {
+            let reader = GameRecordReader::new(std::io::BufReader::new(
+                std::fs::File::open(&file).with_context(|| format!("while opening {}", file))?,
+            ))
+            .with_context(|| format!("while reading {}", file))?;
+
+            for record in reader.take(limit.unwrap_or(usize::MAX)) {
+                let record = record.with_context(|| format!("while reading {}", file))?;
+                println!("{}", record.to_pgn());
+            }
+
+            Ok(())
+        }
+        Some(Commands::Repl { fen }) => {
+            let mut game_state = {
+                if let Some(fen) = &fen {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                } else {
+                    State::default()
+                }
+            };
+
+            let mut session = session::Session::new(&game_state);
+            let mut rl = ext::ClapEditor::<repl::Repl>::new();
+
+            // The starting positions of the sessions so far, most recent first
+            let mut recent_fens: Vec<String> = vec![];
+
+            let mut settings = repl::Settings::default();
+            if let Some(notation) = notation {
+                settings.notation = notation;
+            }
+
+            let option_names: Vec<String> = settings
+                .values()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+
+            rl.set_completions("set", option_names.clone());
+            rl.set_completions("get", option_names);
+
+            loop {
+                if recent_fens.first() != Some(&session.fen) {
+                    recent_fens.retain(|fen| *fen != session.fen);
+                    recent_fens.insert(0, session.fen.clone());
+                    recent_fens.truncate(repl::MAX_RECENT_FENS);
+                }
+
+                rl.set_completions(
+                    "move",
+                    MoveGenerator::compute_legal_moves(&game_state).to_san_list(&game_state),
+                );
+
+                rl.set_completions(
+                    "load",
+                    recent_fens
+                        .iter()
+                        .map(|fen| format!("--fen \"{}\"", fen))
+                        .collect(),
+                );
+
+                let Some(repl) = rl.read_command() else {
+                    continue;
+                };
+
+                crash::record_game(session.fen.clone(), &session.moves);
+
+                match repl.command {
+                    Some(repl::Commands::Comment { text }) => {
+                        session
+                            .annotations
+                            .insert(session.moves.len(), text.join(" "));
+                    }
+                    Some(repl::Commands::Diff) => {
+                        if session.moves.is_empty() {
+                            println!("No moves played yet");
+                            continue;
+                        }
+
+                        match session.state_at(session.moves.len() - 1) {
+                            Ok(previous) => {
+                                println!("{}", weechess_core::diff(&previous, &game_state));
+                            }
+                            Err(e) => {
+                                eprintln!("{} {:#}", "[Error]".red(), e);
+                            }
+                        }
+                    }
+                    Some(repl::Commands::Evaluate { max_depth, seed }) => {
+                        let evaluated_game_state = game_state.clone();
+                        let printed_state = game_state.clone();
+                        let notation = settings.notation;
+                        let previous_artifact = session.artifact.take();
+                        let evaluator = settings.evaluator.clone();
+                        let options = searcher::SearchOptions {
+                            max_depth,
+                            ..settings.search_options()
+                        };
+
+                        let (tx, rx) = mpsc::channel();
+                        let outer_handle = thread::spawn(move || {
+                            println!("Evaluating positions (press enter to stop)...\n");
+                            let start_time = std::time::Instant::now();
+                            let rx = rx;
+                            let searcher = searcher::Searcher::new();
+                            let rng_seed = seed.unwrap_or_else(rand::random);
+                            let (search_handle, send, recv) = searcher.analyze(
+                                evaluated_game_state,
+                                rng_seed,
+                                evaluator,
+                                options,
+                                previous_artifact,
+                            );
+
+                            let print_handle = thread::spawn(move || {
+                                loop {
+                                    match recv.recv() {
+                                        Ok(e) => {
+                                            common::print_search_event(
+                                                &e,
+                                                start_time,
+                                                &printed_state,
+                                                notation,
+                                            );
+                                        }
+                                        Err(..) => {
+                                            break;
+                                        }
+                                    }
+                                }
+
+                                println!("\nEvaluation complete!");
+                            });
+
+                            _ = rx.recv().unwrap();
+                            _ = send.send(searcher::ControlEvent::Stop);
+                            let (artifact, _) = search_handle.join().unwrap();
+                            print_handle.join().unwrap();
+                            artifact
+                        });
+
+                        stdin().read_line(&mut String::new())?;
+                        tx.send(()).unwrap();
+                        session.artifact = Some(outer_handle.join().unwrap());
+                    }
+                    Some(repl::Commands::Load { fen: Some(fen), .. }) => {
+                        match try_from_notation::<_, Fen>(&fen) {
+                            Ok(gs) => {
+                                game_state = gs;
+                                session = session::Session::new(&game_state);
+                                println!("{}", game_state.pretty());
+                            }
+                            Err(..) => {
+                                eprintln!("{} Invalid fen: {}", "[Error]".red(), fen);
+                            }
+                        }
+                    }
+                    Some(repl::Commands::Load {
+                        pgn: Some(pgn),
+                        ply,
+                        ..
+                    }) => match session::Session::from_pgn(&pgn, ply) {
+                        Ok(s) => match s.state() {
+                            Ok(gs) => {
+                                game_state = gs;
+                                session = s;
+                                println!("{}", game_state.pretty());
+                            }
+                            Err(e) => {
+                                eprintln!("{} {:#}", "[Error]".red(), e);
+                            }
+                        },
+                        Err(e) => {
+                            eprintln!("{} {:#}", "[Error]".red(), e);
+                        }
+                    },
+                    Some(repl::Commands::Load {
+                        diagram: true,
+                        turn,
+                        ..
+                    }) => {
+                        let turn_to_move = match turn.as_str() {
+                            "black" => Color::Black,
+                            _ => Color::White,
+                        };
+
+                        // The diagram can have empty lines of its own, so only an
+                        // empty line after a complete board (or nothing at all) ends it
+                        let mut diagram = String::new();
+                        let result = loop {
+                            let Some(line) = rl.read_line(". ") else {
+                                break None;
+                            };
+
+                            if line.trim().is_empty() {
+                                if diagram.trim().is_empty() {
+                                    break None;
+                                }
+
+                                match weechess_core::parse_diagram(&diagram, turn_to_move) {
+                                    Err(DiagramParseError::RankCount(count)) if count < 8 => {}
+                                    result => break Some(result),
+                                }
+                            }
+
+                            diagram.push_str(&line);
+                            diagram.push('\n');
+                        };
+
+                        match result {
+                            Some(Ok(gs)) => {
+                                game_state = gs;
+                                session = session::Session::new(&game_state);
+                                println!("{}", game_state.pretty());
+                            }
+                            Some(Err(e)) => {
+                                eprintln!("{} {}", "[Error]".red(), e);
+                            }
+                            None => {}
+                        }
+                    }
+                    Some(repl::Commands::Load { .. }) => {}
+                    Some(repl::Commands::Move { san }) => {
+                        // UCI moves can't be mistaken for SAN ones, so they're tried first
+                        let query = MoveQuery::try_from_uci(&san)
+                            .ok_or(())
+                            .or_else(|_| try_from_notation::<_, San>(&san));
+
+                        let Ok(query) = query else {
+                            eprintln!("{} Invalid move: {}", "[Error]".red(), san);
+                            continue;
+                        };
+
+                        let legal_moves = MoveGenerator::compute_legal_moves(&game_state);
+                        match legal_moves.filter(query).collect::<Vec<_>>()[..] {
+                            [MoveResult(mv, next_state)] => {
+                                session.moves.push(*mv);
+                                game_state = next_state.clone();
+                                println!("{}", game_state.pretty());
+                            }
+                            [] => eprintln!("{} Illegal move: {}", "[Error]".red(), san),
+                            _ => eprintln!("{} Ambiguous move: {}", "[Error]".red(), san),
+                        }
+                    }
+                    Some(repl::Commands::Moves) => {
+                        let evaluator = eval::Evaluator::default();
+                        let moves = MoveGenerator::compute_legal_moves(&game_state)
+                            .sorted_by_estimate(&game_state, &evaluator);
+
+                        if moves.is_empty() {
+                            println!("No legal moves");
+                        } else {
+                            let moves: Vec<String> = moves
+                                .moves()
+                                .iter()
+                                .map(|result| settings.notation.write(&game_state, &result.0))
+                                .collect();
+
+                            println!("{}", moves.join(" "));
+                        }
+                    }
+                    Some(repl::Commands::Open { file }) => {
+                        match session::Session::open(&file).and_then(|s| Ok((s.state()?, s))) {
+                            Ok((gs, s)) => {
+                                game_state = gs;
+                                session = s;
+                                println!("{}", game_state.pretty());
+                            }
+                            Err(e) => {
+                                eprintln!("{} {:#}", "[Error]".red(), e);
+                            }
+                        }
+                    }
+                    Some(repl::Commands::Pv { fen }) => {
+                        let state = match fen.as_str() {
+                            "current" => game_state.clone(),
+                            fen => match try_from_notation::<_, Fen>(fen) {
+                                Ok(state) => state,
+                                Err(..) => {
+                                    eprintln!("{} Invalid fen: {}", "[Error]".red(), fen);
+                                    continue;
+                                }
+                            },
+                        };
+
+                        let Some(artifact) = &session.artifact else {
+                            eprintln!("{} Nothing evaluated yet", "[Error]".red());
+                            continue;
+                        };
+
+                        let line = artifact.hash_line(&state);
+                        if line.is_empty() {
+                            println!("No moves stored for the position");
+                        }
+
+                        let moves: Vec<_> = line.iter().map(|entry| entry.mv).collect();
+                        let moves = settings.notation.write_line(&state, &moves);
+                        for (ply, (entry, mv)) in line.iter().zip(moves).enumerate() {
+                            let bound = match entry.kind {
+                                searcher::EvaluationKind::Exact => "exact",
+                                searcher::EvaluationKind::UpperBound => "upper bound",
+                                searcher::EvaluationKind::LowerBound => "lower bound (refutation)",
+                            };
+
+                            println!(
+                                "{:>3}. {:<8} depth={:<3} score={} {}",
+                                ply + 1,
+                                mv,
+                                entry.depth,
+                                entry.evaluation,
+                                bound
+                            );
+                        }
+                    }
+                    Some(repl::Commands::Quit) => break,
+                    Some(repl::Commands::Set { name, value }) => {
+                        match settings.set(&name, &value) {
+                            Ok(invalidates_scores) => {
+                                // The tables from the last evaluation are scored with the old weights
+                                if invalidates_scores {
+                                    session.artifact = None;
+                                }
+                            }
+                            Err(e) => eprintln!("{} {:#}", "[Error]".red(), e),
+                        }
+                    }
+                    Some(repl::Commands::Get { name }) => {
+                        let values = settings.values();
+                        let matching: Vec<_> = values
+                            .iter()
+                            .filter(|(option, _)| {
+                                name.as_ref()
+                                    .is_none_or(|name| option.eq_ignore_ascii_case(name))
+                            })
+                            .collect();
+
+                        if matching.is_empty() {
+                            eprintln!(
+                                "{} Unknown option: {}",
+                                "[Error]".red(),
+                                name.unwrap_or_default()
+                            );
+                        }
+
+                        for (option, value) in matching {
+                            println!("{} = {}", option, value);
+                        }
+                    }
+                    Some(repl::Commands::Save { file, analysis }) => {
+                        if let Err(e) = session.save(&file, analysis) {
+                            eprintln!("{} {:#}", "[Error]".red(), e);
+                        }
+                    }
+                    Some(repl::Commands::Show {
+                        what: repl::Showable::Pressure,
+                    }) => {
+                        common::print_pressure(game_state.board());
+                    }
+                    Some(repl::Commands::State) => {
+                        println!("{}", game_state.pretty());
+                    }
+                    None => {}
+                }
+            }
+
+            Ok(())
+        }
+        Some(Commands::Score {
+            input,
+            output,
+            depth,
+            time,
+            workers,
+            hash,
+            seed,
+        }) => {
+            let input =
+                std::fs::File::open(&input).with_context(|| format!("while opening {}", input))?;
+
+            let output: Box<dyn std::io::Write + Send> = match &output {
+                Some(path) => Box::new(
+                    std::fs::File::create(path)
+                        .with_context(|| format!("while creating {}", path))?,
+                ),
+                None => Box::new(std::io::stdout()),
+            };
+
+            let workers = workers.unwrap_or_else(|| {
+                thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+
+            let config = score::Config {
+                depth,
+                budget: time.map(std::time::Duration::from_millis),
+                workers: usize::max(workers, 1),
+                hash_mb: hash,
+                seed: seed.unwrap_or_else(rand::random),
+            };
+
+            let count = score::run(std::io::BufReader::new(input), output, &config)?;
+            eprintln!("Scored {} positions", count);
+
+            Ok(())
+        }
+        Some(Commands::Serve { address }) => {
+            let listener = std::net::TcpListener::bind(&address)
+                .with_context(|| format!("while listening on {}", address))?;
+
+            eprintln!("Listening on http://{}", listener.local_addr()?);
+            serve::run(listener)
+        }
+        Some(Commands::Spsa {
+            params,
+            iterations,
+            depth,
+            opening_plies,
+            adjudication,
+            seed,
+        }) => {
+            let mut parameters = spsa::parameters();
+            if !params.is_empty() {
+                if let Some(unknown) = params
+                    .iter()
+                    .find(|name| !parameters.iter().any(|p| p.name == **name))
+                {
+                    return Err(anyhow::anyhow!(
+                        "Unknown parameter: {} (expected one of {})",
+                        unknown,
+                        parameters
+                            .iter()
+                            .map(|p| p.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+
+                parameters.retain(|p| params.contains(&p.name));
+            }
+
+            let config = spsa::Config {
+                iterations,
+                depth,
+                opening_plies,
+                adjudication,
+                seed: seed.unwrap_or_else(rand::random),
+            };
+
+            spsa::tune(&mut parameters, &config, |iteration, parameters, result| {
+                eprintln!(
+                    "[{}/{}] result={:+} {}",
+                    iteration + 1,
+                    iterations,
+                    result,
+                    parameters
+                        .iter()
+                        .map(|p| format!("{}={:.3}", p.name, p.value))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+            });
+
+            for parameter in parameters.iter() {
+                println!("{} {:.3}", parameter.name, parameter.value);
+            }
+
+            Ok(())
+        }
+        Some(Commands::Structure { fen }) => {
+            let game_state = {
+                if let Some(fen) = &fen {
+                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
+                } else {
+                    State::default()
+                }
+            };
+
+            fn join<T: std::fmt::Display>(items: impl Iterator<Item = T>) -> String {
+                let items: Vec<String> = items.map(|item| item.to_string()).collect();
+                if items.is_empty() {
+                    String::from("-")
+                } else {
+                    items.join(" ")
+                }
+            }
+
+            let squares = |pawns: BitBoard| join(pawns.iter_squares());
+
+            println!("{}", game_state.pretty());
+            println!(
+                "Open files: {}",
+                join(pawns::open_files(game_state.board()).iter())
+            );
+
+            for color in Color::ALL {
+                let structure = PawnStructure::new(game_state.board(), *color);
+                println!("\n{:?}:", color);
+                println!("  Islands: {}", structure.islands);
+                println!("  Passed: {}", squares(structure.passed));
+                println!("  Backward: {}", squares(structure.backward));
+                println!("  Isolated: {}", squares(structure.isolated));
+                println!("  Doubled: {}", squares(structure.doubled));
+                println!(
+                    "  Semi-open files: {}",
+                    join(structure.semi_open_files.iter())
+                );
+
+                match structure.king_shelter {
+                    Some(shelter) => println!("  King shelter: {:.0}%", shelter * 100.0),
+                    None => println!("  King shelter: -"),
+                }
+            }
+
+            Ok(())
+        }
+        Some(Commands::Uci {
+            books,
+            no_default_book,
+            handicap,
+            learn,
+            freeze_book,
+            analysis_cache,
+            cache_depth,
+            config,
+            book_profile,
+        }) => {
+            let mut book = OpeningBook::empty();
+            for (path, depth) in books.iter() {
+                book.merge(
+                    OpeningBook::load(path, *depth)
+                        .with_context(|| format!("while loading book {}", path))?,
+                )?;
+            }
+
+            if !no_default_book {
+                book.merge(OpeningBook::try_default().context("while loading the default book")?)?;
+            }
+
+            let mut client =
+                uci::Client::with_book(book).with_handicap(handicap.map(|h| h as f32 / 100.0));
+
+            if let Some(path) = learn {
+                let learning = BookLearning::load(&path)
+                    .with_context(|| format!("while loading book learning {}", path))?
+                    .with_frozen(freeze_book);
+
+                client = client.with_book_learning(learning, path);
+            }
+
+            if let Some(path) = analysis_cache {
+                let cache = AnalysisCache::open(&path, cache_depth)
+                    .with_context(|| format!("while opening analysis cache {}", path))?;
+
+                client = client.with_analysis_cache(cache);
+            }
+
+            if let Some(path) = config {
+                let config = EngineConfig::load(&path)
+                    .with_context(|| format!("while loading config {}", path))?;
+
+                if let Some(threads) = config.threads()? {
+                    client = client.with_threads(threads);
+                }
+            }
+
+            match BookProfiles::in_user_data_dir() {
+                Some(profiles) => {
+                    if let Some(name) = &book_profile {
+                        profiles.get(name)?;
+                    }
+
+                    client = client.with_book_profiles(profiles, book_profile);
+                }
+                None if book_profile.is_some() => {
+                    anyhow::bail!("No user data directory to find book profiles in")
+                }
+                None => {}
+            }
+
+            client.exec().context("while running UCI client")
+        }
+        Some(Commands::Version { verbose }) => {
+            println!("{}", EngineVersion::CURRENT);
+            if verbose {
+                let build = &EngineVersion::CURRENT.build;
+                println!("Commit: {}", build.commit.unwrap_or("unknown"));
+                println!("Profile: {}", build.profile);
+                println!("Features: {}", build.features().join(" "));
+            }
+
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("[error] {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+mod common {
+    use colored::Colorize;
+    use weechess_core::{notation::MoveNotation, Board, Color, File, Rank, Square, State};
+    use weechess_engine::{book::OpeningBook, searcher};
+
+    /// Splits a book given as `path[:depth]` into its path and how many moves deep to read
+    /// it. The last colon only starts a depth if there's no more path after it, so that
+    /// Windows drives aren't mistaken for books.
+    pub fn parse_book_source(source: &str) -> Result<(String, usize), String> {
+        match source.rsplit_once(':') {
+            Some((path, depth)) if !path.is_empty() && !depth.contains(['/', '\\']) => {
+                let depth = depth
+                    .parse()
+                    .map_err(|_| format!("invalid book depth: {}", depth))?;
+
+                Ok((path.to_string(), depth))
+            }
+            _ => Ok((source.to_string(), OpeningBook::DEFAULT_DEPTH)),
+        }
+    }
+
+    /// Prints the number of white attackers minus black attackers of each square
+    pub fn print_pressure(board: &Board) {
+        let white = board.attack_count_map(Color::White);
+        let black = board.attack_count_map(Color::Black);
+        for rank in Rank::ALL.iter().rev() {
+            let row = File::ALL
+                .iter()
+                .map(|file| {
+                    let square = Square::from((*file, *rank));
+                    let pressure = white[square] as i32 - black[square] as i32;
+                    let cell = format!("{:>3}", pressure);
+                    match pressure {
+                        0 => cell.dimmed().to_string(),
+                        p if p > 0 => cell.bright_white().to_string(),
+                        _ => cell.red().to_string(),
+                    }
+                })
+                .collect::<String>();
+
+            println!("{} {}", rank, row);
+        }
+
+        println!(
+            "  {}",
+            File::ALL
+                .iter()
+                .map(|file| format!("{:>3}", file.to_string()))
+                .collect::<String>()
+        );
+    }
+
+    /// Prints a search event, with its moves written in the given notation from the
+    /// position the search started from
+    pub fn print_search_event(
+        event: &searcher::StatusEvent,
+        start_time: std::time::Instant,
+        state: &State,
+        notation: MoveNotation,
+    ) {
+        match event {
+            searcher::StatusEvent::PvUpdate {
+                depth,
+                line,
+                evaluation,
+            } => {
+                let line = notation.write_line(state, line).join(" ");
+
+                println!(
+                    "[{}] ({}) depth={} {}",
+                    "Best Move".bright_green(),
+                    evaluation,
+                    depth,
+                    line
+                );
+            }
+            searcher::StatusEvent::IterationComplete { depth, stats } => {
+                let elapsed = start_time.elapsed().as_secs_f64();
+                let f = format!(
+                    "time={:.3} depth={} nodes={} tts={:.6}% ordering={}",
+                    elapsed,
+                    depth,
+                    stats.nodes_searched,
+                    stats.transposition_saturation * 100.0,
+                    stats
+                        .ordering
+                        .first_move_rate()
+                        .map(|rate| format!("{:.1}%", rate * 100.0))
+                        .unwrap_or_else(|| String::from("-"))
+                );
+                println!("[{}] {}", "Iteration".bright_blue(), f);
+            }
+            searcher::StatusEvent::Progress {
+                depth,
+                nodes_searched,
+                transposition_saturation,
+            } => {
+                let elapsed = start_time.elapsed().as_secs_f64();
+                let nodes_per_second = *nodes_searched as f64 / elapsed;
+                let f = format!(
+                    "time={:.3} depth={} nodes={} nps={:.0} tts={:.6}%",
+                    elapsed,
+                    depth,
+                    nodes_searched,
+                    nodes_per_second,
+                    transposition_saturation * 100.0
+                );
+                println!("[{} ] {}", "Progress".dimmed(), f.dimmed());
+            }
+            searcher::StatusEvent::Warning { message, .. } => {
+                println!("[{}  ] {}", "Warning".bright_red(), message);
+            }
+            searcher::StatusEvent::MultiPvUpdate {
+                depth,
+                rank,
+                line,
+                evaluation,
+            } => {
+                let line = notation.write_line(state, line).join(" ");
+
+                println!(
+                    "[{}] ({}) depth={} {}",
+                    format!("Line {:<4}", rank).green(),
+                    evaluation,
+                    depth,
+                    line
+                );
+            }
+            // Too noisy to follow in a terminal, this is meant for GUIs
+            searcher::StatusEvent::RootMove { .. } | searcher::StatusEvent::Snapshot { .. } => {}
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_book_source() {
+            let source = |path: &str, depth| Ok((path.to_string(), depth));
+            assert_eq!(parse_book_source("gambits.pgn:6"), source("gambits.pgn", 6));
+            assert_eq!(
+                parse_book_source("gambits.pgn"),
+                source("gambits.pgn", OpeningBook::DEFAULT_DEPTH)
+            );
+            assert_eq!(
+                parse_book_source(r"C:\books\main.pgn:12"),
+                source(r"C:\books\main.pgn", 12)
+            );
+            assert_eq!(
+                parse_book_source(r"C:\books\main.pgn"),
+                source(r"C:\books\main.pgn", OpeningBook::DEFAULT_DEPTH)
+            );
+            assert!(parse_book_source("gambits.pgn:six").is_err());
+            assert!(parse_book_source("gambits.pgn:").is_err());
+        }
+    }
+}
+
+mod compare {
+    use std::{str::FromStr, thread, time::Duration};
+
+    use weechess_core::{Move, State};
+    use weechess_engine::{eval, searcher};
+
+    /// Search settings that can be varied between the two sides of a comparison
+    #[derive(Debug, Clone, Default)]
+    pub struct Config {
+        pub options: searcher::SearchOptions,
+        pub seed: Option<u64>,
+    }
+
+    impl FromStr for Config {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut config = Config::default();
+            for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected `key=value`, found `{}`", pair))?;
+
+                let invalid = |_| format!("invalid value for {}: {}", key, value);
+                match key.trim() {
+                    "handicap" => {
+                        let percent: u8 = value.trim().parse().map_err(invalid)?;
+                        if percent > 100 {
+                            return Err(format!("handicap must be a percentage: {}", value));
+                        }
+
+                        config.options.handicap = Some(percent as f32 / 100.0);
+                    }
+                    "max-table-size" => {
+                        config.options.max_table_size_mb =
+                            Some(value.trim().parse().map_err(invalid)?);
+                    }
+                    "seed" => {
+                        config.seed = Some(value.trim().parse().map_err(invalid)?);
+                    }
+                    "threads" => {
+                        config.options.max_threads = Some(value.trim().parse().map_err(invalid)?);
+                    }
+                    key => return Err(format!("unknown configuration key: {}", key)),
+                }
+            }
+
+            Ok(config)
+        }
+    }
+
+    /// How far a configuration got in the time it was given
+    pub struct Outcome {
+        pub depth: u32,
+        pub nodes_searched: usize,
+        pub best_move: Option<Move>,
+        pub evaluation: Option<eval::Evaluation>,
+    }
+
+    pub fn run(
+        state: &State,
+        config: &Config,
+        max_depth: Option<usize>,
+        budget: Option<Duration>,
+    ) -> Outcome {
+        let searcher = searcher::Searcher::new();
+        let options = searcher::SearchOptions {
+            max_depth,
+            ..config.options.clone()
+        };
+
+        let (search_handle, control, receiver) = searcher.analyze(
+            state.clone(),
+            config.seed.unwrap_or_else(rand::random),
+            eval::Evaluator::default(),
+            options,
+            None,
+        );
+
+        // The search may finish on its own before the time is up, in which case
+        // this stop is simply ignored
+        if let Some(budget) = budget {
+            thread::spawn(move || {
+                thread::sleep(budget);
+                _ = control.send(searcher::ControlEvent::TimeUp);
+            });
+        }
+
+        // Everything needed is in the result at the end
+        drop(receiver);
+
+        let (_, result) = search_handle.join().unwrap();
+        Outcome {
+            depth: result.depth,
+            nodes_searched: result.stats.nodes_searched,
+            best_move: result.best_move(),
+            evaluation: result.best_move().map(|_| result.evaluation),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use weechess_core::notation::{try_from_notation, Fen};
+
+        use super::*;
+
+        #[test]
+        fn test_config() {
+            let config: Config = "handicap=25, threads=2,max-table-size=8,seed=3"
+                .parse()
+                .unwrap();
+
+            assert_eq!(config.options.handicap, Some(0.25));
+            assert_eq!(config.options.max_threads, Some(2));
+            assert_eq!(config.options.max_table_size_mb, Some(8));
+            assert_eq!(config.seed, Some(3));
+
+            assert!("".parse::<Config>().unwrap().seed.is_none());
+            assert!("handicap=101".parse::<Config>().is_err());
+            assert!("threads".parse::<Config>().is_err());
+            assert!("threads=two".parse::<Config>().is_err());
+            assert!("depth=3".parse::<Config>().is_err());
+        }
+
+        #[test]
+        fn test_run() {
+            let state = try_from_notation::<_, Fen>("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap();
+            let config: Config = "threads=1,max-table-size=4,seed=0".parse().unwrap();
+            let outcome = run(&state, &config, Some(3), None);
+
+            assert_eq!(outcome.depth, 3);
+            assert!(outcome.nodes_searched > 0);
+            assert!(outcome.best_move.is_some());
+            assert!(outcome.evaluation.unwrap() > eval::Evaluation::ONE_PAWN * 5);
+        }
+    }
+}
+
+mod batch {
+    use std::{
+        io::{BufRead, Write},
+        thread,
+        time::Duration,
+    };
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use weechess_core::{
+        notation::{into_notation, lan::Lan, try_from_notation, Fen},
+        MoveGenerator, State,
+    };
+    use weechess_engine::{
+        eval::{Evaluation, Evaluator},
+        searcher::{self, SearchArtifact},
+    };
+
+    use crate::score;
+
+    /// Runs commands read one per line, keeping the search tables around between them
+    pub struct Batch {
+        rng: StdRng,
+        evaluator: Evaluator,
+        artifact: Option<SearchArtifact>,
+    }
+
+    impl Batch {
+        pub fn new(seed: u64) -> Self {
+            Self {
+                rng: StdRng::seed_from_u64(seed),
+                evaluator: Evaluator::default(),
+                artifact: None,
+            }
+        }
+
+        /// Writes exactly one line for each line of input, which starts with `error` if the
+        /// command couldn't be run. Each line is flushed as soon as it's written, so that
+        /// scripts can wait on the result of one command before sending the next.
+        pub fn run<R, W>(&mut self, input: R, mut output: W) -> Result<(), anyhow::Error>
+        where
+            R: BufRead,
+            W: Write,
+        {
+            for line in input.lines() {
+                let line = line?;
+                match self.execute(line.trim()) {
+                    Ok(result) => writeln!(output, "{}", result)?,
+                    Err(e) => writeln!(output, "error {}", e)?,
+                }
+
+                output.flush()?;
+            }
+
+            Ok(())
+        }
+
+        fn execute(&mut self, command: &str) -> Result<String, String> {
+            let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+            match name {
+                "eval" => {
+                    let state = parse_fen(args)?;
+                    let evaluation = self.evaluator.evaluate(&state, state.turn_to_move(), 0);
+
+                    Ok(score::format_score(evaluation))
+                }
+                "perft" => {
+                    let (state, depth) = parse_fen_and_number(args)?;
+                    Ok(MoveGenerator::perft(&state, depth as usize, |_, _, _, _| {}).to_string())
+                }
+                "bestmove" => {
+                    let (state, time) = parse_fen_and_number(args)?;
+                    Ok(self.best_move(&state, Duration::from_millis(time)))
+                }
+                "" => Err(String::from("empty command")),
+                name => Err(format!("unknown command: {}", name)),
+            }
+        }
+
+        /// The best move in UCI notation along with its score, or `none`
+        /// and the score of the position if the game is over
+        fn best_move(&mut self, state: &State, time: Duration) -> String {
+            // There's nothing to search once the game is over
+            if MoveGenerator::compute_legal_moves(state).is_empty() {
+                let evaluation = if state.is_check() {
+                    -Evaluation::mate_in_ply(0)
+                } else {
+                    Evaluation::EVEN
+                };
+
+                return format!("none {}", score::format_score(evaluation));
+            }
+
+            let mut artifact = self.artifact.take();
+
+            // The positions aren't from the same game, so none of them repeat
+            if let Some(artifact) = &mut artifact {
+                artifact.load_history(std::iter::empty());
+            }
+
+            let searcher = searcher::Searcher::new();
+            let (search_handle, control, receiver) = searcher.analyze(
+                state.clone(),
+                self.rng.gen(),
+                self.evaluator.clone(),
+                searcher::SearchOptions::default(),
+                artifact,
+            );
+
+            // The search may finish on its own before the time is up, in which
+            // case this stop is simply ignored
+            thread::spawn(move || {
+                thread::sleep(time);
+                _ = control.send(searcher::ControlEvent::TimeUp);
+            });
+
+            // Everything needed is in the result at the end
+            drop(receiver);
+
+            let (artifact, result) = search_handle.join().unwrap();
+            self.artifact = Some(artifact);
+
+            match result.best_move() {
+                Some(mv) => format!(
+                    "{} {}",
+                    into_notation::<_, Lan>(&mv),
+                    score::format_score(result.evaluation)
+                ),
+                None => String::from("none"),
+            }
+        }
+    }
+
+    fn parse_fen(fen: &str) -> Result<State, String> {
+        try_from_notation::<_, Fen>(fen.trim()).map_err(|_| format!("invalid fen: {}", fen))
+    }
+
+    /// Splits off the number at the end of the arguments, after the position
+    fn parse_fen_and_number(args: &str) -> Result<(State, u64), String> {
+        let (fen, number) = args
+            .trim()
+            .rsplit_once(' ')
+            .ok_or_else(|| String::from("expected a fen followed by a number"))?;
+
+        let number = number
+            .parse()
+            .map_err(|_| format!("invalid number: {}", number))?;
+
+        Ok((parse_fen(fen)?, number))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_batch() {
+            let input = "\
+                perft rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 2\n\
+                bestmove k7/8/1K6/8/8/8/8/7R w - - 0 1 200\n\
+                bestmove 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1 200\n\
+                perft 8/8/8/8/8/8/8/8 w - - 0 1 x\n\
+                \n\
+                castle\n";
+
+            let mut output = vec![];
+            Batch::new(0).run(input.as_bytes(), &mut output).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            let lines: Vec<_> = output.lines().collect();
+            assert_eq!(
+                lines,
+                [
+                    "400",
+                    "h1h8 #1",
+                    "none 0",
+                    "error invalid number: x",
+                    "error empty command",
+                    "error unknown command: castle",
+                ]
+            );
+        }
+    }
+}
+
+mod calibrate {
+    use std::time::{Duration, Instant};
+
+    use weechess_core::{
+        notation::{try_from_notation, Fen},
+        State,
+    };
+
+    use crate::compare;
+
+    // An opening, a middlegame and an endgame, since they scale differently
+    const POSITIONS: &[&str] = &[
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    /// How searches with a certain number of threads went, averaged over the positions
+    pub struct Measurement {
+        pub threads: usize,
+        pub mean_depth: f64,
+        pub nodes_per_second: f64,
+    }
+
+    pub fn measure(
+        threads: usize,
+        budget: Duration,
+        hash: Option<usize>,
+        seed: u64,
+    ) -> Measurement {
+        let mut config = compare::Config {
+            seed: Some(seed),
+            ..Default::default()
+        };
+
+        config.options.max_threads = Some(threads);
+        config.options.max_table_size_mb = hash;
+
+        let mut depth = 0;
+        let mut nodes = 0;
+        let mut elapsed = Duration::ZERO;
+        for fen in POSITIONS {
+            let state: State = try_from_notation::<_, Fen>(fen).unwrap();
+            let start = Instant::now();
+            let outcome = compare::run(&state, &config, None, Some(budget));
+            elapsed += start.elapsed();
+            depth += outcome.depth;
+            nodes += outcome.nodes_searched;
+        }
+
+        Measurement {
+            threads,
+            mean_depth: depth as f64 / POSITIONS.len() as f64,
+            nodes_per_second: nodes as f64 / elapsed.as_secs_f64(),
+        }
+    }
+
+    /// Picks the thread count that searched the deepest in the same time, which is what
+    /// extra threads are for. Ties go to fewer threads rather than to raw speed, since
+    /// more threads always search more nodes, even when they don't help a lazy SMP search.
+    pub fn recommend(results: &[Measurement]) -> Option<&Measurement> {
+        results.iter().max_by(|a, b| {
+            a.mean_depth
+                .total_cmp(&b.mean_depth)
+                .then(b.threads.cmp(&a.threads))
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_recommend() {
+            let measurement = |threads, mean_depth, nodes_per_second| Measurement {
+                threads,
+                mean_depth,
+                nodes_per_second,
+            };
+
+            // The extra nodes of more threads don't count for anything without the depth
+            let results = [
+                measurement(1, 9.0, 100_000.0),
+                measurement(2, 10.0, 180_000.0),
+                measurement(4, 10.0, 300_000.0),
+                measurement(8, 9.67, 400_000.0),
+            ];
+
+            assert_eq!(recommend(&results).map(|m| m.threads), Some(2));
+            assert!(recommend(&[]).is_none());
+        }
+    }
+}
+
+mod score {
+    use std::{
+        collections::BTreeMap,
+        io::{BufRead, Write},
+        sync::{mpsc, Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use weechess_core::{
+        notation::{into_notation, lan::Lan, try_from_notation, Fen},
+        MoveGenerator, State,
+    };
+    use weechess_engine::eval::Evaluation;
+
+    use crate::compare;
+
+    pub struct Config {
+        pub depth: usize,
+        pub budget: Option<Duration>,
+        pub workers: usize,
+
+        /// The transposition table memory of all the workers together
+        pub hash_mb: usize,
+        pub seed: u64,
+    }
+
+    /// Searches the positions read from `input` on a pool of workers, writing a CSV line
+    /// for each to `output` in the order they were read. Scores are in centipawns (or `#N`
+    /// for a mate in N) from the perspective of the side to move. Returns the number of
+    /// positions scored.
+    pub fn run<R, W>(input: R, mut output: W, config: &Config) -> Result<usize, anyhow::Error>
+    where
+        R: BufRead + Send,
+        W: Write,
+    {
+        let (job_tx, job_rx) = mpsc::sync_channel::<(usize, String, State)>(config.workers);
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut search_config = compare::Config {
+            seed: Some(config.seed),
+            ..Default::default()
+        };
+
+        // Each worker searches with tables of its own, so they split the memory
+        search_config.options.max_table_size_mb =
+            Some(usize::max(config.hash_mb / config.workers, 1));
+
+        // Only the workers hold onto the receiving end, so that the reader stops
+        // as soon as they do (if writing the results fails, for instance)
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        writeln!(output, "fen,bestmove,score,depth,nodes")?;
+
+        thread::scope(|scope| {
+            // Positions are read as the workers are ready for them, so the whole
+            // file never needs to be held in memory
+            let reader = scope.spawn(move || -> Result<(), anyhow::Error> {
+                let mut index = 0;
+                for line in input.lines() {
+                    let line = line?;
+                    let fen = line.trim();
+                    if fen.is_empty() {
+                        continue;
+                    }
+
+                    match try_from_notation::<_, Fen>(fen) {
+                        Ok(state) => {
+                            if job_tx.send((index, fen.to_string(), state)).is_err() {
+                                break;
+                            }
+
+                            index += 1;
+                        }
+                        Err(..) => eprintln!("Skipping invalid fen: {}", fen),
+                    }
+                }
+
+                Ok(())
+            });
+
+            for _ in 0..config.workers {
+                let job_rx = job_rx.clone();
+                let result_tx = result_tx.clone();
+                let search_config = &search_config;
+                scope.spawn(move || loop {
+                    let job = match job_rx.lock() {
+                        Ok(job_rx) => job_rx.recv(),
+                        Err(..) => break,
+                    };
+
+                    let Ok((index, fen, state)) = job else {
+                        break;
+                    };
+
+                    // There's nothing to search once the game is over
+                    let outcome = if MoveGenerator::compute_legal_moves(&state).is_empty() {
+                        compare::Outcome {
+                            depth: 0,
+                            nodes_searched: 0,
+                            best_move: None,
+                            evaluation: Some(if state.is_check() {
+                                -Evaluation::mate_in_ply(0)
+                            } else {
+                                Evaluation::EVEN
+                            }),
+                        }
+                    } else {
+                        compare::run(&state, search_config, Some(config.depth), config.budget)
+                    };
+
+                    if result_tx.send((index, fen, outcome)).is_err() {
+                        break;
+                    }
+                });
+            }
+
+            drop(job_rx);
+            drop(result_tx);
+
+            // Results come back in whatever order the workers finish them
+            // in, so they're held back until the ones before them are done
+            let mut pending = BTreeMap::new();
+            let mut written = 0;
+            for (index, fen, outcome) in result_rx {
+                pending.insert(index, (fen, outcome));
+                while let Some((fen, outcome)) = pending.remove(&written) {
+                    writeln!(
+                        output,
+                        "{},{},{},{},{}",
+                        fen,
+                        outcome
+                            .best_move
+                            .map(|mv| into_notation::<_, Lan>(&mv).to_string())
+                            .unwrap_or_default(),
+                        outcome.evaluation.map(format_score).unwrap_or_default(),
+                        outcome.depth,
+                        outcome.nodes_searched
+                    )?;
+
+                    written += 1;
+                }
+            }
+
+            reader.join().unwrap()?;
+            output.flush()?;
+            Ok(written)
+        })
+    }
+
+    pub fn format_score(evaluation: Evaluation) -> String {
+        match evaluation.mate_in_moves() {
+            Some(moves) => format!("#{}", moves),
+            None => i32::from(evaluation).to_string(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_score() {
+            let input = "\
+                8/8/8/4k3/8/8/3QK3/8 w - - 0 1\n\
+                \n\
+                k7/8/1K6/8/8/8/8/7R w - - 0 1\n\
+                7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\n";
+
+            let config = Config {
+                depth: 2,
+                budget: None,
+                workers: 2,
+                hash_mb: 8,
+                seed: 0,
+            };
+
+            let mut output = vec![];
+            assert_eq!(run(input.as_bytes(), &mut output, &config).unwrap(), 3);
+
+            // The results come out in the order the positions were read
+            let output = String::from_utf8(output).unwrap();
+            let lines: Vec<_> = output.lines().collect();
+            assert_eq!(lines.len(), 4);
+            assert!(lines[1].starts_with("8/8/8/4k3/8/8/3QK3/8 w - - 0 1,"));
+            assert!(lines[2].starts_with("k7/8/1K6/8/8/8/8/7R w - - 0 1,h1h8,#1,"));
+            assert_eq!(lines[3], "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1,,0,0,0");
+        }
+    }
+}
+
+mod serve {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    use serde::Serialize;
+    use weechess_core::{
+        notation::{try_from_notation, Fen},
+        Color, Square, State,
+    };
+
+    #[derive(Serialize)]
+    struct Pressure {
+        white: Vec<u8>,
+        black: Vec<u8>,
+    }
+
+    #[derive(Serialize)]
+    struct Error {
+        error: String,
+    }
+
+    /// Answers requests one at a time until the listener fails
+    pub fn run(listener: TcpListener) -> Result<(), anyhow::Error> {
+        for stream in listener.incoming() {
+            // A client hanging up early shouldn't stop the server
+            if let Err(e) = handle(stream?) {
+                eprintln!("{:#}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        // Skip the headers, nothing is read from the body
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+            header.clear();
+        }
+
+        let (status, body) = respond(&request_line);
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+
+        Ok(())
+    }
+
+    /// The status and JSON body of the response to a request line like `GET /pressure?fen=... HTTP/1.1`
+    pub fn respond(request_line: &str) -> (&'static str, String) {
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return error("400 Bad Request", "malformed request");
+        };
+
+        if method != "GET" {
+            return error("405 Method Not Allowed", "only GET is supported");
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match path {
+            "/pressure" => {
+                let fen = query.split('&').find_map(|pair| pair.strip_prefix("fen="));
+                let state = match fen.map(decode) {
+                    Some(Some(fen)) => match try_from_notation::<_, Fen>(&fen) {
+                        Ok(state) => state,
+                        Err(_) => return error("400 Bad Request", "invalid fen"),
+                    },
+                    Some(None) => return error("400 Bad Request", "invalid fen"),
+                    None => State::default(),
+                };
+
+                let counts = |color| {
+                    let map = state.board().attack_count_map(color);
+                    Square::ALL.iter().map(|square| map[*square]).collect()
+                };
+
+                let pressure = Pressure {
+                    white: counts(Color::White),
+                    black: counts(Color::Black),
+                };
+
+                ("200 OK", serde_json::to_string(&pressure).unwrap())
+            }
+            _ => error("404 Not Found", "unknown path"),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> (&'static str, String) {
+        let error = Error {
+            error: String::from(message),
+        };
+
+        (status, serde_json::to_string(&error).unwrap())
+    }
+
+    /// Decodes a URL query value, where spaces may be written as `+` and anything else as `%XX`
+    fn decode(value: &str) -> Option<String> {
+        let mut bytes = vec![];
+        let mut chars = value.bytes();
+        while let Some(byte) = chars.next() {
+            match byte {
+                b'+' => bytes.push(b' '),
+                b'%' => {
+                    let hex = [chars.next()?, chars.next()?];
+                    bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                }
+                byte => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8(bytes).ok()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_respond() {
+            let (status, body) =
+                respond("GET /pressure?fen=k7%2F8%2F8%2F8%2F8%2F8%2F8%2FR6K+w+-+-+0+1 HTTP/1.1");
+
+            assert_eq!(status, "200 OK");
+            let pressure: serde_json::Value = serde_json::from_str(&body).unwrap();
+            let white = pressure["white"].as_array().unwrap();
+            assert_eq!(white.len(), 64);
+            assert_eq!(white[Square::A8.index()], 1);
+            assert_eq!(white[Square::G1.index()], 2);
+            assert_eq!(pressure["black"][Square::B7.index()], 1);
+
+            let (status, _) = respond("GET /pressure HTTP/1.1");
+            assert_eq!(status, "200 OK");
+
+            let (status, body) = respond("GET /pressure?fen=nonsense HTTP/1.1");
+            assert_eq!(status, "400 Bad Request");
+            assert_eq!(body, r#"{"error":"invalid fen"}"#);
+
+            assert_eq!(respond("GET /other HTTP/1.1").0, "404 Not Found");
+            assert_eq!(
+                respond("POST /pressure HTTP/1.1").0,
+                "405 Method Not Allowed"
+            );
+        }
+    }
+}
+
+mod selfplay {
+    use std::collections::HashMap;
+
+    use rand::{seq::SliceRandom, Rng};
+    use weechess_core::{Color, GameStatus, Move, MoveGenerator, State};
+    use weechess_engine::{
+        adjudication::{Adjudication, AdjudicationPolicy, Adjudicator},
+        eval::Evaluation,
+        searcher::SearchArtifact,
+    };
+
+    /// A position a few random moves into a game, for games to start from
+    pub fn opening<R: Rng>(plies: usize, rng: &mut R) -> State {
+        let mut state = State::default();
+        for _ in 0..plies {
+            let moves = MoveGenerator::compute_legal_moves(&state);
+            match moves.moves().choose(rng) {
+                Some(result) => state = result.1.clone(),
+                None => break,
+            }
+        }
+
+        state
+    }
+
+    /// Plays a game out from the opening, returning how it ended. The side to move picks
+    /// its move with `search`, which is given the tables that side was left with after its
+    /// last search, and `on_move` is called with every move played along with its score.
+    pub fn play<S, F>(
+        opening: &State,
+        adjudication: AdjudicationPolicy,
+        mut search: S,
+        mut on_move: F,
+    ) -> GameStatus
+    where
+        S: FnMut(
+            &State,
+            Option<SearchArtifact>,
+        ) -> (Option<(Move, Evaluation)>, Option<SearchArtifact>),
+        F: FnMut(Color, Move, Evaluation),
+    {
+        let mut state = opening.clone();
+        let mut adjudicator = Adjudicator::new(adjudication);
+        let mut repetitions = HashMap::new();
+
+        // Each side keeps its own tables for the whole game, since setting up
+        // new ones for every move would take longer than the searches themselves
+        let mut artifacts: [Option<SearchArtifact>; 2] = [None, None];
+        loop {
+            let status = state.game_status();
+            if status != GameStatus::Ongoing {
+                return status;
+            }
+
+            let count = repetitions
+                .entry((state.turn_to_move(), state.canonical_key()))
+                .or_insert(0);
+
+            *count += 1;
+            if *count >= 3 || state.clock().halfmove_clock >= 100 {
+                return GameStatus::Drawn;
+            }
+
+            let color = state.turn_to_move();
+            let artifact = match color {
+                Color::White => &mut artifacts[0],
+                Color::Black => &mut artifacts[1],
+            };
+
+            let (result, next_artifact) = search(&state, artifact.take());
+            *artifact = next_artifact;
+
+            let Some((mv, evaluation)) = result else {
+                return GameStatus::Drawn;
+            };
+
+            match adjudicator.record(color, evaluation) {
+                Some(Adjudication::Resign(loser)) => return GameStatus::Won(!loser),
+                Some(Adjudication::Draw | Adjudication::MoveLimit) => return GameStatus::Drawn,
+                None => {}
+            }
+
+            state = match State::by_performing_move(&state, &mv) {
+                Ok(next) => next,
+                Err(..) => return GameStatus::Drawn,
+            };
+
+            on_move(color, mv, evaluation);
+        }
+    }
+}
+
+mod spsa {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use weechess_core::{Color, GameStatus, Move, State};
+    use weechess_engine::{
+        adjudication::AdjudicationPolicy,
+        eval::{Evaluation, Evaluator},
+        searcher::{self, LateMoveReduction, PruningOptions, SearchArtifact},
+    };
+
+    use crate::selfplay;
+
+    /// The exponents the gains decay with, as recommended by Spall
+    const ALPHA: f64 = 0.602;
+    const GAMMA: f64 = 0.101;
+
+    /// The fraction of the iterations used to keep the first steps from being too large
+    const STABILITY: f64 = 0.1;
+
+    const LMR_BASE: &str = "LmrBase";
+    const LMR_DIVISOR: &str = "LmrDivisor";
+    const FUTILITY_MARGIN: &str = "FutilityMargin";
+    const ASPIRATION_WINDOW: &str = "AspirationWindow";
+
+    /// A parameter being tuned, along with the values it's allowed to take
+    #[derive(Debug, Clone)]
+    pub struct Parameter {
+        pub name: String,
+        pub value: f64,
+        pub min: f64,
+        pub max: f64,
+
+        /// How far the value is nudged either way for the first games. This
+        /// shrinks as the tuning goes on, and so does the size of the updates.
+        pub step: f64,
+    }
+
+    /// Every parameter that can be tuned, along with where the tuning starts from. The
+    /// engine doesn't prune like this unless asked to, see `searcher::PruningOptions`.
+    pub fn parameters() -> Vec<Parameter> {
+        [
+            (LMR_BASE, 0.75, 0.0, 3.0, 0.25),
+            (LMR_DIVISOR, 2.25, 1.0, 4.0, 0.25),
+            (FUTILITY_MARGIN, 200.0, 50.0, 600.0, 25.0),
+            (ASPIRATION_WINDOW, 50.0, 10.0, 200.0, 10.0),
+        ]
+        .into_iter()
+        .map(|(name, value, min, max, step)| Parameter {
+            name: name.to_string(),
+            value,
+            min,
+            max,
+            step,
+        })
+        .collect()
+    }
+
+    pub struct Config {
+        pub iterations: usize,
+        pub depth: usize,
+        pub opening_plies: usize,
+        pub adjudication: AdjudicationPolicy,
+        pub seed: u64,
+    }
+
+    /// Tunes the parameters in place. Every iteration nudges all of the parameters in
+    /// random directions, plays a pair of games between the nudged-up and nudged-down
+    /// settings, and moves the parameters towards whichever side did better. After each
+    /// iteration, `on_iteration` gets the iteration number, the updated parameters, and
+    /// the result of the pair of games (between -2 and 2) for the nudged-up side.
+    pub fn tune<F>(parameters: &mut [Parameter], config: &Config, on_iteration: F)
+    where
+        F: FnMut(usize, &[Parameter], f64),
+    {
+        optimize(
+            parameters,
+            config.iterations,
+            config.seed,
+            |parameters, plus, minus, rng| {
+                let plus = pruning(parameters, plus);
+                let minus = pruning(parameters, minus);
+
+                // Both sides play both colors from the same opening
+                let opening = selfplay::opening(config.opening_plies, rng);
+                score(play(&opening, &plus, &minus, config, rng), true)
+                    + score(play(&opening, &minus, &plus, config, rng), false)
+            },
+            on_iteration,
+        );
+    }
+
+    /// Moves the parameters towards the values that `measure` likes best. It's given the
+    /// nudged-up and nudged-down values, and says how much better the nudged-up ones did.
+    fn optimize<M, F>(
+        parameters: &mut [Parameter],
+        iterations: usize,
+        seed: u64,
+        mut measure: M,
+        mut on_iteration: F,
+    ) where
+        M: FnMut(&[Parameter], &[f64], &[f64], &mut StdRng) -> f64,
+        F: FnMut(usize, &[Parameter], f64),
+    {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let stability = iterations as f64 * STABILITY;
+        for k in 0..iterations {
+            let a = 1.0 / (stability + k as f64 + 1.0).powf(ALPHA);
+            let c = 1.0 / (k as f64 + 1.0).powf(GAMMA);
+
+            let deltas: Vec<f64> = parameters
+                .iter()
+                .map(|_| if rng.gen() { 1.0 } else { -1.0 })
+                .collect();
+
+            let nudged = |sign: f64| -> Vec<f64> {
+                parameters
+                    .iter()
+                    .zip(deltas.iter())
+                    .map(|(p, delta)| (p.value + sign * c * p.step * delta).clamp(p.min, p.max))
+                    .collect()
+            };
+
+            let result = measure(parameters, &nudged(1.0), &nudged(-1.0), &mut rng);
+
+            // The gradient is estimated in steps rather than in the parameters' own units,
+            // so that they all move by about as much
+            for (parameter, delta) in parameters.iter_mut().zip(deltas.iter()) {
+                let gradient = result / (2.0 * c * delta);
+                parameter.value = (parameter.value + a * gradient * parameter.step)
+                    .clamp(parameter.min, parameter.max);
+            }
+
+            on_iteration(k, parameters, result);
+        }
+    }
+
+    /// The pruning to search with, taking the values being tuned from `values` and
+    /// the rest from where the tuning starts
+    fn pruning(parameters: &[Parameter], values: &[f64]) -> PruningOptions {
+        let value = |name: &str| {
+            parameters
+                .iter()
+                .zip(values.iter())
+                .find(|(parameter, _)| parameter.name == name)
+                .map(|(_, value)| *value)
+                .or_else(|| {
+                    self::parameters()
+                        .into_iter()
+                        .find(|parameter| parameter.name == name)
+                        .map(|parameter| parameter.value)
+                })
+                .unwrap()
+        };
+
+        PruningOptions {
+            late_move_reduction: Some(LateMoveReduction {
+                base: value(LMR_BASE) as f32,
+                divisor: value(LMR_DIVISOR) as f32,
+            }),
+            futility_margin: Some(Evaluation::from(value(FUTILITY_MARGIN).round() as i32)),
+            aspiration_window: Some(Evaluation::from(value(ASPIRATION_WINDOW).round() as i32)),
+        }
+    }
+
+    // The result of a game for one side, from -1 for a loss to 1 for a win
+    fn score(status: GameStatus, as_white: bool) -> f64 {
+        match status {
+            GameStatus::Won(color) if (color == Color::White) == as_white => 1.0,
+            GameStatus::Won(..) => -1.0,
+            GameStatus::Drawn | GameStatus::Ongoing => 0.0,
+        }
+    }
+
+    /// Plays a game out from the opening, returning how it ended
+    fn play<R: Rng>(
+        opening: &State,
+        white: &PruningOptions,
+        black: &PruningOptions,
+        config: &Config,
+        rng: &mut R,
+    ) -> GameStatus {
+        selfplay::play(
+            opening,
+            config.adjudication,
+            |state, artifact| {
+                let pruning = match state.turn_to_move() {
+                    Color::White => white,
+                    Color::Black => black,
+                };
+
+                search(state, *pruning, config.depth, rng.gen(), artifact)
+            },
+            |_, _, _| {},
+        )
+    }
+
+    fn search(
+        state: &State,
+        pruning: PruningOptions,
+        depth: usize,
+        rng_seed: u64,
+        previous_artifact: Option<SearchArtifact>,
+    ) -> (Option<(Move, Evaluation)>, Option<SearchArtifact>) {
+        let searcher = searcher::Searcher::new();
+        let (search_handle, _, receiver) = searcher.analyze(
+            state.clone(),
+            rng_seed,
+            Evaluator::default(),
+            searcher::SearchOptions {
+                max_depth: Some(depth),
+                pruning,
+                ..Default::default()
+            },
+            previous_artifact,
+        );
+
+        // Everything needed is in the result at the end
+        drop(receiver);
+
+        match search_handle.join() {
+            Ok((artifact, result)) => (
+                result.best_move().map(|mv| (mv, result.evaluation)),
+                Some(artifact),
+            ),
+            Err(..) => (None, None),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_optimize() {
+            let mut parameters = vec![
+                Parameter {
+                    name: String::from("x"),
+                    value: 0.0,
+                    min: -10.0,
+                    max: 10.0,
+                    step: 1.0,
+                },
+                Parameter {
+                    name: String::from("y"),
+                    value: 10.0,
+                    min: 0.0,
+                    max: 20.0,
+                    step: 2.0,
+                },
+            ];
+
+            // The closer to x = 5 and y = 2 the better, in steps
+            let objective =
+                |values: &[f64]| -((values[0] - 5.0).powi(2) + ((values[1] - 2.0) / 2.0).powi(2));
+
+            let mut iterations = 0;
+            optimize(
+                &mut parameters,
+                200,
+                0,
+                |_, plus, minus, _| objective(plus) - objective(minus),
+                |_, _, _| iterations += 1,
+            );
+
+            assert_eq!(iterations, 200);
+            assert!((parameters[0].value - 5.0).abs() < 0.25, "{:?}", parameters);
+            assert!((parameters[1].value - 2.0).abs() < 0.5, "{:?}", parameters);
+        }
+
+        #[test]
+        fn test_pruning() {
+            // Only the tuned parameters take the nudged values
+            let parameters: Vec<_> = self::parameters()
+                .into_iter()
+                .filter(|p| p.name == FUTILITY_MARGIN)
+                .collect();
+
+            let pruning = pruning(&parameters, &[312.6]);
+            assert_eq!(pruning.futility_margin, Some(Evaluation::from(313)));
+            assert_eq!(pruning.aspiration_window, Some(Evaluation::from(50)));
+            assert_eq!(
+                pruning.late_move_reduction,
+                Some(LateMoveReduction {
+                    base: 0.75,
+                    divisor: 2.25,
+                })
+            );
+        }
+    }
+}
+
+mod match_play {
+    use std::{fmt::Display, str::FromStr, thread, time::Duration};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use weechess_core::{Color, GameRecord, GameStatus, Move, State};
+    use weechess_engine::{
+        adjudication::AdjudicationPolicy,
+        eval::{self, Evaluation},
+        searcher::{self, SearchArtifact},
+    };
+
+    use crate::{compare, selfplay};
+
+    /// How much each side gets to search every move
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Budget {
+        Time(Duration),
+
+        /// Keeps deepening until a depth finishes with at least this many nodes searched
+        Nodes(usize),
+    }
+
+    /// The handicap given to the second configuration, or the head start given to the first
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Odds {
+        /// The first configuration gets this many times the second one's time
+        Time(f64),
+
+        /// Node budgets for the first and second configurations. Without a
+        /// budget, the second configuration searches for its usual time.
+        Nodes(usize, Option<usize>),
+    }
+
+    impl Odds {
+        fn budgets(&self, time: Duration) -> (Budget, Budget) {
+            match *self {
+                Odds::Time(factor) => (Budget::Time(time.mul_f64(factor)), Budget::Time(time)),
+                Odds::Nodes(a, b) => (
+                    Budget::Nodes(a),
+                    b.map_or(Budget::Time(time), Budget::Nodes),
+                ),
+            }
+        }
+    }
+
+    impl FromStr for Odds {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let invalid = || format!("invalid odds: {}", s);
+            let (key, value) = s.trim().split_once('=').ok_or_else(invalid)?;
+            match key.trim() {
+                "time" => value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|factor: &f64| *factor > 0.0)
+                    .map(Odds::Time)
+                    .ok_or_else(invalid),
+                "nodes" => {
+                    let (a, b) = match value.split_once('/') {
+                        Some((a, b)) => (a, Some(b)),
+                        None => (value, None),
+                    };
+
+                    let a = a.trim().parse().map_err(|_| invalid())?;
+                    let b = b
+                        .map(|b| b.trim().parse())
+                        .transpose()
+                        .map_err(|_| invalid())?;
+
+                    Ok(Odds::Nodes(a, b))
+                }
+                key => Err(format!("unknown odds: {}", key)),
+            }
+        }
+    }
+
+    impl Display for Odds {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Odds::Time(factor) => write!(f, "time={}", factor),
+                Odds::Nodes(a, None) => write!(f, "nodes={}", a),
+                Odds::Nodes(a, Some(b)) => write!(f, "nodes={}/{}", a, b),
+            }
+        }
+    }
+
+    pub struct Config {
+        pub a: compare::Config,
+        pub b: compare::Config,
+        pub games: usize,
+
+        /// The time the second configuration gets for each move
+        pub time: Duration,
+        pub opening_plies: usize,
+        pub adjudication: AdjudicationPolicy,
+        pub seed: u64,
+    }
+
+    /// The results of a match, from the first configuration's point of view
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct MatchResult {
+        pub wins: usize,
+        pub draws: usize,
+        pub losses: usize,
+    }
+
+    impl MatchResult {
+        pub fn games(&self) -> usize {
+            self.wins + self.draws + self.losses
+        }
+
+        /// The fraction of the points won, counting draws as half a point
+        pub fn score(&self) -> f64 {
+            if self.games() == 0 {
+                return 0.5;
+            }
+
+            (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+        }
+
+        /// The difference in strength the score points to, which isn't
+        /// finite when one side won (or lost) every game
+        pub fn elo_difference(&self) -> Option<f64> {
+            let score = self.score();
+            if score <= 0.0 || score >= 1.0 {
+                return None;
+            }
+
+            Some(400.0 * (score / (1.0 - score)).log10())
+        }
+    }
+
+    impl Display for MatchResult {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+        }
+    }
+
+    /// Plays the games of a match with the given odds, calling `on_game` with the
+    /// game number, the results so far and the game itself after each one. Every
+    /// opening is played twice, with the first configuration taking each color once.
+    pub fn play_match<F>(config: &Config, odds: Odds, mut on_game: F) -> MatchResult
+    where
+        F: FnMut(usize, &MatchResult, &GameRecord),
+    {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let (budget_a, budget_b) = odds.budgets(config.time);
+        let a = (&config.a, budget_a);
+        let b = (&config.b, budget_b);
+
+        let mut result = MatchResult::default();
+        let mut opening = State::default();
+        for game in 0..config.games {
+            let a_plays_white = game % 2 == 0;
+            if a_plays_white {
+                opening = selfplay::opening(config.opening_plies, &mut rng);
+            }
+
+            let (white, black) = if a_plays_white { (a, b) } else { (b, a) };
+            let mut record = GameRecord::new(opening.clone());
+            record.result = play(&mut record, white, black, config.adjudication, &mut rng);
+            match record.result {
+                GameStatus::Won(color) if (color == Color::White) == a_plays_white => {
+                    result.wins += 1
+                }
+                GameStatus::Won(..) => result.losses += 1,
+                GameStatus::Drawn | GameStatus::Ongoing => result.draws += 1,
+            }
+
+            on_game(game, &result, &record);
+        }
+
+        result
+    }
+
+    /// Plays a game out from the record's initial state, adding the moves to it
+    /// along with their scores, and returning how it ended
+    fn play<R: Rng>(
+        record: &mut GameRecord,
+        white: (&compare::Config, Budget),
+        black: (&compare::Config, Budget),
+        adjudication: AdjudicationPolicy,
+        rng: &mut R,
+    ) -> GameStatus {
+        let moves = &mut record.moves;
+        let scores = record.scores.insert(vec![]);
+        selfplay::play(
+            &record.initial_state,
+            adjudication,
+            |state, artifact| {
+                let (config, budget) = match state.turn_to_move() {
+                    Color::White => white,
+                    Color::Black => black,
+                };
+
+                let seed = config.seed.unwrap_or_else(|| rng.gen());
+                search(state, config, budget, seed, artifact)
+            },
+            |color, mv, evaluation| {
+                // Mates score past any material advantage, so they're just kept at the limit
+                let score = i32::from(evaluation.for_color(color));
+                moves.push(mv);
+                scores.push(score.clamp(-(i16::MAX as i32), i16::MAX as i32) as i16);
+            },
+        )
+    }
+
+    fn search(
+        state: &State,
+        config: &compare::Config,
+        budget: Budget,
+        rng_seed: u64,
+        previous_artifact: Option<SearchArtifact>,
+    ) -> (Option<(Move, Evaluation)>, Option<SearchArtifact>) {
+        let mut options = config.options.clone();
+        if let Budget::Nodes(nodes) = budget {
+            options.max_nodes = Some(nodes);
+        }
+
+        let searcher = searcher::Searcher::new();
+        let (search_handle, control, _) = searcher.analyze(
+            state.clone(),
+            rng_seed,
+            eval::Evaluator::default(),
+            options,
+            previous_artifact,
+        );
+
+        if let Budget::Time(time) = budget {
+            // The search may finish on its own before the time is up, in which
+            // case this stop is simply ignored
+            thread::spawn(move || {
+                thread::sleep(time);
+                _ = control.send(searcher::ControlEvent::TimeUp);
+            });
+        }
+
+        match search_handle.join() {
+            Ok((artifact, result)) => (
+                result.best_move().map(|mv| (mv, result.evaluation)),
+                Some(artifact),
+            ),
+            Err(..) => (None, None),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use weechess_core::notation::{into_notation, lan::Lan, try_from_notation, Fen};
+
+        use super::*;
+
+        #[test]
+        fn test_play() {
+            let mut config = compare::Config {
+                seed: Some(0),
+                ..Default::default()
+            };
+
+            config.options.max_depth = Some(4);
+            config.options.max_table_size_mb = Some(1);
+
+            // Mate in 2, which both sides play out at a fixed depth
+            let state = try_from_notation::<_, Fen>("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+            let mut record = GameRecord::new(state);
+            let budget = Budget::Nodes(usize::MAX);
+            let status = play(
+                &mut record,
+                (&config, budget),
+                (&config, budget),
+                AdjudicationPolicy::default(),
+                &mut StdRng::seed_from_u64(0),
+            );
+
+            assert_eq!(status, GameStatus::Won(Color::White));
+            assert_eq!(record.moves.len(), 3);
+            assert_eq!(
+                into_notation::<_, Lan>(record.moves.last().unwrap()).to_string(),
+                "h1h8"
+            );
+
+            // Every move is scored from white's point of view
+            let scores = record.scores.unwrap();
+            assert_eq!(scores.len(), 3);
+            assert!(scores.iter().all(|score| *score > 0), "{:?}", scores);
+        }
+    }
+}
+
+mod analysis {
+    use weechess_core::{
+        pgn::{Nag, Pgn},
+        Move, MoveGenerator, State,
+    };
+    use weechess_engine::{
+        annotation::Annotator,
+        eval::{self, Evaluation},
+        searcher::{self, SearchArtifact},
+    };
+
+    use crate::interrupt;
+
+    /// The evaluation and best move found in a position, see `search`
+    pub type SearchedPosition = (Evaluation, Option<Move>);
+
+    /// Replays a game and searches every position along it, returning the positions
+    /// and what `search` found in each of them. The progress callback is given the
+    /// index of the position about to be searched, and how many there are. Once
+    /// Ctrl+C is pressed, only the positions that were searched in full are returned.
+    pub fn evaluate_game<F>(
+        pgn: &Pgn,
+        max_depth: usize,
+        rng_seed: u64,
+        ctrl_c: Option<&interrupt::CtrlC>,
+        mut progress: F,
+    ) -> anyhow::Result<(Vec<State>, Vec<SearchedPosition>)>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut states = vec![pgn.initial_state.clone()];
+        for annotated_move in pgn.moves.iter() {
+            let state = State::by_performing_move(states.last().unwrap(), &annotated_move.mv)?;
+            states.push(state);
+        }
+
+        let mut artifact = None;
+        let mut evaluations = vec![];
+        let is_interrupted = || ctrl_c.is_some_and(interrupt::CtrlC::is_interrupted);
+        for (i, state) in states.iter().enumerate() {
+            if is_interrupted() {
+                break;
+            }
+
+            progress(i, states.len());
+            let (evaluation, best_move, next_artifact) =
+                search(state, max_depth, rng_seed, artifact.take(), ctrl_c);
+
+            // The search was stopped short of the depth the others got to
+            if is_interrupted() {
+                break;
+            }
+
+            evaluations.push((evaluation, best_move));
+            artifact = next_artifact;
+        }
+
+        Ok((states, evaluations))
+    }
+
+    /// Searches a position, returning the evaluation from the perspective of
+    /// the side to move and the best move, if the game isn't over. Pressing
+    /// Ctrl+C stops the search, if it's being handled.
+    pub fn search(
+        state: &State,
+        max_depth: usize,
+        rng_seed: u64,
+        previous_artifact: Option<SearchArtifact>,
+        ctrl_c: Option<&interrupt::CtrlC>,
+    ) -> (Evaluation, Option<Move>, Option<SearchArtifact>) {
+        if MoveGenerator::compute_legal_moves(state).is_empty() {
+            let evaluation = if state.is_check() {
+                -Evaluation::mate_in_ply(0)
+            } else {
+                Evaluation::EVEN
+            };
+
+            return (evaluation, None, previous_artifact);
+        }
+
+        let searcher = searcher::Searcher::new();
+        let (search_handle, control, receiver) = searcher.analyze(
+            state.clone(),
+            rng_seed,
+            eval::Evaluator::default(),
+            searcher::SearchOptions {
+                max_depth: Some(max_depth),
+                ..Default::default()
+            },
+            previous_artifact,
+        );
+
+        // Everything needed is in the result at the end
+        drop(receiver);
+
+        if let Some(ctrl_c) = ctrl_c {
+            ctrl_c.watch(control);
+        }
+
+        let (artifact, result) = search_handle.join().unwrap();
+        match result.best_move() {
+            Some(mv) => (result.evaluation, Some(mv), Some(artifact)),
+            None => (Evaluation::EVEN, None, Some(artifact)),
+        }
+    }
+
+    /// Attaches an `[%eval]` comment and a glyph judging the move to every
+    /// move in the game, given the evaluation and best move of each position.
+    /// Moves past the positions with evaluations are left as they were.
+    pub fn annotate(pgn: &mut Pgn, states: &[State], evaluations: &[(Evaluation, Option<Move>)]) {
+        let annotator = Annotator::default();
+        let judged = evaluations.len().saturating_sub(1);
+        for (i, annotated_move) in pgn.moves.iter_mut().enumerate().take(judged) {
+            annotated_move.nag = judge(&annotator, states, evaluations, i, annotated_move.mv);
+
+            let next_state = &states[i + 1];
+            if !MoveGenerator::compute_legal_moves(next_state).is_empty() {
+                let white_evaluation = evaluations[i + 1].0.for_color(next_state.turn_to_move());
+
+                annotated_move.comment = Some(format!("[%eval {}]", format_eval(white_evaluation)));
+            }
+        }
+    }
+
+    /// Judges the move played from the position at the given index, where the best move
+    /// counts as being exactly as good as the evaluation of the position it's played in
+    pub fn judge(
+        annotator: &Annotator,
+        states: &[State],
+        evaluations: &[(Evaluation, Option<Move>)],
+        i: usize,
+        played_move: Move,
+    ) -> Option<Nag> {
+        let (best_evaluation, best_move) = evaluations[i];
+        let played_evaluation = match best_move == Some(played_move) {
+            true => best_evaluation,
+            false => -evaluations[i + 1].0,
+        };
+
+        let previous_evaluation = match i {
+            0 => best_evaluation,
+            _ => -evaluations[i - 1].0,
+        };
+
+        annotator.annotate(
+            states[i].phase(),
+            previous_evaluation,
+            played_evaluation,
+            best_evaluation,
+        )
+    }
+
+    fn format_eval(evaluation: Evaluation) -> String {
+        match evaluation.mate_in_moves() {
+            Some(moves) => format!("#{}", moves),
+            None => format!("{:.2}", i32::from(evaluation) as f32 / 100.0),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_annotate_partial() {
+            let mut pgn = Pgn::parse("1. e4 e5 2. Nf3 *").unwrap();
+            let (states, mut evaluations) = evaluate_game(&pgn, 1, 0, None, |_, _| {}).unwrap();
+            assert_eq!(states.len(), 4);
+            assert_eq!(evaluations.len(), 4);
+
+            // Only the moves between two evaluated positions are annotated
+            evaluations.truncate(2);
+            annotate(&mut pgn, &states, &evaluations);
+            assert!(pgn.moves[0].comment.is_some());
+            assert!(pgn.moves[1..].iter().all(|m| m.comment.is_none()));
+        }
+    }
+}
+
+mod puzzles {
+    use weechess_core::{pgn::Nag, Move, MoveGenerator, MoveResult, State};
+    use weechess_engine::{
+        annotation::{self, Annotator},
+        eval::Evaluation,
+    };
+
+    use crate::analysis;
+
+    /// How far ahead (in centipawns) the side to move has to be for a position to be
+    /// a puzzle, and the most any other move can leave them ahead by
+    const MIN_ADVANTAGE: i32 = 200;
+    const MAX_ALTERNATIVE: i32 = 100;
+
+    /// Solutions are cut short after this many plies, ending on a move of the solver
+    const MAX_SOLUTION_PLIES: usize = 5;
+
+    pub struct Puzzle {
+        pub state: State,
+
+        /// The winning move, followed by the best replies and continuations
+        pub solution: Vec<Move>,
+    }
+
+    /// Finds the positions in an analyzed game (see `analysis::evaluate_game`) right after
+    /// a blunder, where the side to move has exactly one winning move. Each of them is
+    /// searched again to the verify depth, along with every other move one ply less deep.
+    pub fn find(
+        states: &[State],
+        evaluations: &[(Evaluation, Option<Move>)],
+        verify_depth: usize,
+        rng_seed: u64,
+    ) -> Vec<Puzzle> {
+        let annotator = Annotator::default();
+        let mut puzzles = vec![];
+        for i in 1..states.len() {
+            // Only whether the move was a blunder matters here, which doesn't
+            // depend on how the game went before it
+            let best_evaluation = evaluations[i - 1].0;
+            let played_evaluation = -evaluations[i].0;
+            let nag = annotator.annotate(
+                states[i - 1].phase(),
+                best_evaluation,
+                played_evaluation,
+                best_evaluation,
+            );
+
+            if nag != Some(Nag::Blunder) || annotation::clamp(evaluations[i].0) < MIN_ADVANTAGE {
+                continue;
+            }
+
+            puzzles.extend(verify(&states[i], verify_depth, rng_seed));
+        }
+
+        puzzles
+    }
+
+    fn verify(state: &State, depth: usize, rng_seed: u64) -> Option<Puzzle> {
+        let (evaluation, best_move, artifact) =
+            analysis::search(state, depth, rng_seed, None, None);
+        let best_move = best_move?;
+        let mut artifact = artifact?;
+        if annotation::clamp(evaluation) < MIN_ADVANTAGE {
+            return None;
+        }
+
+        // The tables still hold the line the winning move was found along
+        let mut solution: Vec<Move> = artifact
+            .hash_line(state)
+            .iter()
+            .map(|ply| ply.mv)
+            .take(MAX_SOLUTION_PLIES)
+            .collect();
+
+        if solution.first() != Some(&best_move) {
+            solution = vec![best_move];
+        } else if solution.len().is_multiple_of(2) {
+            solution.pop();
+        }
+
+        for MoveResult(mv, next_state) in MoveGenerator::compute_legal_moves(state).moves() {
+            if *mv == best_move {
+                continue;
+            }
+
+            let (alternative, _, next_artifact) = analysis::search(
+                next_state,
+                depth.saturating_sub(1).max(1),
+                rng_seed,
+                Some(artifact),
+                None,
+            );
+
+            if annotation::clamp(-alternative) > MAX_ALTERNATIVE {
+                return None;
+            }
+
+            artifact = next_artifact?;
+        }
+
+        Some(Puzzle {
+            state: state.clone(),
+            solution,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use weechess_core::{
+            notation::{into_notation, lan::Lan},
+            pgn::Pgn,
+        };
+
+        use super::*;
+
+        fn puzzles(pgn: &str) -> Vec<(State, Vec<String>)> {
+            let pgn = Pgn::parse(pgn).unwrap();
+            let (states, evaluations) =
+                analysis::evaluate_game(&pgn, 3, 0, None, |_, _| {}).unwrap();
+            find(&states, &evaluations, 3, 0)
+                .into_iter()
+                .map(|puzzle| {
+                    let solution = puzzle
+                        .solution
+                        .iter()
+                        .map(|mv| into_notation::<_, Lan>(mv).to_string())
+                        .collect();
+
+                    (puzzle.state, solution)
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_find() {
+            // The queen is left to be taken by the pawn
+            let found = puzzles(
+                "[FEN \"4k2r/8/4p3/8/8/8/P7/3QK3 w - - 0 1\"]\n\
+                \n\
+                1. Qd5 Kf7 *",
+            );
+
+            assert_eq!(found.len(), 1);
+            let (state, solution) = &found[0];
+            assert_eq!(state.turn_to_move(), weechess_core::Color::Black);
+            assert_eq!(solution.first().map(String::as_str), Some("e6d5"));
+            assert_eq!(solution.len() % 2, 1);
+
+            // Nothing is given away here
+            assert!(puzzles("1. e4 e5 2. Nf3 Nc6 *").is_empty());
+        }
+    }
+}
+
+mod session {
+    use std::{
+        collections::BTreeMap,
+        fs::File,
+        io::{BufReader, BufWriter},
+        path::Path,
+    };
+
+    use serde::{Deserialize, Serialize};
+    use weechess_core::{
+        notation::{into_notation, try_from_notation, Fen},
+        pgn::Pgn,
+        Move, State,
+    };
+    use weechess_engine::searcher::SearchArtifact;
+
+    /// The state of a REPL session that can be saved and resumed later
+    #[derive(Serialize, Deserialize)]
+    pub struct Session {
+        /// The starting position of the game in FEN notation
+        pub fen: String,
+
+        /// The moves played since the starting position
+        pub moves: Vec<Move>,
+
+        /// Comments, keyed by the number of moves played when they were made
+        pub annotations: BTreeMap<usize, String>,
+
+        /// The search tables left over from the last evaluation
+        pub artifact: Option<SearchArtifact>,
+    }
+
+    #[derive(Serialize)]
+    struct SessionRef<'a> {
+        fen: &'a str,
+        moves: &'a [Move],
+        annotations: &'a BTreeMap<usize, String>,
+        artifact: Option<&'a SearchArtifact>,
+    }
+
+    impl Session {
+        pub fn new(state: &State) -> Self {
+            Self {
+                fen: into_notation::<_, Fen>(state).to_string(),
+                moves: vec![],
+                annotations: BTreeMap::new(),
+                artifact: None,
+            }
+        }
+
+        pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+            let reader = BufReader::new(File::open(path)?);
+            Ok(ciborium::from_reader(reader)?)
+        }
+
+        pub fn save<P: AsRef<Path>>(&self, path: P, include_analysis: bool) -> anyhow::Result<()> {
+            let session = SessionRef {
+                fen: &self.fen,
+                moves: &self.moves,
+                annotations: &self.annotations,
+                artifact: self.artifact.as_ref().filter(|_| include_analysis),
+            };
+
+            let writer = BufWriter::new(File::create(path)?);
+            ciborium::into_writer(&session, writer)?;
+            Ok(())
+        }
+
+        /// Starts a session from a game in a PGN file, with the moves played
+        /// up to the given ply (or the whole game)
+        pub fn from_pgn<P: AsRef<Path>>(path: P, ply: Option<usize>) -> anyhow::Result<Self> {
+            let pgn = Pgn::parse(&std::fs::read_to_string(path)?)?;
+            let ply = ply.unwrap_or(pgn.moves.len());
+            if ply > pgn.moves.len() {
+                anyhow::bail!("The game only has {} plies", pgn.moves.len());
+            }
+
+            let mut session = Self::new(&pgn.initial_state);
+            session.moves = pgn.moves[..ply].iter().map(|m| m.mv).collect();
+            Ok(session)
+        }
+
+        /// Replays the session's moves to get the current game state
+        pub fn state(&self) -> anyhow::Result<State> {
+            self.state_at(self.moves.len())
+        }
+
+        /// Replays the first `ply` moves of the session to get the game state at that point
+        pub fn state_at(&self, ply: usize) -> anyhow::Result<State> {
+            let mut state = try_from_notation::<_, Fen>(&self.fen)
+                .map_err(|_| anyhow::anyhow!("Invalid fen: {}", self.fen))?;
+
+            for mv in self.moves.iter().take(ply) {
+                state = State::by_performing_move(&state, mv)?;
+            }
+
+            Ok(state)
+        }
+    }
+}
+
+mod checkpoint {
+    use std::{
+        fs::File,
+        io::{BufReader, BufWriter},
+        path::Path,
+        time::Duration,
+    };
+
+    use serde::{Deserialize, Serialize};
+    use weechess_core::{
+        notation::{into_notation, Fen},
+        Move, State,
+    };
+    use weechess_engine::{eval::Evaluation, searcher::SearchArtifactSnapshot};
 
-            let outer_handle = thread::spawn(move || {
-                let start_time = std::time::Instant::now();
-                let searcher = searcher::Searcher::new();
-                let evaluator = eval::Evaluator::default();
-                let (search_handle, send, recv) =
-                    searcher.analyze(game_state, rng_seed, evaluator, max_depth, None);
-
-                let print_handle = thread::spawn(move || loop {
-                    match recv.recv() {
-                        Ok(e) => {
-                            common::print_search_event(&e, start_time);
-                        }
-                        Err(..) => {
-                            break;
-                        }
-                    }
-                });
+    /// The most table entries a checkpoint keeps. Only the deepest ones are kept,
+    /// since those save the most searching when resuming.
+    pub const MAX_ENTRIES: usize = 1 << 20;
 
-                // Hold onto the sender so that the searcher doesn't get dropped
-                _ = send;
+    /// Everything needed to pick a long evaluation back up where it left off
+    #[derive(Serialize, Deserialize)]
+    pub struct Checkpoint {
+        pub fen: String,
+        pub seed: u64,
+        pub max_depth: Option<usize>,
 
-                search_handle.join().unwrap();
-                print_handle.join().unwrap();
-            });
+        /// The time spent searching so far, and the nodes searched in it, across every run
+        pub elapsed: Duration,
 
-            outer_handle.join().unwrap();
+        #[serde(default)]
+        pub nodes_searched: usize,
 
-            Ok(())
-        }
-        Some(Commands::Perft { fen, depth }) => {
-            let game_state = {
-                if let Some(fen) = &fen {
-                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
-                } else {
-                    State::default()
-                }
-            };
+        /// The deepest depth searched so far, along with its best line
+        pub depth: u32,
+        pub line: Vec<Move>,
+        pub evaluation: Option<Evaluation>,
 
-            let start_time = std::time::Instant::now();
-            let searcher = searcher::Searcher::new();
-            let count = searcher.perft(&game_state, depth, |gs, mv, depth, count| {
-                if depth == 1 {
-                    println!(
-                        "{}: {} [{}]",
-                        into_notation::<_, Peg>(mv),
-                        count,
-                        into_notation::<_, Fen>(gs)
-                    );
-                }
-            });
+        pub artifact: Option<SearchArtifactSnapshot>,
+    }
 
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let nodes_per_second = count as f64 / elapsed;
+    impl Checkpoint {
+        pub fn new(state: &State, seed: u64, max_depth: Option<usize>) -> Self {
+            Self {
+                fen: into_notation::<_, Fen>(state).to_string(),
+                seed,
+                max_depth,
+                elapsed: Duration::ZERO,
+                nodes_searched: 0,
+                depth: 0,
+                line: vec![],
+                evaluation: None,
+                artifact: None,
+            }
+        }
 
-            println!(
-                "\nTotal nodes: {} ({:.2}s : {:.0}nps)",
-                count, elapsed, nodes_per_second
-            );
+        pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+            let reader = BufReader::new(File::open(path)?);
+            Ok(ciborium::from_reader(reader)?)
+        }
 
+        /// Writes the checkpoint next to the file first, so that being interrupted
+        /// partway through doesn't clobber the last good one
+        pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+            let path = path.as_ref();
+            let mut partial = path.as_os_str().to_owned();
+            partial.push(".partial");
+
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            ciborium::into_writer(self, &mut writer)?;
+            writer.into_inner()?.sync_all()?;
+            std::fs::rename(&partial, path)?;
             Ok(())
         }
-        Some(Commands::Repl { fen }) => {
-            let mut game_state = {
-                if let Some(fen) = &fen {
-                    try_from_notation::<_, Fen>(fen).map_err(|_| anyhow::anyhow!("Invalid fen"))?
-                } else {
-                    State::default()
-                }
-            };
 
-            let mut rl = ext::ClapEditor::<repl::Repl>::new();
+        /// Takes in a depth that the search finished, unless an earlier run got further
+        pub fn record_depth(&mut self, depth: u32, line: Vec<Move>, evaluation: Evaluation) {
+            if depth >= self.depth && !line.is_empty() {
+                self.depth = depth;
+                self.line = line;
+                self.evaluation = Some(evaluation);
+            }
+        }
 
-            loop {
-                let Some(repl) = rl.read_command() else {
-                    continue;
-                };
+        /// Takes in the search's tables, along with the time spent searching and the
+        /// nodes searched across every run so far
+        pub fn record_snapshot(
+            &mut self,
+            artifact: SearchArtifactSnapshot,
+            elapsed: Duration,
+            nodes_searched: usize,
+        ) {
+            self.artifact = Some(artifact);
+            self.elapsed = elapsed;
+            self.nodes_searched = nodes_searched;
+        }
+    }
 
-                match repl.command {
-                    Some(repl::Commands::Evaluate { max_depth, seed }) => {
-                        let evaluated_game_state = game_state.clone();
-                        let (tx, rx) = mpsc::channel();
-                        let outer_handle = thread::spawn(move || {
-                            println!("Evaluating positions (press enter to stop)...\n");
-                            let start_time = std::time::Instant::now();
-                            let rx = rx;
-                            let searcher = searcher::Searcher::new();
-                            let evaluator = eval::Evaluator::default();
-                            let rng_seed = seed.unwrap_or_else(rand::random);
-                            let (search_handle, send, recv) = searcher.analyze(
-                                evaluated_game_state,
-                                rng_seed,
-                                evaluator,
-                                max_depth,
-                                None,
-                            );
+    #[cfg(test)]
+    mod tests {
+        use weechess_engine::{
+            eval::Evaluator,
+            searcher::{SearchArtifact, SearchOptions, Searcher},
+        };
 
-                            let print_handle = thread::spawn(move || {
-                                loop {
-                                    match recv.recv() {
-                                        Ok(e) => {
-                                            common::print_search_event(&e, start_time);
-                                        }
-                                        Err(..) => {
-                                            break;
-                                        }
-                                    }
-                                }
+        use super::*;
 
-                                println!("\nEvaluation complete!");
-                            });
+        #[test]
+        fn test_resume() {
+            let state = State::default();
+            let mut checkpoint = Checkpoint::new(&state, 0, Some(3));
+            let (handle, _send, _recv) = Searcher::new().analyze(
+                state.clone(),
+                checkpoint.seed,
+                Evaluator::default(),
+                SearchOptions {
+                    max_depth: checkpoint.max_depth,
+                    max_threads: Some(1),
+                    max_table_size_mb: Some(4),
+                    ..Default::default()
+                },
+                None,
+            );
 
-                            _ = rx.recv().unwrap();
-                            _ = send.send(searcher::ControlEvent::Stop);
-                            search_handle.join().unwrap();
-                            print_handle.join().unwrap();
-                        });
+            let (artifact, result) = handle.join().unwrap();
+            checkpoint.record_depth(result.depth, result.line.to_vec(), result.evaluation);
+            checkpoint.record_snapshot(
+                artifact.snapshot(Some(MAX_ENTRIES)),
+                result.elapsed,
+                result.stats.nodes_searched,
+            );
 
-                        stdin().read_line(&mut String::new())?;
-                        tx.send(()).unwrap();
-                        outer_handle.join().unwrap();
-                    }
-                    Some(repl::Commands::Load { fen }) => match try_from_notation::<_, Fen>(&fen) {
-                        Ok(gs) => {
-                            game_state = gs;
-                            println!("{}", game_state.pretty());
-                        }
-                        Err(..) => {
-                            eprintln!("{} Invalid fen: {}", "[Error]".red(), fen);
-                        }
-                    },
-                    Some(repl::Commands::Quit) => break,
-                    Some(repl::Commands::State) => {
-                        println!("{}", game_state.pretty());
-                    }
-                    None => {}
-                }
-            }
+            let path = std::env::temp_dir()
+                .join(format!("weechess-checkpoint-{}.bin", std::process::id()));
+            checkpoint.save(&path).unwrap();
+            let mut resumed = Checkpoint::open(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
 
-            Ok(())
-        }
-        Some(Commands::Uci) => uci::Client::new()
-            .exec()
-            .context("while running UCI client"),
-        Some(Commands::Version) => {
-            println!("{}", EngineVersion::CURRENT);
-            Ok(())
+            assert_eq!(resumed.fen, checkpoint.fen);
+            assert_eq!(resumed.max_depth, Some(3));
+            assert_eq!(resumed.depth, 3);
+            assert_eq!(resumed.line, checkpoint.line);
+            assert_eq!(resumed.elapsed, result.elapsed);
+            assert_eq!(resumed.nodes_searched, result.stats.nodes_searched);
+
+            // The tables pick up where the search left off
+            let artifact = SearchArtifact::from(resumed.artifact.take().unwrap());
+            assert_eq!(artifact.best_move(&state), resumed.line.first().copied());
         }
-        None => Ok(()),
     }
 }
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("[error] {:#}", e);
-        std::process::exit(1);
+mod interrupt {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    };
+
+    use weechess_engine::searcher::ControlEvent;
+
+    /// The exit status after being interrupted, which is the usual 128 + SIGINT
+    pub const EXIT_CODE: i32 = 130;
+
+    /// Stops the search being watched when Ctrl+C is pressed, instead of letting the
+    /// process die, so that whatever it found so far can still be printed. Pressing
+    /// Ctrl+C a second time gives up on that and exits right away.
+    pub struct CtrlC {
+        search: Arc<Mutex<Option<mpsc::Sender<ControlEvent>>>>,
+        interrupted: Arc<AtomicBool>,
     }
-}
 
-mod common {
-    use colored::Colorize;
-    use weechess_core::notation::{into_notation, Peg};
-    use weechess_engine::searcher;
+    impl CtrlC {
+        /// Takes over Ctrl+C for the rest of the process, which can only be done once
+        pub fn install() -> anyhow::Result<Self> {
+            let search: Arc<Mutex<Option<mpsc::Sender<ControlEvent>>>> = Default::default();
+            let interrupted = Arc::new(AtomicBool::new(false));
 
-    pub fn print_search_event(event: &searcher::StatusEvent, start_time: std::time::Instant) {
-        match event {
-            searcher::StatusEvent::BestMove { line, evaluation } => {
-                let line = line
-                    .iter()
-                    .map(|m| into_notation::<_, Peg>(m).to_string())
-                    .collect::<Vec<_>>()
-                    .join(" ");
+            ctrlc::set_handler({
+                let search = search.clone();
+                let interrupted = interrupted.clone();
+                move || {
+                    if interrupted.swap(true, Ordering::SeqCst) {
+                        std::process::exit(EXIT_CODE);
+                    }
 
-                println!("[{}] ({}) {}", "Best Move".bright_green(), evaluation, line);
-            }
-            searcher::StatusEvent::Progress {
-                depth,
-                nodes_searched,
-                transposition_saturation,
-            } => {
-                let elapsed = start_time.elapsed().as_secs_f64();
-                let nodes_per_second = *nodes_searched as f64 / elapsed;
-                let f = format!(
-                    "time={:.3} depth={} nodes={} nps={:.0} tts={:.6}%",
-                    elapsed,
-                    depth,
-                    nodes_searched,
-                    nodes_per_second,
-                    transposition_saturation * 100.0
-                );
-                println!("[{} ] {}", "Progress".dimmed(), f.dimmed());
-            }
-            searcher::StatusEvent::Warning { message, .. } => {
-                println!("[{}  ] {}", "Warning".bright_red(), message);
+                    if let Some(send) = search.lock().unwrap().as_ref() {
+                        _ = send.send(ControlEvent::Stop);
+                    }
+                }
+            })?;
+
+            Ok(Self {
+                search,
+                interrupted,
+            })
+        }
+
+        /// Makes Ctrl+C stop the search with the given controller, until the next one is
+        /// watched. A search started after Ctrl+C was already pressed is stopped right away.
+        pub fn watch(&self, send: mpsc::Sender<ControlEvent>) {
+            if self.is_interrupted() {
+                _ = send.send(ControlEvent::Stop);
             }
+
+            *self.search.lock().unwrap() = Some(send);
+        }
+
+        pub fn is_interrupted(&self) -> bool {
+            self.interrupted.load(Ordering::SeqCst)
         }
     }
 }
 
 mod repl {
 
-    use clap::{Parser, Subcommand};
+    use clap::{Parser, Subcommand, ValueEnum};
+    use weechess_core::notation::MoveNotation;
+    use weechess_engine::{eval, searcher, uci};
+
+    /// How many of the positions loaded before `load` offers to complete
+    pub const MAX_RECENT_FENS: usize = 20;
 
     #[derive(Parser)]
     #[command(name = "repl")]
@@ -308,6 +4329,18 @@ mod repl {
 
     #[derive(Subcommand)]
     pub enum Commands {
+        /// Attach a comment to the current position
+        #[command(visible_aliases = ["c"])]
+        Comment {
+            /// The text of the comment
+            #[arg(required = true)]
+            text: Vec<String>,
+        },
+
+        /// Show what changed on the board with the last move
+        #[command(visible_aliases = ["d"])]
+        Diff,
+
         /// Evaluate the current position
         #[command(visible_aliases = ["e"])]
         Evaluate {
@@ -320,11 +4353,57 @@ mod repl {
             seed: Option<u64>,
         },
 
-        /// Load a new game state from a FEN string
+        /// Show the current values of the search options, or just the named one
+        Get {
+            /// The name of the option
+            name: Option<String>,
+        },
+
+        /// Load a new game state from a FEN string, a PGN file or a pasted board diagram
         #[command(visible_aliases = ["l"])]
         Load {
             /// Starting position in FEN notation
+            #[arg(short, long, required_unless_present_any = ["pgn", "diagram"], conflicts_with_all = ["pgn", "diagram"])]
+            fen: Option<String>,
+
+            /// A PGN file to load the game from
+            #[arg(short, long, conflicts_with = "diagram")]
+            pgn: Option<String>,
+
+            /// The number of half moves of the game to play out, defaults to the whole game
+            #[arg(long, requires = "pgn")]
+            ply: Option<usize>,
+
+            /// Paste a board diagram on the following lines, ending it with an empty line
             #[arg(short, long)]
+            diagram: bool,
+
+            /// The color to move in the pasted diagram
+            #[arg(long, requires = "diagram", default_value = "white", value_parser = ["white", "black"])]
+            turn: String,
+        },
+
+        /// List the legal moves in the current position, most promising first
+        Moves,
+
+        /// Play a move in the current position
+        #[command(visible_aliases = ["m"])]
+        Move {
+            /// The move to play in SAN or UCI notation (ex. `Nf3` or `g1f3`)
+            san: String,
+        },
+
+        /// Resume a session previously saved to a file
+        #[command(visible_aliases = ["o"])]
+        Open {
+            /// The file to read the session from
+            file: String,
+        },
+
+        /// Show the line stored in the tables from the last evaluation, with the depth and bound of each move
+        Pv {
+            /// The position to start from in FEN notation, or `current` for the current position
+            #[arg(default_value = "current")]
             fen: String,
         },
 
@@ -332,28 +4411,281 @@ mod repl {
         #[command(visible_aliases = ["q"])]
         Quit,
 
+        /// Save the current session to a file
+        Save {
+            /// The file to write the session to
+            file: String,
+
+            /// Include the transposition tables from the last evaluation
+            #[arg(short, long)]
+            analysis: bool,
+        },
+
+        /// Change a search option, like `setoption` does for a UCI engine (see `get`)
+        Set {
+            /// The name of the option, like `Threads` or `Weight_Tempo`
+            name: String,
+
+            /// The new value of the option
+            value: String,
+        },
+
+        /// Show something about the current position
+        Show {
+            /// What to show
+            what: Showable,
+        },
+
         /// Print out the current state of the board
         #[command(visible_aliases = ["s"])]
         State,
     }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    pub enum Showable {
+        /// How many more white pieces than black ones attack each square
+        Pressure,
+    }
+
+    /// The search options that can be changed with `set`, which are named after (and
+    /// have the same bounds as) the engine's UCI options
+    pub struct Settings {
+        /// How many megabytes the tables can grow to, where 0 keeps them at their initial size
+        pub hash: usize,
+
+        /// How many threads to search with, or none to leave it to the engine
+        pub threads: Option<usize>,
+        pub multi_pv: usize,
+        pub evaluator: eval::Evaluator,
+
+        /// How moves are written in evaluations, move lists and lines from the tables
+        pub notation: MoveNotation,
+    }
+
+    impl Settings {
+        /// Changes an option, returning whether scores from before the change can't be
+        /// trusted anymore (like after changing the weight of an evaluation term)
+        pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<bool> {
+            let parse = |min: usize, max: usize| {
+                value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (min..=max).contains(n))
+                    .ok_or_else(|| anyhow::anyhow!("{} must be between {} and {}", name, min, max))
+            };
+
+            if name.eq_ignore_ascii_case("Hash") {
+                self.hash = parse(0, usize::MAX)?;
+            } else if name.eq_ignore_ascii_case("Threads") {
+                self.threads = Some(parse(1, uci::MAX_THREADS)?);
+            } else if name.eq_ignore_ascii_case("MultiPV") {
+                self.multi_pv = parse(1, uci::MAX_MULTI_PV)?;
+            } else if name.eq_ignore_ascii_case("Notation") {
+                self.notation = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Notation must be san, lan or peg"))?;
+            } else if let Some((term, _)) = self.weight(name) {
+                let percent = parse(0, uci::MAX_WEIGHT_PERCENT as usize)?;
+                let evaluator = std::mem::take(&mut self.evaluator);
+                self.evaluator = evaluator.with_weight(term, percent as f32 / 100.0);
+                return Ok(true);
+            } else {
+                anyhow::bail!("Unknown option: {}", name);
+            }
+
+            Ok(false)
+        }
+
+        /// The names and current values of all the options, in the order `uci` lists them
+        pub fn values(&self) -> Vec<(String, String)> {
+            let mut values = vec![
+                ("Hash".to_string(), self.hash.to_string()),
+                (
+                    "Threads".to_string(),
+                    self.threads
+                        .unwrap_or(searcher::DEFAULT_MAX_THREAD_COUNT)
+                        .to_string(),
+                ),
+                ("MultiPV".to_string(), self.multi_pv.to_string()),
+                ("Notation".to_string(), self.notation.to_string()),
+            ];
+
+            values.extend(self.evaluator.weights().map(|(term, weight)| {
+                (
+                    format!("{}{}", uci::WEIGHT_OPTION_PREFIX, term),
+                    (weight * 100.0).round().to_string(),
+                )
+            }));
+
+            values
+        }
+
+        pub fn search_options(&self) -> searcher::SearchOptions {
+            searcher::SearchOptions {
+                max_table_size_mb: (self.hash > 0).then_some(self.hash),
+                max_threads: self.threads,
+                multi_pv: Some(self.multi_pv),
+                ..Default::default()
+            }
+        }
+
+        fn weight(&self, name: &str) -> Option<(&'static str, f32)> {
+            let term = name
+                .get(..uci::WEIGHT_OPTION_PREFIX.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(uci::WEIGHT_OPTION_PREFIX))
+                .map(|_| &name[uci::WEIGHT_OPTION_PREFIX.len()..])?;
+
+            self.evaluator
+                .weights()
+                .find(|(name, _)| name.eq_ignore_ascii_case(term))
+        }
+    }
+
+    impl Default for Settings {
+        fn default() -> Self {
+            Self {
+                hash: 0,
+                threads: None,
+                multi_pv: 1,
+                evaluator: eval::Evaluator::default(),
+                notation: MoveNotation::San,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_settings() {
+            let mut settings = Settings::default();
+            let value = |settings: &Settings, name: &str| {
+                settings
+                    .values()
+                    .into_iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, value)| value)
+            };
+
+            // Names are matched like UCI options, ignoring case
+            assert!(!settings.set("hash", "64").unwrap());
+            assert!(!settings.set("Threads", "2").unwrap());
+            assert!(!settings.set("MULTIPV", "3").unwrap());
+            assert!(!settings.set("Notation", "lan").unwrap());
+            assert_eq!(value(&settings, "Hash").as_deref(), Some("64"));
+            assert_eq!(value(&settings, "Threads").as_deref(), Some("2"));
+            assert_eq!(value(&settings, "MultiPV").as_deref(), Some("3"));
+            assert_eq!(settings.notation, MoveNotation::Lan);
+
+            let options = settings.search_options();
+            assert_eq!(options.max_table_size_mb, Some(64));
+            assert_eq!(options.max_threads, Some(2));
+            assert_eq!(options.multi_pv, Some(3));
+
+            // Changing a weight makes earlier scores stale
+            assert_eq!(value(&settings, "Weight_Tempo").as_deref(), Some("100"));
+            assert!(settings.set("weight_tempo", "50").unwrap());
+            assert_eq!(value(&settings, "Weight_Tempo").as_deref(), Some("50"));
+
+            // Values out of bounds are rejected, and leave the option alone
+            assert!(settings.set("Threads", "0").is_err());
+            assert!(settings.set("MultiPV", "many").is_err());
+            assert!(settings.set("Weight_Tempo", "1001").is_err());
+            assert!(settings.set("Notation", "fen").is_err());
+            assert!(settings.set("Weight_Nothing", "10").is_err());
+            assert!(settings.set("Ponder", "true").is_err());
+            assert_eq!(value(&settings, "Threads").as_deref(), Some("2"));
+            assert_eq!(value(&settings, "Weight_Tempo").as_deref(), Some("50"));
+        }
+    }
 }
 
 mod ext {
-    use std::{borrow::Cow, marker::PhantomData, process::exit};
+    use std::{borrow::Cow, collections::HashMap, marker::PhantomData, process::exit};
 
     use clap::Parser;
     use console::style;
     use rustyline::{
         completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Cmd,
-        Editor, Event, Helper, KeyCode, KeyEvent, Modifiers,
+        CompletionType, ConditionalEventHandler, Config, Editor, Event, EventContext, EventHandler,
+        Helper, KeyCode, KeyEvent, Modifiers, RepeatCount,
     };
 
+    /// Tab accepts the hint for the subcommand being typed if there is one, and
+    /// otherwise completes as usual
+    struct TabHandler;
+
+    impl TabHandler {
+        fn command(has_hint: bool) -> Option<Cmd> {
+            has_hint.then_some(Cmd::CompleteHint)
+        }
+    }
+
+    impl ConditionalEventHandler for TabHandler {
+        fn handle(
+            &self,
+            _evt: &Event,
+            _n: RepeatCount,
+            _positive: bool,
+            ctx: &EventContext,
+        ) -> Option<Cmd> {
+            Self::command(ctx.has_hint())
+        }
+    }
+
     struct ClapEditorHelper<C: Parser> {
         c_phantom: PhantomData<C>,
+
+        /// What the arguments of each subcommand can be completed with, by subcommand name
+        completions: HashMap<String, Vec<String>>,
     }
 
     impl<C: Parser> Completer for ClapEditorHelper<C> {
-        type Candidate = &'static str;
+        type Candidate = String;
+
+        fn complete(
+            &self,
+            line: &str,
+            pos: usize,
+            _ctx: &rustyline::Context<'_>,
+        ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
+            let line = &line[..pos];
+            let command = C::command();
+            let Some((name, rest)) = line.trim_start().split_once(char::is_whitespace) else {
+                // Still typing the subcommand itself
+                let prefix = line.trim_start();
+                let names = command
+                    .get_subcommands()
+                    .map(|c| c.get_name())
+                    .filter(|name| name.starts_with(prefix))
+                    .map(|name| format!("{} ", name))
+                    .collect();
+
+                return Ok((pos - prefix.len(), names));
+            };
+
+            let Some(subcommand) = command
+                .get_subcommands()
+                .find(|c| c.get_name() == name || c.get_all_aliases().any(|a| a == name))
+            else {
+                return Ok((pos, vec![]));
+            };
+
+            // Everything after the subcommand is completed as a whole, since
+            // candidates (like a quoted FEN) can contain spaces of their own
+            let rest = rest.trim_start();
+            let candidates = self
+                .completions
+                .get(subcommand.get_name())
+                .into_iter()
+                .flatten()
+                .filter(|candidate| candidate.starts_with(rest))
+                .cloned()
+                .collect();
+
+            Ok((pos - rest.len(), candidates))
+        }
     }
 
     impl<C: Parser> Highlighter for ClapEditorHelper<C> {
@@ -402,13 +4734,19 @@ mod ext {
 
     impl<C: Parser> ClapEditor<C> {
         fn construct(prompt: String) -> Self {
-            let mut rl = Editor::<ClapEditorHelper<C>, _>::new().unwrap();
+            // Listing the candidates beats cycling through them, since there
+            // can be a lot of them (like all the moves in a position)
+            let config = Config::builder()
+                .completion_type(CompletionType::List)
+                .build();
+            let mut rl = Editor::<ClapEditorHelper<C>, _>::with_config(config).unwrap();
             rl.set_helper(Some(ClapEditorHelper {
                 c_phantom: PhantomData,
+                completions: HashMap::new(),
             }));
             rl.bind_sequence(
                 Event::KeySeq(vec![KeyEvent(KeyCode::Tab, Modifiers::NONE)]),
-                Cmd::CompleteHint,
+                EventHandler::Conditional(Box::new(TabHandler)),
             );
             ClapEditor { rl, prompt }
         }
@@ -418,6 +4756,21 @@ mod ext {
             Self::construct(style("> ").cyan().bright().to_string())
         }
 
+        /// Sets what tab completes the arguments of a subcommand with, like the moves that
+        /// can be played in the current position. Aliases of the subcommand complete the same.
+        pub fn set_completions(&mut self, subcommand: &str, candidates: Vec<String>) {
+            if let Some(helper) = self.rl.helper_mut() {
+                helper
+                    .completions
+                    .insert(subcommand.to_string(), candidates);
+            }
+        }
+
+        /// Reads a single line of input that isn't a command, or nothing if reading was cut short
+        pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+            self.rl.readline(prompt).ok()
+        }
+
         pub fn read_command(&mut self) -> Option<C> {
             let line = match self.rl.readline(&self.prompt) {
                 Ok(x) => x,
@@ -456,4 +4809,53 @@ mod ext {
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use rustyline::{history::DefaultHistory, Context};
+
+        use super::*;
+        use crate::repl::Repl;
+
+        fn complete(helper: &ClapEditorHelper<Repl>, line: &str) -> (usize, Vec<String>) {
+            let history = DefaultHistory::new();
+            helper
+                .complete(line, line.len(), &Context::new(&history))
+                .unwrap()
+        }
+
+        #[test]
+        fn test_completion() {
+            let mut helper = ClapEditorHelper::<Repl> {
+                c_phantom: PhantomData,
+                completions: HashMap::new(),
+            };
+
+            helper.completions.insert(
+                "move".to_string(),
+                vec!["e4".to_string(), "e3".to_string(), "Nf3".to_string()],
+            );
+
+            // Subcommands complete by name
+            let (start, names) = complete(&helper, "mo");
+            assert_eq!(start, 0);
+            assert!(names.contains(&"move ".to_string()));
+            assert!(names.contains(&"moves ".to_string()));
+
+            // Their arguments complete with what was set, through aliases too
+            assert_eq!(
+                complete(&helper, "move e"),
+                (5, vec!["e4".into(), "e3".into()])
+            );
+            assert_eq!(complete(&helper, "m N"), (2, vec!["Nf3".into()]));
+            assert_eq!(complete(&helper, "load x"), (5, vec![]));
+
+            // Tab accepts a hint when there is one, and otherwise completes
+            let history = DefaultHistory::new();
+            let hint = helper.hint("qu", 2, &Context::new(&history));
+            assert_eq!(hint.as_deref(), Some("it"));
+            assert_eq!(TabHandler::command(true), Some(Cmd::CompleteHint));
+            assert_eq!(TabHandler::command(false), None);
+        }
+    }
 }
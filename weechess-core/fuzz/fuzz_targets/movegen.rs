@@ -0,0 +1,30 @@
+#![no_main]
+#![feature(generic_const_exprs)]
+
+use libfuzzer_sys::fuzz_target;
+use weechess_core::{notation::into_notation, notation::Fen, reference, MoveGenerator, State};
+
+// Each input byte picks one of the legal moves, so the fuzzer
+// explores games rather than arbitrary (and likely invalid) positions
+fuzz_target!(|data: &[u8]| {
+    let mut state = State::default();
+    for choice in data {
+        if let Err(e) = reference::verify(&state) {
+            let shrunk_state = reference::shrink(&state);
+            panic!(
+                "move generation mismatch in {} (shrunk: {}): {}",
+                into_notation::<_, Fen>(&state),
+                into_notation::<_, Fen>(&shrunk_state),
+                e
+            );
+        }
+
+        let legal_moves = MoveGenerator::compute_legal_moves(&state);
+        let moves = legal_moves.moves();
+        if moves.is_empty() {
+            break;
+        }
+
+        state = moves[*choice as usize % moves.len()].1.clone();
+    }
+});
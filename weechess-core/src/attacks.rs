@@ -2,7 +2,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::utils::{ArrayKey, ArrayMap, Index};
 
-use super::{BitBoard, Color, Offset, Piece, PieceIndex, Square};
+use super::{BitBoard, Color, File, Offset, Piece, PieceIndex, Square, FILE_MASKS};
 
 #[repr(u8)]
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
@@ -58,6 +58,12 @@ impl Into<Offset> for Direction {
 pub struct AttackGenerator;
 
 impl AttackGenerator {
+    /// Builds the attack tables now, instead of on their first use. Building the magic
+    /// tables takes a noticeable moment, which is better spent before a search starts.
+    pub fn initialize_tables() {
+        data::initialize();
+    }
+
     pub fn compute(piece: PieceIndex, square: Square, occupancy: BitBoard) -> BitBoard {
         const COMPUTE_MAP: ArrayMap<Piece, fn(Color, Square, BitBoard) -> BitBoard> =
             ArrayMap::new([
@@ -107,6 +113,45 @@ impl AttackGenerator {
     pub fn compute_pawn_attacks(square: Square, color: Color) -> BitBoard {
         data::PAWN_ATTACKS[color][square]
     }
+
+    /// The squares attacked by any of the given pawns, computed all at once by shifting
+    pub fn pawn_attacks_bb(pawns: BitBoard, color: Color) -> BitBoard {
+        let (west, east) = Self::file_neighbours(pawns);
+        let attacks = west | east;
+        match color {
+            Color::White => attacks << 8,
+            Color::Black => attacks >> 8,
+        }
+        .into()
+    }
+
+    /// The squares attacked by any of the given knights, computed all at once by shifting
+    pub fn knight_attacks_bb(knights: BitBoard) -> BitBoard {
+        let (west, east) = Self::file_neighbours(knights);
+        let one_file = west | east;
+
+        let west: u64 = (knights & !FILE_MASKS[File::A] & !FILE_MASKS[File::B]).into();
+        let east: u64 = (knights & !FILE_MASKS[File::G] & !FILE_MASKS[File::H]).into();
+        let two_files = (west >> 2) | (east << 2);
+
+        ((one_file << 16) | (one_file >> 16) | (two_files << 8) | (two_files >> 8)).into()
+    }
+
+    /// The squares attacked by any of the given kings, computed all at once by shifting
+    pub fn king_attacks_bb(kings: BitBoard) -> BitBoard {
+        let (west, east) = Self::file_neighbours(kings);
+        let kings: u64 = kings.into();
+        let row = west | east | kings;
+        ((row << 8) | (row >> 8) | west | east).into()
+    }
+
+    /// Every square one file to the west and east of the given squares, without
+    /// wrapping around the edges of the board
+    fn file_neighbours(squares: BitBoard) -> (u64, u64) {
+        let west: u64 = (squares & !FILE_MASKS[File::A]).into();
+        let east: u64 = (squares & !FILE_MASKS[File::H]).into();
+        (west >> 1, east << 1)
+    }
 }
 
 mod data {
@@ -142,6 +187,16 @@ mod data {
         static ref RAYS: ArrayMap<Direction, SquareMap<BitBoard>> = compute_rays();
     }
 
+    pub fn initialize() {
+        lazy_static::initialize(&ROOK_MAGIC_TABLE);
+        lazy_static::initialize(&ROOK_SLIDE_MASKS);
+        lazy_static::initialize(&BISHOP_MAGIC_TABLE);
+        lazy_static::initialize(&BISHOP_SLIDE_MASKS);
+        lazy_static::initialize(&KNIGHT_ATTACKS);
+        lazy_static::initialize(&KING_ATTACKS);
+        lazy_static::initialize(&PAWN_ATTACKS);
+    }
+
     fn compute_knight_attacks() -> SquareMap<BitBoard> {
         const OFFSETS: [Offset; 8] = [
             Offset { file: 1, rank: 2 },
@@ -254,23 +309,23 @@ mod data {
         let right_ray = RAYS[Direction::East][square];
 
         attacks |= up_ray;
-        if let Some(bit) = (up_ray & blockers).first_one() {
-            attacks &= !RAYS[Direction::North][Square::from(bit)];
+        if let Some(square) = (up_ray & blockers).first_square() {
+            attacks &= !RAYS[Direction::North][square];
         }
 
         attacks |= down_ray;
-        if let Some(bit) = (down_ray & blockers).last_one() {
-            attacks &= !RAYS[Direction::South][Square::from(bit)];
+        if let Some(square) = (down_ray & blockers).last_square() {
+            attacks &= !RAYS[Direction::South][square];
         }
 
         attacks |= left_ray;
-        if let Some(bit) = (left_ray & blockers).last_one() {
-            attacks &= !RAYS[Direction::West][Square::from(bit)];
+        if let Some(square) = (left_ray & blockers).last_square() {
+            attacks &= !RAYS[Direction::West][square];
         }
 
         attacks |= right_ray;
-        if let Some(bit) = (right_ray & blockers).first_one() {
-            attacks &= !RAYS[Direction::East][Square::from(bit)];
+        if let Some(square) = (right_ray & blockers).first_square() {
+            attacks &= !RAYS[Direction::East][square];
         }
 
         attacks
@@ -320,23 +375,23 @@ mod data {
         let south_east_ray = RAYS[Direction::SouthEast][square];
 
         attacks |= north_west_ray;
-        if let Some(bit) = (north_west_ray & blockers).first_one() {
-            attacks &= !RAYS[Direction::NorthWest][Square::from(bit)];
+        if let Some(square) = (north_west_ray & blockers).first_square() {
+            attacks &= !RAYS[Direction::NorthWest][square];
         }
 
         attacks |= south_west_ray;
-        if let Some(bit) = (south_west_ray & blockers).last_one() {
-            attacks &= !RAYS[Direction::SouthWest][Square::from(bit)];
+        if let Some(square) = (south_west_ray & blockers).last_square() {
+            attacks &= !RAYS[Direction::SouthWest][square];
         }
 
         attacks |= north_east_ray;
-        if let Some(bit) = (north_east_ray & blockers).first_one() {
-            attacks &= !RAYS[Direction::NorthEast][Square::from(bit)];
+        if let Some(square) = (north_east_ray & blockers).first_square() {
+            attacks &= !RAYS[Direction::NorthEast][square];
         }
 
         attacks |= south_east_ray;
-        if let Some(bit) = (south_east_ray & blockers).last_one() {
-            attacks &= !RAYS[Direction::SouthEast][Square::from(bit)];
+        if let Some(square) = (south_east_ray & blockers).last_square() {
+            attacks &= !RAYS[Direction::SouthEast][square];
         }
 
         attacks
@@ -579,6 +634,38 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_batch_attacks() {
+        let squares = [Square::A1, Square::H4, Square::D5, Square::B7, Square::G8];
+        let bb = squares.iter().fold(BitBoard::ZERO, |mut bb, sq| {
+            bb.set(*sq, true);
+            bb
+        });
+
+        let union = |f: &dyn Fn(Square) -> BitBoard| {
+            squares
+                .iter()
+                .fold(BitBoard::ZERO, |acc, square| acc | f(*square))
+        };
+
+        assert_eq!(
+            AttackGenerator::knight_attacks_bb(bb),
+            union(&AttackGenerator::compute_knight_attacks)
+        );
+
+        assert_eq!(
+            AttackGenerator::king_attacks_bb(bb),
+            union(&AttackGenerator::compute_king_attacks)
+        );
+
+        for color in Color::ALL {
+            assert_eq!(
+                AttackGenerator::pawn_attacks_bb(bb, *color),
+                union(&|sq| AttackGenerator::compute_pawn_attacks(sq, *color))
+            );
+        }
+    }
+
     #[test]
     fn test_king_attacks() {
         let attacks = AttackGenerator::compute_king_attacks(Square::A3);
@@ -9,6 +9,30 @@ use super::{
     FILE_MASKS,
 };
 
+/// Lets ranges of files, ranks and squares be iterated over (ex. `Square::A1..=Square::H1`)
+macro_rules! impl_step {
+    ($t:ty, $count:expr) => {
+        impl std::iter::Step for $t {
+            fn steps_between(start: &Self, end: &Self) -> (usize, Option<usize>) {
+                usize::steps_between(&(start.0 as usize), &(end.0 as usize))
+            }
+
+            fn forward_checked(start: Self, count: usize) -> Option<Self> {
+                (start.0 as usize)
+                    .checked_add(count)
+                    .filter(|index| *index < $count)
+                    .map(|index| Self(index as u8))
+            }
+
+            fn backward_checked(start: Self, count: usize) -> Option<Self> {
+                (start.0 as usize)
+                    .checked_sub(count)
+                    .map(|index| Self(index as u8))
+            }
+        }
+    };
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Side {
     King,
@@ -44,7 +68,37 @@ impl ArrayKey for Side {
     const COUNT: usize = 2;
 }
 
+/// The shade of a square, which a bishop never leaves
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquareColor {
+    Light,
+    Dark,
+}
+
+impl SquareColor {
+    pub const ALL: &'static [Self] = &[Self::Light, Self::Dark];
+
+    /// All the squares of this color
+    pub fn squares(self) -> BitBoard {
+        match self {
+            SquareColor::Light => BitBoard::LIGHT_SQUARES,
+            SquareColor::Dark => BitBoard::DARK_SQUARES,
+        }
+    }
+}
+
+impl Not for SquareColor {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            SquareColor::Light => SquareColor::Dark,
+            SquareColor::Dark => SquareColor::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct File(u8);
 
 impl File {
@@ -68,6 +122,14 @@ impl File {
         Self::H,
     ];
 
+    pub const fn new(index: u8) -> Option<Self> {
+        if index > 7 {
+            None
+        } else {
+            Some(Self(index))
+        }
+    }
+
     pub fn index(self) -> usize {
         self.0 as usize
     }
@@ -140,11 +202,21 @@ impl From<File> for Index {
     }
 }
 
+impl TryFrom<usize> for File {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Self::from_index(value).ok_or(())
+    }
+}
+
+impl_step!(File, 8);
+
 impl ArrayKey for File {
     const COUNT: usize = 8;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Rank(u8);
 
 impl Rank {
@@ -168,6 +240,14 @@ impl Rank {
         Self::EIGHT,
     ];
 
+    pub const fn new(index: u8) -> Option<Self> {
+        if index > 7 {
+            None
+        } else {
+            Some(Self(index))
+        }
+    }
+
     pub fn index(self) -> usize {
         self.0 as usize
     }
@@ -193,6 +273,15 @@ impl Rank {
         Self(7 - self.0)
     }
 
+    /// The rank as seen from the given side of the board, so that
+    /// `Rank::ONE.relative_to(color)` is always that color's home rank
+    pub fn relative_to(self, color: Color) -> Self {
+        match color {
+            Color::White => self,
+            Color::Black => self.opposing_rank(),
+        }
+    }
+
     pub fn abs_distance_to(self, other: Self) -> u8 {
         debug_assert!(self.0 < 8);
         debug_assert!(other.0 < 8);
@@ -223,6 +312,16 @@ impl From<Rank> for Index {
     }
 }
 
+impl TryFrom<usize> for Rank {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Self::from_index(value).ok_or(())
+    }
+}
+
+impl_step!(Rank, 8);
+
 impl ArrayKey for Rank {
     const COUNT: usize = 8;
 }
@@ -251,7 +350,7 @@ impl Add for Offset {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Square(u8);
 
 impl Square {
@@ -387,6 +486,22 @@ impl Square {
         Self::H8,
     ];
 
+    pub const fn new(file: File, rank: Rank) -> Self {
+        Self(rank.0 * 8 + file.0)
+    }
+
+    pub const fn from_index(index: usize) -> Option<Self> {
+        if index > 63 {
+            None
+        } else {
+            Some(Self(index as u8))
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
     pub fn file(self) -> File {
         File(self.0 % 8)
     }
@@ -399,27 +514,46 @@ impl Square {
         (self.rank(), self.file())
     }
 
+    /// Whether this is a light or a dark square (a1 is dark)
+    pub fn color(self) -> SquareColor {
+        if BitBoard::DARK_SQUARES.test(self) {
+            SquareColor::Dark
+        } else {
+            SquareColor::Light
+        }
+    }
+
     pub fn offset(self, offset: Offset) -> Option<Self> {
         let file = self.file().0 as i8 + offset.file;
         let rank = self.rank().0 as i8 + offset.rank;
         if file < 0 || file > 7 || rank < 0 || rank > 7 {
             None
         } else {
-            Some(Self::from((Rank(rank as u8), File(file as u8))))
+            Some(Self::new(File(file as u8), Rank(rank as u8)))
         }
     }
 
-    pub fn flip_rank(self) -> Self {
-        Self::from((self.file(), self.rank().opposing_rank()))
+    /// Mirrors the square across the middle of the board, so that a1 becomes a8. This
+    /// is where the square would be from the other side's point of view.
+    pub const fn flip(self) -> Self {
+        Self(self.0 ^ 56)
     }
 
     pub fn white_at_bottom_index(self) -> u8 {
-        self.flip_rank().0
+        self.flip().0
     }
 
     pub fn manhattan_distance_to(self, other: Self) -> u8 {
         self.rank().abs_distance_to(other.rank()) + self.file().abs_distance_to(other.file())
     }
+
+    /// The number of moves a king would need to walk between the two squares
+    pub fn distance_to(self, other: Self) -> u8 {
+        u8::max(
+            self.rank().abs_distance_to(other.rank()),
+            self.file().abs_distance_to(other.file()),
+        )
+    }
 }
 
 impl Display for Square {
@@ -430,7 +564,7 @@ impl Display for Square {
 
 impl From<(Rank, File)> for Square {
     fn from(value: (Rank, File)) -> Self {
-        Self((value.0 .0) * 8 + (value.1 .0))
+        Self::new(value.1, value.0)
     }
 }
 
@@ -464,14 +598,25 @@ impl TryFrom<u8> for Square {
     }
 }
 
+impl TryFrom<usize> for Square {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Self::from_index(value).ok_or(())
+    }
+}
+
 impl Into<u32> for Square {
     fn into(self) -> u32 {
         self.0 as u32
     }
 }
 
+/// For the bit indices of a `BitBoard`, which are always in range. Other values should
+/// go through `TryFrom<usize>` instead, since they aren't checked outside debug builds.
 impl From<u32> for Square {
     fn from(value: u32) -> Self {
+        debug_assert!(value < 64, "square index out of range: {}", value);
         Self(value as u8)
     }
 }
@@ -498,6 +643,8 @@ impl ArrayKey for Square {
     const COUNT: usize = 64;
 }
 
+impl_step!(Square, 64);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct AttackMap {
     all: BitBoard,
@@ -511,19 +658,20 @@ impl AttackMap {
         shared_occupancy: BitBoard,
         own_occupancy: BitBoard,
     ) -> Self {
-        let mut all_attacks = BitBoard::ZERO;
-        let mut pawn_attacks = BitBoard::ZERO;
-        for piece in Piece::ALL {
-            let piece_index = PieceIndex::new(color, *piece);
-            let mut occupancy = piece_occupancy[piece_index];
+        let occupancy = |piece: Piece| piece_occupancy[PieceIndex::new(color, piece)];
+
+        // The leaping pieces can be done all at once, only the sliding
+        // pieces need to be looked up square by square
+        let mut pawn_attacks = AttackGenerator::pawn_attacks_bb(occupancy(Piece::Pawn), color);
+        let mut all_attacks = pawn_attacks
+            | AttackGenerator::knight_attacks_bb(occupancy(Piece::Knight))
+            | AttackGenerator::king_attacks_bb(occupancy(Piece::King));
+
+        for piece in [Piece::Bishop, Piece::Rook, Piece::Queen] {
+            let piece_index = PieceIndex::new(color, piece);
+            let mut occupancy = occupancy(piece);
             while let Some(square) = occupancy.pop() {
-                let attacks_this_piece =
-                    AttackGenerator::compute(piece_index, square, shared_occupancy);
-
-                all_attacks |= attacks_this_piece;
-                if *piece == Piece::Pawn {
-                    pawn_attacks |= attacks_this_piece;
-                }
+                all_attacks |= AttackGenerator::compute(piece_index, square, shared_occupancy);
             }
         }
 
@@ -543,6 +691,8 @@ pub struct BitBoard(u64);
 impl BitBoard {
     pub const ZERO: Self = Self(0);
     pub const BIT_COUNT: u32 = u64::BITS;
+    pub const LIGHT_SQUARES: Self = Self(0x55AA_55AA_55AA_55AA);
+    pub const DARK_SQUARES: Self = Self(0xAA55_AA55_AA55_AA55);
 
     #[inline]
     pub const fn new(value: u64) -> Self {
@@ -575,8 +725,7 @@ impl BitBoard {
     }
 
     pub fn first_square(self) -> Option<Square> {
-        self.first_one()
-            .map(|bit| Square::try_from(bit as u8).unwrap())
+        self.first_one().map(|bit| Square(bit as u8))
     }
 
     #[inline]
@@ -589,11 +738,20 @@ impl BitBoard {
         }
     }
 
+    pub fn last_square(self) -> Option<Square> {
+        self.last_one().map(|bit| Square(bit as u8))
+    }
+
     #[inline]
     pub fn iter_ones(self) -> impl Iterator<Item = u32> {
         BitIterator(self)
     }
 
+    #[inline]
+    pub fn iter_squares(self) -> impl Iterator<Item = Square> {
+        BitIterator(self).map(|bit| Square(bit as u8))
+    }
+
     #[inline]
     pub fn set(&mut self, square: Square, value: bool) {
         self.set_raw(square.into(), value);
@@ -626,7 +784,7 @@ impl BitBoard {
         };
 
         self.set_raw(bit, false);
-        Some(Square::try_from(bit as u8).unwrap())
+        Some(Square(bit as u8))
     }
 
     #[inline]
@@ -654,6 +812,32 @@ impl BitBoard {
     pub fn count_ones(self) -> u32 {
         self.0.count_ones()
     }
+
+    /// Mirrors the board top to bottom, so that the first rank becomes the eighth
+    #[inline]
+    pub const fn flip_vertical(self) -> Self {
+        Self(self.0.swap_bytes())
+    }
+
+    /// Mirrors the board left to right, so that the a-file becomes the h-file
+    #[inline]
+    pub const fn flip_horizontal(self) -> Self {
+        // Swap neighbouring bits, then pairs of bits, then nibbles within each rank
+        const K1: u64 = 0x5555_5555_5555_5555;
+        const K2: u64 = 0x3333_3333_3333_3333;
+        const K4: u64 = 0x0f0f_0f0f_0f0f_0f0f;
+        let mut x = self.0;
+        x = ((x >> 1) & K1) | ((x & K1) << 1);
+        x = ((x >> 2) & K2) | ((x & K2) << 2);
+        x = ((x >> 4) & K4) | ((x & K4) << 4);
+        Self(x)
+    }
+
+    /// Turns the board around, so that a1 becomes h8
+    #[inline]
+    pub const fn rotate180(self) -> Self {
+        Self(self.0.reverse_bits())
+    }
 }
 
 impl Not for BitBoard {
@@ -739,6 +923,10 @@ impl std::fmt::Debug for BitBoard {
 pub struct Board {
     occupancy: BitBoard,
     piece_occupancy: ArrayMap<PieceIndex, BitBoard>,
+
+    /// The piece on each square, which is the same as what's in the bitboards
+    /// but can be looked up without going through each of them
+    mailbox: ArrayMap<Square, PieceIndex>,
     colored_occupancy: ArrayMap<Color, BitBoard>,
     colored_attack_map: ArrayMap<Color, OnceCell<AttackMap>>,
 }
@@ -749,6 +937,25 @@ impl Board {
     }
 
     pub fn new(piece_occupancy: ArrayMap<PieceIndex, BitBoard>) -> Self {
+        let mut mailbox = Self::empty_map();
+        for color in Color::ALL {
+            for piece in Piece::ALL {
+                let piece_index = PieceIndex::new(*color, *piece);
+                for square in piece_occupancy[piece_index].iter_squares() {
+                    mailbox[square] = piece_index;
+                }
+            }
+        }
+
+        Self::with_mailbox(piece_occupancy, mailbox)
+    }
+
+    /// Like [`Board::new`], for when the piece on each square is already known (like when
+    /// it was kept up to date while making a move). It has to match the bitboards.
+    pub(crate) fn with_mailbox(
+        piece_occupancy: ArrayMap<PieceIndex, BitBoard>,
+        mailbox: ArrayMap<Square, PieceIndex>,
+    ) -> Self {
         let mut occupancy = BitBoard::ZERO;
         let mut colored_occupancy = ArrayMap::filled(BitBoard::ZERO);
 
@@ -763,11 +970,26 @@ impl Board {
         Self {
             occupancy,
             piece_occupancy,
+            mailbox,
             colored_occupancy,
             colored_attack_map: ArrayMap::new([OnceCell::new(), OnceCell::new()]),
         }
     }
 
+    /// The same position with the colors of the pieces swapped and the board mirrored
+    /// top to bottom, so that white's pieces end up where black's were and vice versa
+    pub fn flipped(&self) -> Self {
+        let mut piece_occupancy = ArrayMap::filled(BitBoard::ZERO);
+        for color in Color::ALL {
+            for piece in Piece::ALL {
+                piece_occupancy[PieceIndex::new(!*color, *piece)] =
+                    self.piece_occupancy[PieceIndex::new(*color, *piece)].flip_vertical();
+            }
+        }
+
+        Self::new(piece_occupancy)
+    }
+
     pub fn occupancy(&self) -> BitBoard {
         self.occupancy
     }
@@ -780,23 +1002,20 @@ impl Board {
         self.piece_occupancy[piece_index]
     }
 
+    #[inline]
     pub fn piece_at(&self, square: Square) -> Option<PieceIndex> {
-        for color in Color::ALL {
-            for piece in Piece::ALL {
-                let piece_index = PieceIndex::new(*color, *piece);
-                if self.piece_occupancy[piece_index].test(square) {
-                    return Some(piece_index);
-                }
-            }
-        }
-
-        None
+        let piece_index = self.mailbox[square];
+        piece_index.some().then_some(piece_index)
     }
 
     pub fn piece_map(&self) -> &ArrayMap<PieceIndex, BitBoard> {
         &self.piece_occupancy
     }
 
+    pub(crate) fn mailbox(&self) -> &ArrayMap<Square, PieceIndex> {
+        &self.mailbox
+    }
+
     pub fn pieces(&self) -> impl Iterator<Item = (Square, PieceIndex)> + '_ {
         Square::ALL.iter().filter_map(move |square| {
             if let Some(piece) = self.piece_at(*square) {
@@ -821,10 +1040,53 @@ impl Board {
         self.attack_map(color).all
     }
 
+    /// The bishops of the given color standing on squares of the given color
+    pub fn bishops_on(&self, color: Color, square_color: SquareColor) -> BitBoard {
+        self.piece_occupancy[PieceIndex::new(color, Piece::Bishop)] & square_color.squares()
+    }
+
+    /// Whether each side has bishops on one color of square only, and they're different colors.
+    /// These endings are often drawn even a pawn or two up, since the bishops can never meet.
+    pub fn has_opposite_colored_bishops(&self) -> bool {
+        let complex = |color: Color| match (
+            self.bishops_on(color, SquareColor::Light).any(),
+            self.bishops_on(color, SquareColor::Dark).any(),
+        ) {
+            (true, false) => Some(SquareColor::Light),
+            (false, true) => Some(SquareColor::Dark),
+            _ => None,
+        };
+
+        match (complex(Color::White), complex(Color::Black)) {
+            (Some(white), Some(black)) => white != black,
+            _ => false,
+        }
+    }
+
     pub fn colored_pawn_attacks(&self, color: Color) -> BitBoard {
         self.attack_map(color).pawn
     }
 
+    /// The number of pieces of the given color attacking each square. Unlike the
+    /// attacks above, squares occupied by their own pieces (which are defended
+    /// rather than attacked) are counted too.
+    pub fn attack_count_map(&self, color: Color) -> ArrayMap<Square, u8> {
+        let mut counts = ArrayMap::filled(0);
+        for piece in Piece::ALL {
+            let piece_index = PieceIndex::new(color, *piece);
+            let mut pieces = self.piece_occupancy[piece_index];
+            while let Some(square) = pieces.pop() {
+                for target in
+                    AttackGenerator::compute(piece_index, square, self.occupancy).iter_squares()
+                {
+                    counts[target] += 1;
+                }
+            }
+        }
+
+        counts
+    }
+
     fn attack_map(&self, color: Color) -> &AttackMap {
         self.colored_attack_map[color].get_or_init(|| {
             AttackMap::from_occupancy(
@@ -851,21 +1113,14 @@ impl From<&ArrayMap<Square, PieceIndex>> for Board {
 
 impl From<&Board> for ArrayMap<Square, PieceIndex> {
     fn from(board: &Board) -> Self {
-        let mut arr = ArrayMap::filled(PieceIndex::NONE);
-        for square in Square::ALL {
-            if let Some(piece) = board.piece_at(*square) {
-                arr[*square] = piece;
-            }
-        }
-
-        arr
+        board.mailbox().clone()
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::Piece;
+    use crate::{Piece, RANK_MASKS};
 
     use super::*;
 
@@ -890,19 +1145,38 @@ mod tests {
         assert_eq!(Square::C5.to_string(), "c5");
     }
 
+    #[test]
+    fn test_square_conversions() {
+        assert_eq!(Square::new(File::C, Rank::FIVE), Square::C5);
+        assert_eq!(Square::try_from(63usize), Ok(Square::H8));
+        assert_eq!(Square::try_from(64usize), Err(()));
+        assert_eq!(Square::from(63u32), Square::H8);
+        assert_eq!(File::try_from(8usize), Err(()));
+        assert_eq!(Square::A1.distance_to(Square::C2), 2);
+        assert_eq!(Square::A1.manhattan_distance_to(Square::C2), 3);
+
+        assert_eq!((File::A..=File::H).count(), 8);
+        assert_eq!((Rank::ONE..Rank::FOUR).last(), Some(Rank::THREE));
+        assert!((Square::A1..=Square::H8).eq(Square::ALL.iter().copied()));
+        assert_eq!(
+            BitBoard::new(0b00100010).iter_squares().collect::<Vec<_>>(),
+            vec![Square::B1, Square::F1]
+        );
+    }
+
     #[test]
     fn test_bitboard_pop() {
         let mut board = BitBoard::ZERO;
-        board.set(Square::try_from(0 as u8).unwrap(), true);
-        board.set(Square::try_from(12 as u8).unwrap(), true);
-        board.set(Square::try_from(13 as u8).unwrap(), true);
-        board.set(Square::try_from(31 as u8).unwrap(), true);
+        board.set(Square::from_index(0).unwrap(), true);
+        board.set(Square::from_index(12).unwrap(), true);
+        board.set(Square::from_index(13).unwrap(), true);
+        board.set(Square::from_index(31).unwrap(), true);
         board.set(Square::C7, true);
 
-        assert_eq!(board.pop(), Some(Square::try_from(0 as u8).unwrap()));
-        assert_eq!(board.pop(), Some(Square::try_from(12 as u8).unwrap()));
-        assert_eq!(board.pop(), Some(Square::try_from(13 as u8).unwrap()));
-        assert_eq!(board.pop(), Some(Square::try_from(31 as u8).unwrap()));
+        assert_eq!(board.pop(), Some(Square::from_index(0).unwrap()));
+        assert_eq!(board.pop(), Some(Square::from_index(12).unwrap()));
+        assert_eq!(board.pop(), Some(Square::from_index(13).unwrap()));
+        assert_eq!(board.pop(), Some(Square::from_index(31).unwrap()));
         assert_eq!(board.pop(), Some(Square::C7.into()));
         assert_eq!(board.pop(), None);
     }
@@ -933,6 +1207,107 @@ mod tests {
         assert!(board.colored_occupancy[Color::White].test(Square::A1));
     }
 
+    #[test]
+    fn test_relative_ranks() {
+        assert_eq!(Rank::TWO.relative_to(Color::White), Rank::TWO);
+        assert_eq!(Rank::TWO.relative_to(Color::Black), Rank::SEVEN);
+
+        assert_eq!(Color::White.home_rank(), Rank::ONE);
+        assert_eq!(Color::Black.home_rank(), Rank::EIGHT);
+        assert_eq!(Color::Black.pawn_rank(), Rank::SEVEN);
+        assert_eq!(Color::Black.promotion_rank(), Rank::ONE);
+        assert_eq!(Color::White.promotion_rank_mask(), RANK_MASKS[Rank::EIGHT]);
+
+        for color in Color::ALL {
+            assert_eq!(color.promotion_rank(), (!*color).home_rank());
+            assert!(color
+                .pawn_rank_mask()
+                .test(Square::from((color.pawn_rank(), File::E))));
+        }
+    }
+
+    #[test]
+    fn test_square_colors() {
+        assert_eq!(Square::A1.color(), SquareColor::Dark);
+        assert_eq!(Square::H1.color(), SquareColor::Light);
+        assert_eq!(Square::D1.color(), SquareColor::Light);
+        assert_eq!(Square::D8.color(), SquareColor::Dark);
+        assert!(Square::ALL
+            .iter()
+            .all(|square| square.color().squares().test(*square)));
+        assert_eq!(BitBoard::LIGHT_SQUARES, !BitBoard::DARK_SQUARES);
+
+        let mut map = Board::empty_map();
+        map[Square::C1] = (Color::White, Piece::Bishop).into();
+        map[Square::C8] = (Color::Black, Piece::Bishop).into();
+        let board = Board::from(&map);
+        assert_eq!(
+            board.bishops_on(Color::White, SquareColor::Dark),
+            BitBoard::just(Square::C1)
+        );
+        assert!(board.has_opposite_colored_bishops());
+
+        map[Square::F8] = (Color::Black, Piece::Bishop).into();
+        assert!(!Board::from(&map).has_opposite_colored_bishops());
+    }
+
+    #[test]
+    fn test_attack_counts() {
+        let mut map = Board::empty_map();
+        map[Square::A1] = (Color::White, Piece::Rook).into();
+        map[Square::D1] = (Color::White, Piece::Rook).into();
+        map[Square::B3] = (Color::White, Piece::Pawn).into();
+        map[Square::D4] = (Color::Black, Piece::Knight).into();
+
+        let board = Board::from(&map);
+        let white = board.attack_count_map(Color::White);
+        assert_eq!(white[Square::C1], 2);
+        assert_eq!(white[Square::D1], 1);
+        assert_eq!(white[Square::C4], 1);
+        assert_eq!(white[Square::D4], 1);
+        assert_eq!(white[Square::D5], 0);
+        assert_eq!(white[Square::A8], 1);
+
+        let black = board.attack_count_map(Color::Black);
+        assert_eq!(black[Square::B3], 1);
+        assert_eq!(black[Square::D4], 0);
+        assert_eq!(Square::ALL.iter().map(|s| black[*s]).sum::<u8>(), 8);
+    }
+
+    #[test]
+    fn test_bitboard_flips() {
+        let bb =
+            BitBoard::just(Square::A1) | BitBoard::just(Square::C2) | BitBoard::just(Square::H5);
+        let squares = |bb: BitBoard| bb.iter_squares().collect::<Vec<_>>();
+
+        assert_eq!(
+            squares(bb.flip_vertical()),
+            vec![Square::H4, Square::C7, Square::A8]
+        );
+        assert_eq!(
+            squares(bb.flip_horizontal()),
+            vec![Square::H1, Square::F2, Square::A5]
+        );
+        assert_eq!(
+            squares(bb.rotate180()),
+            vec![Square::A4, Square::F7, Square::H8]
+        );
+
+        for square in Square::ALL {
+            assert_eq!(square.flip().flip(), *square);
+            assert_eq!(square.flip().file(), square.file());
+            assert_eq!(square.flip().rank(), square.rank().opposing_rank());
+            assert_eq!(
+                BitBoard::just(*square).flip_vertical(),
+                BitBoard::just(square.flip())
+            );
+            assert_eq!(
+                BitBoard::just(*square).rotate180(),
+                BitBoard::just(*square).flip_vertical().flip_horizontal()
+            );
+        }
+    }
+
     #[test]
     fn test_board_shifts() {
         let square = Square::A4;
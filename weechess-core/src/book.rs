@@ -4,8 +4,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     hasher,
-    notation::{try_from_notation, San},
-    Move, MoveGenerator, MoveQuery, State,
+    pgn::{PgnParseError, PgnParser},
+    Move, MoveQuery, State,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -38,6 +38,31 @@ impl Book {
             .or_insert_with(HashSet::new)
             .extend(moves);
     }
+
+    /// Appends the opening moves of every game in a PGN-like file, up to
+    /// `max_depth` plies into each game. Tag pairs and results are ignored.
+    pub fn append_games(
+        &mut self,
+        contents: &str,
+        hasher: &hasher::ZobristHasher,
+        max_depth: usize,
+    ) -> Result<(), BookParseError> {
+        for movetext in contents
+            .trim()
+            .split("\n\n")
+            .filter(|c| c.starts_with("1."))
+        {
+            let moves = BookParser::parse_movetext(movetext, hasher)
+                .take(max_depth)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (hash, mv) in moves.into_iter() {
+                self.append(hash, &[mv]);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -66,47 +91,14 @@ impl BookParser {
         movetext: &'a str,
         hasher: &'a hasher::ZobristHasher,
     ) -> impl Iterator<Item = Result<(hasher::Hash, Move), BookParseError>> + 'a {
-        movetext
-            .split_whitespace()
-            .filter(|t| match *t {
-                "1/2-1/2" => false,
-                "1-0" => false,
-                "0-1" => false,
-                _ => !t.ends_with('.'),
-            })
-            .map(|c| {
-                if let Some(dot_index) = c.find('.') {
-                    &c[dot_index + 1..]
-                } else {
-                    c
-                }
-            })
-            .scan(
-                State::default(),
-                |state, move_str| match try_from_notation::<_, San>(move_str) {
-                    Ok(query) => {
-                        let valid_moves = MoveGenerator::compute_legal_moves(state);
-                        let Some(result) = valid_moves.find(&query) else {
-                            println!("Unknown move '{}': {}", move_str, query);
-                            println!("Game state:\n{}", state.pretty());
-                            println!("Available moves:");
-                            for m in valid_moves.moves() {
-                                println!("  {}", m.0);
-                            }
-
-                            return Some(Err(BookParseError::UnknownMove(
-                                move_str.to_string(),
-                                query,
-                            )));
-                        };
-
-                        let entry = (hasher.hash(state), result.0);
-                        *state = result.1;
-
-                        Some(Ok(entry))
-                    }
-                    Err(..) => Some(Err(BookParseError::InvalidMoveStr(move_str.to_string()))),
-                },
-            )
+        PgnParser::parse_movetext(movetext, State::default()).map(|result| match result {
+            Ok((state, mv)) => Ok((hasher.hash(&state), mv)),
+            Err(PgnParseError::UnknownMove(str, query)) => {
+                Err(BookParseError::UnknownMove(str, query))
+            }
+            Err(PgnParseError::InvalidMoveStr(str))
+            | Err(PgnParseError::InvalidTag(str))
+            | Err(PgnParseError::InvalidFen(str)) => Err(BookParseError::InvalidMoveStr(str)),
+        })
     }
 }
@@ -0,0 +1,120 @@
+use std::fmt::Display;
+
+use crate::{
+    notation::{into_notation, Fen},
+    utils::ArrayMap,
+    Color, Move, State,
+};
+
+/// Which way round a position is compared to its canonical form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// The position is already canonical
+    Original,
+
+    /// The canonical position has the colors swapped and the board mirrored
+    Flipped,
+}
+
+impl Orientation {
+    /// Maps a move between the original and the canonical position. Flipping
+    /// a move twice gives back the same move, so this works in both directions.
+    pub fn map_move(self, mv: &Move) -> Move {
+        match self {
+            Orientation::Original => *mv,
+            Orientation::Flipped => mv.flipped(),
+        }
+    }
+}
+
+/// A key that's the same for positions that only differ by which color is which,
+/// like a position with black to move and the same position mirrored with white to
+/// move. The move clocks aren't part of the key either.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CanonicalKey(String);
+
+impl CanonicalKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CanonicalKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl State {
+    /// The same position with the colors swapped and the board mirrored top to bottom.
+    /// All of the supported variants treat both colors the same way, so the flipped
+    /// position plays out exactly like this one, just with the colors reversed.
+    pub fn flipped(&self) -> Self {
+        let castle_rights = ArrayMap::new([
+            self.castle_rights(Color::Black),
+            self.castle_rights(Color::White),
+        ]);
+
+        State::new(
+            self.board().flipped(),
+            !self.turn_to_move(),
+            castle_rights,
+            self.en_passant_target().map(|square| square.flip()),
+            self.clock().clone(),
+        )
+        .with_variant(self.variant())
+    }
+
+    /// The position in the orientation with white to move, along with how it
+    /// relates to this one
+    pub fn canonical(&self) -> (State, Orientation) {
+        match self.turn_to_move() {
+            Color::White => (self.clone(), Orientation::Original),
+            Color::Black => (self.flipped(), Orientation::Flipped),
+        }
+    }
+
+    pub fn canonical_key(&self) -> CanonicalKey {
+        let (state, _) = self.canonical();
+        let fen = into_notation::<_, Fen>(&state).to_string();
+        CanonicalKey(
+            fen.split_ascii_whitespace()
+                .take(4)
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::try_from_notation, MoveGenerator};
+
+    #[test]
+    fn test_canonical_key() {
+        let black =
+            try_from_notation::<State, Fen>("r3k2r/ppp2ppp/8/8/3pP3/8/PPP2PPP/R3K2R b Kq e3 0 10")
+                .unwrap();
+
+        let white =
+            try_from_notation::<State, Fen>("r3k2r/ppp2ppp/8/3Pp3/8/8/PPP2PPP/R3K2R w Qk e6 0 1")
+                .unwrap();
+
+        assert_eq!(black.canonical_key(), white.canonical_key());
+        assert_eq!(white.canonical().1, Orientation::Original);
+        assert_eq!(black.flipped().flipped(), black);
+
+        // Every move maps onto the same move in the canonical position, and back again
+        let (canonical, orientation) = black.canonical();
+        assert_eq!(orientation, Orientation::Flipped);
+        let moves = MoveGenerator::compute_legal_moves(&black);
+        let canonical_moves = MoveGenerator::compute_legal_moves(&canonical);
+        assert_eq!(moves.moves().len(), canonical_moves.moves().len());
+        for result in moves.moves() {
+            let mapped = orientation.map_move(&result.0);
+            assert!(canonical.is_legal(&mapped), "{} isn't legal", mapped);
+            assert_eq!(orientation.map_move(&mapped), result.0);
+        }
+    }
+}
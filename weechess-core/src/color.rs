@@ -1,11 +1,14 @@
 use std::{fmt::Display, ops::Not};
 
 use num_enum::{IntoPrimitive, TryFromPrimitive, TryFromPrimitiveError};
+use serde::{Deserialize, Serialize};
 
-use super::{utils, utils::ArrayKey, Offset, PieceIndex};
+use super::{utils, utils::ArrayKey, BitBoard, Offset, PieceIndex, Rank, RANK_MASKS};
 
 #[repr(u8)]
-#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(
+    IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub enum Color {
     White,
     Black,
@@ -34,6 +37,33 @@ impl Color {
             Self::Black => Offset::NORTH,
         }
     }
+
+    /// The rank the pieces start on
+    pub fn home_rank(self) -> Rank {
+        Rank::ONE.relative_to(self)
+    }
+
+    /// The rank the pawns start on
+    pub fn pawn_rank(self) -> Rank {
+        Rank::TWO.relative_to(self)
+    }
+
+    /// The rank the pawns promote on, which is the other side's home rank
+    pub fn promotion_rank(self) -> Rank {
+        Rank::EIGHT.relative_to(self)
+    }
+
+    pub fn home_rank_mask(self) -> BitBoard {
+        RANK_MASKS[self.home_rank()]
+    }
+
+    pub fn pawn_rank_mask(self) -> BitBoard {
+        RANK_MASKS[self.pawn_rank()]
+    }
+
+    pub fn promotion_rank_mask(self) -> BitBoard {
+        RANK_MASKS[self.promotion_rank()]
+    }
 }
 
 impl Not for Color {
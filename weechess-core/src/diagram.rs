@@ -0,0 +1,264 @@
+use crate::{
+    notation::{try_from_notation, Fen},
+    Color, State,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagramParseError {
+    /// A diagram needs exactly eight ranks of eight squares, but this many were found
+    RankCount(usize),
+    InvalidPosition(String),
+}
+
+impl std::fmt::Display for DiagramParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagramParseError::RankCount(count) => {
+                write!(f, "expected 8 ranks in the diagram, found {}", count)
+            }
+            DiagramParseError::InvalidPosition(fen) => write!(f, "invalid position: {}", fen),
+        }
+    }
+}
+
+impl std::error::Error for DiagramParseError {}
+
+/// Reads the position out of a board diagram, like the ones drawn by the printer or
+/// pasted from a forum. Each rank goes on its own line, as either piece letters (or
+/// symbols) separated by `|` or `│`, or as a row of eight characters with `.`, `-` or
+/// `_` for empty squares. Lines that aren't ranks, like borders and file labels, are
+/// skipped. A diagram with its ranks labeled from 1 at the top is taken to be drawn from
+/// black's side. The castle rights are guessed from which kings and rooks are still on
+/// their starting squares, since a diagram can't show them.
+pub fn parse_diagram(text: &str, turn_to_move: Color) -> Result<State, DiagramParseError> {
+    let rows: Vec<(Option<u32>, Vec<Option<char>>)> = text.lines().filter_map(parse_row).collect();
+    if rows.len() != 8 {
+        return Err(DiagramParseError::RankCount(rows.len()));
+    }
+
+    let mut ranks: Vec<Vec<Option<char>>> =
+        rows.iter().map(|(_, squares)| squares.clone()).collect();
+    if rows[0].0 == Some(1) {
+        ranks.reverse();
+        for squares in ranks.iter_mut() {
+            squares.reverse();
+        }
+    }
+
+    let placement = ranks
+        .iter()
+        .map(|squares| {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for square in squares {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+
+                        rank.push(*piece);
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+
+            rank
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let piece_at = |rank: usize, file: usize| ranks[rank][file];
+    let mut castle_rights = String::new();
+    for (rank, king, rook, rights) in [(7, 'K', 'R', ['K', 'Q']), (0, 'k', 'r', ['k', 'q'])] {
+        if piece_at(rank, 4) != Some(king) {
+            continue;
+        }
+
+        // The kingside rook starts on the h-file, and the queenside one on the a-file
+        for (file, right) in [7, 0].into_iter().zip(rights) {
+            if piece_at(rank, file) == Some(rook) {
+                castle_rights.push(right);
+            }
+        }
+    }
+
+    if castle_rights.is_empty() {
+        castle_rights.push('-');
+    }
+
+    let fen = format!(
+        "{} {} {} - 0 1",
+        placement,
+        match turn_to_move {
+            Color::White => 'w',
+            Color::Black => 'b',
+        },
+        castle_rights
+    );
+
+    try_from_notation::<_, Fen>(&fen).map_err(|_| DiagramParseError::InvalidPosition(fen))
+}
+
+// Reads a line of the diagram as a rank, along with its label if it has one
+fn parse_row(line: &str) -> Option<(Option<u32>, Vec<Option<char>>)> {
+    let line = line.trim();
+    let (label, line) = match line.chars().next().and_then(|c| c.to_digit(10)) {
+        Some(label @ 1..=8) => (Some(label), &line[1..]),
+        _ => (None, line),
+    };
+
+    // A label at the end of the line counts too, as long as there wasn't one at the start
+    let trimmed = line.trim_end();
+    let (label, line) = match (label, trimmed.chars().last().and_then(|c| c.to_digit(10))) {
+        (None, Some(label @ 1..=8)) => (Some(label), &trimmed[..trimmed.len() - 1]),
+        _ => (label, line),
+    };
+
+    let squares: Option<Vec<Option<char>>> = if line.contains(['|', '│']) {
+        // Anything before the first separator and after the last one is outside the board
+        let cells: Vec<&str> = line.split(['|', '│']).collect();
+        cells[1..cells.len() - 1]
+            .iter()
+            .map(|cell| parse_square(cell.trim().trim_matches(['[', ']'])))
+            .collect()
+    } else {
+        line.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| parse_square(&c.to_string()))
+            .collect()
+    };
+
+    squares
+        .filter(|squares| squares.len() == 8)
+        .map(|squares| (label, squares))
+}
+
+// The piece on a square as a FEN letter, nothing for an empty square, or
+// `None` if the square doesn't make sense
+fn parse_square(square: &str) -> Option<Option<char>> {
+    let mut chars = square.chars();
+    let c = match (chars.next(), chars.next()) {
+        (None, _) => return Some(None),
+        (Some(c), None) => c,
+        _ => return None,
+    };
+
+    let piece = match c {
+        '.' | '-' | '_' | '·' => return Some(None),
+        'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => c,
+        '♙' => 'P',
+        '♘' => 'N',
+        '♗' => 'B',
+        '♖' => 'R',
+        '♕' => 'Q',
+        '♔' => 'K',
+        '♟' => 'p',
+        '♞' => 'n',
+        '♝' => 'b',
+        '♜' => 'r',
+        '♛' => 'q',
+        '♚' => 'k',
+        _ => return None,
+    };
+
+    Some(Some(piece))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::into_notation;
+
+    fn fen(state: &State) -> String {
+        into_notation::<_, Fen>(state).to_string()
+    }
+
+    #[test]
+    fn test_printed_diagram() {
+        let state = try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        )
+        .unwrap();
+
+        let printed = state.pretty().to_string();
+        let parsed = parse_diagram(&printed, Color::Black).unwrap();
+        assert_eq!(fen(&parsed), fen(&state));
+    }
+
+    #[test]
+    fn test_ascii_diagrams() {
+        let labeled = r#"
+              +---+---+---+---+---+---+---+---+
+            8 | r |   | b | q | k |   |   | r |
+              +---+---+---+---+---+---+---+---+
+            7 | p | p | p | p |   | p | p | p |
+              +---+---+---+---+---+---+---+---+
+            6 |   |   | n |   |   | n |   |   |
+              +---+---+---+---+---+---+---+---+
+            5 |   |   | b |   | p |   |   |   |
+              +---+---+---+---+---+---+---+---+
+            4 |   |   | B |   | P |   |   |   |
+              +---+---+---+---+---+---+---+---+
+            3 |   |   |   |   |   | N |   |   |
+              +---+---+---+---+---+---+---+---+
+            2 | P | P | P | P |   | P | P | P |
+              +---+---+---+---+---+---+---+---+
+            1 | R | N | B | Q |   | R | K |   |
+              +---+---+---+---+---+---+---+---+
+                a   b   c   d   e   f   g   h
+        "#;
+
+        let parsed = parse_diagram(labeled, Color::Black).unwrap();
+        assert_eq!(
+            fen(&parsed),
+            "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 0 1"
+        );
+
+        let compact = "
+            rnbqkbnr
+            pppppppp
+            ........
+            ........
+            ....P...
+            ........
+            PPPP.PPP
+            RNBQKBNR
+        ";
+
+        let parsed = parse_diagram(compact, Color::Black).unwrap();
+        assert_eq!(
+            fen(&parsed),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        // Seen from black's side, the ranks count up and the files run backwards
+        let flipped = "
+            1 R N B K Q B N R
+            2 P P P . P P P P
+            3 . . . . . . . .
+            4 . . . P . . . .
+            5 . . . . . . . .
+            6 . . . . . . . .
+            7 p p p p p p p p
+            8 r n b k q b n r
+              h g f e d c b a
+        ";
+
+        let parsed = parse_diagram(flipped, Color::Black).unwrap();
+        assert_eq!(
+            fen(&parsed),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        assert_eq!(
+            parse_diagram("rnbqkbnr\npppppppp", Color::White).err(),
+            Some(DiagramParseError::RankCount(2))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::{
+    notation::{into_notation, Fen},
+    pgn::{Pgn, PgnParseError, PgnParser},
+    Move, State,
+};
+
+// The most common openings, in the same format as the lichess opening tables
+const BUNDLED_TABLE: &str = include_str!("eco.tsv");
+
+#[derive(Debug)]
+pub enum EcoParseError {
+    /// A line (numbered from 1) that isn't an ECO code, a name and the moves, separated by tabs
+    Syntax(usize),
+    InvalidMoves(usize, PgnParseError),
+}
+
+impl std::fmt::Display for EcoParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EcoParseError::Syntax(line) => {
+                write!(f, "expected an ECO code, name and moves on line {}", line)
+            }
+            EcoParseError::InvalidMoves(line, e) => write!(f, "{} on line {}", e, line),
+        }
+    }
+}
+
+impl std::error::Error for EcoParseError {}
+
+/// A named opening, along with the moves that lead to it from the starting position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+    pub moves: Vec<Move>,
+}
+
+/// A table of openings to classify games with. Openings are matched by the position
+/// their moves lead to, so games that transpose into an opening are classified too.
+#[derive(Debug, Clone, Default)]
+pub struct EcoTable {
+    openings: Vec<Opening>,
+    positions: HashMap<String, usize>,
+
+    /// The most moves of any of the openings, past which there's nothing left to match
+    max_plies: usize,
+}
+
+impl EcoTable {
+    /// Reads a table with one opening per line, as its ECO code, its name and its moves
+    /// (like `1. e4 e5 2. Nf3`) separated by tabs. A header line starting with `eco` is
+    /// skipped, and the first of the openings that reach the same position is kept.
+    pub fn parse(contents: &str) -> Result<Self, EcoParseError> {
+        let mut table = Self::default();
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || (number == 0 && line.starts_with("eco")) {
+                continue;
+            }
+
+            let mut fields = line.split('\t').map(str::trim);
+            let (Some(eco), Some(name), Some(movetext)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(EcoParseError::Syntax(number + 1));
+            };
+
+            let mut state = State::default();
+            let mut moves = vec![];
+            for result in PgnParser::parse_movetext(movetext, State::default()) {
+                let (previous, mv) =
+                    result.map_err(|e| EcoParseError::InvalidMoves(number + 1, e))?;
+                state = State::by_performing_move(&previous, &mv).unwrap();
+                moves.push(mv);
+            }
+
+            table.max_plies = table.max_plies.max(moves.len());
+            table
+                .positions
+                .entry(position_key(&state))
+                .or_insert(table.openings.len());
+
+            table.openings.push(Opening {
+                eco: eco.to_string(),
+                name: name.to_string(),
+                moves,
+            });
+        }
+
+        Ok(table)
+    }
+
+    /// The table that comes with the crate, which only has the most common openings
+    pub fn bundled() -> &'static Self {
+        static TABLE: OnceLock<EcoTable> = OnceLock::new();
+        TABLE.get_or_init(|| Self::parse(BUNDLED_TABLE).unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Opening> {
+        self.openings.iter()
+    }
+
+    /// The opening of the last position along the moves that's in the table, if there
+    /// is one. Only games from the standard starting position can be classified.
+    pub fn classify(&self, initial_state: &State, moves: &[Move]) -> Option<&Opening> {
+        if *initial_state != State::default() {
+            return None;
+        }
+
+        let mut state = initial_state.clone();
+        let mut opening = None;
+        for mv in moves.iter().take(self.max_plies) {
+            let Ok(next_state) = State::by_performing_move(&state, mv) else {
+                break;
+            };
+
+            state = next_state;
+            if let Some(index) = self.positions.get(&position_key(&state)) {
+                opening = Some(&self.openings[*index]);
+            }
+        }
+
+        opening
+    }
+}
+
+impl Pgn {
+    /// The opening of the game, as classified by the bundled ECO table
+    pub fn opening(&self) -> Option<&'static Opening> {
+        let moves: Vec<Move> = self.moves.iter().map(|m| m.mv).collect();
+        EcoTable::bundled().classify(&self.initial_state, &moves)
+    }
+}
+
+// The en passant square depends on how the position came about, which
+// would keep games that transpose into an opening from matching it
+fn position_key(state: &State) -> String {
+    let fen = into_notation::<_, Fen>(state).to_string();
+    fen.split_ascii_whitespace()
+        .take(3)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let table = EcoTable::bundled();
+        assert!(table.len() > 50);
+
+        let pgn =
+            Pgn::parse("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 *").unwrap();
+        let opening = pgn.opening().unwrap();
+        assert_eq!(opening.eco, "C84");
+        assert_eq!(opening.name, "Ruy Lopez: Closed");
+
+        // The Nimzo-Indian can be reached with the knight moves the other way round
+        let pgn = Pgn::parse("1. c4 e6 2. Nc3 Nf6 3. d4 Bb4 *").unwrap();
+        assert_eq!(pgn.opening().unwrap().eco, "E20");
+
+        let mut pgn = Pgn::parse("1. e4 e5 *").unwrap();
+        pgn.initial_state = pgn.state_at(1).unwrap().unwrap();
+        pgn.moves.remove(0);
+        assert_eq!(pgn.opening(), None);
+
+        assert!(matches!(
+            EcoTable::parse("C20\tKing's Pawn Game"),
+            Err(EcoParseError::Syntax(1))
+        ));
+        assert!(matches!(
+            EcoTable::parse("C20\tKing's Pawn Game\t1. e4 e4"),
+            Err(EcoParseError::InvalidMoves(1, _))
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::{utils::ArrayMap, Color};
+
+/// How time gets added back to a player's clock as the game goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    /// No time is ever added, the whole game has to be played on the initial time
+    SuddenDeath,
+
+    /// The given time is added to the player's clock after each of their moves
+    Increment(Duration),
+
+    /// Each move starts with the given delay, which counts down before the player's
+    /// own time does. Unused delay is lost, unlike an increment.
+    Delay(Duration),
+}
+
+/// A chess clock, keeping track of how much time each player has left and
+/// whose clock is running
+#[derive(Debug, Clone)]
+pub struct GameClock {
+    control: TimeControl,
+    remaining: ArrayMap<Color, Duration>,
+    running: Option<(Color, Instant)>,
+    flagged: Option<Color>,
+}
+
+impl GameClock {
+    /// Both players start with the same time, and neither clock is running
+    pub fn new(initial: Duration, control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: ArrayMap::new([initial, initial]),
+            running: None,
+            flagged: None,
+        }
+    }
+
+    /// Gives `color` a different amount of time than their opponent
+    pub fn with_remaining(mut self, color: Color, remaining: Duration) -> Self {
+        self.remaining[color] = remaining;
+        self
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// The color whose clock is running, if any
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// The time `color` has left, including the time spent on the current move
+    /// if it's their clock that's running
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining_at(color, Instant::now())
+    }
+
+    /// The color that ran out of time, if either of them has
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged_at(Instant::now())
+    }
+
+    /// Starts the clock of `color`, stopping their opponent's if it was running
+    pub fn start(&mut self, color: Color) {
+        self.start_at(color, Instant::now())
+    }
+
+    /// Stops whichever clock is running, without adding any increment
+    pub fn stop(&mut self) {
+        self.stop_at(Instant::now())
+    }
+
+    /// Ends the move of the player whose clock is running, adding their increment
+    /// and starting their opponent's clock. Returns the color that ran out of time
+    /// instead, if the player took too long (in which case neither clock is running).
+    pub fn press(&mut self) -> Option<Color> {
+        self.press_at(Instant::now())
+    }
+
+    fn remaining_at(&self, color: Color, now: Instant) -> Duration {
+        match self.running {
+            Some((running, started)) if running == color => {
+                self.remaining[color].saturating_sub(self.charge(now - started))
+            }
+            _ => self.remaining[color],
+        }
+    }
+
+    fn flagged_at(&self, now: Instant) -> Option<Color> {
+        self.flagged.or_else(|| {
+            let (color, _) = self.running?;
+            self.remaining_at(color, now).is_zero().then_some(color)
+        })
+    }
+
+    fn start_at(&mut self, color: Color, now: Instant) {
+        self.stop_at(now);
+        if self.flagged.is_none() {
+            self.running = Some((color, now));
+        }
+    }
+
+    fn stop_at(&mut self, now: Instant) {
+        if let Some((color, _)) = self.running {
+            let remaining = self.remaining_at(color, now);
+            if remaining.is_zero() {
+                self.flagged = Some(color);
+            }
+
+            self.remaining[color] = remaining;
+            self.running = None;
+        }
+    }
+
+    fn press_at(&mut self, now: Instant) -> Option<Color> {
+        let (color, _) = self.running?;
+        self.stop_at(now);
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+
+        if let TimeControl::Increment(increment) = self.control {
+            self.remaining[color] += increment;
+        }
+
+        self.start_at(!color, now);
+        None
+    }
+
+    // How much of the time spent on a move comes off the player's clock
+    fn charge(&self, elapsed: Duration) -> Duration {
+        match self.control {
+            TimeControl::Delay(delay) => elapsed.saturating_sub(delay),
+            _ => elapsed,
+        }
+    }
+}
+
+impl Display for GameClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = |remaining: Duration| {
+            let seconds = remaining.as_secs_f64();
+            format!("{}:{:04.1}", (seconds / 60.0) as u64, seconds % 60.0)
+        };
+
+        write!(
+            f,
+            "w {}  |  b {}",
+            format(self.remaining(Color::White)),
+            format(self.remaining(Color::Black))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment() {
+        let start = Instant::now();
+        let seconds = Duration::from_secs;
+        let mut clock = GameClock::new(seconds(60), TimeControl::Increment(seconds(2)));
+
+        clock.start_at(Color::White, start);
+        assert_eq!(clock.running(), Some(Color::White));
+        assert_eq!(
+            clock.remaining_at(Color::White, start + seconds(10)),
+            seconds(50)
+        );
+
+        assert_eq!(clock.press_at(start + seconds(10)), None);
+        assert_eq!(clock.running(), Some(Color::Black));
+        assert_eq!(
+            clock.remaining_at(Color::White, start + seconds(20)),
+            seconds(52)
+        );
+        assert_eq!(
+            clock.remaining_at(Color::Black, start + seconds(20)),
+            seconds(50)
+        );
+
+        // Stopping the clock doesn't earn an increment
+        clock.stop_at(start + seconds(30));
+        assert_eq!(clock.running(), None);
+        assert_eq!(
+            clock.remaining_at(Color::Black, start + seconds(60)),
+            seconds(40)
+        );
+        assert_eq!(clock.flagged_at(start + seconds(60)), None);
+    }
+
+    #[test]
+    fn test_delay() {
+        let start = Instant::now();
+        let seconds = Duration::from_secs;
+        let mut clock = GameClock::new(seconds(60), TimeControl::Delay(seconds(5)));
+
+        // Moving within the delay costs nothing, and the unused delay isn't kept
+        clock.start_at(Color::White, start);
+        assert_eq!(clock.press_at(start + seconds(3)), None);
+        assert_eq!(
+            clock.remaining_at(Color::White, start + seconds(3)),
+            seconds(60)
+        );
+
+        assert_eq!(clock.press_at(start + seconds(13)), None);
+        assert_eq!(
+            clock.remaining_at(Color::Black, start + seconds(13)),
+            seconds(55)
+        );
+    }
+
+    #[test]
+    fn test_flag_fall() {
+        let start = Instant::now();
+        let seconds = Duration::from_secs;
+        let mut clock = GameClock::new(seconds(60), TimeControl::SuddenDeath)
+            .with_remaining(Color::Black, seconds(5));
+
+        clock.start_at(Color::White, start);
+        assert_eq!(clock.press_at(start + seconds(1)), None);
+        assert_eq!(clock.flagged_at(start + seconds(5)), None);
+        assert_eq!(clock.flagged_at(start + seconds(6)), Some(Color::Black));
+
+        // Pressing too late doesn't save them, and their opponent's clock stays stopped
+        assert_eq!(clock.press_at(start + seconds(7)), Some(Color::Black));
+        assert_eq!(clock.running(), None);
+        clock.start_at(Color::White, start + seconds(8));
+        assert_eq!(clock.running(), None);
+        assert_eq!(
+            clock.remaining_at(Color::White, start + seconds(8)),
+            seconds(59)
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::io::{Read, Write};
+
+use crate::{
+    notation::{into_notation, try_from_notation, Fen},
+    pgn::Pgn,
+    Color, GameStatus, Move, MoveQuery, MoveResult, Piece, Square, State, Variant,
+};
+
+/// The start of every file of game records, followed by the format version
+const MAGIC: &[u8; 4] = b"WCGR";
+const VERSION: u8 = 1;
+
+// Flags describing what's stored with a game
+const FLAG_SCORES: u8 = 1;
+const FLAG_INITIAL_STATE: u8 = 1 << 1;
+
+/// A game in a compact binary format, for when there are far too many of them (like the
+/// games played while tuning) for PGN to be practical. Moves take two bytes each, as
+/// their origin, destination and promotion, and can have a score each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub initial_state: State,
+    pub moves: Vec<Move>,
+
+    /// The score of each move in centipawns from white's perspective, as the side
+    /// playing it saw it. Either every move has a score, or none do.
+    pub scores: Option<Vec<i16>>,
+
+    /// How the game ended, or `Ongoing` if it's unknown
+    pub result: GameStatus,
+}
+
+impl GameRecord {
+    pub fn new(initial_state: State) -> Self {
+        Self {
+            initial_state,
+            moves: vec![],
+            scores: None,
+            result: GameStatus::Ongoing,
+        }
+    }
+
+    /// The game in PGN, with the scores (if there are any) as `[%eval]` comments
+    pub fn to_pgn(&self) -> Pgn {
+        let mut pgn = Pgn::new(self.initial_state.clone());
+        pgn.moves = self.moves.iter().map(|mv| (*mv).into()).collect();
+        if let Some(scores) = &self.scores {
+            for (annotated_move, score) in pgn.moves.iter_mut().zip(scores) {
+                annotated_move.comment = Some(format!("[%eval {:.2}]", *score as f32 / 100.0));
+            }
+        }
+
+        pgn.declared_result = match self.result {
+            GameStatus::Won(Color::White) => Some(String::from("1-0")),
+            GameStatus::Won(Color::Black) => Some(String::from("0-1")),
+            GameStatus::Drawn => Some(String::from("1/2-1/2")),
+            GameStatus::Ongoing => None,
+        };
+
+        pgn
+    }
+
+    fn encode_move(mv: &Move) -> u16 {
+        let promotion = match mv.promotion() {
+            Some(Piece::Knight) => 1,
+            Some(Piece::Bishop) => 2,
+            Some(Piece::Rook) => 3,
+            Some(Piece::Queen) => 4,
+            _ => 0,
+        };
+
+        mv.origin().index() as u16 | (mv.destination().index() as u16) << 6 | promotion << 12
+    }
+
+    /// Finds the legal move in the position that was encoded, if there's one. Only the
+    /// moves of the piece on the origin square are generated, so reading doesn't cost a
+    /// full move generation for every ply.
+    fn decode_move(state: &State, encoded: u16) -> Option<MoveResult> {
+        let origin = Square::from_index(encoded as usize & 0x3f)?;
+        let destination = Square::from_index(encoded as usize >> 6 & 0x3f)?;
+        let piece = state.board().piece_at(origin)?.piece();
+
+        let mut query = MoveQuery::by_moving_from_to(origin, destination);
+        query.set_piece(piece);
+        match encoded >> 12 {
+            0 => {}
+            1 => query.set_promotion(Piece::Knight),
+            2 => query.set_promotion(Piece::Bishop),
+            3 => query.set_promotion(Piece::Rook),
+            4 => query.set_promotion(Piece::Queen),
+            _ => return None,
+        }
+
+        state
+            .find_legal(&query)
+            .filter(|result| Self::encode_move(&result.0) == encoded)
+    }
+}
+
+#[derive(Debug)]
+pub enum GameRecordError {
+    Io(std::io::Error),
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    InvalidVariant(u8),
+    InvalidFen(String),
+    InvalidResult(u8),
+
+    /// The move at this index of the game isn't legal in its position
+    InvalidMove(usize),
+
+    /// A game has a different number of scores than moves
+    ScoreCountMismatch,
+    TooManyMoves,
+}
+
+impl std::fmt::Display for GameRecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameRecordError::Io(e) => write!(f, "unable to access game records: {}", e),
+            GameRecordError::InvalidHeader => write!(f, "not a file of game records"),
+            GameRecordError::UnsupportedVersion(version) => {
+                write!(f, "unsupported game record version: {}", version)
+            }
+            GameRecordError::InvalidVariant(variant) => write!(f, "invalid variant: {}", variant),
+            GameRecordError::InvalidFen(fen) => write!(f, "invalid FEN: {}", fen),
+            GameRecordError::InvalidResult(result) => write!(f, "invalid result: {}", result),
+            GameRecordError::InvalidMove(index) => write!(f, "invalid move at ply {}", index + 1),
+            GameRecordError::ScoreCountMismatch => {
+                write!(f, "a game needs as many scores as it has moves")
+            }
+            GameRecordError::TooManyMoves => write!(f, "too many moves in a game"),
+        }
+    }
+}
+
+impl std::error::Error for GameRecordError {}
+
+impl From<std::io::Error> for GameRecordError {
+    fn from(e: std::io::Error) -> Self {
+        GameRecordError::Io(e)
+    }
+}
+
+/// Writes games to a file of records one after the other, see `GameRecordReader`
+pub struct GameRecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> GameRecordWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, GameRecordError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, record: &GameRecord) -> Result<(), GameRecordError> {
+        let move_count =
+            u16::try_from(record.moves.len()).map_err(|_| GameRecordError::TooManyMoves)?;
+
+        if record
+            .scores
+            .as_ref()
+            .is_some_and(|scores| scores.len() != record.moves.len())
+        {
+            return Err(GameRecordError::ScoreCountMismatch);
+        }
+
+        // Games from the standard starting position are the usual case, and don't need a FEN
+        let initial_state = &record.initial_state;
+        let is_standard_start =
+            *initial_state == State::default().with_variant(initial_state.variant());
+
+        let mut flags = 0;
+        if record.scores.is_some() {
+            flags |= FLAG_SCORES;
+        }
+
+        if !is_standard_start {
+            flags |= FLAG_INITIAL_STATE;
+        }
+
+        let variant = Variant::ALL
+            .iter()
+            .position(|variant| *variant == initial_state.variant())
+            .unwrap_or_default() as u8;
+
+        let result = match record.result {
+            GameStatus::Ongoing => 0,
+            GameStatus::Won(Color::White) => 1,
+            GameStatus::Won(Color::Black) => 2,
+            GameStatus::Drawn => 3,
+        };
+
+        let mut bytes = vec![flags, variant, result];
+        if !is_standard_start {
+            let fen = into_notation::<_, Fen>(initial_state).to_string();
+            bytes.extend((fen.len() as u16).to_le_bytes());
+            bytes.extend(fen.as_bytes());
+        }
+
+        bytes.extend(move_count.to_le_bytes());
+        for mv in record.moves.iter() {
+            bytes.extend(GameRecord::encode_move(mv).to_le_bytes());
+        }
+
+        for score in record.scores.iter().flatten() {
+            bytes.extend(score.to_le_bytes());
+        }
+
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), GameRecordError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads back the games written by a `GameRecordWriter`, in the order they were written
+pub struct GameRecordReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> GameRecordReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, GameRecordError> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC[..] {
+            return Err(GameRecordError::InvalidHeader);
+        }
+
+        if header[4] != VERSION {
+            return Err(GameRecordError::UnsupportedVersion(header[4]));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Reads the next game, or nothing at the end of the file. The moves are checked
+    /// to be legal while they're read, so every game can be replayed.
+    pub fn read(&mut self) -> Result<Option<GameRecord>, GameRecordError> {
+        let mut flags = [0];
+        if self.reader.read(&mut flags)? == 0 {
+            return Ok(None);
+        }
+
+        let [flags] = flags;
+        let [variant, result] = self.read_bytes::<2>()?;
+        let variant = *Variant::ALL
+            .get(variant as usize)
+            .ok_or(GameRecordError::InvalidVariant(variant))?;
+
+        let result = match result {
+            0 => GameStatus::Ongoing,
+            1 => GameStatus::Won(Color::White),
+            2 => GameStatus::Won(Color::Black),
+            3 => GameStatus::Drawn,
+            _ => return Err(GameRecordError::InvalidResult(result)),
+        };
+
+        let initial_state = if flags & FLAG_INITIAL_STATE != 0 {
+            let length = u16::from_le_bytes(self.read_bytes()?);
+            let mut fen = vec![0; length as usize];
+            self.reader.read_exact(&mut fen)?;
+            let fen = String::from_utf8_lossy(&fen).to_string();
+            try_from_notation::<_, Fen>(&fen).map_err(|_| GameRecordError::InvalidFen(fen))?
+        } else {
+            State::default()
+        }
+        .with_variant(variant);
+
+        let move_count = u16::from_le_bytes(self.read_bytes()?) as usize;
+        let mut moves = Vec::with_capacity(move_count);
+        let mut state = initial_state.clone();
+        for index in 0..move_count {
+            let encoded = u16::from_le_bytes(self.read_bytes()?);
+            let MoveResult(mv, next_state) = GameRecord::decode_move(&state, encoded)
+                .ok_or(GameRecordError::InvalidMove(index))?;
+
+            moves.push(mv);
+            state = next_state;
+        }
+
+        let scores = if flags & FLAG_SCORES != 0 {
+            let mut scores = Vec::with_capacity(move_count);
+            for _ in 0..move_count {
+                scores.push(i16::from_le_bytes(self.read_bytes()?));
+            }
+
+            Some(scores)
+        } else {
+            None
+        };
+
+        Ok(Some(GameRecord {
+            initial_state,
+            moves,
+            scores,
+            result,
+        }))
+    }
+
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], GameRecordError> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Iterator for GameRecordReader<R> {
+    type Item = Result<GameRecord, GameRecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::San;
+
+    fn play(initial_state: State, moves: &[&str]) -> GameRecord {
+        let mut record = GameRecord::new(initial_state.clone());
+        let mut state = initial_state;
+        for san in moves {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            let MoveResult(mv, next) = state.find_legal(&query).unwrap();
+            record.moves.push(mv);
+            state = next;
+        }
+
+        record
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut scholars_mate = play(
+            State::default(),
+            &["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"],
+        );
+
+        scholars_mate.scores = Some(vec![30, 25, 10, 40, 35, -500, 32000]);
+        scholars_mate.result = GameStatus::Won(Color::White);
+
+        let endgame = try_from_notation::<_, Fen>("8/P6k/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let mut promotion = play(endgame, &["O-O", "Kg6", "a8=N"]);
+        promotion.result = GameStatus::Drawn;
+
+        let antichess = play(State::default().with_variant(Variant::Antichess), &["e3"]);
+
+        let mut writer = GameRecordWriter::new(vec![]).unwrap();
+        for record in [&scholars_mate, &promotion, &antichess] {
+            writer.write(record).unwrap();
+        }
+
+        let bytes = writer.into_inner();
+        let records: Vec<GameRecord> = GameRecordReader::new(&bytes[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(records, vec![scholars_mate.clone(), promotion, antichess]);
+
+        // Much smaller than the same game in PGN
+        let mut writer = GameRecordWriter::new(vec![]).unwrap();
+        writer.write(&scholars_mate).unwrap();
+        assert_eq!(writer.into_inner().len(), 5 + 5 + 7 * 4);
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let mut record = play(State::default(), &["e4", "e5", "Nf3"]);
+        record.result = GameStatus::Drawn;
+        assert!(record
+            .to_pgn()
+            .to_string()
+            .ends_with("1. e4 e5 2. Nf3 1/2-1/2\n"));
+
+        record.result = GameStatus::Ongoing;
+        assert!(record.to_pgn().to_string().ends_with("2. Nf3 *\n"));
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        assert!(matches!(
+            GameRecordReader::new(&b"[Event \"?\"]"[..]),
+            Err(GameRecordError::InvalidHeader)
+        ));
+
+        let mut record = play(State::default(), &["e4"]);
+        record.scores = Some(vec![]);
+        let mut writer = GameRecordWriter::new(vec![]).unwrap();
+        assert!(matches!(
+            writer.write(&record),
+            Err(GameRecordError::ScoreCountMismatch)
+        ));
+
+        // A game cut short partway through
+        record.scores = None;
+        writer.write(&record).unwrap();
+        let bytes = writer.into_inner();
+        let mut reader = GameRecordReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(reader.read(), Err(GameRecordError::Io(_))));
+    }
+}
@@ -1,13 +1,22 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{utils::ArrayMap, Color, Piece, PieceIndex, Square, State};
+use crate::{
+    utils::{ArrayMap, Index},
+    Color, Piece, PieceIndex, Square, State, Variant,
+};
 
 pub type Hash = u64;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ZobristHasher {
     turn_hash: ArrayMap<Color, u64>,
     piece_hash: ArrayMap<Square, ArrayMap<PieceIndex, u64>>,
+
+    /// Keeps the same position under different rules from sharing a hash. Hashers
+    /// saved before there were variants hash every variant like standard chess.
+    #[serde(default)]
+    variant_hash: ArrayMap<Variant, u64>,
 }
 
 impl ZobristHasher {
@@ -15,9 +24,21 @@ impl ZobristHasher {
     where
         R: Rng,
     {
+        let turn_hash = ArrayMap::from_fn(|_| rng.next_u64());
+        let piece_hash = ArrayMap::from_fn(|_| ArrayMap::from_fn(|_| rng.next_u64()));
+
+        // Standard positions hash the same as they did before there were variants,
+        // so that the books and tables built back then still line up
+        let standard = Index::from(Variant::Standard).0;
+        let variant_hash = ArrayMap::from_fn(|i| match i {
+            i if i == standard => 0,
+            _ => rng.next_u64(),
+        });
+
         Self {
-            turn_hash: ArrayMap::from_fn(|_| rng.next_u64()),
-            piece_hash: ArrayMap::from_fn(|_| ArrayMap::from_fn(|_| rng.next_u64())),
+            turn_hash,
+            piece_hash,
+            variant_hash,
         }
     }
 
@@ -27,14 +48,41 @@ impl ZobristHasher {
             for piece in Piece::ALL_INCLUDING_NONE {
                 let piece_index = PieceIndex::new(*color, *piece);
                 let occupancy = state.board().piece_occupancy(piece_index);
-                for square in occupancy.iter_ones() {
-                    let square = Square::from(square);
+                for square in occupancy.iter_squares() {
                     hash ^= self.piece_hash[square][piece_index];
                 }
             }
         }
 
         hash ^= self.turn_hash[state.turn_to_move()];
+        hash ^= self.variant_hash[state.variant()];
         hash
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_variants() {
+        let hasher = ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(0));
+        let state = State::default();
+        let hashes: Vec<Hash> = Variant::ALL
+            .iter()
+            .map(|variant| hasher.hash(&state.clone().with_variant(*variant)))
+            .collect();
+
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(hashes[i + 1..].iter().all(|other| other != hash));
+        }
+
+        // Standard positions hash as if there were no variants at all
+        let mut without_variants = hasher.clone();
+        without_variants.variant_hash = ArrayMap::default();
+        assert_eq!(hashes[0], without_variants.hash(&state));
+    }
+}
@@ -1,29 +1,47 @@
 #![feature(generic_const_exprs)]
+#![feature(step_trait)]
 #![feature(test)]
 
 mod attacks;
 mod board;
 mod book;
+mod canonical;
 mod color;
 mod common;
+mod diagram;
+mod eco;
+mod game_clock;
+mod game_record;
 mod hasher;
 mod movegen;
 mod moves;
 mod piece;
+mod piece_squares;
 mod printer;
 mod state;
+mod variant;
 
 pub mod notation;
+pub mod pawns;
+pub mod pgn;
+pub mod reference;
 pub mod utils;
 
 pub use attacks::*;
 pub use board::*;
 pub use book::*;
+pub use canonical::*;
 pub use color::*;
 pub use common::*;
+pub use diagram::*;
+pub use eco::*;
+pub use game_clock::*;
+pub use game_record::*;
 pub use hasher::*;
 pub use movegen::*;
 pub use moves::*;
 pub use piece::*;
+pub use piece_squares::*;
 pub use printer::*;
 pub use state::*;
+pub use variant::*;
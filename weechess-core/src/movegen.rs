@@ -3,8 +3,8 @@ use std::ops::Deref;
 use crate::Offset;
 
 use super::{
-    AttackGenerator, BitBoard, CastleRights, Color, Move, MoveResult, MoveSet, Piece, PieceIndex,
-    Rank, Side, Square, State, CASTLE_CHECK_MASKS, CASTLE_PATH_MASKS, RANK_MASKS,
+    AttackGenerator, BitBoard, CastleRights, File, Move, MoveResult, MoveSet, Piece, PieceIndex,
+    Side, Square, State, Variant, CASTLE_CHECK_MASKS, CASTLE_PATH_MASKS,
 };
 
 #[derive(Debug, Clone)]
@@ -48,6 +48,10 @@ impl PseudoLegalMove {
     pub fn try_as_legal_move(self, state: &State) -> Option<MoveResult> {
         let king = PieceIndex::new(state.turn_to_move(), Piece::King);
         let next_state = State::by_performing_move(state, &self.0).unwrap();
+        if !state.variant().has_royal_king() {
+            return Some(MoveResult(self.0, next_state));
+        }
+
         let king_position = next_state.board().piece_occupancy(king);
         let attacked_positions = next_state
             .board()
@@ -59,6 +63,64 @@ impl PseudoLegalMove {
             None
         }
     }
+
+    /// Whether the move keeps the king out of check, like [`PseudoLegalMove::try_as_legal_move`]
+    /// but without building the position it leads to. Only the occupancy the move changes is
+    /// updated, and the king's square is checked for attackers by looking outwards from it.
+    pub fn is_legal(self, state: &State) -> bool {
+        if !state.variant().has_royal_king() {
+            return true;
+        }
+
+        let mv = self.0;
+        let color = state.turn_to_move();
+        let board = state.board();
+
+        let captured = if mv.is_en_passant() {
+            match mv.destination().offset(color.backward()) {
+                Some(square) => BitBoard::just(square),
+                None => return false,
+            }
+        } else if mv.is_capture() {
+            BitBoard::just(mv.destination())
+        } else {
+            BitBoard::ZERO
+        };
+
+        let mut occupancy = (board.occupancy() & !BitBoard::just(mv.origin()) & !captured)
+            | BitBoard::just(mv.destination());
+
+        if let Some(side) = mv.castle_side() {
+            let rank = mv.origin().rank();
+            let (start, end) = match side {
+                Side::King => (File::H, File::F),
+                Side::Queen => (File::A, File::D),
+            };
+
+            occupancy.set(Square::from((rank, start)), false);
+            occupancy.set(Square::from((rank, end)), true);
+        }
+
+        let mut kings = board.piece_occupancy(PieceIndex::new(color, Piece::King));
+        if mv.piece() == Piece::King {
+            kings.set(mv.origin(), false);
+            kings.set(mv.destination(), true);
+        }
+
+        let enemy = |piece| board.piece_occupancy(PieceIndex::new(!color, piece)) & !captured;
+        let diagonals = enemy(Piece::Bishop) | enemy(Piece::Queen);
+        let lines = enemy(Piece::Rook) | enemy(Piece::Queen);
+
+        kings.iter_squares().all(|king| {
+            let attackers = (AttackGenerator::compute_bishop_attacks(king, occupancy) & diagonals)
+                | (AttackGenerator::compute_rook_attacks(king, occupancy) & lines)
+                | (AttackGenerator::compute_knight_attacks(king) & enemy(Piece::Knight))
+                | (AttackGenerator::compute_pawn_attacks(king, color) & enemy(Piece::Pawn))
+                | (AttackGenerator::compute_king_attacks(king) & enemy(Piece::King));
+
+            attackers.none()
+        })
+    }
 }
 
 impl Deref for PseudoLegalMove {
@@ -91,6 +153,72 @@ impl MoveGenerator {
         }
     }
 
+    /// Counts the legal moves without building the position each of them leads to,
+    /// for when the number of moves is all that's needed (like at the leaves of perft)
+    pub fn count_legal_moves(state: &State) -> usize {
+        let mut buffer = MoveGenerationBuffer::new();
+        Self::count_legal_moves_with_buffer(state, &mut buffer)
+    }
+
+    /// Like [`MoveGenerator::count_legal_moves`], but generates the pseudo-legal
+    /// moves into the given buffer. The legal moves in it are left empty.
+    pub fn count_legal_moves_with_buffer(
+        state: &State,
+        buffer: &mut MoveGenerationBuffer,
+    ) -> usize {
+        buffer.clear();
+
+        let moves = &mut buffer.psuedo_legal_moves;
+        Self::compute_psuedo_legal_moves_into(state, moves);
+        moves.iter().filter(|m| m.is_legal(state)).count()
+    }
+
+    /// Counts the positions reachable in exactly `depth` moves. The callback is given
+    /// each position along the way with the move leading to it, its depth (from 1),
+    /// and the number of positions counted below it. Each depth gets its own buffer,
+    /// so nothing is allocated while walking the tree.
+    pub fn perft<F>(state: &State, depth: usize, mut f: F) -> usize
+    where
+        F: FnMut(&State, &Move, usize, usize),
+    {
+        let mut buffers: Vec<MoveGenerationBuffer> =
+            std::iter::repeat_with(MoveGenerationBuffer::new)
+                .take(depth)
+                .collect();
+
+        let mut count = 0;
+        Self::perft_recursive(state, 1, &mut buffers[..], &mut count, &mut f);
+        count
+    }
+
+    fn perft_recursive<F>(
+        state: &State,
+        depth: usize,
+        buffers: &mut [MoveGenerationBuffer],
+        count: &mut usize,
+        f: &mut F,
+    ) where
+        F: FnMut(&State, &Move, usize, usize),
+    {
+        if let Some((buffer, remaining_buffers)) = buffers.split_first_mut() {
+            // The positions past the last depth are only counted, so there's no need to build them
+            if remaining_buffers.is_empty() {
+                *count += Self::count_legal_moves_with_buffer(state, buffer);
+                return;
+            }
+
+            Self::compute_legal_moves_into(state, buffer);
+
+            for MoveResult(mv, new_state) in buffer.legal_moves.iter() {
+                let mut c0 = 0;
+                Self::perft_recursive(new_state, depth + 1, remaining_buffers, &mut c0, f);
+                (*f)(new_state, mv, depth, c0);
+
+                *count += c0;
+            }
+        }
+    }
+
     pub fn compute_psuedo_legal_moves_into(state: &State, result: &mut Vec<PseudoLegalMove>) {
         result.clear();
         let helper = GameStateHelper { state };
@@ -100,6 +228,54 @@ impl MoveGenerator {
         Self::compute_bishop_moves(helper, result);
         Self::compute_rook_moves(helper, result);
         Self::compute_queen_moves(helper, result);
+
+        // Captures are compulsory in antichess, and since there's no such thing as
+        // check in it, these moves are exactly the legal ones already
+        if state.variant() == Variant::Antichess && result.iter().any(|m| m.is_capture()) {
+            result.retain(|m| m.is_capture());
+        }
+    }
+
+    /// Computes the pseudo-legal quiet moves for the side to move that directly attack the
+    /// given square from where they land, which is how to find the moves that check a king
+    /// standing on it. Captures and castling are left out, as are discovered checks.
+    pub fn compute_psuedo_legal_checks_into(
+        state: &State,
+        king_square: Square,
+        result: &mut Vec<PseudoLegalMove>,
+    ) {
+        Self::compute_psuedo_legal_moves_into(state, result);
+
+        let occupancy = state.board().occupancy();
+        result.retain(|mv| {
+            if mv.is_capture() || mv.castle_side().is_some() {
+                return false;
+            }
+
+            let piece = PieceIndex::new(mv.color(), mv.resulting_piece());
+            let occupancy =
+                (occupancy & !BitBoard::just(mv.origin())) | BitBoard::just(mv.destination());
+            AttackGenerator::compute(piece, mv.destination(), occupancy).test(king_square)
+        });
+    }
+
+    /// Computes the pseudo-legal moves of a single type of piece for the side to move
+    pub fn compute_pseudo_legal_piece_moves_into(
+        state: &State,
+        piece: Piece,
+        result: &mut Vec<PseudoLegalMove>,
+    ) {
+        result.clear();
+        let helper = GameStateHelper { state };
+        match piece {
+            Piece::Pawn => Self::compute_pawn_moves(helper, result),
+            Piece::Knight => Self::compute_knight_moves(helper, result),
+            Piece::King => Self::compute_king_moves(helper, result),
+            Piece::Bishop => Self::compute_bishop_moves(helper, result),
+            Piece::Rook => Self::compute_rook_moves(helper, result),
+            Piece::Queen => Self::compute_queen_moves(helper, result),
+            Piece::None => {}
+        }
     }
 
     fn compute_pawn_moves<'a>(helper: GameStateHelper<'a>, result: &mut Vec<PseudoLegalMove>) {
@@ -109,27 +285,39 @@ impl MoveGenerator {
         const PROMOTION_TYPES: &'static [Piece] =
             &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
 
+        // Kings aren't special in antichess, so pawns can promote to them too
+        const ANTICHESS_PROMOTION_TYPES: &[Piece] = &[
+            Piece::Queen,
+            Piece::Rook,
+            Piece::Bishop,
+            Piece::Knight,
+            Piece::King,
+        ];
+
+        let promotion_types = match helper.variant() {
+            Variant::Antichess => ANTICHESS_PROMOTION_TYPES,
+            _ => PROMOTION_TYPES,
+        };
+
         // Simple pawn push
         {
             let positions = pawns.shift(helper.turn_to_move().forward()) & helper.board().vacancy();
-            let promotion_positions = positions & helper.own_backrank_mask();
-            let non_promption_positions = positions & !helper.own_backrank_mask();
+            let promotion_positions = positions & helper.own_promotion_rank_mask();
+            let non_promption_positions = positions & !helper.own_promotion_rank_mask();
 
             let backwards = helper.turn_to_move().backward();
 
             // Non-promotion moves
-            for pos in non_promption_positions.iter_ones() {
-                let target = Square::from(pos);
+            for target in non_promption_positions.iter_squares() {
                 let origin = target.offset(backwards).unwrap();
                 let mv = Move::by_moving(pawn, origin, target);
                 result.push(PseudoLegalMove(mv));
             }
 
             // Promotion moves
-            for pos in promotion_positions.iter_ones() {
-                let target = Square::from(pos);
+            for target in promotion_positions.iter_squares() {
                 let origin = target.offset(backwards).unwrap();
-                for piece in PROMOTION_TYPES {
+                for piece in promotion_types {
                     let mv = Move::by_promoting(pawn, origin, target, *piece);
                     result.push(PseudoLegalMove(mv));
                 }
@@ -143,8 +331,7 @@ impl MoveGenerator {
                 pawns.shift(helper.turn_to_move().forward()) & helper.board().vacancy()
             });
 
-            for pos in positons.iter_ones() {
-                let target = Square::from(pos);
+            for target in positons.iter_squares() {
                 let origin = (0..2).fold(target, |p, _| {
                     p.offset(helper.turn_to_move().backward()).unwrap()
                 });
@@ -168,9 +355,9 @@ impl MoveGenerator {
                     .shift(*file_offset);
 
                 let attacks_with_promotion =
-                    attacks & helper.own_backrank_mask() & helper.opposing_pieces();
+                    attacks & helper.own_promotion_rank_mask() & helper.opposing_pieces();
                 let attacks_without_promotion =
-                    attacks & !helper.own_backrank_mask() & helper.opposing_pieces();
+                    attacks & !helper.own_promotion_rank_mask() & helper.opposing_pieces();
                 let attacks_with_en_passant = attacks
                     & helper
                         .en_passant_target()
@@ -178,8 +365,7 @@ impl MoveGenerator {
                         .unwrap_or(BitBoard::ZERO);
 
                 // Non-promotion captures
-                for pos in attacks_without_promotion.iter_ones() {
-                    let target = Square::from(pos);
+                for target in attacks_without_promotion.iter_squares() {
                     let origin = target.offset(inverted_capture_offset).unwrap();
                     let capture = helper.board().piece_at(target).unwrap();
                     let mv = Move::by_capturing(pawn, origin, target, capture.piece());
@@ -187,11 +373,10 @@ impl MoveGenerator {
                 }
 
                 // Promotion captures
-                for pos in attacks_with_promotion.iter_ones() {
-                    let target = Square::from(pos);
+                for target in attacks_with_promotion.iter_squares() {
                     let origin = target.offset(inverted_capture_offset).unwrap();
                     let capture = helper.board().piece_at(target).unwrap();
-                    for piece in PROMOTION_TYPES {
+                    for piece in promotion_types {
                         let mv = Move::by_capture_promoting(
                             pawn,
                             origin,
@@ -205,8 +390,7 @@ impl MoveGenerator {
                 }
 
                 // En passant captures
-                if let Some(bit) = attacks_with_en_passant.first_one() {
-                    let target = Square::from(bit);
+                if let Some(target) = attacks_with_en_passant.first_square() {
                     let origin = target.offset(inverted_capture_offset).unwrap();
                     let mv = Move::by_en_passant(pawn, origin, target);
                     result.push(PseudoLegalMove(mv));
@@ -217,8 +401,7 @@ impl MoveGenerator {
 
     fn compute_knight_moves<'a>(helper: GameStateHelper<'a>, result: &mut Vec<PseudoLegalMove>) {
         let knights = helper.own_piece(Piece::Knight);
-        for bit in knights.iter_ones() {
-            let square = Square::from(bit);
+        for square in knights.iter_squares() {
             let jumps = AttackGenerator::compute_knight_attacks(square)
                 & (helper.opposing_pieces() | helper.board().vacancy());
 
@@ -229,8 +412,7 @@ impl MoveGenerator {
     fn compute_king_moves<'a>(helper: GameStateHelper<'a>, result: &mut Vec<PseudoLegalMove>) {
         let color = helper.turn_to_move();
         let kings = helper.own_piece(Piece::King);
-        for bit in kings.iter_ones() {
-            let origin = Square::from(bit);
+        for origin in kings.iter_squares() {
             let jumps = AttackGenerator::compute_king_attacks(origin)
                 & (helper.opposing_pieces() | helper.board().vacancy())
                 & !helper.opposing_attacks();
@@ -253,8 +435,7 @@ impl MoveGenerator {
         let occupancy = helper.board().occupancy();
         let bishops = helper.own_piece(Piece::Bishop);
         let own_pieces = helper.own_pieces();
-        for bit in bishops.iter_ones() {
-            let origin = Square::from(bit);
+        for origin in bishops.iter_squares() {
             let attacks = AttackGenerator::compute_bishop_attacks(origin, occupancy);
             let slides = attacks & !own_pieces;
             helper.expand_moves(origin, slides, Piece::Bishop, result);
@@ -265,8 +446,7 @@ impl MoveGenerator {
         let occupancy = helper.board().occupancy();
         let rooks = helper.own_piece(Piece::Rook);
         let own_pieces = helper.own_pieces();
-        for bit in rooks.iter_ones() {
-            let origin = Square::from(bit);
+        for origin in rooks.iter_squares() {
             let attacks = AttackGenerator::compute_rook_attacks(origin, occupancy);
             let slides = attacks & !own_pieces;
             helper.expand_moves(origin, slides, Piece::Rook, result);
@@ -277,8 +457,7 @@ impl MoveGenerator {
         let occupancy = helper.board().occupancy();
         let queens = helper.own_piece(Piece::Queen);
         let own_pieces = helper.own_pieces();
-        for bit in queens.iter_ones() {
-            let origin = Square::from(bit);
+        for origin in queens.iter_squares() {
             let attacks = AttackGenerator::compute_queen_attacks(origin, occupancy);
             let slides = attacks & !own_pieces;
             helper.expand_moves(origin, slides, Piece::Queen, result);
@@ -304,18 +483,12 @@ impl GameStateHelper<'_> {
         self.castle_rights(self.turn_to_move())
     }
 
-    fn own_backrank_mask(&self) -> BitBoard {
-        match self.turn_to_move() {
-            Color::White => RANK_MASKS[Rank::EIGHT],
-            Color::Black => RANK_MASKS[Rank::ONE],
-        }
+    fn own_promotion_rank_mask(&self) -> BitBoard {
+        self.turn_to_move().promotion_rank_mask()
     }
 
     fn own_pawn_home_rank_mask(&self) -> BitBoard {
-        match self.turn_to_move() {
-            Color::White => RANK_MASKS[Rank::TWO],
-            Color::Black => RANK_MASKS[Rank::SEVEN],
-        }
+        self.turn_to_move().pawn_rank_mask()
     }
 
     fn to_own_piece(&self, piece: Piece) -> PieceIndex {
@@ -341,8 +514,7 @@ impl GameStateHelper<'_> {
     ) {
         let piece = self.to_own_piece(piece);
 
-        for bit in destinations.iter_ones() {
-            let target = Square::from(bit);
+        for target in destinations.iter_squares() {
             if let Some(capture) = self.board().piece_at(target) {
                 let mv = Move::by_capturing(piece, origin, target, capture.piece());
                 result.push(PseudoLegalMove(mv));
@@ -364,5 +536,92 @@ impl Deref for GameStateHelper<'_> {
 
 #[cfg(test)]
 mod tests {
-    //
+    use crate::notation::{try_from_notation, Fen};
+
+    use super::*;
+
+    #[test]
+    fn test_checking_moves() {
+        let checks = |fen: &str, king_square: Square| {
+            let state = try_from_notation::<_, Fen>(fen).unwrap();
+            let mut moves = Vec::new();
+            MoveGenerator::compute_psuedo_legal_checks_into(&state, king_square, &mut moves);
+            moves
+                .iter()
+                .map(|mv| (mv.origin(), mv.destination()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            checks("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Square::G8),
+            vec![(Square::A1, Square::A8)]
+        );
+
+        // Only the single pawn push attacks the king, and promoting doesn't
+        assert_eq!(
+            checks("8/1P6/8/8/3k4/8/4P3/4K3 w - - 0 1", Square::D4),
+            vec![(Square::E2, Square::E3)]
+        );
+    }
+
+    #[test]
+    fn test_count_legal_moves() {
+        let positions = [
+            Fen::DEFAULT,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Taking en passant would expose the king along the rank
+            "8/8/8/K2pP2r/8/8/8/7k w - d6 0 1",
+        ];
+
+        for fen in positions {
+            let state = try_from_notation::<_, Fen>(fen).unwrap();
+            let moves = MoveGenerator::compute_legal_moves(&state);
+            assert_eq!(
+                MoveGenerator::count_legal_moves(&state),
+                moves.moves().len()
+            );
+
+            for result in moves.moves() {
+                let next_state = &result.1;
+                assert_eq!(
+                    MoveGenerator::count_legal_moves(next_state),
+                    MoveGenerator::compute_legal_moves(next_state).moves().len(),
+                    "{}",
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft() {
+        assert_eq!(
+            MoveGenerator::perft(&State::default(), 0, |_, _, _, _| {}),
+            0
+        );
+        assert_eq!(
+            MoveGenerator::perft(&State::default(), 1, |_, _, _, _| {}),
+            20
+        );
+
+        let mut divided = 0;
+        let count = MoveGenerator::perft(&State::default(), 3, |_, _, depth, count| {
+            if depth == 1 {
+                divided += count;
+            }
+        });
+
+        assert_eq!(count, 8902);
+        assert_eq!(divided, count);
+
+        let state = try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+
+        assert_eq!(MoveGenerator::perft(&state, 3, |_, _, _, _| {}), 97862);
+    }
 }
@@ -2,7 +2,10 @@ use std::fmt::{Display, Formatter};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{File, Rank};
+use crate::{
+    notation::{into_notation, lan::Lan, try_from_notation, San},
+    File, Rank,
+};
 
 use self::compact::BitSetExt;
 
@@ -41,12 +44,53 @@ impl MoveQuery {
         this
     }
 
+    /// Parses a move the way UCI writes them (ex. `e2e4`, `e7e8q`, or `e1g1` to castle)
+    pub fn try_from_uci(notation: &str) -> Option<Self> {
+        try_from_notation::<_, Lan>(notation).ok()
+    }
+
     pub fn by_castling(side: Side) -> Self {
         let mut this = Self::new();
         this.set_castle(side);
         this
     }
 
+    /// Rewrites a king moving onto its own rook (ex. `e1h1`), which is how some GUIs
+    /// write castling, as the castle on that side. Any other query is left as it is.
+    pub fn normalized_castling(self, state: &State) -> Self {
+        let (Some(origin_rank), Some(origin_file), Some(dest_rank), Some(dest_file)) = (
+            self.origin_rank,
+            self.origin_file,
+            self.dest_rank,
+            self.dest_file,
+        ) else {
+            return self;
+        };
+
+        let color = state.turn_to_move();
+        let origin = Square::new(origin_file, origin_rank);
+        let destination = Square::new(dest_file, dest_rank);
+        let board = state.board();
+        if origin_rank != dest_rank
+            || board.piece_at(origin) != Some(PieceIndex::new(color, Piece::King))
+            || board.piece_at(destination) != Some(PieceIndex::new(color, Piece::Rook))
+        {
+            return self;
+        }
+
+        let side = if dest_file > origin_file {
+            Side::King
+        } else {
+            Side::Queen
+        };
+
+        if !state.castle_rights(color).for_side(side) {
+            return self;
+        }
+
+        Self::by_castling(side)
+    }
+
     pub fn set_origin(&mut self, origin: Square) {
         self.origin_rank = Some(origin.rank());
         self.origin_file = Some(origin.file());
@@ -323,9 +367,23 @@ impl Move {
             && !self.is_double_pawn()
     }
 
+    /// The same move played by the other side on a board mirrored top to bottom
+    pub fn flipped(&self) -> Self {
+        let mut bits = self.0 & !(compact::ORIGIN_MASK | compact::DEST_MASK);
+        bits.set_origin(self.origin().flip());
+        bits.set_dest(self.destination().flip());
+        bits.set_color(!self.0.color());
+        Self(bits)
+    }
+
     pub fn as_raw(&self) -> u32 {
         self.0
     }
+
+    /// Rebuilds a move from the value returned by `as_raw`
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
 }
 
 impl Display for Move {
@@ -373,6 +431,40 @@ impl MoveSet {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// The same moves, ordered from most to least promising according to the
+    /// estimator. Moves with the same estimate keep their original order.
+    pub fn sorted_by_estimate<E: MoveEstimator>(&self, state: &State, estimator: &E) -> Self {
+        let mut moves = self.0.clone();
+        moves.sort_by_cached_key(|MoveResult(mv, _)| {
+            std::cmp::Reverse(estimator.estimate_move(state, mv))
+        });
+
+        Self(moves)
+    }
+
+    /// The moves in standard algebraic notation, as played from the given state
+    pub fn to_san_list(&self, state: &State) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|MoveResult(mv, _)| into_notation::<_, San>(&(state.clone(), *mv)).to_string())
+            .collect()
+    }
+}
+
+/// Something that can guess how good a move is at face value, without searching it
+pub trait MoveEstimator {
+    /// Higher estimates are better for the side making the move
+    fn estimate_move(&self, state: &State, mv: &Move) -> i32;
+}
+
+impl<F> MoveEstimator for F
+where
+    F: Fn(&State, &Move) -> i32,
+{
+    fn estimate_move(&self, state: &State, mv: &Move) -> i32 {
+        self(state, mv)
+    }
 }
 
 impl Into<Vec<MoveResult>> for MoveSet {
@@ -387,6 +479,115 @@ impl From<Vec<MoveResult>> for MoveSet {
     }
 }
 
+/// A line of moves, like a principal variation, kept inline rather than on the heap
+/// so it can be passed around freely while searching. Moves past the capacity are
+/// dropped, which is far deeper than any line a search will find.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Line {
+    moves: [Move; Line::CAPACITY],
+    len: u8,
+}
+
+impl Line {
+    pub const CAPACITY: usize = 128;
+
+    pub const fn new() -> Self {
+        Self {
+            moves: [Move::NULL; Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Adds a move to the end of the line, unless the line is already full
+    pub fn push(&mut self, mv: Move) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        self.moves[self.len as usize] = mv;
+        self.len += 1;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len as usize == Self::CAPACITY
+    }
+
+    /// The line in standard algebraic notation, as played from the given state. Moves
+    /// that can't be played from there stop the line, along with everything after them.
+    pub fn to_san(&self, state: &State) -> String {
+        let mut state = state.clone();
+        let mut san = Vec::with_capacity(self.len());
+        for mv in self.iter() {
+            let Ok(next_state) = State::by_performing_move(&state, mv) else {
+                break;
+            };
+
+            san.push(into_notation::<_, San>(&(state, *mv)).to_string());
+            state = next_state;
+        }
+
+        san.join(" ")
+    }
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::Deref for Line {
+    type Target = [Move];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len as usize]
+    }
+}
+
+impl FromIterator<Move> for Line {
+    fn from_iter<T: IntoIterator<Item = Move>>(iter: T) -> Self {
+        let mut line = Self::new();
+        for mv in iter.into_iter().take(Self::CAPACITY) {
+            line.push(mv);
+        }
+
+        line
+    }
+}
+
+impl<'a> IntoIterator for &'a Line {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<&[Move]> for Line {
+    fn from(moves: &[Move]) -> Self {
+        moves.iter().copied().collect()
+    }
+}
+
+impl std::fmt::Debug for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Writes the line in long algebraic notation, separated by spaces
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", into_notation::<_, Lan>(&&self[..]))
+    }
+}
+
 mod compact {
     use num_enum::TryFromPrimitive;
 
@@ -576,9 +777,12 @@ mod compact {
 
 #[cfg(test)]
 mod test {
-    use crate::{Color, MoveQuery, Piece, PieceIndex, Square};
+    use crate::{
+        notation::{try_from_notation, Fen},
+        Color, MoveGenerator, MoveQuery, Piece, PieceIndex, Square,
+    };
 
-    use super::Move;
+    use super::*;
 
     #[test]
     fn test_simple_move() {
@@ -754,4 +958,48 @@ mod test {
         let q = MoveQuery::by_moving_from_to(Square::A1, Square::A3);
         assert!(!q.test(&m));
     }
+
+    #[test]
+    fn test_sorted_san_list() {
+        let state = try_from_notation::<_, Fen>("4k3/8/8/3p4/4P3/8/8/R3K3 w Q - 0 1").unwrap();
+        let moves = MoveGenerator::compute_legal_moves(&state);
+
+        // Prefer captures, then castling, then everything else
+        let estimator = |_: &State, mv: &Move| match (mv.capture(), mv.castle_side()) {
+            (Some(..), _) => 2,
+            (_, Some(..)) => 1,
+            _ => 0,
+        };
+
+        let sorted = moves.sorted_by_estimate(&state, &estimator);
+        assert_eq!(sorted.moves().len(), moves.moves().len());
+
+        let san = sorted.to_san_list(&state);
+        assert_eq!(&san[..2], &["exd5", "O-O-O"]);
+        assert!(san.contains(&String::from("Ra8+")));
+    }
+
+    #[test]
+    fn test_line() {
+        let state = State::default();
+        let mut line = Line::new();
+        assert!(line.is_empty());
+
+        let mut current = state.clone();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            let MoveResult(mv, next) = current.find_legal(&query).unwrap();
+            assert!(line.push(mv));
+            current = next;
+        }
+
+        assert_eq!(line.len(), 5);
+        assert_eq!(line.to_string(), "e2e4 e7e5 g1f3 b8c6 f1b5");
+        assert_eq!(line.to_san(&state), "e4 e5 Nf3 Nc6 Bb5");
+        assert_eq!(Line::from(&line[..2]).to_san(&state), "e4 e5");
+
+        let full: Line = std::iter::repeat_n(line[0], Line::CAPACITY + 1).collect();
+        assert!(full.is_full());
+        assert_eq!(full.len(), Line::CAPACITY);
+    }
 }
@@ -1,4 +1,6 @@
-use std::{borrow::Cow, fmt::Display, marker::PhantomData, ops::Deref};
+use std::{borrow::Cow, fmt::Display, marker::PhantomData, ops::Deref, str::FromStr};
+
+use crate::{Move, MoveGenerator, MoveResult, State};
 
 pub use fen::*;
 pub use peg::*;
@@ -77,6 +79,75 @@ where
     }
 }
 
+/// The notations moves can be written in for people to read, for when the
+/// notation is picked at runtime (ex. with a command line flag)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveNotation {
+    San,
+    Lan,
+    Peg,
+}
+
+impl MoveNotation {
+    /// Writes a move played from the given state
+    pub fn write(self, state: &State, mv: &Move) -> String {
+        match self {
+            MoveNotation::San => into_notation::<_, San>(&(state.clone(), *mv)).to_string(),
+            MoveNotation::Lan => into_notation::<_, lan::Lan>(mv).to_string(),
+            MoveNotation::Peg => into_notation::<_, Peg>(mv).to_string(),
+        }
+    }
+
+    /// Writes each move of a line played from the given state. Standard algebraic
+    /// notation depends on the position, so if the line turns out not to be playable
+    /// from there, the rest of it is written in pure engine notation instead.
+    pub fn write_line(self, state: &State, line: &[Move]) -> Vec<String> {
+        if self != MoveNotation::San {
+            return line.iter().map(|mv| self.write(state, mv)).collect();
+        }
+
+        let mut state = Some(state.clone());
+        line.iter()
+            .map(|mv| {
+                let legal_moves = state.as_ref().map(MoveGenerator::compute_legal_moves);
+                let next = legal_moves
+                    .and_then(|moves| moves.moves().iter().find(|result| result.0 == *mv).cloned());
+
+                match (state.take(), next) {
+                    (Some(current), Some(MoveResult(_, next))) => {
+                        state = Some(next);
+                        self.write(&current, mv)
+                    }
+                    _ => into_notation::<_, Peg>(mv).to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for MoveNotation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "san" => Ok(MoveNotation::San),
+            "lan" | "uci" => Ok(MoveNotation::Lan),
+            "peg" => Ok(MoveNotation::Peg),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for MoveNotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveNotation::San => write!(f, "san"),
+            MoveNotation::Lan => write!(f, "lan"),
+            MoveNotation::Peg => write!(f, "peg"),
+        }
+    }
+}
+
 mod peg {
     use super::*;
     use crate::{Move, Piece, Side};
@@ -395,12 +466,71 @@ mod fen {
 }
 
 mod san {
-    use crate::{File, MoveQuery, Piece, Rank, Side};
+    use crate::{File, Move, MoveGenerator, MoveQuery, Piece, Rank, Side, State};
 
-    use super::TryFromNotation;
+    use super::{into_notation, IntoNotation, Peg, TryFromNotation};
 
     pub struct San;
 
+    /// Writes a move played from the given state in standard algebraic notation,
+    /// with the origin disambiguated against the other legal moves and a
+    /// check or checkmate indicator.
+    impl IntoNotation<(State, Move)> for San {
+        fn into_notation(
+            value: &(State, Move),
+            f: &mut std::fmt::Formatter<'_>,
+        ) -> std::fmt::Result {
+            let (state, mv) = value;
+            if mv.is_any_castle() || mv.piece() == Piece::Pawn {
+                write!(f, "{}", into_notation::<_, Peg>(mv))?;
+            } else {
+                write!(f, "{}", mv.piece())?;
+
+                let legal_moves = MoveGenerator::compute_legal_moves(state);
+                let ambiguous_origins = legal_moves
+                    .moves()
+                    .iter()
+                    .map(|m| m.0)
+                    .filter(|m| {
+                        m.piece() == mv.piece()
+                            && m.destination() == mv.destination()
+                            && m.origin() != mv.origin()
+                    })
+                    .map(|m| m.origin())
+                    .collect::<Vec<_>>();
+
+                if !ambiguous_origins.is_empty() {
+                    let origin = mv.origin();
+                    if ambiguous_origins.iter().all(|o| o.file() != origin.file()) {
+                        write!(f, "{}", origin.file())?;
+                    } else if ambiguous_origins.iter().all(|o| o.rank() != origin.rank()) {
+                        write!(f, "{}", origin.rank())?;
+                    } else {
+                        write!(f, "{}", origin)?;
+                    }
+                }
+
+                if mv.is_capture() {
+                    write!(f, "x")?;
+                }
+
+                write!(f, "{}", mv.destination())?;
+            }
+
+            if let Ok(next_state) = State::by_performing_move(state, mv) {
+                if next_state.is_check() {
+                    if MoveGenerator::compute_legal_moves(&next_state).is_empty() {
+                        write!(f, "#")?;
+                    } else {
+                        write!(f, "+")?;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
     impl TryFromNotation<MoveQuery> for San {
         type Error = ();
 
@@ -430,6 +560,7 @@ mod san {
                         'R' => query.set_promotion(Piece::Rook),
                         'B' => query.set_promotion(Piece::Bishop),
                         'N' => query.set_promotion(Piece::Knight),
+                        'K' => query.set_promotion(Piece::King),
                         _ => return Err(()),
                     }
 
@@ -551,15 +682,146 @@ mod san {
             Ok(query)
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{notation::Fen, MoveResult};
+
+        fn san(fen: &str, query: MoveQuery) -> String {
+            let state = super::super::try_from_notation::<_, Fen>(fen).unwrap();
+            let MoveResult(mv, _) = MoveGenerator::compute_legal_moves(&state)
+                .find(&query)
+                .unwrap();
+
+            into_notation::<_, San>(&(state, mv)).to_string()
+        }
+
+        #[test]
+        fn test_move_notation() {
+            let state = State::default();
+            let mut line = vec![];
+            let mut current = state.clone();
+            for (origin, destination) in [
+                (crate::Square::G1, crate::Square::F3),
+                (crate::Square::D7, crate::Square::D5),
+            ] {
+                let query = MoveQuery::by_moving_from_to(origin, destination);
+                let MoveResult(mv, next) = current.find_legal(&query).unwrap();
+                line.push(mv);
+                current = next;
+            }
+
+            let written = |notation: &str| {
+                let notation: super::super::MoveNotation = notation.parse().unwrap();
+                notation.write_line(&state, &line).join(" ")
+            };
+
+            assert_eq!(written("san"), "Nf3 d5");
+            assert_eq!(written("lan"), "g1f3 d7d5");
+            assert_eq!(written("PEG"), "Nf3 d5");
+
+            // Once the line can't be played any further, the rest of it falls back to
+            // PEG, which has no checks. Using a capture with check tells the two apart.
+            let state =
+                super::super::try_from_notation::<_, Fen>("r3k3/8/8/8/8/8/8/R3K3 w Qq - 0 1")
+                    .unwrap();
+            let mut line = vec![];
+            let mut current = state.clone();
+            for (origin, destination) in [
+                (crate::Square::A1, crate::Square::A8),
+                (crate::Square::E8, crate::Square::D7),
+                (crate::Square::A8, crate::Square::A7),
+            ] {
+                let query = MoveQuery::by_moving_from_to(origin, destination);
+                let MoveResult(mv, next) = current.find_legal(&query).unwrap();
+                line.push(mv);
+                current = next;
+            }
+
+            let notation = super::super::MoveNotation::San;
+            assert_eq!(notation.write_line(&state, &line), ["Rxa8+", "Kd7", "Ra7+"]);
+
+            let moved = State::by_performing_move(&state, &line[0]).unwrap();
+            assert_eq!(notation.write_line(&moved, &line), ["Rxa8", "Kd7", "Ra7"]);
+            assert_eq!(notation.write_line(&moved, &line[1..]), ["Kd7", "Ra7+"]);
+            assert_eq!(
+                notation.write_line(&state, &[line[0], line[2]]),
+                ["Rxa8+", "Ra7"]
+            );
+        }
+
+        #[test]
+        fn test_disambiguation() {
+            let fen = "4k3/8/8/8/8/8/4K3/R6R w - - 0 1";
+            let query = MoveQuery::by_moving_from_to(crate::Square::A1, crate::Square::D1);
+            assert_eq!(san(fen, query), "Rad1");
+
+            let fen = "4k3/8/8/8/R7/8/8/R3K3 w - - 0 1";
+            let query = MoveQuery::by_moving_from_to(crate::Square::A1, crate::Square::A2);
+            assert_eq!(san(fen, query), "R1a2");
+        }
+
+        #[test]
+        fn test_check_and_mate() {
+            let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1";
+            let query = MoveQuery::by_moving_from_to(crate::Square::A1, crate::Square::A8);
+            assert_eq!(san(fen, query), "Ra8#");
+
+            let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+            let query = MoveQuery::by_moving_from_to(crate::Square::A1, crate::Square::A8);
+            assert_eq!(san(fen, query), "Ra8+");
+
+            let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+            assert_eq!(san(fen, MoveQuery::by_castling(Side::King)), "O-O");
+        }
+    }
 }
 
 pub mod lan {
-    use crate::Move;
+    use crate::{File, Move, MoveQuery, Piece, Side, Square};
 
-    use super::{into_notation, IntoNotation};
+    use super::{into_notation, IntoNotation, TryFromNotation};
 
     pub struct Lan;
 
+    /// Long algebraic notation the way UCI writes it for Chess960, where castling is
+    /// written as the king taking its own rook (ex. `e1h1`). Moves are parsed as `Lan`,
+    /// which understands both ways of writing castling.
+    pub struct Lan960;
+
+    impl TryFromNotation<MoveQuery> for Lan {
+        type Error = ();
+
+        /// Parses a move in the long algebraic notation used by UCI (ex. `e2e4`, `e7e8q`).
+        /// Castling is written as the king's move (ex. `e1g1`), which is also how the
+        /// castling moves themselves are stored, so it needs no special handling. Castling
+        /// written as the king taking its rook (ex. `e1h1`) is resolved once the position
+        /// is known, see [`MoveQuery::normalized_castling`].
+        fn try_from_notation(notation: &str) -> Result<MoveQuery, Self::Error> {
+            if !notation.is_ascii() || !(4..=5).contains(&notation.len()) {
+                return Err(());
+            }
+
+            let origin = Square::try_from(&notation[0..2]).map_err(|_| ())?;
+            let destination = Square::try_from(&notation[2..4]).map_err(|_| ())?;
+            let mut query = MoveQuery::by_moving_from_to(origin, destination);
+            if let Some(promotion) = notation.chars().nth(4) {
+                query.set_promotion(match promotion {
+                    'q' => Piece::Queen,
+                    'r' => Piece::Rook,
+                    'b' => Piece::Bishop,
+                    'n' => Piece::Knight,
+                    // Kings can be promoted to in antichess
+                    'k' => Piece::King,
+                    _ => return Err(()),
+                });
+            }
+
+            Ok(query)
+        }
+    }
+
     impl IntoNotation<Move> for Lan {
         fn into_notation(value: &Move, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(f, "{}{}", value.origin(), value.destination())?;
@@ -583,4 +845,74 @@ pub mod lan {
             Ok(())
         }
     }
+
+    impl IntoNotation<Move> for Lan960 {
+        fn into_notation(value: &Move, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            // The rooks always start in the corners, since only standard starting positions
+            // are supported
+            let rook_file = match value.castle_side() {
+                Some(Side::King) => File::H,
+                Some(Side::Queen) => File::A,
+                None => return write!(f, "{}", into_notation::<_, Lan>(value)),
+            };
+
+            let rook = Square::new(rook_file, value.origin().rank());
+            write!(f, "{}{}", value.origin(), rook)
+        }
+    }
+
+    impl IntoNotation<&[Move]> for Lan960 {
+        fn into_notation(value: &&[Move], f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for (i, mv) in value.iter().enumerate() {
+                write!(f, "{}", into_notation::<_, Lan960>(mv))?;
+                if i < value.len() - 1 {
+                    write!(f, " ")?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::notation::{try_from_notation, Fen};
+
+        #[test]
+        fn test_parse_lan() {
+            let query = try_from_notation::<MoveQuery, Lan>("e7e8n").unwrap();
+            assert_eq!(
+                query,
+                MoveQuery {
+                    promotion: Some(Piece::Knight),
+                    ..MoveQuery::by_moving_from_to(Square::E7, Square::E8)
+                }
+            );
+
+            for invalid in ["", "e2", "e2e", "e2e9", "e7e8x", "e2e4qq", "0000", "é2e4"] {
+                assert!(try_from_notation::<MoveQuery, Lan>(invalid).is_err());
+            }
+
+            // Castling is written as the king's move
+            let state = try_from_notation::<_, Fen>("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+            let query = try_from_notation::<MoveQuery, Lan>("e1g1").unwrap();
+            let castle = state.find_legal(&query).unwrap();
+            assert!(castle.0.is_castle(crate::Side::King));
+            assert_eq!(into_notation::<_, Lan>(&castle.0).to_string(), "e1g1");
+
+            // ...or as the king taking its own rook, the way Chess960 GUIs write it
+            let state = try_from_notation::<_, Fen>("r3k3/8/8/8/8/8/8/R3K2R b KQq - 0 1").unwrap();
+            let query = try_from_notation::<MoveQuery, Lan>("e8a8").unwrap();
+            let castle = state.find_legal(&query).unwrap();
+            assert!(castle.0.is_castle(crate::Side::Queen));
+            assert_eq!(into_notation::<_, Lan>(&castle.0).to_string(), "e8c8");
+            assert_eq!(into_notation::<_, Lan960>(&castle.0).to_string(), "e8a8");
+
+            // Without the castle rights, it's just an illegal move
+            let state = try_from_notation::<_, Fen>("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+            let query = try_from_notation::<MoveQuery, Lan>("e1h1").unwrap();
+            assert!(state.find_legal(&query).is_none());
+        }
+    }
 }
@@ -0,0 +1,232 @@
+use crate::{
+    AttackGenerator, BitBoard, Board, Color, File, Offset, Piece, PieceIndex, Square, FILE_MASKS,
+};
+
+/// A summary of one side's pawns and how well they cover their king
+#[derive(Debug, Clone, PartialEq)]
+pub struct PawnStructure {
+    /// The number of groups of pawns on adjacent files
+    pub islands: u32,
+
+    /// Pawns with no enemy pawns in front of them, on their own or an adjacent file
+    pub passed: BitBoard,
+
+    /// Pawns that can't be defended by a neighbour and can't safely advance either
+    pub backward: BitBoard,
+
+    /// Pawns with no friendly pawns on the adjacent files
+    pub isolated: BitBoard,
+
+    /// Pawns sharing a file with another friendly pawn
+    pub doubled: BitBoard,
+
+    /// Files with only enemy pawns on them
+    pub semi_open_files: Vec<File>,
+
+    /// How well the king is covered by pawns, between 0 (not at all) and 1,
+    /// or nothing if there's no king on the board
+    pub king_shelter: Option<f32>,
+}
+
+impl PawnStructure {
+    pub fn new(board: &Board, color: Color) -> Self {
+        let (ours, theirs) = pawns(board, color);
+        Self {
+            islands: islands(ours),
+            passed: passed(ours, theirs, color),
+            backward: backward(ours, theirs, color),
+            isolated: isolated(ours),
+            doubled: doubled(ours),
+            semi_open_files: semi_open_files(board, color),
+            king_shelter: king_shelter(board, color),
+        }
+    }
+}
+
+/// The pawns of the given color, followed by the pawns of their opponent
+pub fn pawns(board: &Board, color: Color) -> (BitBoard, BitBoard) {
+    (
+        board.piece_occupancy(PieceIndex::new(color, Piece::Pawn)),
+        board.piece_occupancy(PieceIndex::new(!color, Piece::Pawn)),
+    )
+}
+
+pub fn islands(pawns: BitBoard) -> u32 {
+    let files = occupied_files(pawns);
+    (files & !(files << 1)).count_ones()
+}
+
+pub fn passed(ours: BitBoard, theirs: BitBoard, color: Color) -> BitBoard {
+    filter(ours, |square| {
+        let span = fill(BitBoard::just(square), color.forward());
+        (theirs & (span | neighbours(span))).none()
+    })
+}
+
+pub fn backward(ours: BitBoard, theirs: BitBoard, color: Color) -> BitBoard {
+    let isolated = isolated(ours);
+    let enemy_attacks = AttackGenerator::pawn_attacks_bb(theirs, !color);
+    filter(ours & !isolated, |square| {
+        let supporting =
+            neighbours(BitBoard::just(square) | fill(BitBoard::just(square), color.backward()));
+        (ours & supporting).none()
+            && square
+                .offset(color.forward())
+                .is_some_and(|stop| enemy_attacks.test(stop))
+    })
+}
+
+pub fn isolated(pawns: BitBoard) -> BitBoard {
+    filter(pawns, |square| {
+        (pawns & neighbours(FILE_MASKS[square.file()])).none()
+    })
+}
+
+pub fn doubled(pawns: BitBoard) -> BitBoard {
+    filter(pawns, |square| {
+        (pawns & FILE_MASKS[square.file()]).count_ones() > 1
+    })
+}
+
+/// Files without any pawns on them
+pub fn open_files(board: &Board) -> Vec<File> {
+    let (white, black) = pawns(board, Color::White);
+    File::ALL
+        .iter()
+        .filter(|file| ((white | black) & FILE_MASKS[**file]).none())
+        .copied()
+        .collect()
+}
+
+/// Files where the given color has no pawns, but their opponent does
+pub fn semi_open_files(board: &Board, color: Color) -> Vec<File> {
+    let (ours, theirs) = pawns(board, color);
+    File::ALL
+        .iter()
+        .filter(|file| (ours & FILE_MASKS[**file]).none() && (theirs & FILE_MASKS[**file]).any())
+        .copied()
+        .collect()
+}
+
+/// How well the king is covered by the pawns right in front of it, between 0 and 1. A
+/// pawn directly in front of the king covers its file fully, and one a rank further
+/// ahead covers it halfway.
+pub fn king_shelter(board: &Board, color: Color) -> Option<f32> {
+    let king = board
+        .piece_occupancy(PieceIndex::new(color, Piece::King))
+        .first_square()?;
+
+    let (ours, _) = pawns(board, color);
+    let forward = color.forward();
+    let shelter = |distance: i8| {
+        let offset = Offset {
+            file: 0,
+            rank: forward.rank * distance,
+        };
+
+        let square = BitBoard::just(king).shift(offset);
+        square | neighbours(square)
+    };
+
+    let files = (BitBoard::just(king) | neighbours(BitBoard::just(king))).count_ones();
+    let near = ours & shelter(1);
+    let far = ours & shelter(2) & !near.shift(forward);
+    Some((near.count_ones() as f32 + far.count_ones() as f32 * 0.5) / files as f32)
+}
+
+fn occupied_files(pawns: BitBoard) -> u8 {
+    File::ALL
+        .iter()
+        .filter(|file| (pawns & FILE_MASKS[**file]).any())
+        .fold(0, |files, file| files | (1 << file.index()))
+}
+
+fn neighbours(squares: BitBoard) -> BitBoard {
+    squares.shift(Offset::EAST) | squares.shift(Offset::WEST)
+}
+
+// All the squares in a direction from the given ones, not including themselves
+fn fill(squares: BitBoard, direction: Offset) -> BitBoard {
+    let mut filled = BitBoard::ZERO;
+    let mut frontier = squares;
+    while frontier.any() {
+        frontier = frontier.shift(direction);
+        filled |= frontier;
+    }
+
+    filled
+}
+
+fn filter<F>(squares: BitBoard, f: F) -> BitBoard
+where
+    F: Fn(Square) -> bool,
+{
+    squares
+        .iter_squares()
+        .filter(|square| f(*square))
+        .fold(BitBoard::ZERO, |result, square| {
+            result | BitBoard::just(square)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{try_from_notation, Fen};
+
+    #[test]
+    fn test_pawn_structure() {
+        // White: islands a2-b2, d4-e3, h2; black: a7, c5, h7
+        let state =
+            try_from_notation::<_, Fen>("4k3/p6p/8/2p5/3P4/4P3/PP5P/4K3 w - - 0 1").unwrap();
+
+        let white = PawnStructure::new(state.board(), Color::White);
+        assert_eq!(white.islands, 3);
+        assert_eq!(white.isolated, BitBoard::just(Square::H2));
+        assert_eq!(white.doubled, BitBoard::ZERO);
+        assert_eq!(white.passed, BitBoard::just(Square::E3));
+        assert_eq!(white.backward, BitBoard::ZERO);
+        assert_eq!(white.semi_open_files, vec![File::C]);
+
+        let black = PawnStructure::new(state.board(), Color::Black);
+        assert_eq!(black.islands, 3);
+        assert_eq!(
+            black.isolated,
+            BitBoard::just(Square::A7) | BitBoard::just(Square::C5) | BitBoard::just(Square::H7)
+        );
+        assert_eq!(black.passed, BitBoard::ZERO);
+        assert_eq!(black.semi_open_files, vec![File::B, File::D, File::E]);
+
+        assert_eq!(open_files(state.board()), vec![File::F, File::G]);
+    }
+
+    #[test]
+    fn test_doubled_and_backward() {
+        // Nothing beside or behind the c3 pawn can support it, and c4 is covered by the d5 pawn
+        let state = try_from_notation::<_, Fen>("4k3/8/8/3p4/1P6/2P5/2P5/4K3 w - - 0 1").unwrap();
+
+        let white = PawnStructure::new(state.board(), Color::White);
+        assert_eq!(
+            white.doubled,
+            BitBoard::just(Square::C2) | BitBoard::just(Square::C3)
+        );
+        assert_eq!(white.backward, BitBoard::just(Square::C3));
+        assert_eq!(white.islands, 1);
+    }
+
+    #[test]
+    fn test_king_shelter() {
+        let shelter = |fen: &str| {
+            let state = try_from_notation::<_, Fen>(fen).unwrap();
+            king_shelter(state.board(), Color::White)
+        };
+
+        assert_eq!(shelter("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1"), Some(1.0));
+        assert_eq!(
+            shelter("4k3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1"),
+            Some(2.5 / 3.0)
+        );
+        assert_eq!(shelter("4k3/8/8/8/8/8/8/6K1 w - - 0 1"), Some(0.0));
+        assert_eq!(shelter("4k3/8/8/8/8/8/8/8 w - - 0 1"), None);
+    }
+}
@@ -0,0 +1,496 @@
+use std::fmt::Display;
+
+use crate::{
+    notation::{into_notation, try_from_notation, Fen, San},
+    Color, Move, MoveGenerator, MoveQuery, State,
+};
+
+/// The longest line of movetext written before wrapping
+const MAX_LINE_LENGTH: usize = 80;
+
+/// Numeric annotation glyphs for judging the quality of a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nag {
+    Good,
+    Mistake,
+    Brilliant,
+    Blunder,
+    Interesting,
+    Dubious,
+}
+
+impl Nag {
+    /// The number of the glyph in the PGN standard (ex. `$4`)
+    pub fn code(self) -> u8 {
+        match self {
+            Nag::Good => 1,
+            Nag::Mistake => 2,
+            Nag::Brilliant => 3,
+            Nag::Blunder => 4,
+            Nag::Interesting => 5,
+            Nag::Dubious => 6,
+        }
+    }
+
+    /// The suffix written after the move (ex. `??`)
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Nag::Good => "!",
+            Nag::Mistake => "?",
+            Nag::Brilliant => "!!",
+            Nag::Blunder => "??",
+            Nag::Interesting => "!?",
+            Nag::Dubious => "?!",
+        }
+    }
+}
+
+impl Display for Nag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedMove {
+    pub mv: Move,
+    pub nag: Option<Nag>,
+    pub comment: Option<String>,
+}
+
+impl From<Move> for AnnotatedMove {
+    fn from(mv: Move) -> Self {
+        Self {
+            mv,
+            nag: None,
+            comment: None,
+        }
+    }
+}
+
+/// A single game with its tag pairs, to be read from or written out in PGN
+#[derive(Debug, Clone)]
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    pub initial_state: State,
+    pub moves: Vec<AnnotatedMove>,
+
+    /// The result given in the game's Result tag, if it had one. It's the only way to
+    /// know how a game ended when a player resigned or ran out of time, so it's written
+    /// out in place of the result of the final position.
+    pub declared_result: Option<String>,
+}
+
+impl Pgn {
+    pub fn new(initial_state: State) -> Self {
+        Self {
+            tags: vec![],
+            initial_state,
+            moves: vec![],
+            declared_result: None,
+        }
+    }
+
+    /// Parses the first game in a PGN file. Comments, variations and
+    /// annotations in the movetext are skipped over.
+    pub fn parse(contents: &str) -> Result<Self, PgnParseError> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for line in contents.lines().map(str::trim) {
+            if let Some(tag) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if !movetext.trim().is_empty() {
+                    break;
+                }
+
+                let (name, value) = tag
+                    .split_once(' ')
+                    .ok_or_else(|| PgnParseError::InvalidTag(line.to_string()))?;
+
+                tags.push((name.to_string(), value.trim().trim_matches('"').to_string()));
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        let initial_state = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => try_from_notation::<_, Fen>(fen)
+                .map_err(|_| PgnParseError::InvalidFen(fen.clone()))?,
+            None => State::default(),
+        };
+
+        let moves = PgnParser::parse_movetext(&movetext, initial_state.clone())
+            .map(|r| r.map(|(_, mv)| AnnotatedMove::from(mv)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let declared_result = tags
+            .iter()
+            .find(|(name, _)| name == "Result")
+            .map(|(_, result)| result.clone());
+
+        tags.retain(|(name, _)| !matches!(name.as_str(), "FEN" | "SetUp" | "Result"));
+
+        Ok(Self {
+            tags,
+            initial_state,
+            moves,
+            declared_result,
+        })
+    }
+
+    /// Parses every game in a PGN file, like a database of games. A game that can't be
+    /// parsed doesn't stop the games after it from being parsed.
+    pub fn parse_all(contents: &str) -> impl Iterator<Item = Result<Self, PgnParseError>> + '_ {
+        Self::split_games(contents).into_iter().map(Self::parse)
+    }
+
+    // Splits a file into the text of each game, which starts wherever
+    // a tag follows the movetext of the previous game
+    fn split_games(contents: &str) -> Vec<&str> {
+        let mut starts = vec![0];
+        let mut in_movetext = false;
+        let mut offset = 0;
+        for line in contents.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                if in_movetext {
+                    starts.push(offset);
+                    in_movetext = false;
+                }
+            } else if !trimmed.is_empty() {
+                in_movetext = true;
+            }
+
+            offset += line.len();
+        }
+
+        starts.push(contents.len());
+        starts
+            .windows(2)
+            .map(|range| &contents[range[0]..range[1]])
+            .filter(|game| !game.trim().is_empty())
+            .collect()
+    }
+
+    /// The position after the given number of half moves have been played,
+    /// or `None` if the game is shorter than that
+    pub fn state_at(&self, ply: usize) -> Option<Result<State, PgnWriteError>> {
+        let moves = self.moves.get(..ply)?;
+        Some(
+            moves
+                .iter()
+                .try_fold(self.initial_state.clone(), |state, annotated_move| {
+                    State::by_performing_move(&state, &annotated_move.mv)
+                        .map_err(|_| PgnWriteError::IllegalMove)
+                }),
+        )
+    }
+
+    /// The result of the game, as far as it can be told from the final position
+    pub fn result(&self) -> Result<&'static str, PgnWriteError> {
+        let state = self.state_at(self.moves.len()).unwrap()?;
+
+        if !MoveGenerator::compute_legal_moves(&state).is_empty() {
+            Ok("*")
+        } else if !state.is_check() {
+            Ok("1/2-1/2")
+        } else if state.turn_to_move() == Color::White {
+            Ok("0-1")
+        } else {
+            Ok("1-0")
+        }
+    }
+}
+
+impl Display for Pgn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match &self.declared_result {
+            Some(result) => result.as_str(),
+            None => self.result().map_err(|_| std::fmt::Error)?,
+        };
+
+        for (name, value) in self.tags.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('"', "\\\""))?;
+        }
+
+        if self.initial_state != State::default() {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(
+                f,
+                "[FEN \"{}\"]",
+                into_notation::<_, Fen>(&self.initial_state)
+            )?;
+        }
+
+        writeln!(f, "[Result \"{}\"]", result)?;
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        let mut state = self.initial_state.clone();
+        let mut needs_move_number = true;
+        for annotated_move in self.moves.iter() {
+            let fullmove_number = state.clock().fullmove_number;
+            match state.turn_to_move() {
+                Color::White => tokens.push(format!("{}.", fullmove_number)),
+                Color::Black if needs_move_number => tokens.push(format!("{}...", fullmove_number)),
+                Color::Black => {}
+            }
+
+            let mv = annotated_move.mv;
+            let san = into_notation::<_, San>(&(state.clone(), mv)).to_string();
+            match annotated_move.nag {
+                Some(nag) => tokens.push(format!("{}{}", san, nag)),
+                None => tokens.push(san),
+            }
+
+            needs_move_number = false;
+            if let Some(comment) = &annotated_move.comment {
+                tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+                needs_move_number = true;
+            }
+
+            state = State::by_performing_move(&state, &mv).map_err(|_| std::fmt::Error)?;
+        }
+
+        tokens.push(result.to_string());
+
+        let mut line_length = 0;
+        for token in tokens.iter() {
+            if line_length > 0 && line_length + token.len() + 1 > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            } else if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+
+        writeln!(f)
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnParseError {
+    InvalidTag(String),
+    InvalidFen(String),
+    InvalidMoveStr(String),
+    UnknownMove(String, MoveQuery),
+}
+
+impl std::fmt::Display for PgnParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnParseError::InvalidTag(str) => write!(f, "invalid tag: {}", str),
+            PgnParseError::InvalidFen(str) => write!(f, "invalid fen: {}", str),
+            PgnParseError::InvalidMoveStr(str) => write!(f, "invalid move string: {}", str),
+            PgnParseError::UnknownMove(str, query) => {
+                write!(f, "unknown move: {} for {}", str, query)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnParseError {}
+
+#[derive(Debug)]
+pub enum PgnWriteError {
+    IllegalMove,
+}
+
+impl std::fmt::Display for PgnWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnWriteError::IllegalMove => write!(f, "illegal move in game"),
+        }
+    }
+}
+
+impl std::error::Error for PgnWriteError {}
+
+pub struct PgnParser;
+
+impl PgnParser {
+    /// Parses SAN movetext played from the given state, yielding each move
+    /// along with the state it was played from. Iteration stops after the
+    /// first error.
+    pub fn parse_movetext(
+        movetext: &str,
+        state: State,
+    ) -> impl Iterator<Item = Result<(State, Move), PgnParseError>> {
+        Self::tokenize(movetext)
+            .into_iter()
+            .scan(Some(state), |state, move_str| {
+                let current_state = state.take()?;
+                let Ok(query) = try_from_notation::<_, San>(&move_str) else {
+                    return Some(Err(PgnParseError::InvalidMoveStr(move_str)));
+                };
+
+                let valid_moves = MoveGenerator::compute_legal_moves(&current_state);
+                let Some(result) = valid_moves.find(&query) else {
+                    return Some(Err(PgnParseError::UnknownMove(move_str, query)));
+                };
+
+                *state = Some(result.1);
+                Some(Ok((current_state, result.0)))
+            })
+    }
+
+    /// Splits movetext into move strings, dropping move numbers, results,
+    /// comments, variations and annotation glyphs
+    fn tokenize(movetext: &str) -> Vec<String> {
+        let mut stripped = String::with_capacity(movetext.len());
+        let mut in_comment = false;
+        let mut in_line_comment = false;
+        let mut variation_depth = 0;
+        for c in movetext.chars() {
+            match c {
+                _ if in_line_comment => in_line_comment = c != '\n',
+                '}' if in_comment => in_comment = false,
+                _ if in_comment => {}
+                '{' => in_comment = true,
+                ';' => in_line_comment = true,
+                '(' => variation_depth += 1,
+                ')' => variation_depth -= 1,
+                _ if variation_depth > 0 => {}
+                _ => stripped.push(c),
+            }
+        }
+
+        stripped
+            .split_whitespace()
+            .filter(|t| !matches!(*t, "1/2-1/2" | "1-0" | "0-1" | "*"))
+            .filter(|t| !t.starts_with('$'))
+            .map(|t| match t.rfind('.') {
+                Some(dot_index) => &t[dot_index + 1..],
+                None => t,
+            })
+            .map(|t| t.trim_end_matches(['!', '?']))
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let contents = r#"
+            [Event "Test"]
+            [White "A"]
+            [Black "B"]
+
+            1. e4 {Best by test} e5 2. Nf3 $1 Nc6 (2... d6 3. d4) 3. Bc4?! Nd4?
+            4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1
+        "#;
+
+        let mut pgn = Pgn::parse(contents).unwrap();
+        assert_eq!(pgn.tags.len(), 3);
+        assert_eq!(pgn.moves.len(), 14);
+        assert_eq!(pgn.result().unwrap(), "0-1");
+
+        pgn.moves[4].nag = Some(Nag::Dubious);
+        pgn.moves[5].comment = Some(String::from("[%eval 0.5]"));
+
+        let output = pgn.to_string();
+        assert_eq!(
+            output,
+            concat!(
+                "[Event \"Test\"]\n",
+                "[White \"A\"]\n",
+                "[Black \"B\"]\n",
+                "[Result \"0-1\"]\n",
+                "\n",
+                "1. e4 e5 2. Nf3 Nc6 3. Bc4?! Nd4 {[%eval 0.5]} 4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1\n",
+                "Qxe4+ 7. Be2 Nf3# 0-1\n",
+            )
+        );
+
+        let reparsed = Pgn::parse(&output).unwrap();
+        assert_eq!(reparsed.moves.len(), pgn.moves.len());
+    }
+
+    #[test]
+    fn test_declared_result() {
+        let contents = r#"
+            [White "A"]
+            [Result "1-0"]
+
+            1. e4 e5 2. Nf3 1-0
+        "#;
+
+        let pgn = Pgn::parse(contents).unwrap();
+        assert_eq!(pgn.result().unwrap(), "*");
+
+        let output = pgn.to_string();
+        assert!(output.contains("[Result \"1-0\"]"), "{}", output);
+        assert!(output.ends_with("1. e4 e5 2. Nf3 1-0\n"), "{}", output);
+        assert_eq!(
+            Pgn::parse(&output).unwrap().declared_result.as_deref(),
+            Some("1-0")
+        );
+    }
+
+    #[test]
+    fn test_state_at() {
+        let pgn = Pgn::parse("1. e4 e5 2. Nf3 *").unwrap();
+        assert_eq!(pgn.state_at(0).unwrap().unwrap(), State::default());
+        assert_eq!(
+            into_notation::<_, Fen>(&pgn.state_at(2).unwrap().unwrap()).to_string(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+
+        assert!(pgn.state_at(3).is_some());
+        assert!(pgn.state_at(4).is_none());
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let contents = r#"
+            [White "A"]
+            [Result "1-0"]
+
+            1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+            [White "B"]
+            [Result "1/2-1/2"]
+
+            1. d4 d5 1/2-1/2
+            [White "C"]
+
+            1. Kf3 *
+        "#;
+
+        let games: Vec<_> = Pgn::parse_all(contents).collect();
+        assert_eq!(games.len(), 3);
+        assert!(games[2].is_err());
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.moves.len(), 7);
+        assert_eq!(first.declared_result.as_deref(), Some("1-0"));
+
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(
+            second.tags,
+            vec![(String::from("White"), String::from("B"))]
+        );
+        assert_eq!(second.declared_result.as_deref(), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn test_initial_position() {
+        let fen = "4k3/8/8/8/8/8/8/4K2R b K - 0 1";
+        let contents = format!("[FEN \"{}\"]\n\n1... Kd7 2. O-O *", fen);
+        let pgn = Pgn::parse(&contents).unwrap();
+        assert_eq!(pgn.moves.len(), 2);
+
+        let output = pgn.to_string();
+        assert!(output.contains(&format!("[FEN \"{}\"]", fen)));
+        assert!(output.contains("1... Kd7 2. O-O *"));
+    }
+}
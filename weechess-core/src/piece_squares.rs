@@ -0,0 +1,115 @@
+use crate::{utils::ArrayMap, Color, Piece, Square};
+
+/// The value of a piece on each square, from white's point of view, in the
+/// middle game and in the end game. Positions lerp between the two as the
+/// game goes on.
+pub const PIECE_SQUARE_MAP: ArrayMap<Piece, [ArrayMap<Square, i32>; 2]> = ArrayMap::new([
+    [ZERO_MAP, ZERO_MAP],
+    [PAWN_MAP, PAWN_MAP],
+    [KNIGHT_MAP, KNIGHT_MAP],
+    [BISHOP_MAP, BISHOP_MAP],
+    [ROOK_MAP, ROOK_MAP],
+    [QUEEN_MAP, QUEEN_MAP],
+    [KING_MIDDLE_GAME_MAP, KING_END_GAME_MAP],
+]);
+
+#[rustfmt::skip]
+const ZERO_MAP: ArrayMap<Square, i32> = ArrayMap::new([0; 64]);
+
+#[rustfmt::skip]
+const PAWN_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+]);
+
+#[rustfmt::skip]
+const KNIGHT_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+]);
+
+#[rustfmt::skip]
+const BISHOP_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+]);
+
+#[rustfmt::skip]
+const ROOK_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+]);
+
+#[rustfmt::skip]
+const QUEEN_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+]);
+
+#[rustfmt::skip]
+const KING_MIDDLE_GAME_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+]);
+
+#[rustfmt::skip]
+const KING_END_GAME_MAP: ArrayMap<Square, i32> = ArrayMap::new([
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+]);
+
+/// The middle game and end game values of a piece on a square for the given color
+pub fn piece_square_values(piece: Piece, square: Square, color: Color) -> [i32; 2] {
+    let square = match color {
+        Color::White => square,
+        Color::Black => square.flip(),
+    };
+
+    let index = square.white_at_bottom_index();
+    [
+        *PIECE_SQUARE_MAP[piece][0].index(index),
+        *PIECE_SQUARE_MAP[piece][1].index(index),
+    ]
+}
@@ -2,7 +2,7 @@ use std::{borrow::Cow, fmt::Display};
 
 use crate::{
     notation::{into_notation, Fen},
-    {utils::ArrayMap, Color, File, Piece, PieceIndex, Rank, Square, State},
+    {utils::ArrayMap, BitBoard, Color, File, Piece, PieceIndex, Rank, Square, State},
 };
 
 const BOARD_TEMPLATE_ROWS: &'static [&'static str] = &[
@@ -48,81 +48,176 @@ where
     }
 }
 
-impl Display for GamePrinter<'_> {
+/// Draws the board of one position, highlighting the squares that changed since another
+pub struct DiffPrinter<'a> {
+    pub before: &'a State,
+    pub after: &'a State,
+}
+
+/// Prints the board of `after`, with the squares that are different from `before` (the
+/// squares a piece moved from and to, captures, the rook when castling) highlighted
+pub fn diff<'a>(before: &'a State, after: &'a State) -> DiffPrinter<'a> {
+    DiffPrinter { before, after }
+}
+
+impl DiffPrinter<'_> {
+    /// The squares holding a different piece (or no piece) in the two positions
+    pub fn changed_squares(&self) -> BitBoard {
+        let before: ArrayMap<Square, PieceIndex> = self.before.board().into();
+        let after: ArrayMap<Square, PieceIndex> = self.after.board().into();
+        Square::ALL
+            .iter()
+            .filter(|square| before[**square] != after[**square])
+            .fold(BitBoard::ZERO, |changed, square| {
+                changed | BitBoard::just(*square)
+            })
+    }
+}
+
+impl Display for DiffPrinter<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let pieces: ArrayMap<Square, PieceIndex> = self.game.board().into();
+        let before: ArrayMap<Square, PieceIndex> = self.before.board().into();
+        let after: ArrayMap<Square, PieceIndex> = self.after.board().into();
+        let changed = self.changed_squares();
 
-        write!(f, "\n")?;
-        write!(f, " {}\n\n", into_notation::<_, Fen>(&self.game))?;
+        writeln!(f)?;
+        write_board(f, &after, changed)?;
+        writeln!(f)?;
 
-        let mut square_index = 0;
-        let mut rank_index = 0;
-        let mut file_index = 0;
-        for line in BOARD_TEMPLATE_ROWS.iter() {
-            write!(f, " ")?;
-
-            for c in line.chars() {
-                let c = match c {
-                    '.' => {
-                        let rank = Rank::from_index(square_index / 8).unwrap();
-                        let file = File::from_index(square_index % 8).unwrap();
-                        let piece = pieces[Square::from((rank.opposing_rank(), file))];
-                        square_index += 1;
-                        match piece.piece_and_color() {
-                            (Piece::Pawn, Color::White) => '♙',
-                            (Piece::Pawn, Color::Black) => '♟',
-                            (Piece::Knight, Color::White) => '♘',
-                            (Piece::Knight, Color::Black) => '♞',
-                            (Piece::Bishop, Color::White) => '♗',
-                            (Piece::Bishop, Color::Black) => '♝',
-                            (Piece::Rook, Color::White) => '♖',
-                            (Piece::Rook, Color::Black) => '♜',
-                            (Piece::Queen, Color::White) => '♕',
-                            (Piece::Queen, Color::Black) => '♛',
-                            (Piece::King, Color::White) => '♔',
-                            (Piece::King, Color::Black) => '♚',
-                            _ => ' ',
-                        }
-                    }
-                    'r' => {
-                        let _r = rank_index;
-                        rank_index += 1;
-                        match _r {
-                            0 => '8',
-                            1 => '7',
-                            2 => '6',
-                            3 => '5',
-                            4 => '4',
-                            5 => '3',
-                            6 => '2',
-                            7 => '1',
-                            _ => ' ',
-                        }
+        if changed.none() {
+            writeln!(f, "  No changes")?;
+        }
+
+        for square in changed.iter_squares() {
+            writeln!(
+                f,
+                "  {}: {} -> {}",
+                square,
+                change_char(before[square]),
+                change_char(after[square])
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Draws the board, with brackets around the highlighted squares
+fn write_board(
+    f: &mut std::fmt::Formatter<'_>,
+    pieces: &ArrayMap<Square, PieceIndex>,
+    highlighted: BitBoard,
+) -> std::fmt::Result {
+    let square_at = |index: usize| {
+        let rank = Rank::from_index(index / 8).unwrap();
+        let file = File::from_index(index % 8).unwrap();
+        Square::new(file, rank.opposing_rank())
+    };
+
+    let mut square_index = 0;
+    let mut rank_index = 0;
+    let mut file_index = 0;
+    for line in BOARD_TEMPLATE_ROWS.iter() {
+        write!(f, " ")?;
+
+        let chars: Vec<char> = line.chars().collect();
+        for (i, c) in chars.iter().copied().enumerate() {
+            let c = match c {
+                // Squares are drawn as ` . `, so a highlighted square swaps
+                // the spaces on either side of it for brackets
+                ' ' if chars.get(i + 1) == Some(&'.')
+                    && highlighted.test(square_at(square_index)) =>
+                {
+                    '['
+                }
+                ' ' if i > 0
+                    && chars[i - 1] == '.'
+                    && highlighted.test(square_at(square_index - 1)) =>
+                {
+                    ']'
+                }
+                '.' => {
+                    let piece = pieces[square_at(square_index)];
+                    square_index += 1;
+                    piece_char(piece)
+                }
+                'r' => {
+                    let _r = rank_index;
+                    rank_index += 1;
+                    match _r {
+                        0 => '8',
+                        1 => '7',
+                        2 => '6',
+                        3 => '5',
+                        4 => '4',
+                        5 => '3',
+                        6 => '2',
+                        7 => '1',
+                        _ => ' ',
                     }
-                    'f' => {
-                        let _f = file_index;
-                        file_index += 1;
-                        match _f {
-                            0 => 'a',
-                            1 => 'b',
-                            2 => 'c',
-                            3 => 'd',
-                            4 => 'e',
-                            5 => 'f',
-                            6 => 'g',
-                            7 => 'h',
-                            _ => ' ',
-                        }
+                }
+                'f' => {
+                    let _f = file_index;
+                    file_index += 1;
+                    match _f {
+                        0 => 'a',
+                        1 => 'b',
+                        2 => 'c',
+                        3 => 'd',
+                        4 => 'e',
+                        5 => 'f',
+                        6 => 'g',
+                        7 => 'h',
+                        _ => ' ',
                     }
-                    _ => c,
-                };
-
-                write!(f, "{}", c)?;
-            }
+                }
+                _ => c,
+            };
 
-            write!(f, "\n")?;
+            write!(f, "{}", c)?;
         }
 
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+fn piece_char(piece: PieceIndex) -> char {
+    match piece.piece_and_color() {
+        (Piece::Pawn, Color::White) => '♙',
+        (Piece::Pawn, Color::Black) => '♟',
+        (Piece::Knight, Color::White) => '♘',
+        (Piece::Knight, Color::Black) => '♞',
+        (Piece::Bishop, Color::White) => '♗',
+        (Piece::Bishop, Color::Black) => '♝',
+        (Piece::Rook, Color::White) => '♖',
+        (Piece::Rook, Color::Black) => '♜',
+        (Piece::Queen, Color::White) => '♕',
+        (Piece::Queen, Color::Black) => '♛',
+        (Piece::King, Color::White) => '♔',
+        (Piece::King, Color::Black) => '♚',
+        _ => ' ',
+    }
+}
+
+// Empty squares are drawn as blanks on the board, but need something visible in a list
+fn change_char(piece: PieceIndex) -> char {
+    match piece {
+        PieceIndex::NONE => '-',
+        piece => piece_char(piece),
+    }
+}
+
+impl Display for GamePrinter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pieces: ArrayMap<Square, PieceIndex> = self.game.board().into();
+
+        write!(f, "\n")?;
+        write!(f, " {}\n\n", into_notation::<_, Fen>(&self.game))?;
+
+        write_board(f, &pieces, BitBoard::ZERO)?;
+
         write!(f, "\n\n")?;
         write!(f, "  Turn to move: {}\n", self.game.turn_to_move())?;
         write!(
@@ -153,3 +248,48 @@ impl Display for GamePrinter<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        notation::{try_from_notation, San},
+        MoveGenerator,
+    };
+
+    fn play(state: &State, san: &str) -> State {
+        let query = try_from_notation::<_, San>(san).unwrap();
+        let legal_moves = MoveGenerator::compute_legal_moves(state);
+        let result = legal_moves.filter(query).next().unwrap();
+        result.1.clone()
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = State::default();
+        let after = play(&before, "e4");
+        assert_eq!(
+            diff(&before, &after).changed_squares(),
+            BitBoard::just(Square::E2) | BitBoard::just(Square::E4)
+        );
+
+        let printed = diff(&before, &after).to_string();
+        assert!(printed.contains("[ ]"));
+        assert!(printed.contains("e4: - -> ♙"));
+
+        // Castling moves the rook as well as the king
+        let before =
+            try_from_notation::<_, Fen>("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let after = play(&before, "O-O");
+        assert_eq!(
+            diff(&before, &after).changed_squares(),
+            BitBoard::just(Square::E1)
+                | BitBoard::just(Square::F1)
+                | BitBoard::just(Square::G1)
+                | BitBoard::just(Square::H1)
+        );
+
+        assert!(diff(&after, &after).to_string().contains("No changes"));
+    }
+}
@@ -0,0 +1,436 @@
+//! A slow but straightforward move generator used to check the real one.
+//!
+//! Nothing in here uses magic numbers, attack tables, or bitboard tricks:
+//! every move is found by walking the board one square at a time. This makes
+//! it easy to convince yourself that it's correct, but far too slow to use
+//! for searching.
+
+use std::collections::HashSet;
+
+use crate::{
+    common::{CASTLE_DESTS, KING_ORIGINS},
+    Board, CastleRights, Color, File, Move, MoveGenerator, Offset, Piece, PieceIndex, Side, Square,
+    State,
+};
+
+const KNIGHT_OFFSETS: &[Offset] = &[
+    Offset { file: 1, rank: 2 },
+    Offset { file: 2, rank: 1 },
+    Offset { file: 2, rank: -1 },
+    Offset { file: 1, rank: -2 },
+    Offset { file: -1, rank: -2 },
+    Offset { file: -2, rank: -1 },
+    Offset { file: -2, rank: 1 },
+    Offset { file: -1, rank: 2 },
+];
+
+const ORTHOGONAL_OFFSETS: &[Offset] = &[Offset::NORTH, Offset::SOUTH, Offset::EAST, Offset::WEST];
+
+const DIAGONAL_OFFSETS: &[Offset] = &[
+    Offset { file: 1, rank: 1 },
+    Offset { file: -1, rank: 1 },
+    Offset { file: 1, rank: -1 },
+    Offset { file: -1, rank: -1 },
+];
+
+const PROMOTION_TYPES: &[Piece] = &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveGenerationMismatch {
+    /// Legal moves that the move generator didn't produce
+    pub missing: Vec<Move>,
+    /// Moves produced by the move generator that aren't legal
+    pub extra: Vec<Move>,
+}
+
+impl std::fmt::Display for MoveGenerationMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "missing moves: [")?;
+        for (i, mv) in self.missing.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { " " } else { "" }, mv)?;
+        }
+
+        write!(f, "], extra moves: [")?;
+        for (i, mv) in self.extra.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { " " } else { "" }, mv)?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl std::error::Error for MoveGenerationMismatch {}
+
+pub fn compute_legal_moves(state: &State) -> Vec<Move> {
+    let color = state.turn_to_move();
+    let mut moves = Vec::new();
+
+    for square in Square::ALL {
+        let Some(piece) = state.board().piece_at(*square) else {
+            continue;
+        };
+
+        if piece.color() != color {
+            continue;
+        }
+
+        match piece.piece() {
+            Piece::Pawn => compute_pawn_moves(state, *square, &mut moves),
+            Piece::Knight => compute_step_moves(state, *square, KNIGHT_OFFSETS, &mut moves),
+            Piece::Bishop => compute_slide_moves(state, *square, DIAGONAL_OFFSETS, &mut moves),
+            Piece::Rook => compute_slide_moves(state, *square, ORTHOGONAL_OFFSETS, &mut moves),
+            Piece::Queen => {
+                compute_slide_moves(state, *square, DIAGONAL_OFFSETS, &mut moves);
+                compute_slide_moves(state, *square, ORTHOGONAL_OFFSETS, &mut moves);
+            }
+            Piece::King => {
+                compute_step_moves(state, *square, DIAGONAL_OFFSETS, &mut moves);
+                compute_step_moves(state, *square, ORTHOGONAL_OFFSETS, &mut moves);
+                compute_castle_moves(state, &mut moves);
+            }
+            Piece::None => {}
+        }
+    }
+
+    // Only keep the moves that don't leave our own king under attack
+    moves.retain(|mv| {
+        State::by_performing_move(state, mv)
+            .map(|next_state| !is_king_attacked(next_state.board(), color))
+            .unwrap_or(false)
+    });
+
+    moves
+}
+
+/// Compares the moves from the real move generator against this one
+pub fn verify(state: &State) -> Result<(), MoveGenerationMismatch> {
+    let expected: HashSet<Move> = compute_legal_moves(state).into_iter().collect();
+    let actual: HashSet<Move> = MoveGenerator::compute_legal_moves(state)
+        .moves()
+        .iter()
+        .map(|r| r.0)
+        .collect();
+
+    if expected == actual {
+        return Ok(());
+    }
+
+    Err(MoveGenerationMismatch {
+        missing: expected.difference(&actual).copied().collect(),
+        extra: actual.difference(&expected).copied().collect(),
+    })
+}
+
+/// Removes as many pieces as possible from a position while keeping the
+/// move generators in disagreement, to make a failing position easier to debug.
+pub fn shrink(state: &State) -> State {
+    let mut state = state.clone();
+
+    'outer: loop {
+        for square in Square::ALL {
+            let Some(piece) = state.board().piece_at(*square) else {
+                continue;
+            };
+
+            if piece.piece() == Piece::King {
+                continue;
+            }
+
+            let candidate = without_piece(&state, *square);
+
+            // The side that just moved can't be left in check
+            if candidate.board().is_check(!candidate.turn_to_move()) {
+                continue;
+            }
+
+            if verify(&candidate).is_err() {
+                state = candidate;
+                continue 'outer;
+            }
+        }
+
+        return state;
+    }
+}
+
+fn without_piece(state: &State, square: Square) -> State {
+    let mut map = state.board().piece_map().clone();
+    for color in Color::ALL {
+        for piece in Piece::ALL {
+            map[PieceIndex::new(*color, *piece)].set(square, false);
+        }
+    }
+
+    let board = Board::new(map);
+
+    // Keep the castle rights and en passant target consistent with the pieces left over
+    let mut castle_rights = crate::utils::ArrayMap::filled(CastleRights::NONE);
+    for color in Color::ALL {
+        castle_rights[*color] = CastleRights {
+            kingside: state.castle_rights(*color).kingside
+                && has_castle_pieces(&board, *color, Side::King),
+            queenside: state.castle_rights(*color).queenside
+                && has_castle_pieces(&board, *color, Side::Queen),
+        };
+    }
+
+    let en_passant_target = state.en_passant_target().filter(|target| {
+        let mover = !state.turn_to_move();
+        target
+            .offset(mover.forward())
+            .map(|s| board.piece_at(s) == Some(PieceIndex::new(mover, Piece::Pawn)))
+            .unwrap_or(false)
+    });
+
+    State::new(
+        board,
+        state.turn_to_move(),
+        castle_rights,
+        en_passant_target,
+        state.clock().clone(),
+    )
+}
+
+fn compute_pawn_moves(state: &State, origin: Square, moves: &mut Vec<Move>) {
+    let color = state.turn_to_move();
+    let pawn = PieceIndex::new(color, Piece::Pawn);
+    let (home_rank, promotion_rank) = (color.pawn_rank(), color.promotion_rank());
+
+    // Pushes
+    if let Some(target) = origin.offset(color.forward()) {
+        if state.board().piece_at(target).is_none() {
+            if target.rank() == promotion_rank {
+                for promotion in PROMOTION_TYPES {
+                    moves.push(Move::by_promoting(pawn, origin, target, *promotion));
+                }
+            } else {
+                moves.push(Move::by_moving(pawn, origin, target));
+            }
+
+            if origin.rank() == home_rank {
+                if let Some(target) = target.offset(color.forward()) {
+                    if state.board().piece_at(target).is_none() {
+                        moves.push(Move::by_moving(pawn, origin, target));
+                    }
+                }
+            }
+        }
+    }
+
+    // Captures
+    for side in [Offset::EAST, Offset::WEST] {
+        let Some(target) = origin.offset(color.forward() + side) else {
+            continue;
+        };
+
+        match state.board().piece_at(target) {
+            Some(capture) if capture.color() != color => {
+                if target.rank() == promotion_rank {
+                    for promotion in PROMOTION_TYPES {
+                        moves.push(Move::by_capture_promoting(
+                            pawn,
+                            origin,
+                            target,
+                            capture.piece(),
+                            *promotion,
+                        ));
+                    }
+                } else {
+                    moves.push(Move::by_capturing(pawn, origin, target, capture.piece()));
+                }
+            }
+            None if state.en_passant_target() == Some(target) => {
+                moves.push(Move::by_en_passant(pawn, origin, target));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn compute_step_moves(state: &State, origin: Square, offsets: &[Offset], moves: &mut Vec<Move>) {
+    let piece = state.board().piece_at(origin).unwrap();
+    for offset in offsets {
+        let Some(target) = origin.offset(*offset) else {
+            continue;
+        };
+
+        match state.board().piece_at(target) {
+            None => moves.push(Move::by_moving(piece, origin, target)),
+            Some(capture) if capture.color() != piece.color() => {
+                moves.push(Move::by_capturing(piece, origin, target, capture.piece()))
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn compute_slide_moves(state: &State, origin: Square, offsets: &[Offset], moves: &mut Vec<Move>) {
+    let piece = state.board().piece_at(origin).unwrap();
+    for offset in offsets {
+        let mut target = origin;
+        while let Some(next) = target.offset(*offset) {
+            target = next;
+            match state.board().piece_at(target) {
+                None => moves.push(Move::by_moving(piece, origin, target)),
+                Some(capture) => {
+                    if capture.color() != piece.color() {
+                        moves.push(Move::by_capturing(piece, origin, target, capture.piece()));
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn compute_castle_moves(state: &State, moves: &mut Vec<Move>) {
+    let color = state.turn_to_move();
+    let board = state.board();
+
+    for side in Side::ALL {
+        if !state.castle_rights(color).for_side(*side) || !has_castle_pieces(board, color, *side) {
+            continue;
+        }
+
+        let king_origin = KING_ORIGINS[color];
+        let king_dest = CASTLE_DESTS[color][*side];
+        let rook_origin = castle_rook_origin(color, *side);
+
+        // Every square between the king and the rook needs to be empty
+        let (low, high) = min_max_file(king_origin.file(), rook_origin.file());
+        let path_clear = (low.index() + 1..high.index())
+            .map(|f| Square::from((File::from_index(f).unwrap(), king_origin.rank())))
+            .all(|s| board.piece_at(s).is_none());
+
+        // The king can't castle out of, through, or into check
+        let (low, high) = min_max_file(king_origin.file(), king_dest.file());
+        let path_safe = (low.index()..=high.index())
+            .map(|f| Square::from((File::from_index(f).unwrap(), king_origin.rank())))
+            .all(|s| !is_attacked(board, s, !color));
+
+        if path_clear && path_safe {
+            moves.push(Move::by_castling(color, *side));
+        }
+    }
+}
+
+fn has_castle_pieces(board: &Board, color: Color, side: Side) -> bool {
+    board.piece_at(KING_ORIGINS[color]) == Some(PieceIndex::new(color, Piece::King))
+        && board.piece_at(castle_rook_origin(color, side))
+            == Some(PieceIndex::new(color, Piece::Rook))
+}
+
+fn castle_rook_origin(color: Color, side: Side) -> Square {
+    let file = match side {
+        Side::King => File::H,
+        Side::Queen => File::A,
+    };
+
+    Square::from((file, KING_ORIGINS[color].rank()))
+}
+
+fn min_max_file(a: File, b: File) -> (File, File) {
+    if a.index() < b.index() {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn is_king_attacked(board: &Board, color: Color) -> bool {
+    Square::ALL
+        .iter()
+        .filter(|s| board.piece_at(**s) == Some(PieceIndex::new(color, Piece::King)))
+        .any(|s| is_attacked(board, *s, !color))
+}
+
+fn is_attacked(board: &Board, square: Square, by: Color) -> bool {
+    let is = |s: Option<Square>, pieces: &[Piece]| {
+        s.and_then(|s| board.piece_at(s))
+            .map(|p| p.color() == by && pieces.contains(&p.piece()))
+            .unwrap_or(false)
+    };
+
+    // Pawns attack diagonally forward, so look diagonally backward for them
+    for side in [Offset::EAST, Offset::WEST] {
+        if is(square.offset(by.backward() + side), &[Piece::Pawn]) {
+            return true;
+        }
+    }
+
+    for offset in KNIGHT_OFFSETS {
+        if is(square.offset(*offset), &[Piece::Knight]) {
+            return true;
+        }
+    }
+
+    for offset in DIAGONAL_OFFSETS.iter().chain(ORTHOGONAL_OFFSETS) {
+        if is(square.offset(*offset), &[Piece::King]) {
+            return true;
+        }
+    }
+
+    let sliders: [(&[Offset], &[Piece]); 2] = [
+        (DIAGONAL_OFFSETS, &[Piece::Bishop, Piece::Queen]),
+        (ORTHOGONAL_OFFSETS, &[Piece::Rook, Piece::Queen]),
+    ];
+
+    for (offsets, pieces) in sliders {
+        for offset in offsets {
+            let mut target = square;
+            while let Some(next) = target.offset(*offset) {
+                target = next;
+                if board.piece_at(target).is_some() {
+                    if is(Some(target), pieces) {
+                        return true;
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notation::{try_from_notation, Fen};
+
+    use super::*;
+
+    #[test]
+    fn test_reference_perft() {
+        let state = State::default();
+        let count: usize = compute_legal_moves(&state)
+            .iter()
+            .map(|mv| {
+                let next_state = State::by_performing_move(&state, mv).unwrap();
+                compute_legal_moves(&next_state).len()
+            })
+            .sum();
+
+        assert_eq!(count, 400);
+    }
+
+    #[test]
+    fn test_verify_positions() {
+        let positions = [
+            Fen::DEFAULT,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18",
+        ];
+
+        for fen in positions {
+            let state = try_from_notation::<_, Fen>(fen).unwrap();
+            if let Err(e) = verify(&state) {
+                panic!("{}: {}", fen, e);
+            }
+        }
+    }
+}
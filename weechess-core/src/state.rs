@@ -1,13 +1,15 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     notation::{self, Fen},
-    GamePrinter,
+    piece_square_values, GamePrinter, GameStatus, Variant,
 };
 
 use super::{
-    utils::ArrayMap, Board, Color, File, Move, MoveGenerator, MoveQuery, MoveResult, Piece,
-    PieceIndex, Side, Square,
+    utils::ArrayMap, BitBoard, Board, Color, File, Move, MoveGenerationBuffer, MoveGenerator,
+    MoveQuery, MoveResult, Piece, PieceIndex, Side, Square,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -88,6 +90,23 @@ impl Default for Clock {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamePhase {
+    Opening,
+    MiddleGame,
+    EndGame,
+}
+
+impl Display for GamePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GamePhase::Opening => write!(f, "opening"),
+            GamePhase::MiddleGame => write!(f, "middlegame"),
+            GamePhase::EndGame => write!(f, "endgame"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MovePerformError {
     AmbiguousMove,
@@ -107,6 +126,130 @@ impl Display for MovePerformError {
 
 impl std::error::Error for MovePerformError {}
 
+/// Everything a move changes on the board, so that it can be shown (or animated)
+/// without working it out again from the move itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveEffects {
+    /// The piece that moved, which is the pawn when promoting
+    pub piece: PieceIndex,
+    pub from: Square,
+    pub to: Square,
+
+    /// The piece that was captured and the square it was taken from, which is
+    /// only different from the destination when capturing en passant
+    pub capture: Option<(PieceIndex, Square)>,
+
+    /// Where the rook moved from and to when castling
+    pub rook: Option<(Square, Square)>,
+
+    /// What the pawn turned into on the destination square
+    pub promotion: Option<PieceIndex>,
+}
+
+impl MoveEffects {
+    fn of(state: &State, mv: &Move) -> Result<Self, MovePerformError> {
+        let color = state.turn_to_move;
+        let capture = if mv.is_en_passant() {
+            let capture_square = state
+                .en_passant_target
+                .and_then(|target| target.offset(color.backward()))
+                .ok_or(MovePerformError::IllegalEnPassant)?;
+
+            Some((
+                PieceIndex::new(color.opposing_color(), Piece::Pawn),
+                capture_square,
+            ))
+        } else {
+            mv.capture().map(|piece| {
+                (
+                    PieceIndex::new(color.opposing_color(), piece),
+                    mv.destination(),
+                )
+            })
+        };
+
+        let rook = mv.castle_side().map(|side| {
+            let (from, to) = match side {
+                Side::King => (File::H, File::F),
+                Side::Queen => (File::A, File::D),
+            };
+
+            let rank = mv.origin().rank();
+            (Square::from((rank, from)), Square::from((rank, to)))
+        });
+
+        Ok(Self {
+            piece: PieceIndex::new(color, mv.piece()),
+            from: mv.origin(),
+            to: mv.destination(),
+            capture,
+            rook,
+            promotion: mv
+                .promotion()
+                .map(|promotion| PieceIndex::new(color, promotion)),
+        })
+    }
+
+    /// The squares that had a piece put on or taken off of them
+    pub fn changed_squares(&self) -> BitBoard {
+        let mut squares = BitBoard::just(self.from) | BitBoard::just(self.to);
+        if let Some((_, square)) = self.capture {
+            squares |= BitBoard::just(square);
+        }
+
+        if let Some((from, to)) = self.rook {
+            squares |= BitBoard::just(from) | BitBoard::just(to);
+        }
+
+        squares
+    }
+}
+
+/// Running totals over the pieces on the board, kept up to date as moves are
+/// performed so they don't need to be recounted from the bitboards
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Material {
+    piece_counts: ArrayMap<PieceIndex, u8>,
+    color_counts: ArrayMap<Color, u8>,
+    piece_squares: ArrayMap<Color, [i32; 2]>,
+}
+
+impl Material {
+    fn add(&mut self, piece_index: PieceIndex, square: Square) {
+        let (piece, color) = piece_index.piece_and_color();
+        let [middle_game, end_game] = piece_square_values(piece, square, color);
+        self.piece_counts[piece_index] += 1;
+        self.color_counts[color] += 1;
+        self.piece_squares[color][0] += middle_game;
+        self.piece_squares[color][1] += end_game;
+    }
+
+    fn remove(&mut self, piece_index: PieceIndex, square: Square) {
+        let (piece, color) = piece_index.piece_and_color();
+        let [middle_game, end_game] = piece_square_values(piece, square, color);
+        self.piece_counts[piece_index] -= 1;
+        self.color_counts[color] -= 1;
+        self.piece_squares[color][0] -= middle_game;
+        self.piece_squares[color][1] -= end_game;
+    }
+}
+
+impl From<&Board> for Material {
+    fn from(board: &Board) -> Self {
+        let mut material = Material {
+            piece_counts: ArrayMap::default(),
+            color_counts: ArrayMap::default(),
+            piece_squares: ArrayMap::default(),
+        };
+
+        for (square, piece_index) in board.pieces() {
+            material.add(piece_index, square);
+        }
+
+        material
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct State {
     board: Board,
@@ -114,6 +257,8 @@ pub struct State {
     castle_rights: ArrayMap<Color, CastleRights>,
     en_passant_target: Option<Square>,
     clock: Clock,
+    material: Material,
+    variant: Variant,
 }
 
 impl State {
@@ -124,15 +269,37 @@ impl State {
         en_passant_target: Option<Square>,
         clock: Clock,
     ) -> Self {
+        let material = Material::from(&board);
         Self {
             board,
             turn_to_move,
             castle_rights,
             en_passant_target,
             clock,
+            material,
+            variant: Variant::Standard,
+        }
+    }
+
+    /// The same position, played under the rules of a different variant
+    pub fn with_variant(self, variant: Variant) -> Self {
+        let castle_rights = if variant == Variant::Antichess {
+            ArrayMap::filled(CastleRights::NONE)
+        } else {
+            self.castle_rights
+        };
+
+        Self {
+            variant,
+            castle_rights,
+            ..self
         }
     }
 
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
     pub fn board(&self) -> &Board {
         &self.board
     }
@@ -154,62 +321,240 @@ impl State {
     }
 
     pub fn is_check(&self) -> bool {
-        self.board.is_check(self.turn_to_move)
+        self.variant.has_royal_king() && self.board.is_check(self.turn_to_move)
+    }
+
+    /// Whether the game is over in this position according to the rules of its variant
+    pub fn game_status(&self) -> GameStatus {
+        if let Some(winner) = self.variant_winner() {
+            return GameStatus::Won(winner);
+        }
+
+        if !MoveGenerator::compute_legal_moves(self).is_empty() {
+            GameStatus::Ongoing
+        } else if self.variant == Variant::Antichess {
+            GameStatus::Won(self.turn_to_move)
+        } else if self.is_check() {
+            GameStatus::Won(self.turn_to_move.opposing_color())
+        } else {
+            GameStatus::Drawn
+        }
+    }
+
+    /// The winner of the game by a variant-specific rule that doesn't depend on
+    /// the available moves, which makes it a lot cheaper to check than `game_status`
+    pub fn variant_winner(&self) -> Option<Color> {
+        match self.variant {
+            Variant::Standard => None,
+            Variant::Antichess => {
+                (self.material.color_counts[self.turn_to_move] == 0).then_some(self.turn_to_move)
+            }
+            Variant::KingOfTheHill => Color::ALL.iter().copied().find(|color| {
+                let king = self
+                    .board
+                    .piece_occupancy(PieceIndex::new(*color, Piece::King));
+                (king & Variant::HILL).any()
+            }),
+        }
+    }
+
+    pub fn piece_count(&self, piece_index: PieceIndex) -> u8 {
+        self.material.piece_counts[piece_index]
+    }
+
+    pub fn piece_counts(&self) -> &ArrayMap<PieceIndex, u8> {
+        &self.material.piece_counts
+    }
+
+    /// The number of pieces of each color on the board, including the kings
+    pub fn color_counts(&self) -> &ArrayMap<Color, u8> {
+        &self.material.color_counts
+    }
+
+    /// The summed middle game and end game piece-square values of all the
+    /// pieces of a color
+    pub fn piece_square_totals(&self, color: Color) -> [i32; 2] {
+        self.material.piece_squares[color]
+    }
+
+    /// How far along into the end game this position is, from 0.0 with all
+    /// the pieces on the board up to 1.0 with only the kings left.
+    pub fn end_game_weight(&self) -> f32 {
+        let count_pieces = |piece: Piece| {
+            (self.piece_count(PieceIndex::new(Color::White, piece))
+                + self.piece_count(PieceIndex::new(Color::Black, piece))) as f32
+        };
+
+        let w1 = 3.0;
+        let v1 = count_pieces(Piece::Pawn) / 16.0;
+
+        let w2 = 1.0;
+        let v2 = count_pieces(Piece::Queen) / 2.0;
+
+        let w3 = 1.0;
+        let v3 = (self.material.color_counts[Color::White]
+            + self.material.color_counts[Color::Black]) as f32
+            / 32.0;
+
+        1.0 - (w1 * v1 + w2 * v2 + w3 * v3) / (w1 + w2 + w3)
+    }
+
+    pub fn phase(&self) -> GamePhase {
+        let end_game_weight = self.end_game_weight();
+        if end_game_weight >= 0.75 {
+            GamePhase::EndGame
+        } else if end_game_weight < 0.1 {
+            GamePhase::Opening
+        } else {
+            GamePhase::MiddleGame
+        }
+    }
+
+    /// The pieces on the board, strongest first with white in uppercase
+    /// followed by black in lowercase (ex. `KRPPkrp`).
+    pub fn material_signature(&self) -> String {
+        const ORDER: &[Piece] = &[
+            Piece::King,
+            Piece::Queen,
+            Piece::Rook,
+            Piece::Bishop,
+            Piece::Knight,
+            Piece::Pawn,
+        ];
+
+        let mut signature = String::new();
+        for color in Color::ALL {
+            for piece in ORDER {
+                let count = self.piece_count(PieceIndex::new(*color, *piece));
+
+                let c: char = (*piece).into();
+                let c = match color {
+                    Color::White => c,
+                    Color::Black => c.to_ascii_lowercase(),
+                };
+
+                signature.extend(std::iter::repeat_n(c, count as usize));
+            }
+        }
+
+        signature
     }
 
     pub fn pretty<'a>(&'a self) -> impl Display + 'a {
         GamePrinter::new(self)
     }
 
-    pub fn by_performing_move(state: &Self, mv: &Move) -> Result<State, MovePerformError> {
-        let board = {
-            let mut map = state.board().piece_map().clone();
+    /// Checks whether a move can be played in this position. Only the moves of the
+    /// type of piece being moved are generated, and only the given move is checked
+    /// for leaving the king in check, by looking at the lines it opens up to the king
+    /// (like moving a pinned piece) instead of playing it out.
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        if mv.color() != self.turn_to_move {
+            return false;
+        }
+
+        // Whether a move is legal depends on whether any captures are available
+        if self.variant == Variant::Antichess {
+            return MoveGenerator::compute_legal_moves(self)
+                .moves()
+                .iter()
+                .any(|m| m.0 == *mv);
+        }
 
-            let moving_piece = PieceIndex::new(state.turn_to_move, mv.piece());
-            let moving_color = state.turn_to_move;
-            let opposing_color = moving_color.opposing_color();
+        let mut moves = Vec::new();
+        MoveGenerator::compute_pseudo_legal_piece_moves_into(self, mv.piece(), &mut moves);
+        moves.into_iter().any(|m| *m == *mv && m.is_legal(self))
+    }
 
-            // Update the start and end positions of the moving piece
-            map[moving_piece].set(mv.origin(), false);
-            map[moving_piece].set(mv.destination(), true);
-
-            if mv.is_en_passant() {
-                let en_passant_target = state
-                    .en_passant_target
-                    .ok_or(MovePerformError::IllegalEnPassant)?;
-
-                let capture = PieceIndex::new(opposing_color, Piece::Pawn);
-                let capture_square = en_passant_target
-                    .offset(moving_color.backward())
-                    .ok_or(MovePerformError::IllegalEnPassant)?;
-
-                map[capture].set(capture_square, false);
-            } else if let Some(capture) = mv.capture() {
-                let capture = PieceIndex::new(opposing_color, capture);
-                map[capture].set(mv.destination(), false);
+    /// Finds the legal move matching the query, if exactly one does, since a query that
+    /// matches more than one (like a promotion without the piece) can't be played. The
+    /// moves of the piece types the query rules out aren't generated, and only the
+    /// matching ones are checked for leaving the king in check, like in `is_legal`.
+    pub fn find_legal(&self, query: &MoveQuery) -> Option<MoveResult> {
+        let query = &query.normalized_castling(self);
+        if self.variant == Variant::Antichess {
+            let moves = MoveGenerator::compute_legal_moves(self);
+            let mut matches = moves.moves().iter().filter(|m| query.test(&m.0));
+            return match (matches.next(), matches.next()) {
+                (Some(result), None) => Some(result.clone()),
+                _ => None,
+            };
+        }
+
+        // Only the piece standing on the square a move starts from can make it
+        let origin = query.origin_rank.zip(query.origin_file).map(Square::from);
+        let pieces = match (query.piece, query.castle, origin) {
+            (Some(piece), _, _) => vec![piece],
+            (None, Some(_), _) => vec![Piece::King],
+            (None, None, Some(origin)) => self
+                .board
+                .piece_at(origin)
+                .map(|p| p.piece())
+                .into_iter()
+                .collect(),
+            (None, None, None) => Piece::ALL.to_vec(),
+        };
+
+        let mut found = None;
+        let mut moves = Vec::new();
+        for piece in pieces {
+            MoveGenerator::compute_pseudo_legal_piece_moves_into(self, piece, &mut moves);
+            for mv in moves.iter().filter(|m| query.test(m) && m.is_legal(self)) {
+                if found.replace(**mv).is_some() {
+                    return None;
+                }
             }
+        }
+
+        let mv = found?;
+        Self::by_performing_move(self, &mv)
+            .ok()
+            .map(|next| MoveResult(mv, next))
+    }
+
+    pub fn by_performing_move(state: &Self, mv: &Move) -> Result<State, MovePerformError> {
+        Self::by_performing_move_with_effects(state, mv).map(|(state, _)| state)
+    }
 
-            if let Some(promotion) = mv.promotion() {
-                let promotion = PieceIndex::new(moving_color, promotion);
-                map[moving_piece].set(mv.destination(), false);
-                map[promotion].set(mv.destination(), true);
+    /// Like [`State::by_performing_move`], but also returns what the move changed on the board
+    pub fn by_performing_move_with_effects(
+        state: &Self,
+        mv: &Move,
+    ) -> Result<(State, MoveEffects), MovePerformError> {
+        let effects = MoveEffects::of(state, mv)?;
+        let mut material = state.material.clone();
+        let board = {
+            let mut map = state.board().piece_map().clone();
+            let mut mailbox = state.board().mailbox().clone();
+
+            // Take the captured piece off first, since it's usually where the moving piece lands
+            if let Some((capture, square)) = effects.capture {
+                map[capture].set(square, false);
+                mailbox[square] = PieceIndex::NONE;
+                material.remove(capture, square);
             }
 
-            if mv.is_castle(Side::King) {
-                let rook_start = Square::from((mv.origin().rank(), File::H));
-                let rook_end = Square::from((mv.origin().rank(), File::F));
-                let rook = PieceIndex::new(moving_color, Piece::Rook);
-                map[rook].set(rook_start, false);
-                map[rook].set(rook_end, true);
-            } else if mv.is_castle(Side::Queen) {
-                let rook_start = Square::from((mv.origin().rank(), File::A));
-                let rook_end = Square::from((mv.origin().rank(), File::D));
-                let rook = PieceIndex::new(moving_color, Piece::Rook);
+            // Update the start and end positions of the moving piece
+            let moving_piece = effects.piece;
+            let landing_piece = effects.promotion.unwrap_or(moving_piece);
+            map[moving_piece].set(effects.from, false);
+            map[landing_piece].set(effects.to, true);
+            mailbox[effects.from] = PieceIndex::NONE;
+            mailbox[effects.to] = landing_piece;
+            material.remove(moving_piece, effects.from);
+            material.add(landing_piece, effects.to);
+
+            if let Some((rook_start, rook_end)) = effects.rook {
+                let rook = PieceIndex::new(state.turn_to_move, Piece::Rook);
                 map[rook].set(rook_start, false);
                 map[rook].set(rook_end, true);
+                mailbox[rook_start] = PieceIndex::NONE;
+                mailbox[rook_end] = rook;
+                material.remove(rook, rook_start);
+                material.add(rook, rook_end);
             }
 
-            Board::new(map)
+            Board::with_mailbox(map, mailbox)
         };
 
         let castle_rights = {
@@ -238,7 +583,7 @@ impl State {
             castle_rights
         };
 
-        Ok(State {
+        let state = State {
             board,
             castle_rights,
             turn_to_move: state.turn_to_move().opposing_color(),
@@ -259,25 +604,47 @@ impl State {
                     state.clock.fullmove_number
                 },
             },
-        })
+            material,
+            variant: state.variant,
+        };
+
+        Ok((state, effects))
     }
 
     pub fn by_performing_moves(
         state: &Self,
         moves: &[MoveQuery],
     ) -> Result<State, MovePerformError> {
+        let mut buffer = MoveGenerationBuffer::new();
+        Self::by_performing_moves_with_buffer(state, moves, &mut buffer, |_, _| {})
+    }
+
+    /// Like [`State::by_performing_moves`], but generates the legal moves of each position
+    /// into the given buffer instead of allocating new ones every ply. The state before each
+    /// move is passed to `on_move`, along with the move that was played from it.
+    pub fn by_performing_moves_with_buffer<F>(
+        state: &Self,
+        moves: &[MoveQuery],
+        buffer: &mut MoveGenerationBuffer,
+        mut on_move: F,
+    ) -> Result<State, MovePerformError>
+    where
+        F: FnMut(&State, &Move),
+    {
         let mut state = state.clone();
-        for mv in moves {
-            let move_set = MoveGenerator::compute_legal_moves(&state);
-            let valid_moves: Vec<&MoveResult> = move_set.filter(*mv).collect();
-            match valid_moves[..] {
-                [mv] => {
-                    state = Self::by_performing_move(&state, &mv.0)?;
+        for query in moves {
+            let query = query.normalized_castling(&state);
+            MoveGenerator::compute_legal_moves_into(&state, buffer);
+            let mut valid_moves = buffer.legal_moves.iter().filter(|m| query.test(&m.0));
+            match (valid_moves.next(), valid_moves.next()) {
+                (Some(MoveResult(mv, next_state)), None) => {
+                    on_move(&state, mv);
+                    state = next_state.clone();
                 }
-                [] => {
+                (None, _) => {
                     return Err(MovePerformError::UnknownMove);
                 }
-                _ => {
+                (Some(_), Some(_)) => {
                     return Err(MovePerformError::AmbiguousMove);
                 }
             }
@@ -294,6 +661,49 @@ impl Default for State {
     }
 }
 
+/// A string that isn't a valid FEN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FenParseError(pub String);
+
+impl Display for FenParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid FEN: {}", self.0)
+    }
+}
+
+impl std::error::Error for FenParseError {}
+
+/// Reads the position from its FEN, like `try_from_notation::<_, Fen>`
+impl FromStr for State {
+    type Err = FenParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        notation::try_from_notation::<_, Fen>(s).map_err(|_| FenParseError(s.to_string()))
+    }
+}
+
+/// Writes the position as its FEN, like `into_notation::<_, Fen>`
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", notation::into_notation::<_, Fen>(self))
+    }
+}
+
+/// Positions are stored as their FEN
+impl Serialize for State {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::notation::{into_notation, San};
@@ -305,6 +715,211 @@ mod tests {
         let _ = State::default();
     }
 
+    #[test]
+    fn test_fen_str() {
+        let fen = "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18";
+        let state: State = fen.parse().unwrap();
+        assert_eq!(state.to_string(), fen);
+        assert_eq!(format!("{}", State::default()), Fen::DEFAULT);
+
+        assert_eq!(
+            "not a position".parse::<State>().err(),
+            Some(FenParseError("not a position".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_phase() {
+        assert_eq!(State::default().phase(), GamePhase::Opening);
+
+        let state = notation::try_from_notation::<_, Fen>(
+            "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18",
+        )
+        .unwrap();
+
+        assert_eq!(state.phase(), GamePhase::MiddleGame);
+
+        let state =
+            notation::try_from_notation::<_, Fen>("8/5k2/8/8/2R5/2K5/8/8 w - - 0 1").unwrap();
+        assert_eq!(state.phase(), GamePhase::EndGame);
+    }
+
+    #[test]
+    fn test_material_signature() {
+        assert_eq!(
+            State::default().material_signature(),
+            "KQRRBBNNPPPPPPPPkqrrbbnnpppppppp"
+        );
+
+        let state =
+            notation::try_from_notation::<_, Fen>("8/5kp1/8/8/2R5/2K5/4P3/3r4 w - - 0 1").unwrap();
+        assert_eq!(state.material_signature(), "KRPkrp");
+    }
+
+    #[test]
+    fn test_is_legal() {
+        // The knight on d2 is pinned to the king
+        let state =
+            notation::try_from_notation::<_, Fen>("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+
+        let knight = PieceIndex::new(Color::White, Piece::Knight);
+        assert!(!state.is_legal(&Move::by_moving(knight, Square::D2, Square::F3)));
+
+        let king = PieceIndex::new(Color::White, Piece::King);
+        assert!(state.is_legal(&Move::by_moving(king, Square::E1, Square::F1)));
+        assert!(!state.is_legal(&Move::by_moving(king, Square::E1, Square::E3)));
+
+        // Every legal move agrees with the move generator, and nothing else is legal
+        let state = notation::try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+
+        let legal_moves = MoveGenerator::compute_legal_moves(&state);
+        let mut pseudo_legal_moves = Vec::new();
+        MoveGenerator::compute_psuedo_legal_moves_into(&state, &mut pseudo_legal_moves);
+        for mv in pseudo_legal_moves.iter() {
+            let expected = legal_moves.moves().iter().any(|r| r.0 == **mv);
+            assert_eq!(state.is_legal(mv), expected, "{}", **mv);
+        }
+    }
+
+    #[test]
+    fn test_find_legal() {
+        let state = notation::try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+
+        for san in ["O-O", "O-O-O", "Nxf7", "d6", "Qxf6", "gxh3"] {
+            let query = notation::try_from_notation::<_, San>(san).unwrap();
+            let expected = MoveGenerator::compute_legal_moves(&state).find(&query);
+            let found = state.find_legal(&query);
+            assert!(expected.is_some(), "{}", san);
+
+            // The boards cache their attacks once they're generated, so compare positions by FEN
+            let fen = |result: Option<MoveResult>| {
+                result.map(|MoveResult(mv, next)| (mv, into_notation::<_, Fen>(&next).to_string()))
+            };
+            assert_eq!(fen(found), fen(expected), "{}", san);
+        }
+
+        let query = MoveQuery::by_moving_from_to(Square::E1, Square::E3);
+        assert_eq!(state.find_legal(&query), None);
+
+        // Either knight can go to d2, so the move has to say which
+        let state =
+            notation::try_from_notation::<_, Fen>("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        let find =
+            |san: &str| state.find_legal(&notation::try_from_notation::<_, San>(san).unwrap());
+        assert_eq!(find("Nd2"), None);
+        assert_eq!(find("Nbd2").unwrap().0.origin(), Square::B1);
+        assert_eq!(find("Nfd2").unwrap().0.origin(), Square::F3);
+
+        // The same goes for promotions
+        let state =
+            notation::try_from_notation::<_, Fen>("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let find = |uci: &str| state.find_legal(&MoveQuery::try_from_uci(uci).unwrap());
+        assert_eq!(find("a7a8"), None);
+        assert_eq!(find("a7a8n").unwrap().0.promotion(), Some(Piece::Knight));
+    }
+
+    #[test]
+    fn test_antichess() {
+        // Taking the pawn is compulsory, even though the king ends up being attacked
+        let state = notation::try_from_notation::<_, Fen>("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1")
+            .unwrap()
+            .with_variant(Variant::Antichess);
+
+        let king = PieceIndex::new(Color::White, Piece::King);
+        assert_eq!(MoveGenerator::compute_legal_moves(&state).moves().len(), 1);
+        assert!(state.is_legal(&Move::by_capturing(
+            king,
+            Square::E1,
+            Square::D2,
+            Piece::Pawn
+        )));
+        assert!(!state.is_legal(&Move::by_moving(king, Square::E1, Square::F1)));
+        assert_eq!(state.game_status(), GameStatus::Ongoing);
+
+        // Pawns can promote to kings, and there's no castling
+        let state = notation::try_from_notation::<_, Fen>("8/P7/8/8/8/8/8/R3K2k w Q - 0 1")
+            .unwrap()
+            .with_variant(Variant::Antichess);
+
+        assert_eq!(state.castle_rights(Color::White), CastleRights::NONE);
+        let query = notation::try_from_notation::<_, San>("a8=K").unwrap();
+        assert!(state.find_legal(&query).is_some());
+
+        // Running out of pieces wins the game
+        let state = notation::try_from_notation::<_, Fen>("8/8/8/8/8/8/8/6Rk b - - 0 1")
+            .unwrap()
+            .with_variant(Variant::Antichess);
+
+        let moves = MoveGenerator::compute_legal_moves(&state);
+        let MoveResult(_, state) = &moves.moves()[0];
+        assert_eq!(moves.moves().len(), 1);
+        assert_eq!(state.game_status(), GameStatus::Won(Color::White));
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let state = notation::try_from_notation::<_, Fen>("8/8/8/4k3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(state.game_status(), GameStatus::Ongoing);
+
+        let state = state.with_variant(Variant::KingOfTheHill);
+        assert_eq!(state.variant_winner(), Some(Color::Black));
+        assert_eq!(state.game_status(), GameStatus::Won(Color::Black));
+
+        // Checkmate still ends the game as usual
+        let state = notation::try_from_notation::<_, Fen>("R5k1/5ppp/8/8/8/8/8/4K3 b - - 0 1")
+            .unwrap()
+            .with_variant(Variant::KingOfTheHill);
+        assert_eq!(state.game_status(), GameStatus::Won(Color::White));
+    }
+
+    #[test]
+    fn test_perform_moves_with_buffer() {
+        let queries: Vec<MoveQuery> = ["e4", "e5", "Nf3", "Nc6"]
+            .iter()
+            .map(|san| notation::try_from_notation::<_, San>(san).unwrap())
+            .collect();
+
+        let mut buffer = MoveGenerationBuffer::new();
+        let mut history = vec![];
+        let state = State::by_performing_moves_with_buffer(
+            &State::default(),
+            &queries,
+            &mut buffer,
+            |state, mv| history.push((state.clone(), *mv)),
+        )
+        .unwrap();
+
+        // The boards cache their attacks once they're generated, so compare positions by FEN
+        let fen = |state: &State| into_notation::<_, Fen>(state).to_string();
+        assert_eq!(
+            fen(&state),
+            fen(&State::by_performing_moves(&State::default(), &queries).unwrap())
+        );
+        assert_eq!(history.len(), 4);
+        assert_eq!(fen(&history[0].0), Fen::DEFAULT);
+        assert_eq!(
+            fen(&State::by_performing_move(&history[3].0, &history[3].1).unwrap()),
+            fen(&state)
+        );
+
+        let illegal = notation::try_from_notation::<_, San>("Ke2").unwrap();
+        assert_eq!(
+            State::by_performing_moves_with_buffer(
+                &State::default(),
+                &[illegal],
+                &mut buffer,
+                |_, _| {}
+            ),
+            Err(MovePerformError::UnknownMove)
+        );
+    }
+
     #[test]
     fn test_apply_en_passant_move() {
         let state = notation::try_from_notation::<_, Fen>(
@@ -322,4 +937,85 @@ mod tests {
             new_state.pretty()
         );
     }
+
+    #[test]
+    fn test_move_effects() {
+        let effects = |fen: &str, san: &str| {
+            let state = notation::try_from_notation::<_, Fen>(fen).unwrap();
+            let query = notation::try_from_notation::<_, San>(san).unwrap();
+            let MoveResult(mv, next) = state.find_legal(&query).unwrap();
+            let (performed, effects) = State::by_performing_move_with_effects(&state, &mv).unwrap();
+            assert_eq!(
+                into_notation::<_, Fen>(&performed).to_string(),
+                into_notation::<_, Fen>(&next).to_string()
+            );
+            effects
+        };
+
+        let castle = effects(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "O-O-O",
+        );
+
+        assert_eq!(castle.piece, PieceIndex::new(Color::White, Piece::King));
+        assert_eq!((castle.from, castle.to), (Square::E1, Square::C1));
+        assert_eq!(castle.rook, Some((Square::A1, Square::D1)));
+        assert_eq!(castle.capture, None);
+        assert_eq!(castle.changed_squares().count_ones(), 4);
+
+        // The captured pawn isn't on the square the capturing one lands on
+        let en_passant = effects(
+            "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18",
+            "axb6",
+        );
+
+        assert_eq!(en_passant.to, Square::B6);
+        assert_eq!(
+            en_passant.capture,
+            Some((PieceIndex::new(Color::Black, Piece::Pawn), Square::B5))
+        );
+
+        let promotion = effects("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1", "bxa8=Q");
+        assert_eq!(promotion.piece, PieceIndex::new(Color::White, Piece::Pawn));
+        assert_eq!(
+            promotion.promotion,
+            Some(PieceIndex::new(Color::White, Piece::Queen))
+        );
+        assert_eq!(
+            promotion.capture,
+            Some((PieceIndex::new(Color::Black, Piece::Knight), Square::A8))
+        );
+        assert_eq!(promotion.changed_squares().count_ones(), 2);
+    }
+
+    #[test]
+    fn test_incremental_material() {
+        // Covers castling, en passant, captures and promotions two plies deep
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+
+        for fen in fens {
+            let state = notation::try_from_notation::<_, Fen>(fen).unwrap();
+            for MoveResult(_, s1) in MoveGenerator::compute_legal_moves(&state).moves() {
+                for MoveResult(_, s2) in MoveGenerator::compute_legal_moves(s1).moves() {
+                    let recomputed = State::new(
+                        s2.board().clone(),
+                        s2.turn_to_move(),
+                        s2.castle_rights.clone(),
+                        s2.en_passant_target(),
+                        s2.clock().clone(),
+                    );
+
+                    // Equality covers the piece counts, which are kept up to date too
+                    assert_eq!(*s2, recomputed, "{}", s2.pretty());
+
+                    // The pieces on each square are kept up to date along with the bitboards
+                    let rebuilt = Board::new(s2.board().piece_map().clone());
+                    assert_eq!(s2.board().mailbox(), rebuilt.mailbox(), "{}", s2.pretty());
+                }
+            }
+        }
+    }
 }
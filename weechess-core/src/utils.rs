@@ -1,5 +1,7 @@
 use std::{fmt::Debug, marker::PhantomData, ops::Deref};
 
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
 pub trait ArrayKey: Into<Index> + Copy {
     const COUNT: usize;
 }
@@ -175,6 +177,34 @@ where
 {
 }
 
+impl<I, T> Serialize for ArrayMap<I, T>
+where
+    I: ArrayKey,
+    [(); I::COUNT]:,
+    T: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.array.iter())
+    }
+}
+
+impl<'de, I, T> Deserialize<'de> for ArrayMap<I, T>
+where
+    I: ArrayKey,
+    [(); I::COUNT]:,
+    T: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        let len = items.len();
+        let array: [T; I::COUNT] = items
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &"one item per key"))?;
+
+        Ok(Self::new(array))
+    }
+}
+
 #[macro_export]
 macro_rules! arraymap {
     ($( $key: expr => $val: expr ),*$(,)?) => {{
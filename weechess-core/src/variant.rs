@@ -0,0 +1,101 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    utils::{ArrayKey, Index},
+    BitBoard, Color,
+};
+
+/// The rules the game is being played under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+
+    /// Captures are compulsory, the king is just another piece, and the
+    /// first side to run out of pieces (or moves) wins
+    Antichess,
+
+    /// Standard chess, except getting a king onto one of the four
+    /// center squares also wins the game
+    KingOfTheHill,
+}
+
+impl Variant {
+    pub const ALL: &'static [Self] = &[Self::Standard, Self::Antichess, Self::KingOfTheHill];
+
+    /// The squares a king needs to reach to win a king-of-the-hill game (d4, e4, d5 and e5)
+    pub const HILL: BitBoard = BitBoard::new(0x0000_0018_1800_0000);
+
+    /// Whether the king has to be kept out of check, as opposed to being a piece
+    /// that can be captured like any other
+    pub fn has_royal_king(self) -> bool {
+        self != Self::Antichess
+    }
+}
+
+impl From<Variant> for Index {
+    fn from(value: Variant) -> Self {
+        Index(match value {
+            Variant::Standard => 0,
+            Variant::Antichess => 1,
+            Variant::KingOfTheHill => 2,
+        })
+    }
+}
+
+impl ArrayKey for Variant {
+    const COUNT: usize = 3;
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variant::Standard => write!(f, "chess"),
+            Variant::Antichess => write!(f, "antichess"),
+            Variant::KingOfTheHill => write!(f, "kingofthehill"),
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "chess" | "standard" | "normal" => Ok(Variant::Standard),
+            "antichess" | "giveaway" | "suicide" => Ok(Variant::Antichess),
+            "kingofthehill" | "koth" => Ok(Variant::KingOfTheHill),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether the game has finished, and how
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameStatus {
+    Ongoing,
+    Won(Color),
+    Drawn,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Square;
+
+    #[test]
+    fn test_hill() {
+        let hill = [Square::D4, Square::E4, Square::D5, Square::E5];
+        assert_eq!(Variant::HILL.count_ones(), 4);
+        assert!(hill.iter().all(|square| Variant::HILL.test(*square)));
+    }
+
+    #[test]
+    fn test_names() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse::<Variant>(), Ok(*variant));
+        }
+    }
+}
@@ -1,12 +1,14 @@
-use std::{fs, path::Path};
+use std::{fs, path::Path, process::Command};
 
 use rand::RngCore;
 use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
-use weechess_core::{Book, BookParseError, BookParser};
+use weechess_core::{Book, BookParseError};
 
 const BOOK_DEPTH: usize = 10;
 const BOOK_SEED_ENV_VAR: &'static str = "WEECHESS_BOOK_SEED";
 const BOOK_DATA_FILE_NAME: &'static str = "book_data.bin";
+const GIT_COMMIT_ENV_VAR: &str = "WEECHESS_GIT_COMMIT";
+const BUILD_PROFILE_ENV_VAR: &str = "WEECHESS_BUILD_PROFILE";
 
 #[derive(Debug)]
 enum BuildError {
@@ -15,7 +17,7 @@ enum BuildError {
     Serialization(ciborium::ser::Error<std::io::Error>),
 }
 
-fn generate_book_data() -> Result<(), BuildError> {
+fn generate_book_data(seed: u64) -> Result<(), BuildError> {
     let book_dir: std::path::PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
         .parent()
         .unwrap()
@@ -25,35 +27,17 @@ fn generate_book_data() -> Result<(), BuildError> {
 
     let mut book: Book = Book::new();
 
-    let hasher = weechess_core::ZobristHasher::with(&mut {
-        // NOTE: this rng implementation and seed needs to match what we use in
-        // the engine when initializing the book lookup table.
-        let seed = rand::thread_rng().next_u64();
-        println!("cargo:rustc-env={}={}", BOOK_SEED_ENV_VAR, seed);
-        ChaCha8Rng::seed_from_u64(seed)
-    });
+    // NOTE: this rng implementation needs to match what we use in
+    // the engine when initializing the book lookup table.
+    let hasher = weechess_core::ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(seed));
 
     for entry in fs::read_dir(book_dir).unwrap() {
         let entry = entry.map_err(|e| BuildError::Io(e))?;
         if entry.file_type().unwrap().is_file() {
             println!("cargo:rerun-if-changed={}", entry.path().display());
             let book_contents = fs::read_to_string(entry.path()).map_err(|e| BuildError::Io(e))?;
-            book = book_contents
-                .trim()
-                .split("\n\n")
-                .filter(|c| c.starts_with("1."))
-                .try_fold(book, |mut book, movetext| {
-                    let moves = BookParser::parse_movetext(movetext, &hasher)
-                        .take(BOOK_DEPTH)
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(BuildError::BookParsing)?;
-
-                    for (hash, mov) in moves.into_iter() {
-                        book.append(hash, &[mov]);
-                    }
-
-                    Ok(book)
-                })?;
+            book.append_games(&book_contents, &hasher, BOOK_DEPTH)
+                .map_err(BuildError::BookParsing)?;
         }
     }
 
@@ -70,6 +54,37 @@ fn generate_book_data() -> Result<(), BuildError> {
     Ok(())
 }
 
+fn record_build_info() {
+    let profile = std::env::var("PROFILE").unwrap_or_else(|_| String::from("unknown"));
+    println!("cargo:rustc-env={}={}", BUILD_PROFILE_ENV_VAR, profile);
+
+    // Builds from a source archive won't have a commit to report
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env={}={}", GIT_COMMIT_ENV_VAR, commit.trim());
+
+        let git_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../.git");
+        for path in ["HEAD", "refs/heads"] {
+            println!("cargo:rerun-if-changed={}", git_dir.join(path).display());
+        }
+    }
+}
+
 fn main() {
-    generate_book_data().unwrap();
+    // Books loaded at runtime still need a hasher that matches the default one
+    let seed = rand::thread_rng().next_u64();
+    println!("cargo:rustc-env={}={}", BOOK_SEED_ENV_VAR, seed);
+
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_BOOK").is_some() {
+        generate_book_data(seed).unwrap();
+    }
+
+    record_build_info();
 }
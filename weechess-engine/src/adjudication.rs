@@ -0,0 +1,246 @@
+use std::str::FromStr;
+
+use weechess_core::Color;
+
+use crate::eval::Evaluation;
+
+/// A score that has to hold for a number of moves in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreRule {
+    /// How far from even the score is allowed to be, in centipawns
+    pub threshold: i32,
+    pub moves: usize,
+}
+
+impl FromStr for ScoreRule {
+    type Err = String;
+
+    /// Parses a rule written as `threshold/moves` (ex. `600/4`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, moves) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected `threshold/moves`, found `{}`", s))?;
+
+        let threshold: i32 = threshold
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid threshold: {}", threshold))?;
+
+        let moves: usize = moves
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid number of moves: {}", moves))?;
+
+        if threshold < 0 || moves == 0 {
+            return Err(format!("the threshold and moves must be positive: {}", s));
+        }
+
+        Ok(Self { threshold, moves })
+    }
+}
+
+/// When to stop a game early instead of playing it out, which saves time in
+/// matches where most games are decided (or dead drawn) long before they end
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdjudicationPolicy {
+    /// Resign once a side's own score has been at or below minus the threshold for
+    /// this many of its moves in a row
+    pub resign: Option<ScoreRule>,
+
+    /// Offer (or accept) a draw once both sides' scores have been within the
+    /// threshold of even for this many moves in a row
+    pub draw: Option<ScoreRule>,
+
+    /// Call the game a draw once this many full moves have been played
+    pub max_moves: Option<usize>,
+}
+
+impl FromStr for AdjudicationPolicy {
+    type Err = String;
+
+    /// Parses comma separated `key=value` pairs (ex. `resign=600/4,draw=10/8,max-moves=200`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = AdjudicationPolicy::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, found `{}`", pair))?;
+
+            match key.trim() {
+                "resign" => policy.resign = Some(value.parse()?),
+                "draw" => policy.draw = Some(value.parse()?),
+                "max-moves" => {
+                    let moves = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid value for {}: {}", key, value))?;
+
+                    policy.max_moves = Some(moves);
+                }
+                key => return Err(format!("unknown adjudication key: {}", key)),
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+/// How a game was ended early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjudication {
+    /// The given side resigned
+    Resign(Color),
+    Draw,
+    MoveLimit,
+}
+
+/// Follows the scores of a game as it's played, deciding when it's time to end it
+#[derive(Debug, Clone)]
+pub struct Adjudicator {
+    policy: AdjudicationPolicy,
+    losing_streaks: [usize; 2],
+    drawn_streak: usize,
+    plies: usize,
+}
+
+impl Adjudicator {
+    pub fn new(policy: AdjudicationPolicy) -> Self {
+        Self {
+            policy,
+            losing_streaks: [0; 2],
+            drawn_streak: 0,
+            plies: 0,
+        }
+    }
+
+    pub fn policy(&self) -> &AdjudicationPolicy {
+        &self.policy
+    }
+
+    /// Records a move played by `color`, along with the score its engine gave the
+    /// position from its own perspective, returning how to end the game if it's
+    /// time to. Resigning takes priority over a draw, and a draw over the move limit.
+    pub fn record(&mut self, color: Color, evaluation: Evaluation) -> Option<Adjudication> {
+        let score = i32::from(evaluation);
+        let side = match color {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+
+        self.plies += 1;
+
+        self.losing_streaks[side] = match self.policy.resign {
+            Some(rule) if score <= -rule.threshold => self.losing_streaks[side] + 1,
+            _ => 0,
+        };
+
+        self.drawn_streak = match self.policy.draw {
+            Some(rule) if score.abs() <= rule.threshold => self.drawn_streak + 1,
+            _ => 0,
+        };
+
+        if let Some(rule) = self.policy.resign {
+            if self.losing_streaks[side] >= rule.moves {
+                return Some(Adjudication::Resign(color));
+            }
+        }
+
+        // Both sides have to agree, so every move of each of them counts
+        if let Some(rule) = self.policy.draw {
+            if self.drawn_streak >= rule.moves * 2 {
+                return Some(Adjudication::Draw);
+            }
+        }
+
+        match self.policy.max_moves {
+            Some(max_moves) if self.plies >= max_moves * 2 => Some(Adjudication::MoveLimit),
+            _ => None,
+        }
+    }
+
+    /// Starts following a new game with the same policy
+    pub fn reset(&mut self) {
+        *self = Self::new(self.policy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        let policy: AdjudicationPolicy = "resign=600/4, draw=10/8,max-moves=200".parse().unwrap();
+        assert_eq!(
+            policy,
+            AdjudicationPolicy {
+                resign: Some(ScoreRule {
+                    threshold: 600,
+                    moves: 4
+                }),
+                draw: Some(ScoreRule {
+                    threshold: 10,
+                    moves: 8
+                }),
+                max_moves: Some(200),
+            }
+        );
+
+        assert!("resign=600".parse::<AdjudicationPolicy>().is_err());
+        assert!("resign=600/0".parse::<AdjudicationPolicy>().is_err());
+        assert!("timeout=5".parse::<AdjudicationPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_adjudication() {
+        let policy: AdjudicationPolicy = "resign=500/2,draw=20/2,max-moves=10".parse().unwrap();
+        let mut adjudicator = Adjudicator::new(policy);
+
+        // Black has to be losing on two of its own moves in a row
+        assert_eq!(
+            adjudicator.record(Color::White, Evaluation::from(600)),
+            None
+        );
+        assert_eq!(
+            adjudicator.record(Color::Black, Evaluation::from(-600)),
+            None
+        );
+        assert_eq!(
+            adjudicator.record(Color::White, Evaluation::from(650)),
+            None
+        );
+        assert_eq!(
+            adjudicator.record(Color::Black, Evaluation::from(-650)),
+            Some(Adjudication::Resign(Color::Black))
+        );
+
+        // A draw needs two moves from each side near even, and one
+        // lopsided score starts the count over
+        adjudicator.reset();
+        assert_eq!(adjudicator.record(Color::White, Evaluation::from(10)), None);
+        assert_eq!(adjudicator.record(Color::Black, Evaluation::from(50)), None);
+        assert_eq!(adjudicator.record(Color::White, Evaluation::from(10)), None);
+        assert_eq!(adjudicator.record(Color::Black, Evaluation::from(-5)), None);
+        assert_eq!(adjudicator.record(Color::White, Evaluation::from(0)), None);
+        assert_eq!(
+            adjudicator.record(Color::Black, Evaluation::from(15)),
+            Some(Adjudication::Draw)
+        );
+
+        adjudicator.reset();
+        let adjudications: Vec<_> = (0..20)
+            .map(|ply| {
+                let color = if ply % 2 == 0 {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+
+                adjudicator.record(color, Evaluation::from(100))
+            })
+            .collect();
+
+        assert!(adjudications[..19].iter().all(Option::is_none));
+        assert_eq!(adjudications[19], Some(Adjudication::MoveLimit));
+    }
+}
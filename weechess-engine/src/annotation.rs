@@ -0,0 +1,160 @@
+use weechess_core::{pgn::Nag, GamePhase};
+
+use crate::eval::Evaluation;
+
+/// Evaluations are clamped to this before comparing them, so that the
+/// bonus for faster mates doesn't count as a loss
+pub const MAX_SWING: i32 = 1000;
+
+pub fn clamp(evaluation: Evaluation) -> i32 {
+    i32::from(evaluation).clamp(-MAX_SWING, MAX_SWING)
+}
+
+/// Losses (in centipawns) past which a move is marked as dubious, a mistake, or a blunder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NagThresholds {
+    pub dubious: i32,
+    pub mistake: i32,
+    pub blunder: i32,
+}
+
+impl NagThresholds {
+    pub const MIDDLEGAME: Self = Self {
+        dubious: 50,
+        mistake: 100,
+        blunder: 300,
+    };
+
+    /// With fewer pieces left, a pawn or two is often the difference between a win and a draw
+    pub const ENDGAME: Self = Self {
+        dubious: 40,
+        mistake: 80,
+        blunder: 200,
+    };
+
+    /// Judges a move by how much worse it is than the best move, with every evaluation
+    /// from the perspective of the side making it. `played_eval` and `best_eval` are
+    /// after the played and the best move, and `prev_eval` is from before the opponent's
+    /// last move, so that finding the best move right after they slipped up is noticed.
+    pub fn annotate(
+        &self,
+        prev_eval: Evaluation,
+        played_eval: Evaluation,
+        best_eval: Evaluation,
+    ) -> Option<Nag> {
+        let loss = clamp(best_eval) - clamp(played_eval);
+        if loss <= 0 {
+            let gift = clamp(best_eval) - clamp(prev_eval);
+            (gift >= self.mistake).then_some(Nag::Good)
+        } else if loss >= self.blunder {
+            Some(Nag::Blunder)
+        } else if loss >= self.mistake {
+            Some(Nag::Mistake)
+        } else if loss >= self.dubious {
+            Some(Nag::Dubious)
+        } else {
+            None
+        }
+    }
+}
+
+/// Picks the thresholds to judge moves by depending on the phase of the game
+/// they're played in, so that every tool annotating moves agrees on them
+#[derive(Debug, Clone)]
+pub struct Annotator {
+    opening: NagThresholds,
+    middlegame: NagThresholds,
+    endgame: NagThresholds,
+}
+
+impl Annotator {
+    pub fn with_thresholds(mut self, phase: GamePhase, thresholds: NagThresholds) -> Self {
+        match phase {
+            GamePhase::Opening => self.opening = thresholds,
+            GamePhase::MiddleGame => self.middlegame = thresholds,
+            GamePhase::EndGame => self.endgame = thresholds,
+        }
+
+        self
+    }
+
+    pub fn thresholds(&self, phase: GamePhase) -> &NagThresholds {
+        match phase {
+            GamePhase::Opening => &self.opening,
+            GamePhase::MiddleGame => &self.middlegame,
+            GamePhase::EndGame => &self.endgame,
+        }
+    }
+
+    /// See `NagThresholds::annotate`, where the phase is the one the move is played in
+    pub fn annotate(
+        &self,
+        phase: GamePhase,
+        prev_eval: Evaluation,
+        played_eval: Evaluation,
+        best_eval: Evaluation,
+    ) -> Option<Nag> {
+        self.thresholds(phase)
+            .annotate(prev_eval, played_eval, best_eval)
+    }
+}
+
+impl Default for Annotator {
+    fn default() -> Self {
+        Self {
+            opening: NagThresholds::MIDDLEGAME,
+            middlegame: NagThresholds::MIDDLEGAME,
+            endgame: NagThresholds::ENDGAME,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cp(centipawns: i32) -> Evaluation {
+        Evaluation::from(centipawns)
+    }
+
+    #[test]
+    fn test_annotate() {
+        let annotator = Annotator::default();
+        let nag =
+            |phase, prev, played, best| annotator.annotate(phase, cp(prev), cp(played), cp(best));
+
+        assert_eq!(nag(GamePhase::MiddleGame, 0, 0, 20), None);
+        assert_eq!(nag(GamePhase::MiddleGame, 0, -40, 20), Some(Nag::Dubious));
+        assert_eq!(nag(GamePhase::MiddleGame, 0, -100, 20), Some(Nag::Mistake));
+        assert_eq!(nag(GamePhase::MiddleGame, 0, -300, 20), Some(Nag::Blunder));
+
+        // The same loss counts for more in an endgame
+        assert_eq!(nag(GamePhase::EndGame, 0, -200, 20), Some(Nag::Blunder));
+
+        // Punishing the opponent's mistake, versus just playing on
+        assert_eq!(nag(GamePhase::MiddleGame, 0, 300, 300), Some(Nag::Good));
+        assert_eq!(nag(GamePhase::MiddleGame, 250, 300, 300), None);
+
+        // Winning slower doesn't count as a loss
+        let mate = Evaluation::mate_in_ply(1);
+        let slower_mate = Evaluation::mate_in_ply(5);
+        assert_eq!(
+            annotator.annotate(GamePhase::EndGame, mate, slower_mate, mate),
+            None
+        );
+
+        let strict = annotator.with_thresholds(
+            GamePhase::EndGame,
+            NagThresholds {
+                dubious: 10,
+                mistake: 20,
+                blunder: 30,
+            },
+        );
+
+        assert_eq!(
+            strict.annotate(GamePhase::EndGame, cp(0), cp(0), cp(30)),
+            Some(Nag::Blunder)
+        );
+    }
+}
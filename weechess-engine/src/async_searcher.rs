@@ -0,0 +1,158 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::mpsc,
+    task::{Context, Poll},
+    thread,
+};
+
+use futures_core::Stream;
+use tokio::sync::{mpsc as async_mpsc, oneshot};
+use weechess_core::State;
+
+use crate::{
+    eval::Evaluator,
+    searcher::{ControlEvent, SearchArtifact, SearchOptions, SearchResult, Searcher, StatusEvent},
+};
+
+/// The search thread panicked before it could produce a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchFailed;
+
+impl std::fmt::Display for SearchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the search failed without a result")
+    }
+}
+
+impl std::error::Error for SearchFailed {}
+
+/// Runs searches for async code. The search itself still runs on its own threads, and
+/// a forwarding thread hands its events over to async channels, so waiting on a search
+/// never blocks the executor. Nothing here depends on a particular runtime.
+pub struct AsyncSearcher {
+    searcher: Searcher,
+}
+
+impl AsyncSearcher {
+    pub fn new() -> Self {
+        Self {
+            searcher: Searcher::new(),
+        }
+    }
+
+    /// Starts searching the position, see [`Searcher::analyze`]
+    pub fn analyze(
+        &self,
+        state: State,
+        rng_seed: u64,
+        evaluator: Evaluator,
+        options: SearchOptions,
+        previous_artifact: Option<SearchArtifact>,
+    ) -> AsyncSearch {
+        let (search_handle, control, receiver) =
+            self.searcher
+                .analyze(state, rng_seed, evaluator, options, previous_artifact);
+
+        let (events_tx, events_rx) = async_mpsc::unbounded_channel();
+        let (result_tx, result_rx) = oneshot::channel();
+        thread::spawn(move || {
+            // The events stop once the search is over. Nobody listening for them (or the
+            // result) anymore is fine, the search still runs until it's stopped.
+            while let Ok(event) = receiver.recv() {
+                _ = events_tx.send(event);
+            }
+
+            _ = result_tx.send(search_handle.join().map_err(|_| SearchFailed));
+        });
+
+        AsyncSearch {
+            events: events_rx,
+            result: SearchFuture(result_rx),
+            control,
+        }
+    }
+}
+
+/// A search in progress. It's a stream of the search's status events, which ends when
+/// the search does, and [`AsyncSearch::result`] waits for what the search found.
+pub struct AsyncSearch {
+    events: async_mpsc::UnboundedReceiver<StatusEvent>,
+    result: SearchFuture,
+    control: mpsc::Sender<ControlEvent>,
+}
+
+impl AsyncSearch {
+    /// Tells the search to stop, after which it still sends its final events and result
+    pub fn stop(&self) {
+        // This can error if the search already finished. That's ok
+        _ = self.control.send(ControlEvent::Stop);
+    }
+
+    /// Tells the search that its time is up, which is like stopping it
+    /// except for the reason given in the result
+    pub fn time_up(&self) {
+        _ = self.control.send(ControlEvent::TimeUp);
+    }
+
+    /// A future that resolves once the search is over, skipping any events not read yet
+    pub fn result(self) -> SearchFuture {
+        self.result
+    }
+}
+
+impl Stream for AsyncSearch {
+    type Item = StatusEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// The artifact and result of a search, once it's over
+pub struct SearchFuture(oneshot::Receiver<Result<(SearchArtifact, SearchResult), SearchFailed>>);
+
+impl Future for SearchFuture {
+    type Output = Result<(SearchArtifact, SearchResult), SearchFailed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(SearchFailed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_search() {
+        let searcher = AsyncSearcher::new();
+        let mut search = searcher.analyze(
+            State::default(),
+            0,
+            Evaluator::default(),
+            SearchOptions {
+                max_depth: Some(3),
+                ..Default::default()
+            },
+            None,
+        );
+
+        let mut completed_depths = vec![];
+        while let Some(event) = poll_fn(|cx| Pin::new(&mut search).poll_next(cx)).await {
+            if let StatusEvent::IterationComplete { depth, .. } = event {
+                completed_depths.push(depth);
+            }
+        }
+
+        assert_eq!(completed_depths, vec![1, 2, 3]);
+
+        let (_, result) = search.result().await.unwrap();
+        assert_eq!(result.depth, 3);
+        assert!(result.best_move().is_some());
+    }
+}
@@ -1,43 +1,647 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
 
-use rand::SeedableRng;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use weechess_core::{Book, State, ZobristHasher};
+use serde::{Deserialize, Serialize};
+use weechess_core::{
+    notation::{into_notation, Fen},
+    pgn::Pgn,
+    Book, BookParseError, Color, GameStatus, Move, MoveGenerator, State, ZobristHasher,
+};
 
+use crate::{
+    annotation,
+    eval::{Evaluation, Evaluator},
+    searcher::{self, SearchOptions, Searcher},
+};
+
+// How often a book move check asks whether it's been stopped
+const BOOK_CHECK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    Parse(BookParseError),
+    IncompatibleHasher,
+    Decode(ciborium::de::Error<std::io::Error>),
+    Encode(ciborium::ser::Error<std::io::Error>),
+    Unavailable,
+}
+
+impl std::fmt::Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "unable to read book: {}", e),
+            BookError::Parse(e) => write!(f, "unable to parse book: {}", e),
+            BookError::IncompatibleHasher => write!(f, "books use different hashers"),
+            BookError::Decode(e) => write!(f, "unable to decode learned weights: {}", e),
+            BookError::Encode(e) => write!(f, "unable to encode learned weights: {}", e),
+            BookError::Unavailable => write!(f, "no default book is available in this build"),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// A prioritized stack of opening books. Lookups are answered by the
+/// first book that knows about the position.
 pub struct OpeningBook {
-    book: Book,
+    books: Vec<Book>,
     hasher: ZobristHasher,
 }
 
 impl OpeningBook {
-    pub fn try_default() -> Result<Self, ()> {
-        let hash_seed = u64::from_str_radix(env!("WEECHESS_BOOK_SEED"), 10).map_err(|_| ())?;
-        let hasher = ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(hash_seed));
+    /// The number of plies into each game that are added to the book
+    /// when loading book files at runtime.
+    pub const DEFAULT_DEPTH: usize = 10;
 
+    /// Builds with the `runtime-book` feature (and without `embedded-book`) load the
+    /// default book from the file or directory of games this variable points to
+    pub const RUNTIME_BOOK_ENV_VAR: &'static str = "WEECHESS_BOOK";
+
+    /// The book compiled into the binary
+    #[cfg(feature = "embedded-book")]
+    pub fn try_default() -> Result<Self, BookError> {
         let bytes = include_bytes!(concat!(env!("OUT_DIR"), "/", "book_data.bin"));
-        let book = ciborium::de::from_reader(&bytes[..]).map_err(|_| ())?;
-        Ok(Self { book, hasher })
+        let mut book = Self::empty();
+        book.books
+            .push(ciborium::de::from_reader(&bytes[..]).map_err(BookError::Decode)?);
+
+        Ok(book)
+    }
+
+    /// The book found at the path in the `WEECHESS_BOOK` environment variable
+    #[cfg(all(feature = "runtime-book", not(feature = "embedded-book")))]
+    pub fn try_default() -> Result<Self, BookError> {
+        let path = std::env::var_os(Self::RUNTIME_BOOK_ENV_VAR);
+        Self::runtime_from(path.as_deref().map(Path::new))
+    }
+
+    /// The default book of a `runtime-book` build, loaded from `path` if there is one
+    #[cfg(all(feature = "runtime-book", not(feature = "embedded-book")))]
+    fn runtime_from(path: Option<&Path>) -> Result<Self, BookError> {
+        Self::load(path.ok_or(BookError::Unavailable)?, Self::DEFAULT_DEPTH)
+    }
+
+    #[cfg(not(any(feature = "embedded-book", feature = "runtime-book")))]
+    pub fn try_default() -> Result<Self, BookError> {
+        Err(BookError::Unavailable)
+    }
+
+    /// An empty book that is compatible with the built-in book
+    pub fn empty() -> Self {
+        Self {
+            books: vec![],
+            hasher: Self::default_hasher().unwrap(),
+        }
+    }
+
+    /// Loads a book from a file of PGN games (or a directory of them), only
+    /// including moves up to `max_depth` plies into each game.
+    pub fn load<P: AsRef<Path>>(path: P, max_depth: usize) -> Result<Self, BookError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            let contents = std::fs::read_to_string(path).map_err(BookError::Io)?;
+            return Self::parse(&contents, max_depth);
+        }
+
+        let mut book = Self::empty();
+        let mut games = Book::new();
+        for entry in std::fs::read_dir(path).map_err(BookError::Io)? {
+            let path = entry.map_err(BookError::Io)?.path();
+            if path.is_file() {
+                let contents = std::fs::read_to_string(path).map_err(BookError::Io)?;
+                games
+                    .append_games(&contents, &book.hasher, max_depth)
+                    .map_err(BookError::Parse)?;
+            }
+        }
+
+        book.books.push(games);
+        Ok(book)
+    }
+
+    pub fn parse(contents: &str, max_depth: usize) -> Result<Self, BookError> {
+        let mut book = Self::empty();
+        let mut games = Book::new();
+        games
+            .append_games(contents, &book.hasher, max_depth)
+            .map_err(BookError::Parse)?;
+
+        book.books.push(games);
+        Ok(book)
+    }
+
+    /// Adds the books from `other` behind the books already in this one, so
+    /// they are only consulted for positions this book doesn't cover.
+    pub fn merge(&mut self, other: OpeningBook) -> Result<(), BookError> {
+        if self.hasher != other.hasher {
+            return Err(BookError::IncompatibleHasher);
+        }
+
+        self.books.extend(other.books);
+        Ok(())
     }
 
     pub fn lookup(&self, state: &State) -> Option<&HashSet<weechess_core::Move>> {
         let hash = self.hasher.hash(state);
-        self.book
-            .find(hash)
-            .and_then(|moves| if moves.len() > 0 { Some(moves) } else { None })
+        self.books
+            .iter()
+            .filter_map(|book| book.find(hash))
+            .find(|moves| !moves.is_empty())
+    }
+
+    /// The moves at the start of a game that came from this book, each with the position
+    /// it was played from, up to the first move the book doesn't know
+    pub fn book_moves(&self, pgn: &Pgn) -> Vec<(State, Move)> {
+        let mut moves = vec![];
+        let mut state = pgn.initial_state.clone();
+        for mv in pgn.moves.iter().map(|m| m.mv) {
+            if !self.lookup(&state).is_some_and(|book| book.contains(&mv)) {
+                break;
+            }
+
+            let Ok(next) = State::by_performing_move(&state, &mv) else {
+                break;
+            };
+
+            moves.push((state, mv));
+            state = next;
+        }
+
+        moves
+    }
+
+    /// Looks up the book moves for a position, leaving out any that aren't legal in it.
+    /// A corrupt book or a hash collision could otherwise have us play an illegal move,
+    /// so the rejected moves are passed to `on_illegal` to be reported.
+    pub fn lookup_legal<F>(&self, state: &State, mut on_illegal: F) -> Vec<Move>
+    where
+        F: FnMut(&Move),
+    {
+        let Some(moves) = self.lookup(state) else {
+            return vec![];
+        };
+
+        moves
+            .iter()
+            .filter(|mv| {
+                let is_legal = state.is_legal(mv);
+                if !is_legal {
+                    on_illegal(mv);
+                }
+
+                is_legal
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Leaves out the book moves that lead back to a position from earlier in the game.
+    /// The book doesn't know how the game got here, so it can't tell when one of its
+    /// moves would walk into a repetition.
+    pub fn without_repetitions(
+        &self,
+        state: &State,
+        moves: Vec<Move>,
+        history: &[State],
+    ) -> Vec<Move> {
+        let seen: HashSet<_> = history.iter().map(|s| self.hasher.hash(s)).collect();
+        moves
+            .into_iter()
+            .filter(|mv| {
+                State::by_performing_move(state, mv)
+                    .is_ok_and(|next| !seen.contains(&self.hasher.hash(&next)))
+            })
+            .collect()
+    }
+
+    fn default_hasher() -> Result<ZobristHasher, ()> {
+        // NOTE: this needs to match the hasher used by the build script
+        let hash_seed: u64 = env!("WEECHESS_BOOK_SEED").parse().map_err(|_| ())?;
+        let mut rng = ChaCha8Rng::seed_from_u64(hash_seed);
+        Ok(ZobristHasher::with(&mut rng))
+    }
+}
+
+/// What a quick search thinks of a book move, compared to the move it would rather play.
+/// Third-party books can have unsound gambits in them, and this catches the worst of them.
+#[derive(Debug, Clone, Copy)]
+pub struct BookMoveCheck {
+    pub book_evaluation: Evaluation,
+    pub best_evaluation: Evaluation,
+    pub best_move: Option<Move>,
+}
+
+impl BookMoveCheck {
+    /// Searches the position and the position after the book move, splitting the
+    /// nodes between the two. Both evaluations are from the perspective of the side
+    /// to move. The searches are cut short once `is_stopped` says so, which it's asked
+    /// every so often, so a check that was stopped can't be trusted as much.
+    pub fn run(
+        state: &State,
+        mv: &Move,
+        evaluator: &Evaluator,
+        rng_seed: u64,
+        max_nodes: usize,
+        is_stopped: &mut dyn FnMut() -> bool,
+    ) -> Self {
+        let (best_evaluation, best_move) =
+            quick_search(state, evaluator, rng_seed, max_nodes / 2, is_stopped);
+        let book_evaluation = if best_move == Some(*mv) {
+            best_evaluation
+        } else {
+            match State::by_performing_move(state, mv) {
+                Ok(next) => -quick_search(&next, evaluator, rng_seed, max_nodes / 2, is_stopped).0,
+                Err(..) => Evaluation::NEG_INF,
+            }
+        };
+
+        Self {
+            book_evaluation,
+            best_evaluation,
+            best_move,
+        }
+    }
+
+    /// How many centipawns worse the book move is than the best move found
+    pub fn loss(&self) -> i32 {
+        (annotation::clamp(self.best_evaluation) - annotation::clamp(self.book_evaluation)).max(0)
+    }
+
+    pub fn is_sound(&self, margin: i32) -> bool {
+        self.loss() <= margin
+    }
+}
+
+// Searches a position until the nodes run out, returning the evaluation from the
+// perspective of the side to move and the best move, if the game isn't over
+fn quick_search(
+    state: &State,
+    evaluator: &Evaluator,
+    rng_seed: u64,
+    max_nodes: usize,
+    is_stopped: &mut dyn FnMut() -> bool,
+) -> (Evaluation, Option<Move>) {
+    if MoveGenerator::compute_legal_moves(state).is_empty() {
+        let evaluation = if state.is_check() {
+            -Evaluation::mate_in_ply(0)
+        } else {
+            Evaluation::EVEN
+        };
+
+        return (evaluation, None);
+    }
+
+    // A single thread always finds the same thing with the same number of nodes
+    let options = SearchOptions {
+        max_threads: Some(1),
+        max_nodes: Some(max_nodes),
+        ..Default::default()
+    };
+
+    let (search_handle, control, receiver) =
+        Searcher::new().analyze(state.clone(), rng_seed, evaluator.clone(), options, None);
+
+    // The events stop once the search runs out of nodes
+    while let Ok(..) | Err(mpsc::RecvTimeoutError::Timeout) =
+        receiver.recv_timeout(BOOK_CHECK_POLL_INTERVAL)
+    {
+        if is_stopped() {
+            _ = control.send(searcher::ControlEvent::Stop);
+            break;
+        }
+    }
+
+    match search_handle.join() {
+        Ok((_, result)) if !result.line.is_empty() => (result.evaluation, result.best_move()),
+        _ => (Evaluation::EVEN, None),
+    }
+}
+
+/// Weights for book moves, learned from the results of the games they were played in.
+/// Moves that keep winning get picked more often, and moves that keep losing less so.
+///
+/// Positions are keyed by their FEN without the move clocks rather than by their hash,
+/// since every build of the engine hashes the built-in book with a different seed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookLearning {
+    weights: BTreeMap<String, HashMap<Move, f32>>,
+
+    #[serde(skip)]
+    frozen: bool,
+}
+
+impl BookLearning {
+    /// The weight of a book move that hasn't been played in any finished game yet
+    pub const DEFAULT_WEIGHT: f32 = 1.0;
+
+    /// How much the weight of a move grows after a win
+    const REINFORCEMENT: f32 = 1.25;
+
+    /// How much the weight of a move shrinks after a loss
+    const DECAY: f32 = 0.5;
+
+    /// Moves are never decayed out of the book entirely, in case they were just unlucky
+    const WEIGHT_RANGE: (f32, f32) = (0.01, 100.0);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the weights from a file, starting from scratch if it doesn't exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BookError> {
+        match std::fs::File::open(path) {
+            Ok(file) => {
+                ciborium::from_reader(std::io::BufReader::new(file)).map_err(BookError::Decode)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(BookError::Io(e)),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BookError> {
+        let file = std::fs::File::create(path).map_err(BookError::Io)?;
+        ciborium::into_writer(self, std::io::BufWriter::new(file)).map_err(BookError::Encode)
+    }
+
+    /// Keeps using the learned weights, but stops updating them
+    pub fn with_frozen(self, frozen: bool) -> Self {
+        Self { frozen, ..self }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn weight(&self, state: &State, mv: &Move) -> f32 {
+        self.weights
+            .get(&Self::position_key(state))
+            .and_then(|weights| weights.get(mv))
+            .copied()
+            .unwrap_or(Self::DEFAULT_WEIGHT)
+    }
+
+    /// Picks one of the book moves at random, in proportion to their weights
+    pub fn choose<R: Rng>(&self, state: &State, moves: &[Move], rng: &mut R) -> Option<Move> {
+        let weights = moves.iter().map(|mv| self.weight(state, mv));
+        let distribution = WeightedIndex::new(weights).ok()?;
+        Some(moves[distribution.sample(rng)])
+    }
+
+    /// Updates the weights of the book moves played in a game with its result. Each move
+    /// is credited to the side that played it, and drawn or unfinished games change nothing.
+    /// Returns whether any weights were updated.
+    pub fn learn(&mut self, moves: &[(State, Move)], result: GameStatus) -> bool {
+        let GameStatus::Won(winner) = result else {
+            return false;
+        };
+
+        if self.frozen || moves.is_empty() {
+            return false;
+        }
+
+        for (state, mv) in moves {
+            let factor = if mv.color() == winner {
+                Self::REINFORCEMENT
+            } else {
+                Self::DECAY
+            };
+
+            let (min, max) = Self::WEIGHT_RANGE;
+            let weight = self
+                .weights
+                .entry(Self::position_key(state))
+                .or_default()
+                .entry(*mv)
+                .or_insert(Self::DEFAULT_WEIGHT);
+
+            *weight = (*weight * factor).clamp(min, max);
+        }
+
+        true
+    }
+
+    fn position_key(state: &State) -> String {
+        let fen = into_notation::<_, Fen>(state).to_string();
+        fen.split_ascii_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Parses a game result the way it's written in PGN files (ex. `1-0`)
+pub fn parse_result(result: &str) -> Option<GameStatus> {
+    match result {
+        "1-0" => Some(GameStatus::Won(Color::White)),
+        "0-1" => Some(GameStatus::Won(Color::Black)),
+        "1/2-1/2" => Some(GameStatus::Drawn),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use weechess_core::State;
+    use weechess_core::{
+        notation::{try_from_notation, San},
+        MoveQuery, State,
+    };
 
     use super::*;
 
     #[test]
+    #[cfg(feature = "embedded-book")]
     fn test_book() {
         let book = OpeningBook::try_default().unwrap();
         let state = State::default();
         let moves = book.lookup(&state).unwrap();
         assert!(moves.len() > 0);
     }
+
+    #[test]
+    #[cfg(feature = "embedded-book")]
+    fn test_merged_book_priority() {
+        let mut book = OpeningBook::parse("1. h4 h5 2. g4 1-0", 2).unwrap();
+        book.merge(OpeningBook::try_default().unwrap()).unwrap();
+
+        // The first book takes priority over the default book
+        let state = State::default();
+        let moves = book.lookup(&state).unwrap();
+        assert_eq!(moves.len(), 1);
+
+        // The depth limit leaves 2. g4 out of the first book
+        let query: Vec<MoveQuery> = ["h4", "h5"]
+            .iter()
+            .map(|m| try_from_notation::<_, San>(m).unwrap())
+            .collect();
+
+        let state = State::by_performing_moves(&state, &query).unwrap();
+        assert!(book.lookup(&state).is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "runtime-book", not(feature = "embedded-book")))]
+    fn test_runtime_book() {
+        assert!(matches!(
+            OpeningBook::runtime_from(None),
+            Err(BookError::Unavailable)
+        ));
+
+        let path = std::env::temp_dir().join(format!("weechess-book-{}.pgn", std::process::id()));
+        std::fs::write(&path, "1. d4 d5 2. c4 *").unwrap();
+
+        let book = OpeningBook::runtime_from(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        let moves = book.unwrap().lookup(&State::default()).unwrap().clone();
+        assert_eq!(moves.len(), 1);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "embedded-book", feature = "runtime-book")))]
+    fn test_no_default_book() {
+        assert!(matches!(
+            OpeningBook::try_default(),
+            Err(BookError::Unavailable)
+        ));
+
+        // Books can still be parsed without a default one
+        let book = OpeningBook::parse("1. d4 d5 *", 2).unwrap();
+        assert!(book.lookup(&State::default()).is_some());
+    }
+
+    #[test]
+    fn test_lookup_legal() {
+        let book = OpeningBook::parse("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O *", 10).unwrap();
+        let play = |moves: &[&str]| {
+            let query: Vec<MoveQuery> = moves
+                .iter()
+                .map(|m| try_from_notation::<_, San>(m).unwrap())
+                .collect();
+
+            State::by_performing_moves(&State::default(), &query).unwrap()
+        };
+
+        let state = play(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+        let moves = book.lookup_legal(&state, |_| panic!("unexpected illegal move"));
+        assert_eq!(moves.len(), 1);
+
+        // The pieces are all back where they were, but the kings have given up
+        // their castling rights, so the book move can no longer be played
+        let state = play(&[
+            "e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "Kf1", "Kf8", "Ke1", "Ke8",
+        ]);
+
+        let mut illegal_moves = vec![];
+        let moves = book.lookup_legal(&state, |mv| illegal_moves.push(*mv));
+        assert!(moves.is_empty());
+        assert_eq!(illegal_moves.len(), 1);
+    }
+
+    #[test]
+    fn test_without_repetitions() {
+        let book = OpeningBook::empty();
+        let mut history = vec![State::default()];
+        for san in ["Nf3", "Nf6", "Ng1"] {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            let next = history.last().unwrap().find_legal(&query).unwrap().1;
+            history.push(next);
+        }
+
+        // Going back to g8 repeats the starting position, but e6 is new
+        let state = history.pop().unwrap();
+        let play = |san: &str| {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            state.find_legal(&query).unwrap().0
+        };
+
+        let (ng8, e6) = (play("Ng8"), play("e6"));
+        let moves = book.without_repetitions(&state, vec![ng8, e6], &history);
+        assert_eq!(moves, vec![e6]);
+    }
+
+    #[test]
+    fn test_book_move_check() {
+        let state = try_from_notation::<_, Fen>("4k3/8/8/3r4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let play = |san: &str| {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            state.find_legal(&query).unwrap().0
+        };
+
+        let evaluator = Evaluator::default();
+        let check = |san: &str| {
+            BookMoveCheck::run(&state, &play(san), &evaluator, 0, 20_000, &mut || false)
+        };
+
+        // Putting the queen where the rook can take it is a blunder
+        let blunder = check("Qd4");
+        assert!(!blunder.is_sound(100));
+        assert_eq!(blunder.best_move, Some(play("Qxd5")));
+
+        let capture = check("Qxd5");
+        assert!(capture.is_sound(100));
+        assert_eq!(capture.loss(), 0);
+
+        // Stopping the check straight away still answers, just without searching much
+        let stopped = BookMoveCheck::run(&state, &play("Qd4"), &evaluator, 0, 20_000, &mut || true);
+        assert!(stopped.best_move.is_some());
+    }
+
+    #[test]
+    fn test_book_learning() {
+        let state = State::default();
+        let play = |san: &str| {
+            let query = try_from_notation::<_, San>(san).unwrap();
+            state.find_legal(&query).unwrap().0
+        };
+
+        let (e4, d4) = (play("e4"), play("d4"));
+        let mut learning = BookLearning::new();
+        assert!(learning.learn(&[(state.clone(), e4)], GameStatus::Won(Color::White)));
+        assert!(learning.learn(&[(state.clone(), d4)], GameStatus::Won(Color::Black)));
+        assert!(!learning.learn(&[(state.clone(), d4)], GameStatus::Drawn));
+        assert!(learning.weight(&state, &e4) > BookLearning::DEFAULT_WEIGHT);
+        assert!(learning.weight(&state, &d4) < BookLearning::DEFAULT_WEIGHT);
+
+        // The weights survive a round trip through a file
+        let mut buf = Vec::new();
+        ciborium::into_writer(&learning, &mut buf).unwrap();
+        let restored: BookLearning = ciborium::from_reader(&buf[..]).unwrap();
+        assert_eq!(restored.weight(&state, &e4), learning.weight(&state, &e4));
+
+        // A frozen book is still used, just never updated
+        let mut frozen = restored.with_frozen(true);
+        assert!(!frozen.learn(&[(state.clone(), e4)], GameStatus::Won(Color::Black)));
+        assert_eq!(frozen.weight(&state, &e4), learning.weight(&state, &e4));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let chosen = frozen.choose(&state, &[e4, d4], &mut rng);
+        assert!(chosen == Some(e4) || chosen == Some(d4));
+    }
+
+    #[test]
+    fn test_book_moves() {
+        let book = OpeningBook::parse("1. e4 e5 2. Nf3 Nc6 *", 10).unwrap();
+        let pgn = Pgn::parse("1. e4 e5 2. Nc3 Nf6 1-0").unwrap();
+
+        // The game leaves the book with its third move
+        let moves = book.book_moves(&pgn);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].0, State::default());
+        assert_eq!(
+            moves[1].0,
+            State::by_performing_move(&moves[0].0, &moves[0].1).unwrap()
+        );
+
+        let mut learning = BookLearning::new();
+        assert!(learning.learn(&moves, GameStatus::Won(Color::White)));
+        assert!(learning.weight(&moves[0].0, &moves[0].1) > BookLearning::DEFAULT_WEIGHT);
+        assert!(learning.weight(&moves[1].0, &moves[1].1) < BookLearning::DEFAULT_WEIGHT);
+    }
 }
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use weechess_core::{Hash, Move, State, ZobristHasher};
+
+use crate::eval::Evaluation;
+
+// Every search uses a differently seeded hasher, so the cache needs a
+// fixed one of its own for the hashes to mean anything across sessions
+const CACHE_HASH_SEED: u64 = 0x7765_6563_6865_7373;
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(std::io::Error),
+    Decode(ciborium::de::Error<std::io::Error>),
+    Encode(ciborium::ser::Error<std::io::Error>),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::Io(e) => write!(f, "unable to access analysis cache: {}", e),
+            CacheError::Decode(e) => write!(f, "unable to decode analysis cache: {}", e),
+            CacheError::Encode(e) => write!(f, "unable to encode analysis cache: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// The outcome of searching a position to some depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedAnalysis {
+    pub depth: u32,
+    pub evaluation: Evaluation,
+    pub best_move: Move,
+}
+
+/// A persistent record of positions that have been searched deeply, so that analyzing
+/// the same positions again (in the same or a later session) doesn't need a new search.
+///
+/// The cache is stored as an append-only file of records keyed by position hash. When
+/// a position shows up more than once, the deepest analysis of it wins. Positions are
+/// looked up along with the game that led to them, since a position that has already
+/// come up before could be drawn by repetition and so isn't worth the same.
+pub struct AnalysisCache {
+    hasher: ZobristHasher,
+    entries: HashMap<Hash, CachedAnalysis>,
+    writer: BufWriter<File>,
+    min_depth: usize,
+}
+
+impl AnalysisCache {
+    /// Searches shallower than this aren't worth remembering by default
+    pub const DEFAULT_MIN_DEPTH: usize = 8;
+
+    /// Opens the cache file at `path`, creating it if it doesn't exist yet. Only
+    /// searches that reach at least `min_depth` are added to it.
+    pub fn open<P: AsRef<Path>>(path: P, min_depth: usize) -> Result<Self, CacheError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(CacheError::Io)?;
+
+        let mut entries = HashMap::new();
+        let mut reader = BufReader::new(file.try_clone().map_err(CacheError::Io)?);
+        while !reader.fill_buf().map_err(CacheError::Io)?.is_empty() {
+            match ciborium::from_reader::<(Hash, CachedAnalysis), _>(&mut reader) {
+                Ok((hash, analysis)) => Self::insert(&mut entries, hash, analysis),
+                // The last record might have been cut short if we crashed while writing it
+                Err(ciborium::de::Error::Io(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                Err(e) => return Err(CacheError::Decode(e)),
+            }
+        }
+
+        Ok(Self {
+            hasher: ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(CACHE_HASH_SEED)),
+            entries,
+            writer: BufWriter::new(file),
+            min_depth,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Looks up the analysis of a position reached after the positions in `history`.
+    /// The hash doesn't cover everything about a position, so the move is checked
+    /// to be legal before using it.
+    pub fn probe(&self, state: &State, history: &[State]) -> Option<CachedAnalysis> {
+        self.entries
+            .get(&self.key(state, history))
+            .filter(|analysis| state.is_legal(&analysis.best_move))
+            .copied()
+    }
+
+    /// Remembers the analysis of a position reached after the positions in `history` if
+    /// it's deep enough and deeper than what's already known about it, returning whether
+    /// it was added to the cache
+    pub fn store(
+        &mut self,
+        state: &State,
+        history: &[State],
+        analysis: CachedAnalysis,
+    ) -> Result<bool, CacheError> {
+        let hash = self.key(state, history);
+        let is_deeper = self
+            .entries
+            .get(&hash)
+            .is_none_or(|existing| existing.depth < analysis.depth);
+
+        if (analysis.depth as usize) < self.min_depth || !is_deeper {
+            return Ok(false);
+        }
+
+        ciborium::into_writer(&(hash, analysis), &mut self.writer).map_err(CacheError::Encode)?;
+        self.writer.flush().map_err(CacheError::Io)?;
+        Self::insert(&mut self.entries, hash, analysis);
+        Ok(true)
+    }
+
+    /// The hash of the position, mixed with the earlier positions that could still come
+    /// up again (the ones since the last capture or pawn move). The order they came up in
+    /// doesn't matter, but how often they did does. Without any of them, it's just the
+    /// hash of the position, so that the cache works the same across games.
+    fn key(&self, state: &State, history: &[State]) -> Hash {
+        let reversible = state.clock().halfmove_clock.min(history.len());
+        history[history.len() - reversible..]
+            .iter()
+            .fold(self.hasher.hash(state), |key, previous| {
+                key.wrapping_add(self.hasher.hash(previous).rotate_left(1))
+            })
+    }
+
+    fn insert(entries: &mut HashMap<Hash, CachedAnalysis>, hash: Hash, analysis: CachedAnalysis) {
+        let entry = entries.entry(hash).or_insert(analysis);
+        if entry.depth < analysis.depth {
+            *entry = analysis;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::notation::{try_from_notation, San};
+
+    use super::*;
+
+    #[test]
+    fn test_persistence() {
+        let path = std::env::temp_dir().join(format!("weechess-cache-{}.bin", std::process::id()));
+        _ = std::fs::remove_file(&path);
+
+        let state = State::default();
+        let e4 = state
+            .find_legal(&try_from_notation::<_, San>("e4").unwrap())
+            .unwrap()
+            .0;
+
+        let analysis = |depth| CachedAnalysis {
+            depth,
+            evaluation: Evaluation::from(25),
+            best_move: e4,
+        };
+
+        {
+            let mut cache = AnalysisCache::open(&path, 4).unwrap();
+            assert_eq!(cache.probe(&state, &[]), None);
+            assert!(!cache.store(&state, &[], analysis(3)).unwrap());
+            assert!(cache.store(&state, &[], analysis(5)).unwrap());
+            assert!(cache.store(&state, &[], analysis(6)).unwrap());
+            assert!(!cache.store(&state, &[], analysis(6)).unwrap());
+        }
+
+        // The deepest analysis survives reopening the cache
+        let cache = AnalysisCache::open(&path, 4).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.probe(&state, &[]), Some(analysis(6)));
+
+        _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_repetition_history() {
+        let path =
+            std::env::temp_dir().join(format!("weechess-cache-history-{}.bin", std::process::id()));
+        _ = std::fs::remove_file(&path);
+
+        // Shuffling the knights out and back reaches the starting position again
+        let mut history = vec![];
+        let mut state = State::default();
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let next = state
+                .find_legal(&try_from_notation::<_, San>(san).unwrap())
+                .unwrap()
+                .1;
+
+            history.push(std::mem::replace(&mut state, next));
+        }
+
+        let e4 = state
+            .find_legal(&try_from_notation::<_, San>("e4").unwrap())
+            .unwrap()
+            .0;
+
+        let analysis = CachedAnalysis {
+            depth: 8,
+            evaluation: Evaluation::from(25),
+            best_move: e4,
+        };
+
+        let mut cache = AnalysisCache::open(&path, 4).unwrap();
+        assert!(cache.store(&State::default(), &[], analysis).unwrap());
+
+        // Having come up before, the position could be drawn by repetition now
+        assert_eq!(cache.probe(&state, &history), None);
+        assert!(cache.store(&state, &history, analysis).unwrap());
+        assert_eq!(cache.probe(&state, &history), Some(analysis));
+
+        // Only the positions since the last pawn move could come up again
+        let after_e4 = state
+            .find_legal(&try_from_notation::<_, San>("e4").unwrap())
+            .unwrap()
+            .1;
+        history.push(state);
+        assert_eq!(cache.key(&after_e4, &history), cache.key(&after_e4, &[]));
+
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+
+    /// A line (numbered from 1) that isn't a `name = value` pair
+    Syntax(usize),
+
+    /// A known setting with a value that doesn't make sense for it
+    InvalidValue(String, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "unable to access config: {}", e),
+            ConfigError::Syntax(line) => {
+                write!(f, "expected `name = value` on line {} of the config", line)
+            }
+            ConfigError::InvalidValue(name, value) => {
+                write!(f, "invalid value for {}: {}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Settings for the engine that are kept between sessions, like the ones found by
+/// calibrating it on this machine. They're stored one per line as `name = value`,
+/// with `#` starting a comment. Names aren't case sensitive, and settings this
+/// version doesn't know about are kept as they are when the config is saved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineConfig {
+    entries: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    pub const THREADS: &'static str = "Threads";
+
+    /// Reads the config from a file, which is empty if the file doesn't exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Io(e)),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        std::fs::write(path, self.to_string()).map_err(ConfigError::Io)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .filter(|(name, _)| !name.is_empty())
+                .ok_or(ConfigError::Syntax(number + 1))?;
+
+            config.set(name, value);
+        }
+
+        config.threads()?;
+        Ok(config)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Changes a setting, or adds it if it isn't set yet
+    pub fn set(&mut self, name: &str, value: impl ToString) {
+        let value = value.to_string();
+        match self
+            .entries
+            .iter_mut()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((name.to_string(), value)),
+        }
+    }
+
+    /// The most threads a search should use, if it's been set
+    pub fn threads(&self) -> Result<Option<usize>, ConfigError> {
+        self.get(Self::THREADS)
+            .map(|value| {
+                value
+                    .parse()
+                    .ok()
+                    .filter(|threads| *threads > 0)
+                    .ok_or_else(|| ConfigError::InvalidValue(Self::THREADS.into(), value.into()))
+            })
+            .transpose()
+    }
+}
+
+impl std::fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in self.entries.iter() {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let mut config = EngineConfig::parse(
+            r#"
+            # Found with `weechess calibrate`
+            threads = 4
+            Hash = 256 # not used yet
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.threads().unwrap(), Some(4));
+        assert_eq!(config.get("hash"), Some("256"));
+
+        config.set(EngineConfig::THREADS, 8);
+        assert_eq!(config.to_string(), "threads = 8\nHash = 256\n");
+        assert_eq!(EngineConfig::parse(&config.to_string()).unwrap(), config);
+
+        assert!(matches!(
+            EngineConfig::parse("Threads 4"),
+            Err(ConfigError::Syntax(1))
+        ));
+        assert!(matches!(
+            EngineConfig::parse("Threads = 0"),
+            Err(ConfigError::InvalidValue(..))
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fmt::Display,
+    fs::OpenOptions,
+    io::Write,
+    panic,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use weechess_core::{
+    notation::{into_notation, lan::Lan, Fen},
+    Move, State,
+};
+
+use crate::version::EngineVersion;
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext::new());
+
+/// What the engine was last working on, kept around so that it
+/// can be included in a crash report if anything panics
+#[derive(Debug, Clone)]
+pub struct CrashContext {
+    /// The starting position of the game being played and the moves since
+    pub game: Option<(String, Vec<Move>)>,
+
+    /// The position being searched, in FEN notation
+    pub position: Option<String>,
+
+    /// The random number seed of the current search
+    pub seed: Option<u64>,
+
+    /// The depth currently being searched
+    pub depth: Option<usize>,
+}
+
+impl CrashContext {
+    const fn new() -> Self {
+        Self {
+            game: None,
+            position: None,
+            seed: None,
+            depth: None,
+        }
+    }
+}
+
+impl Display for CrashContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Version: {}", EngineVersion::CURRENT)?;
+        writeln!(f, "Build: {}", EngineVersion::CURRENT.build)?;
+
+        if let Some((fen, moves)) = &self.game {
+            writeln!(f, "Game: {}", fen)?;
+            writeln!(f, "Moves: {}", into_notation::<_, Lan>(&&moves[..]))?;
+        }
+
+        if let Some(position) = &self.position {
+            writeln!(f, "Position: {}", position)?;
+        }
+
+        if let Some(seed) = self.seed {
+            writeln!(f, "Seed: {}", seed)?;
+        }
+
+        if let Some(depth) = self.depth {
+            writeln!(f, "Depth: {}", depth)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records the game being played from a starting position
+/// in FEN notation, replacing any previous one
+pub fn record_game(initial_fen: String, moves: &[Move]) {
+    update(|context| context.game = Some((initial_fen, moves.to_vec())));
+}
+
+/// Records the start of a new search
+pub(crate) fn record_search(state: &State, seed: u64) {
+    update(|context| {
+        context.position = Some(into_notation::<_, Fen>(state).to_string());
+        context.seed = Some(seed);
+        context.depth = None;
+    });
+}
+
+pub(crate) fn record_depth(depth: usize) {
+    update(|context| context.depth = Some(depth));
+}
+
+/// Prints the crash context when a panic happens and appends it to the given
+/// file (creating its directory if needed), before handing off to the previously
+/// installed hook
+pub fn install_panic_hook(crash_file: impl Into<PathBuf>) {
+    let crash_file = crash_file.into();
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // The panic could have happened while the context was being updated,
+        // in which case it's better to report nothing than to deadlock
+        let context = match CONTEXT.try_lock() {
+            Ok(context) => context.clone(),
+            Err(..) => CrashContext::new(),
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let report = format!("--- Crash at {} ---\n{}{}\n", timestamp, context, info);
+        eprintln!("{}", report);
+
+        if let Some(dir) = crash_file.parent() {
+            _ = std::fs::create_dir_all(dir);
+        }
+
+        match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&crash_file)
+        {
+            Ok(mut file) => {
+                _ = writeln!(file, "{}", report);
+                eprintln!("Crash report appended to {}", crash_file.display());
+            }
+            Err(e) => {
+                eprintln!("Unable to write to {}: {}", crash_file.display(), e);
+            }
+        }
+
+        previous_hook(info);
+    }));
+}
+
+fn update<F>(f: F)
+where
+    F: FnOnce(&mut CrashContext),
+{
+    match CONTEXT.lock() {
+        Ok(mut context) => f(&mut context),
+        Err(e) => f(&mut e.into_inner()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::{Color, Side};
+
+    use super::*;
+
+    #[test]
+    fn test_context_report() {
+        let state = State::default();
+        let moves = [Move::by_castling(Color::White, Side::King)];
+
+        let mut context = CrashContext::new();
+        context.game = Some((into_notation::<_, Fen>(&state).to_string(), moves.to_vec()));
+        context.seed = Some(42);
+        context.depth = Some(3);
+
+        let report = context.to_string();
+        assert!(report.contains(&format!("Game: {}", Fen::DEFAULT)));
+        assert!(report.contains("Moves: e1g1"));
+        assert!(report.contains("Seed: 42"));
+        assert!(report.contains("Depth: 3"));
+        assert!(!report.contains("Position:"));
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use weechess_core::{
+    pgn::{Pgn, PgnWriteError},
+    Color, GameStatus, Hash, Move, State, ZobristHasher,
+};
+
+use crate::book;
+
+// A fixed seed keeps lookups reproducible from one run to the next, collisions and all
+const DATABASE_HASH_SEED: u64 = 0x6761_6d65_7364_6221;
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(std::io::Error),
+    Decode(ciborium::de::Error<std::io::Error>),
+    Encode(ciborium::ser::Error<std::io::Error>),
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::Io(e) => write!(f, "unable to access index: {}", e),
+            DatabaseError::Decode(e) => write!(f, "unable to decode index: {}", e),
+            DatabaseError::Encode(e) => write!(f, "unable to encode index: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// What's known about a game in the database, besides its moves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub tags: Vec<(String, String)>,
+
+    /// How the game ended, if it did and the result was recorded
+    pub result: Option<GameStatus>,
+
+    /// The number of plies in the game
+    pub length: usize,
+}
+
+impl GameSummary {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A position coming up in one of the games
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Occurrence {
+    pub game: usize,
+    pub ply: usize,
+
+    /// The move played from the position, unless the game ended there
+    pub next_move: Option<Move>,
+}
+
+/// How a move played from a position has worked out across the games
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveStats {
+    pub mv: Move,
+    pub games: usize,
+    pub white_wins: usize,
+    pub draws: usize,
+    pub black_wins: usize,
+}
+
+impl MoveStats {
+    /// The fraction of points scored by `color` in the games with a result
+    pub fn score(&self, color: Color) -> Option<f32> {
+        let decided = self.white_wins + self.draws + self.black_wins;
+        if decided == 0 {
+            return None;
+        }
+
+        let wins = match color {
+            Color::White => self.white_wins,
+            Color::Black => self.black_wins,
+        };
+
+        Some((wins as f32 + self.draws as f32 * 0.5) / decided as f32)
+    }
+}
+
+/// An index of the positions reached in a collection of games, for finding the games
+/// that went through a position and what was played from it. Transpositions count as
+/// the same position, since positions are looked up by hash instead of by move order.
+#[derive(Serialize, Deserialize)]
+pub struct GameDatabase {
+    hasher: ZobristHasher,
+    games: Vec<GameSummary>,
+    positions: HashMap<Hash, Vec<Occurrence>>,
+    max_plies: Option<usize>,
+}
+
+impl GameDatabase {
+    /// Creates an empty database, which only indexes the first `max_plies` plies
+    /// of each game if given (which is all that's needed for opening statistics)
+    pub fn new(max_plies: Option<usize>) -> Self {
+        Self {
+            hasher: ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(DATABASE_HASH_SEED)),
+            games: vec![],
+            positions: HashMap::new(),
+            max_plies,
+        }
+    }
+
+    /// Loads an index saved with `save`, so that the games don't have to be parsed again
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let file = std::fs::File::open(path).map_err(DatabaseError::Io)?;
+        ciborium::from_reader(std::io::BufReader::new(file)).map_err(DatabaseError::Decode)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DatabaseError> {
+        let file = std::fs::File::create(path).map_err(DatabaseError::Io)?;
+        ciborium::into_writer(self, std::io::BufWriter::new(file)).map_err(DatabaseError::Encode)
+    }
+
+    /// The number of plies indexed from each game, if limited
+    pub fn max_plies(&self) -> Option<usize> {
+        self.max_plies
+    }
+
+    pub fn games(&self) -> &[GameSummary] {
+        &self.games
+    }
+
+    /// The number of distinct positions in the index
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Adds a game to the database, indexing every position in it
+    pub fn add_game(&mut self, pgn: &Pgn) -> Result<(), PgnWriteError> {
+        let index = self.games.len();
+        let plies = self
+            .max_plies
+            .map_or(pgn.moves.len(), |max_plies| max_plies.min(pgn.moves.len()));
+
+        // Make sure the whole game can be played out before any of it is indexed
+        let mut occurrences = Vec::with_capacity(plies + 1);
+        let mut state = pgn.initial_state.clone();
+        for ply in 0..=plies {
+            let next_move = pgn.moves.get(ply).map(|m| m.mv).filter(|_| ply < plies);
+            occurrences.push((self.hasher.hash(&state), ply, next_move));
+            if let Some(mv) = next_move {
+                state = State::by_performing_move(&state, &mv)
+                    .map_err(|_| PgnWriteError::IllegalMove)?;
+            }
+        }
+
+        for (hash, ply, next_move) in occurrences {
+            self.positions.entry(hash).or_default().push(Occurrence {
+                game: index,
+                ply,
+                next_move,
+            });
+        }
+
+        self.games.push(GameSummary {
+            tags: pgn.tags.clone(),
+            result: pgn.declared_result.as_deref().and_then(book::parse_result),
+            length: pgn.moves.len(),
+        });
+
+        Ok(())
+    }
+
+    /// The places the position came up in, at most once per game. Different positions
+    /// can hash the same, so the moves played from them are checked to be legal here.
+    pub fn lookup(&self, state: &State) -> Vec<Occurrence> {
+        let mut seen = HashSet::new();
+        self.positions
+            .get(&self.hasher.hash(state))
+            .into_iter()
+            .flatten()
+            .filter(|o| o.next_move.is_none_or(|mv| state.is_legal(&mv)))
+            .filter(|o| seen.insert(o.game))
+            .copied()
+            .collect()
+    }
+
+    /// The moves played from the position and how they scored, most played first
+    pub fn move_stats(&self, state: &State) -> Vec<MoveStats> {
+        let mut stats: Vec<MoveStats> = vec![];
+        for occurrence in self.lookup(state) {
+            let Some(mv) = occurrence.next_move else {
+                continue;
+            };
+
+            let index = match stats.iter().position(|s| s.mv == mv) {
+                Some(index) => index,
+                None => {
+                    stats.push(MoveStats {
+                        mv,
+                        games: 0,
+                        white_wins: 0,
+                        draws: 0,
+                        black_wins: 0,
+                    });
+
+                    stats.len() - 1
+                }
+            };
+
+            let entry = &mut stats[index];
+            entry.games += 1;
+            match self.games[occurrence.game].result {
+                Some(GameStatus::Won(Color::White)) => entry.white_wins += 1,
+                Some(GameStatus::Won(Color::Black)) => entry.black_wins += 1,
+                Some(GameStatus::Drawn) => entry.draws += 1,
+                Some(GameStatus::Ongoing) | None => {}
+            }
+        }
+
+        stats.sort_by_key(|s| std::cmp::Reverse(s.games));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::{
+        notation::{try_from_notation, San},
+        MoveQuery,
+    };
+
+    use super::*;
+
+    fn play(moves: &[&str]) -> State {
+        let queries: Vec<MoveQuery> = moves
+            .iter()
+            .map(|m| try_from_notation::<_, San>(m).unwrap())
+            .collect();
+
+        State::by_performing_moves(&State::default(), &queries).unwrap()
+    }
+
+    #[test]
+    fn test_move_stats() {
+        let contents = r#"
+            [White "A"]
+            [Result "1-0"]
+
+            1. e4 e5 2. Nf3 Nc6 1-0
+
+            [White "B"]
+            [Result "0-1"]
+
+            1. Nf3 Nc6 2. e4 e5 0-1
+
+            [White "C"]
+            [Result "1/2-1/2"]
+
+            1. e4 c5 1/2-1/2
+        "#;
+
+        let mut database = GameDatabase::new(None);
+        for pgn in Pgn::parse_all(contents) {
+            database.add_game(&pgn.unwrap()).unwrap();
+        }
+
+        assert_eq!(database.games().len(), 3);
+        assert_eq!(database.games()[1].tag("White"), Some("B"));
+
+        let stats = database.move_stats(&State::default());
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].games, 2);
+        assert_eq!(stats[0].score(Color::White), Some(0.75));
+
+        // The first two games transpose into the same position
+        let transposed = play(&["e4", "e5", "Nf3", "Nc6"]);
+        let occurrences = database.lookup(&transposed);
+        assert_eq!(occurrences.len(), 2);
+        assert!(occurrences
+            .iter()
+            .all(|o| o.ply == 4 && o.next_move.is_none()));
+
+        // Only the opening is indexed when the plies are limited
+        let mut openings = GameDatabase::new(Some(1));
+        for pgn in Pgn::parse_all(contents) {
+            openings.add_game(&pgn.unwrap()).unwrap();
+        }
+
+        assert!(openings.lookup(&transposed).is_empty());
+        assert_eq!(openings.lookup(&play(&["e4"])).len(), 2);
+
+        // A saved index answers the same as the one it was saved from
+        let path = std::env::temp_dir().join(format!("weechess-db-{}.bin", std::process::id()));
+        database.save(&path).unwrap();
+        let loaded = GameDatabase::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.max_plies(), None);
+        assert_eq!(loaded.games()[1].tag("White"), Some("B"));
+        assert_eq!(loaded.lookup(&transposed), database.lookup(&transposed));
+        assert_eq!(
+            loaded.move_stats(&State::default()),
+            database.move_stats(&State::default())
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use weechess_core::{AttackGenerator, BitBoard, Color, File, Piece, PieceIndex, Square, State};
+
+/// Past this many pieces (kings included), none of the known endgames can apply
+const MAX_PIECES: u32 = 5;
+
+/// How many of each piece one side has, indexed by the piece
+type MaterialCounts = [u8; Piece::ALL_INCLUDING_NONE.len()];
+
+/// An endgame that is known to be drawn despite one side being up material, keyed by its
+/// material signature from the stronger side's point of view (in uppercase, see
+/// `State::material_signature`) along with the test for the key squares that make it a draw
+struct KnownDraw {
+    signature: &'static str,
+    is_drawn: fn(state: &State, strong: Color) -> bool,
+}
+
+/// Like the opening book, but for the end of the game: the classic endings where the
+/// evaluation terms see a winning advantage, but that can't actually be won
+const KNOWN_DRAWS: &[KnownDraw] = &[
+    // Two knights can't force mate against a lone king
+    KnownDraw {
+        signature: "KNNk",
+        is_drawn: |_, _| true,
+    },
+    // A rook pawn can't promote once the defending king reaches the corner in front of it
+    KnownDraw {
+        signature: "KPk",
+        is_drawn: defends_rook_pawn_corner,
+    },
+    // Neither can it when the bishop doesn't control the promotion square
+    KnownDraw {
+        signature: "KBPk",
+        is_drawn: defends_wrong_bishop_corner,
+    },
+    KnownDraw {
+        signature: "KBPPk",
+        is_drawn: defends_wrong_bishop_corner,
+    },
+    // With bishops on opposite colors, a king in front of the pawn can't be driven away,
+    // and a bishop that can reach the pawn's path can always give itself up for it
+    KnownDraw {
+        signature: "KBPkb",
+        is_drawn: blockades_opposite_bishops_pawn,
+    },
+];
+
+/// The side that is up material in a known drawn endgame, if the position is one
+pub fn drawn_despite(state: &State) -> Option<Color> {
+    if state.board().occupancy().count_ones() > MAX_PIECES {
+        return None;
+    }
+
+    Color::ALL.iter().copied().find(|strong| {
+        let counts = (material(state, *strong), material(state, !*strong));
+        KNOWN_DRAWS.iter().any(|draw| {
+            parse_signature(draw.signature) == counts && (draw.is_drawn)(state, *strong)
+        })
+    })
+}
+
+fn material(state: &State, color: Color) -> MaterialCounts {
+    let mut counts = MaterialCounts::default();
+    for piece in Piece::ALL {
+        counts[*piece as usize] = state.piece_count(PieceIndex::new(color, *piece));
+    }
+
+    counts
+}
+
+fn parse_signature(signature: &str) -> (MaterialCounts, MaterialCounts) {
+    let mut strong = MaterialCounts::default();
+    let mut weak = MaterialCounts::default();
+    for c in signature.chars() {
+        let counts = if c.is_ascii_uppercase() {
+            &mut strong
+        } else {
+            &mut weak
+        };
+
+        let piece = Piece::ALL
+            .iter()
+            .find(|piece| Into::<char>::into(**piece) == c.to_ascii_uppercase())
+            .expect("known draws only use piece letters");
+
+        counts[*piece as usize] += 1;
+    }
+
+    (strong, weak)
+}
+
+fn occupancy(state: &State, color: Color, piece: Piece) -> BitBoard {
+    state.board().piece_occupancy(PieceIndex::new(color, piece))
+}
+
+fn king(state: &State, color: Color) -> Option<Square> {
+    occupancy(state, color, Piece::King).first_square()
+}
+
+/// The square the strong side's pawns all promote on, if they're all on one rook file
+fn rook_pawn_promotion_square(state: &State, strong: Color) -> Option<Square> {
+    let pawns = occupancy(state, strong, Piece::Pawn);
+    let file = pawns.first_square()?.file();
+    let on_one_rook_file = (file == File::A || file == File::H)
+        && pawns.iter_squares().all(|square| square.file() == file);
+
+    on_one_rook_file.then(|| Square::new(file, strong.promotion_rank()))
+}
+
+fn defends_rook_pawn_corner(state: &State, strong: Color) -> bool {
+    let (Some(promotion_square), Some(weak_king)) = (
+        rook_pawn_promotion_square(state, strong),
+        king(state, !strong),
+    ) else {
+        return false;
+    };
+
+    weak_king.distance_to(promotion_square) <= 1
+}
+
+fn defends_wrong_bishop_corner(state: &State, strong: Color) -> bool {
+    let Some(promotion_square) = rook_pawn_promotion_square(state, strong) else {
+        return false;
+    };
+
+    let bishops = occupancy(state, strong, Piece::Bishop);
+    let is_wrong_bishop = (bishops & promotion_square.color().squares()).none();
+    is_wrong_bishop && defends_rook_pawn_corner(state, strong)
+}
+
+fn blockades_opposite_bishops_pawn(state: &State, strong: Color) -> bool {
+    let (Some(pawn), Some(weak_king)) = (
+        occupancy(state, strong, Piece::Pawn).first_square(),
+        king(state, !strong),
+    ) else {
+        return false;
+    };
+
+    if !state.board().has_opposite_colored_bishops() {
+        return false;
+    }
+
+    // Whether the square is somewhere between the pawn and its promotion square
+    let ahead = |square: Square| {
+        square.file() == pawn.file()
+            && match strong {
+                Color::White => square.rank() > pawn.rank(),
+                Color::Black => square.rank() < pawn.rank(),
+            }
+    };
+
+    // Otherwise, the bishop can give itself up for the pawn, and a lone bishop can't mate
+    let weak_bishop = occupancy(state, !strong, Piece::Bishop);
+    ahead(weak_king)
+        || weak_bishop.iter_squares().any(|bishop| {
+            AttackGenerator::compute_bishop_attacks(bishop, state.board().occupancy())
+                .iter_squares()
+                .any(ahead)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weechess_core::notation::{try_from_notation, Fen};
+
+    fn drawn(fen: &str) -> Option<Color> {
+        drawn_despite(&try_from_notation::<_, Fen>(fen).unwrap())
+    }
+
+    #[test]
+    fn test_known_draws() {
+        assert_eq!(
+            drawn("8/8/4k3/8/8/3NN3/8/4K3 w - - 0 1"),
+            Some(Color::White)
+        );
+
+        // The defending king in the corner, or still too far away from it
+        assert_eq!(drawn("k7/8/8/P7/8/8/8/4K3 w - - 0 1"), Some(Color::White));
+        assert_eq!(drawn("8/8/8/P5k1/8/8/8/4K3 w - - 0 1"), None);
+        assert_eq!(drawn("8/8/8/8/7p/8/4k3/6K1 b - - 0 1"), Some(Color::Black));
+
+        // A light squared bishop can't control h8
+        assert_eq!(drawn("7k/8/8/7P/8/8/8/4KB2 w - - 0 1"), Some(Color::White));
+        assert_eq!(drawn("7k/8/8/7P/8/8/8/4K1B1 w - - 0 1"), None);
+        assert_eq!(drawn("7k/8/7P/7P/8/8/8/4KB2 w - - 0 1"), Some(Color::White));
+
+        // Opposite colored bishops, with the pawn blockaded or its path covered
+        assert_eq!(
+            drawn("8/8/3k4/8/3P4/8/5B2/4K2b w - - 0 1"),
+            Some(Color::White)
+        );
+        assert_eq!(
+            drawn("8/1b6/8/4k3/3P4/8/5B2/4K3 w - - 0 1"),
+            Some(Color::White)
+        );
+        assert_eq!(drawn("8/8/8/4k2b/3P4/8/5B2/4K3 w - - 0 1"), None);
+    }
+}
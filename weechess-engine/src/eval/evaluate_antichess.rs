@@ -0,0 +1,70 @@
+use weechess_core::{Color, Variant};
+
+use super::{Evaluation, StateVariation};
+
+pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
+    if v.variant() != Variant::Antichess {
+        return;
+    }
+
+    // Every piece still on the board is one more that has to be given away, and it
+    // doesn't matter much which kind it is, since the king is just another piece
+    *eval -= Evaluation::ONE_PAWN * v.color_counts[*perspective] as i32;
+
+    // Captures are compulsory, so the other side will have to take a piece of ours
+    // that they attack (unless they have another capture to make instead)
+    let attacked =
+        v.board().colored_occupancy(*perspective) & v.board().colored_attacks(!*perspective);
+    *eval += Evaluation::ONE_PAWN * 0.25 * attacked.count_ones() as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::{
+        notation::{try_from_notation, Fen},
+        Color, Variant,
+    };
+
+    use crate::eval::{Evaluation, StateVariation};
+
+    fn evaluate(fen: &str, variant: Variant, perspective: Color) -> Evaluation {
+        let s = try_from_notation::<_, Fen>(fen)
+            .unwrap()
+            .with_variant(variant);
+
+        let s = StateVariation::from(&s);
+        let mut e = Evaluation::EVEN;
+        super::evaluate(&s, &perspective, &mut e, &mut false);
+        e
+    }
+
+    #[test]
+    fn test_fewer_pieces() {
+        let fen = "k7/8/8/8/8/8/PPP5/K7 w - - 0 1";
+        let white = evaluate(fen, Variant::Antichess, Color::White);
+        let black = evaluate(fen, Variant::Antichess, Color::Black);
+        assert!(white < black, "{} < {}", white, black);
+
+        // It only counts in antichess
+        assert_eq!(
+            evaluate(fen, Variant::Standard, Color::White),
+            Evaluation::EVEN
+        );
+    }
+
+    #[test]
+    fn test_attacked_pieces() {
+        // The knight on c6 is attacked by the pawn on b5 and has to be taken
+        let attacked = evaluate(
+            "k7/8/2n5/1P6/8/8/8/K7 w - - 0 1",
+            Variant::Antichess,
+            Color::Black,
+        );
+        let safe = evaluate(
+            "k7/8/7n/1P6/8/8/8/K7 w - - 0 1",
+            Variant::Antichess,
+            Color::Black,
+        );
+        assert!(attacked > safe, "{} > {}", attacked, safe);
+    }
+}
@@ -0,0 +1,87 @@
+use weechess_core::{AttackGenerator, BitBoard, Color, Piece, PieceIndex};
+
+use super::{Evaluation, StateVariation};
+
+pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
+    let color = *perspective;
+    let back_rank = color.home_rank_mask();
+
+    let Some(king) = v
+        .board()
+        .piece_occupancy(PieceIndex::new(color, Piece::King))
+        .first_square()
+    else {
+        return;
+    };
+
+    if !back_rank.test(king) {
+        return;
+    }
+
+    let heavy_pieces = v
+        .board()
+        .piece_occupancy(PieceIndex::new(!color, Piece::Rook))
+        | v.board()
+            .piece_occupancy(PieceIndex::new(!color, Piece::Queen));
+
+    if heavy_pieces.none() {
+        return;
+    }
+
+    // Stepping sideways along the back rank doesn't get away from a rook or queen
+    // checking along it, so only the squares in front of the king are any help
+    let enemy_attacks = v.board().colored_attacks(!color);
+    let escapes = AttackGenerator::compute_king_attacks(king)
+        & !back_rank
+        & !v.board().colored_occupancy(color)
+        & !enemy_attacks;
+
+    if escapes.any() {
+        return;
+    }
+
+    *eval -= Evaluation::ONE_PAWN * 0.2;
+
+    // It's a lot worse if one of them can already get onto the back rank without
+    // being taken there, which is often mate on the spot
+    let landing_squares = heavy_pieces
+        .iter_squares()
+        .fold(BitBoard::ZERO, |squares, square| {
+            squares | AttackGenerator::compute_rook_attacks(square, v.board().occupancy())
+        })
+        & back_rank
+        & !v.board().colored_attacks(color);
+
+    if landing_squares.any() {
+        *eval -= Evaluation::ONE_PAWN * 0.6;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::Color;
+
+    use crate::eval::{evaluate_term, Evaluation};
+
+    fn evaluate(fen: &str) -> Evaluation {
+        evaluate_term(super::evaluate, fen, Color::White)
+    }
+
+    #[test]
+    fn test_back_rank() {
+        // The rook can come down to e1 and mate
+        let mate_threat = evaluate("4r1k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+
+        // The rook on d1 covers the back rank
+        let covered = evaluate("4r1k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+
+        // Once there's some room, there's nothing to worry about
+        let luft = evaluate("4r1k1/5ppp/8/8/8/7P/5PP1/6K1 w - - 0 1");
+        let no_rooks = evaluate("4b1k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+
+        assert!(mate_threat < covered, "{} < {}", mate_threat, covered);
+        assert!(covered < Evaluation::EVEN);
+        assert_eq!(luft, Evaluation::EVEN);
+        assert_eq!(no_rooks, Evaluation::EVEN);
+    }
+}
@@ -1,24 +1,20 @@
-use weechess_core::{BitBoard, Color, File, Piece, PieceIndex, FILE_MASKS};
+use weechess_core::{pawns, Color, File, FILE_MASKS};
 
 use super::{Evaluation, StateVariation};
 
 pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
-    let our_pawns = v
-        .board()
-        .piece_occupancy(PieceIndex::new(*perspective, Piece::Pawn));
+    let (our_pawns, _) = pawns::pawns(v.board(), *perspective);
+    let doubled = pawns::doubled(our_pawns);
+    let isolated = pawns::isolated(our_pawns);
 
     for file in File::ALL {
         // Punish doubled pawns
-        if (our_pawns & FILE_MASKS[*file]).count_ones() > 1 {
+        if (doubled & FILE_MASKS[*file]).any() {
             *eval -= Evaluation::ONE_PAWN * 0.4;
         }
 
         // Punish isolated pawns
-        let mask = BitBoard::ZERO
-            | file.left().map(|f| FILE_MASKS[f]).unwrap_or_default()
-            | file.right().map(|f| FILE_MASKS[f]).unwrap_or_default();
-
-        if (our_pawns & mask).none() {
+        if (isolated & FILE_MASKS[*file]).any() {
             *eval -= Evaluation::ONE_PAWN * 0.5;
         }
     }
@@ -62,4 +58,36 @@ mod tests {
 
         assert!(e2 < e1, "{} < {}", e2, e1);
     }
+
+    #[test]
+    fn test_bad_pawn_penalties() {
+        let evaluate = |fen| crate::eval::evaluate_term(super::evaluate, fen, Color::White);
+
+        // Connected pawns, and files without any pawns, cost nothing
+        assert_eq!(
+            evaluate("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1"),
+            Evaluation::EVEN
+        );
+
+        // Each bad file is only punished once, however many pawns are on it
+        let doubled_and_isolated = -(Evaluation::ONE_PAWN * 0.4) - Evaluation::ONE_PAWN * 0.5;
+        assert_eq!(
+            evaluate("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1"),
+            doubled_and_isolated
+        );
+        assert_eq!(
+            evaluate("4k3/8/8/8/2P5/2P5/2P5/4K3 w - - 0 1"),
+            doubled_and_isolated
+        );
+
+        // Only the pawns of the side being evaluated count
+        assert_eq!(
+            evaluate("4k3/2p5/2p5/8/8/8/PP6/4K3 w - - 0 1"),
+            Evaluation::EVEN
+        );
+        assert_eq!(
+            evaluate("4k3/8/8/8/8/8/P1P1P3/4K3 w - - 0 1"),
+            -(Evaluation::ONE_PAWN * 0.5) * 3.0
+        );
+    }
 }
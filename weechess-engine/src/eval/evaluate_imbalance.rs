@@ -0,0 +1,96 @@
+use weechess_core::{Color, Piece, PieceIndex, SquareColor};
+
+use super::{Evaluation, StateVariation};
+
+// Bishops covering both colors of squares leave nowhere for the other side to hide
+const BISHOP_PAIR_BONUS: i32 = 50;
+
+// A second rook, or a rook next to a queen, overlaps with what's already on the board
+const ROOK_REDUNDANCY_PENALTY: i32 = 16;
+const QUEEN_ROOK_REDUNDANCY_PENALTY: i32 = 8;
+
+// Centipawns for each of our own pawns above five. Knights need outposts that the pawns
+// give them, while rooks need the open files that the pawns take away.
+const KNIGHT_PAWN_ADJUSTMENT: i32 = 6;
+const ROOK_PAWN_ADJUSTMENT: i32 = -12;
+
+// Three minors against a queen, or two against a rook, tend to do better than their
+// worths say, since they can work together on the squares the lone piece can't cover
+const MINORS_FOR_QUEEN_BONUS: i32 = 50;
+const MINORS_FOR_ROOK_BONUS: i32 = 25;
+
+pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
+    let count = |color: Color, piece: Piece| v.piece_counts[PieceIndex::new(color, piece)] as i32;
+    let minors = |color: Color| count(color, Piece::Knight) + count(color, Piece::Bishop);
+
+    let us = *perspective;
+    let them = !us;
+    let mut imbalance = 0;
+
+    if SquareColor::ALL
+        .iter()
+        .all(|square_color| v.board().bishops_on(us, *square_color).any())
+    {
+        imbalance += BISHOP_PAIR_BONUS;
+    }
+
+    let rooks = count(us, Piece::Rook);
+    if rooks > 1 {
+        imbalance -= ROOK_REDUNDANCY_PENALTY * (rooks - 1);
+    }
+
+    if rooks > 0 && count(us, Piece::Queen) > 0 {
+        imbalance -= QUEEN_ROOK_REDUNDANCY_PENALTY;
+    }
+
+    let extra_pawns = count(us, Piece::Pawn) - 5;
+    imbalance += KNIGHT_PAWN_ADJUSTMENT * extra_pawns * count(us, Piece::Knight);
+    imbalance += ROOK_PAWN_ADJUSTMENT * extra_pawns * rooks;
+
+    let extra_minors = minors(us) - minors(them);
+    if extra_minors >= 3 && count(them, Piece::Queen) > count(us, Piece::Queen) {
+        imbalance += MINORS_FOR_QUEEN_BONUS;
+    } else if extra_minors >= 2 && count(them, Piece::Rook) > rooks {
+        imbalance += MINORS_FOR_ROOK_BONUS;
+    }
+
+    *eval += Evaluation::from(imbalance);
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::Color;
+
+    use crate::eval::{evaluate_term, Evaluation};
+
+    fn evaluate(fen: &str, perspective: Color) -> Evaluation {
+        evaluate_term(super::evaluate, fen, perspective)
+    }
+
+    #[test]
+    fn test_imbalance() {
+        // Two bishops on the same color of squares aren't a pair
+        let pair = evaluate("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", Color::White);
+        let same_color = evaluate("4k3/8/8/8/8/8/8/1B2KB2 w - - 0 1", Color::White);
+        assert_eq!(pair - same_color, Evaluation::from(50));
+
+        // A second rook is worth a bit less than the first
+        let one_rook = evaluate("4k3/8/8/8/8/8/PPPPP3/R3K3 w - - 0 1", Color::White);
+        let two_rooks = evaluate("4k3/8/8/8/8/8/PPPPP3/R3K2R w - - 0 1", Color::White);
+        assert!(two_rooks < one_rook);
+
+        // Knights like a closed position more than rooks do
+        let closed = "4k3/8/8/8/8/8/PPPPPPPP/1N2K2R w - - 0 1";
+        let open = "4k3/8/8/8/8/8/PPPP4/1N2K2R w - - 0 1";
+        assert_eq!(
+            evaluate(closed, Color::White),
+            Evaluation::from(3 * 6 - 3 * 12)
+        );
+        assert_eq!(evaluate(open, Color::White), Evaluation::from(12 - 6));
+
+        // Three minors against a queen
+        let fen = "3qk3/8/8/8/8/8/PPPPP3/1NB1KN2 w - - 0 1";
+        assert_eq!(evaluate(fen, Color::White), Evaluation::from(50));
+        assert_eq!(evaluate(fen, Color::Black), Evaluation::EVEN);
+    }
+}
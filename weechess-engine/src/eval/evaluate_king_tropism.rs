@@ -0,0 +1,93 @@
+use weechess_core::{Color, Piece, PieceIndex};
+
+use super::{Evaluation, StateVariation};
+
+// How close each pair of squares is, from 7 for the same square down to 0 for squares
+// a full seven king moves apart
+const CLOSENESS: [[u8; 64]; 64] = {
+    let mut table = [[0; 64]; 64];
+    let mut a: usize = 0;
+    while a < 64 {
+        let mut b: usize = 0;
+        while b < 64 {
+            let rank_distance = (a / 8).abs_diff(b / 8);
+            let file_distance = (a % 8).abs_diff(b % 8);
+            let distance = if rank_distance > file_distance {
+                rank_distance
+            } else {
+                file_distance
+            };
+
+            table[a][b] = 7 - distance as u8;
+            b += 1;
+        }
+
+        a += 1;
+    }
+
+    table
+};
+
+// Centipawns for each step closer to the enemy king. Queens and knights need to be close
+// to join an attack, while the long range pieces can help from further away.
+const TROPISM_WEIGHTS: &[(Piece, i32)] = &[
+    (Piece::Knight, 3),
+    (Piece::Bishop, 1),
+    (Piece::Rook, 2),
+    (Piece::Queen, 4),
+];
+
+pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
+    // Closing in on the king only matters while there's enough material to mate
+    // with, by the end game it's up to driving the king to the edge instead
+    let middle_game_weight = 1.0 - v.end_game_weight;
+    if middle_game_weight <= 0.0 {
+        return;
+    }
+
+    let Some(their_king) = v
+        .board()
+        .piece_occupancy(PieceIndex::new(!*perspective, Piece::King))
+        .first_square()
+    else {
+        return;
+    };
+
+    let mut tropism = 0;
+    for (piece, weight) in TROPISM_WEIGHTS {
+        for square in v
+            .board()
+            .piece_occupancy(PieceIndex::new(*perspective, *piece))
+            .iter_squares()
+        {
+            tropism += weight * CLOSENESS[square.index()][their_king.index()] as i32;
+        }
+    }
+
+    *eval += Evaluation::from(tropism) * middle_game_weight;
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::Color;
+
+    use crate::eval::{evaluate_term, Evaluation};
+
+    fn evaluate(fen: &str) -> Evaluation {
+        evaluate_term(super::evaluate, fen, Color::White)
+    }
+
+    #[test]
+    fn test_king_tropism() {
+        // The same pieces, with the queen and knight swung over towards the king
+        let far = evaluate("r1b2rk1/ppq2ppp/2n1pn2/8/8/2N5/PPPQ1PPP/R1B1R1K1 w - - 0 1");
+        let near = evaluate("r1b2rk1/ppq2ppp/2n1pn2/6N1/7Q/8/PPP2PPP/R1B1R1K1 w - - 0 1");
+        assert!(near > far, "{} > {}", near, far);
+
+        // Nothing is added in a pawn ending
+        assert_eq!(
+            evaluate("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1"),
+            Evaluation::EVEN
+        );
+    }
+}
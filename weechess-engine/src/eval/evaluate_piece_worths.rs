@@ -5,7 +5,12 @@ use super::{Evaluation, StateVariation};
 pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
     for piece in Piece::ALL {
         let piece_index = PieceIndex::new(*perspective, *piece);
-        let piece_worth = Evaluation::ONE_PAWN * PIECE_PAWN_WORTHS[*piece];
+        let piece_worth = if *piece == Piece::King {
+            Evaluation::ONE_PAWN * PIECE_PAWN_WORTHS[*piece]
+        } else {
+            Evaluation::ONE_PAWN * (PIECE_PAWN_WORTHS[*piece] * v.material_scales[*perspective])
+        };
+
         let piece_count: i32 = v.piece_counts[piece_index] as i32;
         *eval += piece_worth * piece_count;
     }
@@ -0,0 +1,65 @@
+use weechess_core::{Color, Piece, PieceIndex};
+
+use super::{Evaluation, StateVariation};
+
+pub fn evaluate(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, _: &mut bool) {
+    if v.turn_to_move() != *perspective {
+        return;
+    }
+
+    // Having the move is worth more with heavy pieces on the board that
+    // can make use of it, and not worth much at all in the end game
+    let heavy_pieces = Color::ALL
+        .iter()
+        .map(|color| {
+            v.piece_counts[PieceIndex::new(*color, Piece::Rook)]
+                + v.piece_counts[PieceIndex::new(*color, Piece::Queen)]
+        })
+        .sum::<u8>() as f32;
+
+    let middle_game_bonus = 0.15 + 0.02 * heavy_pieces;
+    let end_game_bonus = 0.05;
+    let bonus = middle_game_bonus * (1.0 - v.end_game_weight) + end_game_bonus * v.end_game_weight;
+
+    *eval += Evaluation::ONE_PAWN * bonus;
+}
+
+#[cfg(test)]
+mod tests {
+    use weechess_core::{
+        notation::{try_from_notation, Fen},
+        Color,
+    };
+
+    use crate::eval::{Evaluation, StateVariation};
+
+    #[test]
+    fn test_side_to_move() {
+        let s = try_from_notation::<_, Fen>(Fen::DEFAULT).unwrap();
+        let s = StateVariation::from(&s);
+
+        let mut e1 = Evaluation::EVEN;
+        super::evaluate(&s, &Color::White, &mut e1, &mut false);
+
+        let mut e2 = Evaluation::EVEN;
+        super::evaluate(&s, &Color::Black, &mut e2, &mut false);
+
+        assert!(e1 > Evaluation::EVEN);
+        assert_eq!(e2, Evaluation::EVEN);
+    }
+
+    #[test]
+    fn test_heavy_pieces() {
+        let s1 = try_from_notation::<_, Fen>(Fen::DEFAULT).unwrap();
+        let s1 = StateVariation::from(&s1);
+        let mut e1 = Evaluation::EVEN;
+        super::evaluate(&s1, &Color::White, &mut e1, &mut false);
+
+        let s2 = try_from_notation::<_, Fen>("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let s2 = StateVariation::from(&s2);
+        let mut e2 = Evaluation::EVEN;
+        super::evaluate(&s2, &Color::White, &mut e2, &mut false);
+
+        assert!(e2 < e1, "{} < {}", e2, e1);
+    }
+}
@@ -1,31 +1,106 @@
 use std::{
     fmt::Display,
     ops::{Add, AddAssign, Deref, Mul, Neg, Sub, SubAssign},
+    sync::Arc,
 };
 
+use serde::{Deserialize, Serialize};
 use weechess_core::{
-    utils::ArrayMap, AttackGenerator, BitBoard, Color, Move, MoveGenerator, Piece, PieceIndex,
-    State,
+    pawns, utils::ArrayMap, AttackGenerator, BitBoard, Color, Move, MoveEstimator, MoveGenerator,
+    Piece, PieceIndex, State, Variant,
 };
 
+mod endgame_book;
+mod evaluate_antichess;
+mod evaluate_back_rank;
 mod evaluate_bad_pawns;
 mod evaluate_force_king_to_edge;
+mod evaluate_imbalance;
+mod evaluate_king_tropism;
 mod evaluate_piece_squares;
 mod evaluate_piece_worths;
+mod evaluate_tempo;
+mod pawn_cache;
 
 pub use evaluate_piece_worths::PIECE_PAWN_WORTHS;
 
+use pawn_cache::PawnCache;
+
 type EvaluationFunction =
     fn(v: &StateVariation<'_>, perspective: &Color, eval: &mut Evaluation, stop: &mut bool);
 
-const EVALUATORS: &'static [(f32, EvaluationFunction)] = &[
-    (1.0, evaluate_piece_worths::evaluate),
-    (0.8, evaluate_piece_squares::evaluate),
-    (1.0, evaluate_force_king_to_edge::evaluate),
-    (0.2, evaluate_bad_pawns::evaluate),
+/// How expensive an evaluation term is to compute. Cheap terms are always evaluated, while
+/// expensive ones are skipped when the cheap terms already put the position far enough
+/// outside of the search window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TermCost {
+    Cheap,
+    Expensive,
+
+    /// An expensive term that only looks at where the pawns are, so that its score can
+    /// be cached for each pawn structure. These terms can't stop the evaluation early.
+    PawnStructure,
+}
+
+/// A named evaluation term, along with how much it counts towards the total
+type EvaluationTerm = (&'static str, f32, TermCost, EvaluationFunction);
+
+const EVALUATORS: &'static [EvaluationTerm] = &[
+    (
+        "PieceWorths",
+        1.0,
+        TermCost::Cheap,
+        evaluate_piece_worths::evaluate,
+    ),
+    (
+        "PieceSquares",
+        0.8,
+        TermCost::Cheap,
+        evaluate_piece_squares::evaluate,
+    ),
+    (
+        "KingToEdge",
+        1.0,
+        TermCost::Cheap,
+        evaluate_force_king_to_edge::evaluate,
+    ),
+    (
+        "KingTropism",
+        1.0,
+        TermCost::Cheap,
+        evaluate_king_tropism::evaluate,
+    ),
+    (
+        "Imbalance",
+        1.0,
+        TermCost::Cheap,
+        evaluate_imbalance::evaluate,
+    ),
+    (
+        "BadPawns",
+        0.2,
+        TermCost::PawnStructure,
+        evaluate_bad_pawns::evaluate,
+    ),
+    (
+        "BackRank",
+        1.0,
+        TermCost::Expensive,
+        evaluate_back_rank::evaluate,
+    ),
+    ("Tempo", 1.0, TermCost::Cheap, evaluate_tempo::evaluate),
+    (
+        ANTICHESS_TERM,
+        1.0,
+        TermCost::Cheap,
+        evaluate_antichess::evaluate,
+    ),
 ];
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
+/// The only term that antichess positions are evaluated with
+const ANTICHESS_TERM: &str = "Antichess";
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
 pub struct Evaluation(i32);
 
 impl From<i32> for Evaluation {
@@ -98,15 +173,39 @@ impl Evaluation {
     pub const POS_INF: Evaluation = Evaluation(Self::ONE_PAWN.0 * 100);
     pub const NEG_INF: Evaluation = Evaluation(Self::ONE_PAWN.0 * -100);
 
+    /// Mates are scored past `POS_INF`, one less for every ply it takes to deliver them,
+    /// so that faster mates are preferred and the distance can be read back out
+    const MAX_MATE_PLY: i32 = Self::ONE_PAWN.0 * 10;
+
     pub fn mate_in_ply(ply: usize) -> Evaluation {
-        // Give a little bonus for faster mates
-        Evaluation::POS_INF + Evaluation::ONE_PAWN * i32::max(10 - (ply as i32), 0)
+        let ply = i32::try_from(ply).map_or(Self::MAX_MATE_PLY, |ply| ply.min(Self::MAX_MATE_PLY));
+        Evaluation(Self::POS_INF.0 + Self::MAX_MATE_PLY - ply)
+    }
+
+    /// The number of moves until mate, negative if we're the ones getting mated
+    pub fn mate_in_moves(self) -> Option<i32> {
+        if !self.is_terminal() {
+            return None;
+        }
+
+        let ply = Self::MAX_MATE_PLY - (self.0.abs() - Self::POS_INF.0);
+        let moves = (i32::max(ply, 1) + 1) / 2;
+        Some(if self.0 > 0 { moves } else { -moves })
     }
 
     pub fn is_terminal(self) -> bool {
         self <= Self::NEG_INF || self >= Self::POS_INF
     }
 
+    /// Converts a score from white's perspective to the given color's. Scores are
+    /// symmetric, so this also converts a color's score back to white's.
+    pub fn for_color(self, color: Color) -> Evaluation {
+        match color {
+            Color::White => self,
+            Color::Black => -self,
+        }
+    }
+
     pub fn cp(self) -> Centipawn {
         Centipawn(self.0 as f32)
     }
@@ -133,8 +232,9 @@ impl Display for Centipawn {
 struct StateVariation<'a> {
     state: &'a State,
     end_game_weight: f32,
-    piece_counts: ArrayMap<PieceIndex, u8>,
-    color_counts: ArrayMap<Color, u8>,
+    piece_counts: &'a ArrayMap<PieceIndex, u8>,
+    color_counts: &'a ArrayMap<Color, u8>,
+    material_scales: ArrayMap<Color, f32>,
 }
 
 impl Deref for StateVariation<'_> {
@@ -147,63 +247,133 @@ impl Deref for StateVariation<'_> {
 
 impl<'a> From<&'a State> for StateVariation<'a> {
     fn from(state: &'a State) -> Self {
-        let (piece_counts, color_counts) = {
-            let mut piece_counts = ArrayMap::default();
-            let mut color_counts = ArrayMap::default();
-            for color in Color::ALL {
-                for piece in Piece::ALL {
-                    let piece_index = PieceIndex::new(*color, *piece);
-                    let count = state.board().piece_occupancy(piece_index).count_ones() as u8;
-                    color_counts[*color] += count;
-                    piece_counts[piece_index] = count;
-                }
-            }
-
-            (piece_counts, color_counts)
-        };
-
-        let end_game_weight = {
-            let count_pieces = |piece: Piece| {
-                (piece_counts[PieceIndex::new(Color::White, piece)]
-                    + piece_counts[PieceIndex::new(Color::Black, piece)]) as f32
-            };
-
-            let w1 = 3.0;
-            let v1 = count_pieces(Piece::Pawn) / 16.0;
-
-            let w2 = 1.0;
-            let v2 = count_pieces(Piece::Queen) / 2.0;
-
-            let w3 = 1.0;
-            let v3 = (state.board().occupancy().count_ones() as f32) / 32.0;
-
-            1.0 - (w1 * v1 + w2 * v2 + w3 * v3) / (w1 + w2 + w3)
-        };
+        let end_game_weight = state.end_game_weight();
 
         Self {
             state,
-            piece_counts,
-            color_counts,
+            piece_counts: state.piece_counts(),
+            color_counts: state.color_counts(),
             end_game_weight,
+            material_scales: ArrayMap::filled(1.0),
         }
     }
 }
 
+/// Evaluates positions with a set of weighted terms. A clone shares its caches with the
+/// evaluator it was cloned from, so one evaluator can be shared by every search thread.
 #[derive(Clone)]
 pub struct Evaluator {
-    fns: &'static [(f32, EvaluationFunction)],
+    fns: Vec<EvaluationTerm>,
+    handicap: Option<(Color, f32)>,
+    stalemate_margin: Option<Evaluation>,
+    lazy_margin: Option<Evaluation>,
+    known_draws: bool,
+    pawn_cache: Arc<PawnCache>,
 }
 
 impl Default for Evaluator {
     fn default() -> Self {
-        Self { fns: &EVALUATORS }
+        Self {
+            fns: EVALUATORS.to_vec(),
+            handicap: None,
+            stalemate_margin: Some(Self::DEFAULT_STALEMATE_MARGIN),
+            lazy_margin: Some(Self::DEFAULT_LAZY_MARGIN),
+            known_draws: true,
+            pawn_cache: Arc::new(PawnCache::new()),
+        }
+    }
+}
+
+impl MoveEstimator for Evaluator {
+    fn estimate_move(&self, state: &State, mv: &Move) -> i32 {
+        self.estimate(state, mv).into()
     }
 }
 
 impl Evaluator {
+    /// The material advantage past which a stalemate is no longer treated as a draw
+    pub const DEFAULT_STALEMATE_MARGIN: Evaluation = Evaluation(300);
+
+    /// How far outside of the search window the cheap evaluation terms need to put a
+    /// position for the expensive ones to be skipped. This needs to be larger than the
+    /// expensive terms could ever add up to, otherwise they could change the outcome.
+    pub const DEFAULT_LAZY_MARGIN: Evaluation = Evaluation(250);
+
+    /// The most a side can be ahead by in an endgame that is known to be drawn. It's
+    /// kept above zero so that the side with more material still prefers keeping it.
+    pub const KNOWN_DRAW_MARGIN: Evaluation = Evaluation(20);
+
     #[cfg(test)]
-    fn just(fns: &'static [(f32, EvaluationFunction)]) -> Self {
-        Self { fns }
+    fn just(fns: &[EvaluationTerm]) -> Self {
+        Self {
+            fns: fns.to_vec(),
+            handicap: None,
+            stalemate_margin: None,
+            lazy_margin: None,
+            known_draws: false,
+            pawn_cache: Arc::new(PawnCache::new()),
+        }
+    }
+
+    /// Makes the evaluator undervalue the pieces of `color` by `handicap`
+    /// (a fraction between 0 and 1), so that it plays weaker.
+    pub fn with_handicap(self, color: Color, handicap: f32) -> Self {
+        Self {
+            handicap: Some((color, handicap.clamp(0.0, 1.0))),
+            ..self
+        }
+    }
+
+    /// Makes a side that is up by more than `margin` in material treat stalemate as
+    /// losing its advantage (and the other side as gaining it), rather than as a draw.
+    /// Without a margin, stalemate is always scored as even.
+    pub fn with_stalemate_margin(self, margin: Option<Evaluation>) -> Self {
+        Self {
+            stalemate_margin: margin,
+            ..self
+        }
+    }
+
+    /// Clamps the scores of endgames that are known to be drawn despite one side being up
+    /// material (like a rook pawn with the wrong colored bishop) to `KNOWN_DRAW_MARGIN`.
+    /// This is on by default, and turning it off leaves the scores to the terms alone.
+    pub fn with_known_draws(self, enabled: bool) -> Self {
+        Self {
+            known_draws: enabled,
+            ..self
+        }
+    }
+
+    /// The names of the evaluation terms and their weights
+    pub fn weights(&self) -> impl Iterator<Item = (&'static str, f32)> + '_ {
+        self.fns.iter().map(|(name, weight, _, _)| (*name, *weight))
+    }
+
+    /// Whether every term still has the weight it starts out with
+    pub fn has_default_weights(&self) -> bool {
+        self.weights().eq(EVALUATORS
+            .iter()
+            .map(|(name, weight, _, _)| (*name, *weight)))
+    }
+
+    /// Changes the weight of the evaluation term with the given name (see `weights`)
+    pub fn with_weight(mut self, name: &str, weight: f32) -> Self {
+        for term in self.fns.iter_mut().filter(|term| term.0 == name) {
+            term.1 = weight;
+        }
+
+        // The cached scores were weighted with the old weights
+        self.pawn_cache = Arc::new(PawnCache::new());
+        self
+    }
+
+    /// Skips the expensive evaluation terms for positions that are more than `margin`
+    /// outside of the search window. Without a margin, every term is always evaluated.
+    pub fn with_lazy_margin(self, margin: Option<Evaluation>) -> Self {
+        Self {
+            lazy_margin: margin,
+            ..self
+        }
     }
 
     pub fn estimate(&self, state: &State, mv: &Move) -> Evaluation {
@@ -260,59 +430,257 @@ impl Evaluator {
     }
 
     pub fn evaluate(&self, state: &State, perspective: Color, depth: usize) -> Evaluation {
-        let v = StateVariation::from(state);
-
-        let king_has_move = {
-            let king = state
-                .board()
-                .piece_occupancy(PieceIndex::new(state.turn_to_move(), Piece::King));
-            let king_square = king.first_square().unwrap();
-            let spaces_around_king = AttackGenerator::compute_king_attacks(king_square);
-            let valid_king_squares = spaces_around_king
-                & !state.board().occupancy()
-                & !state.board().colored_attacks(!state.turn_to_move());
-
-            valid_king_squares.any()
+        self.evaluate_within(
+            state,
+            perspective,
+            depth,
+            Evaluation::NEG_INF,
+            Evaluation::POS_INF,
+        )
+    }
+
+    /// Like `evaluate`, but the result is only exact within the `alpha` to `beta` window,
+    /// outside of which it might just be a rough estimate on the right side of the window
+    pub fn evaluate_within(
+        &self,
+        state: &State,
+        perspective: Color,
+        depth: usize,
+        alpha: Evaluation,
+        beta: Evaluation,
+    ) -> Evaluation {
+        // Flipping the perspective flips the window around too
+        let (alpha, beta) = match perspective {
+            Color::White => (alpha, beta),
+            Color::Black => (-beta, -alpha),
         };
 
-        // If the king can move, we're definitely not in checkmate or stalemate, so we can
-        // skip the expensive check for checkmate or stalemate through move generation
-        if !king_has_move {
+        self.evaluate_white_within(state, depth, alpha, beta)
+            .for_color(perspective)
+    }
+
+    /// Evaluates the position from white's perspective. Each term is only computed once
+    /// for each color, so use `Evaluation::for_color` to get the score for black instead
+    /// of evaluating the position again.
+    pub fn evaluate_white(&self, state: &State, depth: usize) -> Evaluation {
+        self.evaluate_white_within(state, depth, Evaluation::NEG_INF, Evaluation::POS_INF)
+    }
+
+    /// Like `evaluate_white`, with the `alpha` to `beta` window from white's perspective
+    pub fn evaluate_white_within(
+        &self,
+        state: &State,
+        depth: usize,
+        alpha: Evaluation,
+        beta: Evaluation,
+    ) -> Evaluation {
+        let won_by = |color: Color| Evaluation::mate_in_ply(depth).for_color(color);
+
+        // Some variants can end the game without anyone being mated
+        if let Some(winner) = state.variant_winner() {
+            return won_by(winner);
+        }
+
+        if !state.variant().has_royal_king() {
+            // Without a king to protect, running out of moves wins the game
+            if MoveGenerator::compute_legal_moves(state).is_empty() {
+                return won_by(state.turn_to_move());
+            }
+        } else if !Self::king_has_move(state) {
+            // If the king can move, we're definitely not in checkmate or stalemate, so we can
+            // skip the expensive check for checkmate or stalemate through move generation
             let legal_moves = MoveGenerator::compute_legal_moves(state);
             if legal_moves.is_empty() && state.is_check() {
-                return if state.turn_to_move() == perspective {
-                    -Evaluation::mate_in_ply(depth)
-                } else {
-                    Evaluation::mate_in_ply(depth)
-                };
+                return won_by(!state.turn_to_move());
             } else if legal_moves.is_empty() {
-                return Evaluation::EVEN;
+                return self.evaluate_stalemate(state, Color::White);
             }
         }
 
+        self.evaluate_terms_within(state, alpha, beta)
+    }
+
+    /// Like `evaluate_within`, for a position the caller already knows there are legal moves
+    /// in (because it generated them, say). Finding out whether the game is over is a good
+    /// part of the work of evaluating a position, and this skips it.
+    pub fn evaluate_playable_within(
+        &self,
+        state: &State,
+        perspective: Color,
+        depth: usize,
+        alpha: Evaluation,
+        beta: Evaluation,
+    ) -> Evaluation {
+        let (alpha, beta) = match perspective {
+            Color::White => (alpha, beta),
+            Color::Black => (-beta, -alpha),
+        };
+
+        let evaluation = match state.variant_winner() {
+            Some(winner) => Evaluation::mate_in_ply(depth).for_color(winner),
+            None => self.evaluate_terms_within(state, alpha, beta),
+        };
+
+        evaluation.for_color(perspective)
+    }
+
+    /// Adds up the terms for a position that isn't over, from white's perspective
+    fn evaluate_terms_within(
+        &self,
+        state: &State,
+        alpha: Evaluation,
+        beta: Evaluation,
+    ) -> Evaluation {
+        let mut v = StateVariation::from(state);
+        if let Some((color, handicap)) = self.handicap {
+            v.material_scales[color] = 1.0 - handicap;
+        }
+
+        // The other terms reward holding onto material and keeping the king safe,
+        // which is backwards when the aim is to give every piece away
+        if state.variant() == Variant::Antichess {
+            return self
+                .fns
+                .iter()
+                .filter(|(name, _, _, _)| *name == ANTICHESS_TERM)
+                .fold(Evaluation::EVEN, |eval, (_, w, _, f)| {
+                    let mut white = Evaluation::EVEN;
+                    f(&v, &Color::White, &mut white, &mut false);
+
+                    let mut black = Evaluation::EVEN;
+                    f(&v, &Color::Black, &mut black, &mut false);
+
+                    eval + (white - black) * (*w)
+                });
+        }
+
         let mut eval = Evaluation::EVEN;
         let mut stop = false;
 
-        for (w, f) in self.fns {
+        // Evaluate all the cheap terms first, so that we know whether
+        // it's worth bothering with the expensive ones at all
+        for cost in [
+            TermCost::Cheap,
+            TermCost::PawnStructure,
+            TermCost::Expensive,
+        ] {
+            if let (TermCost::PawnStructure, Some(margin)) = (cost, self.lazy_margin) {
+                if eval + margin <= alpha || eval - margin >= beta {
+                    break;
+                }
+            }
+
+            if cost == TermCost::PawnStructure {
+                let (white, black) = pawns::pawns(v.board(), Color::White);
+                eval += self
+                    .pawn_cache
+                    .get_or_insert_with(white, black, || self.evaluate_terms(&v, cost, &mut false));
+
+                continue;
+            }
+
+            eval += self.evaluate_terms(&v, cost, &mut stop);
+            if stop {
+                break;
+            }
+        }
+
+        // The terms can't tell that some endgames are drawn no matter how much material is left.
+        // These are only draws under the standard rules, since in the other variants the king
+        // can win by walking to the center, or isn't royal at all.
+        if self.known_draws && state.variant() == Variant::Standard {
+            match endgame_book::drawn_despite(state) {
+                Some(Color::White) => eval = eval.min(Self::KNOWN_DRAW_MARGIN),
+                Some(Color::Black) => eval = eval.max(-Self::KNOWN_DRAW_MARGIN),
+                None => {}
+            }
+        }
+
+        eval
+    }
+
+    /// Adds up the weighted terms of the given cost, from white's perspective
+    fn evaluate_terms(
+        &self,
+        v: &StateVariation<'_>,
+        cost: TermCost,
+        stop: &mut bool,
+    ) -> Evaluation {
+        let mut eval = Evaluation::EVEN;
+        for (_, w, _, f) in self.fns.iter().filter(|(_, _, c, _)| *c == cost) {
             let e = {
-                let mut e1 = Evaluation::EVEN;
-                f(&v, &perspective, &mut e1, &mut stop);
+                let mut white = Evaluation::EVEN;
+                f(v, &Color::White, &mut white, stop);
 
-                let mut e2 = Evaluation::EVEN;
-                f(&v, &!perspective, &mut e2, &mut stop);
+                let mut black = Evaluation::EVEN;
+                f(v, &Color::Black, &mut black, stop);
 
-                e1 - e2
+                white - black
             };
 
             eval += e * (*w);
 
-            if stop {
+            if *stop {
                 break;
             }
         }
 
         eval
     }
+
+    fn king_has_move(state: &State) -> bool {
+        // The attacks of a checking slider stop at the king, so the square behind it
+        // would look safe to move to. Being in check is rare enough to just generate moves.
+        if state.is_check() {
+            return false;
+        }
+
+        let king = state
+            .board()
+            .piece_occupancy(PieceIndex::new(state.turn_to_move(), Piece::King));
+        let king_square = king.first_square().unwrap();
+        let spaces_around_king = AttackGenerator::compute_king_attacks(king_square);
+        let valid_king_squares = spaces_around_king
+            & !state.board().occupancy()
+            & !state.board().colored_attacks(!state.turn_to_move());
+
+        valid_king_squares.any()
+    }
+
+    fn evaluate_stalemate(&self, state: &State, perspective: Color) -> Evaluation {
+        let Some(margin) = self.stalemate_margin else {
+            return Evaluation::EVEN;
+        };
+
+        let material = |color: Color| {
+            Piece::ALL
+                .iter()
+                .filter(|piece| **piece != Piece::King)
+                .fold(Evaluation::EVEN, |material, piece| {
+                    let count = state.piece_count(PieceIndex::new(color, *piece)) as i32;
+                    material + Evaluation::ONE_PAWN * PIECE_PAWN_WORTHS[*piece] * count
+                })
+        };
+
+        let advantage = material(perspective) - material(!perspective);
+        if advantage > margin || advantage < -margin {
+            -advantage
+        } else {
+            Evaluation::EVEN
+        }
+    }
+}
+
+/// Evaluates a position given as a FEN with just one term, for testing the terms
+#[cfg(test)]
+fn evaluate_term(term: EvaluationFunction, fen: &str, perspective: Color) -> Evaluation {
+    use weechess_core::notation::{try_from_notation, Fen};
+
+    let state = try_from_notation::<_, Fen>(fen).unwrap();
+    let v = StateVariation::from(&state);
+    let mut eval = Evaluation::EVEN;
+    term(&v, &perspective, &mut eval, &mut false);
+    eval
 }
 
 #[cfg(test)]
@@ -344,14 +712,77 @@ mod tests {
         let evaluator = Evaluator::default();
         let game_state = State::default();
 
-        assert_eq!(
-            evaluator.evaluate(&game_state, Color::White, 0),
-            Evaluation::EVEN
-        );
+        // The only difference between the sides is the tempo for the side to move
+        let e1 = evaluator.evaluate(&game_state, Color::White, 0);
+        let e2 = evaluator.evaluate(&game_state, Color::Black, 0);
+        assert!(e1 > Evaluation::EVEN);
+        assert!(e1 < Evaluation::ONE_PAWN);
+        assert_eq!(e1, -e2);
+    }
+
+    #[test]
+    fn test_perspectives() {
+        let evaluator = Evaluator::default();
+        for fen in [
+            Fen::DEFAULT,
+            "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18",
+            "k7/2Q5/1K6/8/8/8/8/8 b - - 0 1",
+            "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1",
+        ] {
+            let game_state = try_from_notation::<_, Fen>(fen).unwrap();
+            let white = evaluator.evaluate_white(&game_state, 0);
+
+            for color in Color::ALL {
+                assert_eq!(
+                    evaluator.evaluate(&game_state, *color, 0),
+                    white.for_color(*color),
+                    "{}",
+                    fen
+                );
+            }
+        }
+    }
 
+    #[test]
+    fn test_playable() {
+        let evaluator = Evaluator::default();
+        let windows = [
+            (Evaluation::NEG_INF, Evaluation::POS_INF),
+            (Evaluation::from(-50), Evaluation::from(50)),
+            (Evaluation::from(600), Evaluation::from(601)),
+        ];
+
+        for fen in [
+            Fen::DEFAULT,
+            "r1bq2k1/3nb1pp/p2p2r1/Pp1P1p2/1BN1p2P/6P1/1PPQ1P2/R3KB1R w KQ b6 0 18",
+            "8/8/8/8/8/2k5/2p5/2K1B3 w - - 0 1",
+            "k7/8/1K6/8/8/8/8/7R w - - 0 1",
+        ] {
+            let game_state = try_from_notation::<_, Fen>(fen).unwrap();
+            for color in Color::ALL {
+                for (alpha, beta) in windows {
+                    assert_eq!(
+                        evaluator.evaluate_playable_within(&game_state, *color, 0, alpha, beta),
+                        evaluator.evaluate_within(&game_state, *color, 0, alpha, beta),
+                        "{}",
+                        fen
+                    );
+                }
+            }
+        }
+
+        // The variant can still end the game with moves left
+        let game_state = try_from_notation::<_, Fen>("8/8/8/3K4/8/8/8/k7 b - - 0 1").unwrap();
+        let game_state = game_state.with_variant(Variant::KingOfTheHill);
         assert_eq!(
-            evaluator.evaluate(&game_state, Color::Black, 0),
-            Evaluation::EVEN
+            evaluator.evaluate_playable_within(
+                &game_state,
+                Color::Black,
+                2,
+                Evaluation::NEG_INF,
+                Evaluation::POS_INF
+            ),
+            -Evaluation::mate_in_ply(2)
         );
     }
 
@@ -361,11 +792,185 @@ mod tests {
         let v = StateVariation::from(&game_state);
         assert_eq!(v.end_game_weight, 0.0);
 
+        // The counts are borrowed from the state rather than counted again
+        assert!(std::ptr::eq(v.piece_counts, game_state.piece_counts()));
+        assert!(std::ptr::eq(v.color_counts, game_state.color_counts()));
+
         let game_state = try_from_notation::<_, Fen>("8/5k2/8/8/2R5/2K5/8/8 w - - 0 1").unwrap();
         let v = StateVariation::from(&game_state);
         assert!(v.end_game_weight > 0.95, "weight={}", v.end_game_weight);
     }
 
+    #[test]
+    fn test_mate_in_moves() {
+        assert_eq!(Evaluation::ONE_PAWN.mate_in_moves(), None);
+        assert_eq!(Evaluation::mate_in_ply(1).mate_in_moves(), Some(1));
+        assert_eq!(Evaluation::mate_in_ply(5).mate_in_moves(), Some(3));
+        assert_eq!(Evaluation::mate_in_ply(9).mate_in_moves(), Some(5));
+        assert_eq!(Evaluation::mate_in_ply(15).mate_in_moves(), Some(8));
+        assert_eq!(Evaluation::mate_in_ply(39).mate_in_moves(), Some(20));
+        assert_eq!((-Evaluation::mate_in_ply(40)).mate_in_moves(), Some(-20));
+        assert!(Evaluation::mate_in_ply(19) > Evaluation::mate_in_ply(21));
+        assert_eq!((-Evaluation::mate_in_ply(2)).mate_in_moves(), Some(-1));
+    }
+
+    #[test]
+    fn test_handicap() {
+        let game_state =
+            try_from_notation::<_, Fen>("4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1").unwrap();
+
+        let e1 = Evaluator::default().evaluate(&game_state, Color::White, 0);
+        let e2 = Evaluator::default()
+            .with_handicap(Color::White, 0.5)
+            .evaluate(&game_state, Color::White, 0);
+
+        assert!(e2 < e1, "{} < {}", e2, e1);
+        assert!(e2 > Evaluation::EVEN);
+    }
+
+    #[test]
+    fn test_known_draws() {
+        // Up a bishop and a pawn, but the defending king holds the corner
+        let game_state = try_from_notation::<_, Fen>("7k/8/8/7P/8/8/8/4KB2 w - - 0 1").unwrap();
+
+        let e1 = Evaluator::default().evaluate(&game_state, Color::White, 0);
+        let e2 =
+            Evaluator::default()
+                .with_known_draws(false)
+                .evaluate(&game_state, Color::White, 0);
+
+        assert_eq!(e1, Evaluator::KNOWN_DRAW_MARGIN);
+        assert!(e2 > Evaluation::ONE_PAWN * 3, "{}", e2);
+
+        // Two knights can't mate, but the king can still walk to the center
+        let game_state = try_from_notation::<_, Fen>("8/8/4k3/8/8/3NN3/8/4K3 w - - 0 1").unwrap();
+        let e3 = Evaluator::default().evaluate(
+            &game_state.clone().with_variant(Variant::KingOfTheHill),
+            Color::White,
+            0,
+        );
+        let e4 = Evaluator::default().evaluate(&game_state, Color::White, 0);
+
+        assert!(e3 > Evaluation::ONE_PAWN * 3, "{}", e3);
+        assert_eq!(e4, Evaluator::KNOWN_DRAW_MARGIN);
+    }
+
+    #[test]
+    fn test_stalemate_contempt() {
+        // Black is stalemated while down a queen
+        let game_state = try_from_notation::<_, Fen>("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+
+        let e1 = Evaluator::default().evaluate(&game_state, Color::White, 0);
+        let e2 = Evaluator::default().evaluate(&game_state, Color::Black, 0);
+        assert!(e1 < -Evaluator::DEFAULT_STALEMATE_MARGIN, "{}", e1);
+        assert_eq!(e1, -e2);
+
+        let e3 =
+            Evaluator::default()
+                .with_stalemate_margin(None)
+                .evaluate(&game_state, Color::White, 0);
+        assert_eq!(e3, Evaluation::EVEN);
+
+        // An extra pawn isn't enough to care about
+        let game_state = try_from_notation::<_, Fen>("k7/P7/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        let e4 = Evaluator::default().evaluate(&game_state, Color::White, 0);
+        assert_eq!(e4, Evaluation::EVEN);
+    }
+
+    #[test]
+    fn test_lazy_evaluation() {
+        // White is up a queen, but has a couple of doubled pawns
+        let game_state =
+            try_from_notation::<_, Fen>("3qk3/pppppppp/8/8/8/2P2P2/PPP2PPP/3QKQ2 w - - 0 1")
+                .unwrap();
+
+        let evaluator = Evaluator::default();
+        let full = evaluator.evaluate(&game_state, Color::White, 0);
+
+        // Far above the window, the pawn structure doesn't matter
+        let window = (Evaluation::EVEN - Evaluation::ONE_PAWN, Evaluation::EVEN);
+        let lazy = evaluator.evaluate_within(&game_state, Color::White, 0, window.0, window.1);
+        assert!(lazy >= window.1 && full >= window.1);
+        assert_ne!(lazy, full);
+
+        let eager = evaluator.with_lazy_margin(None).evaluate_within(
+            &game_state,
+            Color::White,
+            0,
+            window.0,
+            window.1,
+        );
+        assert_eq!(eager, full);
+    }
+
+    #[test]
+    fn test_weights() {
+        let game_state =
+            try_from_notation::<_, Fen>("4k3/pppppppp/8/8/8/2P2P2/PP3P2/4K3 w - - 0 1").unwrap();
+
+        let evaluate = |evaluator: Evaluator| evaluator.evaluate(&game_state, Color::White, 0);
+
+        let weights: Vec<_> = Evaluator::default().weights().collect();
+        assert_eq!(weights.len(), EVALUATORS.len());
+        assert!(weights.contains(&("BadPawns", 0.2)));
+
+        // White's pawns are a mess, which stops counting once the term is turned off
+        let e1 = evaluate(Evaluator::default());
+        let e2 = evaluate(Evaluator::default().with_weight("BadPawns", 0.0));
+        assert!(e1 < e2);
+
+        let e3 = evaluate(Evaluator::default().with_weight("Unknown", 0.0));
+        assert_eq!(e1, e3);
+    }
+
+    #[test]
+    fn test_variant_wins() {
+        // The black king is on the hill
+        let game_state = try_from_notation::<_, Fen>("8/8/8/4k3/8/8/8/4K3 w - - 0 1")
+            .unwrap()
+            .with_variant(Variant::KingOfTheHill);
+
+        let e1 = Evaluator::default().evaluate(&game_state, Color::Black, 2);
+        assert_eq!(e1, Evaluation::mate_in_ply(2));
+
+        // White has nothing left to give away
+        let game_state = try_from_notation::<_, Fen>("8/8/8/8/8/8/8/7k w - - 0 1")
+            .unwrap()
+            .with_variant(Variant::Antichess);
+
+        let e2 = Evaluator::default().evaluate(&game_state, Color::Black, 2);
+        assert_eq!(e2, -Evaluation::mate_in_ply(2));
+    }
+
+    #[test]
+    fn test_antichess() {
+        // White is up a lot of material, which is a long way from winning in antichess
+        let game_state =
+            try_from_notation::<_, Fen>("4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+
+        let evaluator = Evaluator::default();
+        let standard = evaluator.evaluate(&game_state, Color::White, 0);
+        let antichess = evaluator.evaluate(
+            &game_state.clone().with_variant(Variant::Antichess),
+            Color::White,
+            0,
+        );
+
+        assert!(standard > Evaluation::EVEN);
+        assert!(antichess < Evaluation::EVEN, "{}", antichess);
+
+        // None of the other terms count towards it
+        let evaluator = evaluator.with_weight(ANTICHESS_TERM, 0.0);
+        assert_eq!(
+            evaluator.evaluate(
+                &game_state.with_variant(Variant::Antichess),
+                Color::White,
+                0
+            ),
+            Evaluation::EVEN
+        );
+    }
+
     #[test]
     fn test_clearly_winning() {
         let game_state =
@@ -375,4 +980,54 @@ mod tests {
         let e2 = Evaluator::default().evaluate(&game_state, Color::Black, 0);
         assert!(e1 > e2);
     }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let evaluator = Evaluator::default();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/2p5/2p5/8/8/8/PPP5/4K3 w - - 0 1",
+            "4k3/p1p5/8/8/8/8/1P1P4/4K3 b - - 0 1",
+        ];
+
+        let states: Vec<State> = fens
+            .iter()
+            .map(|fen| try_from_notation::<_, Fen>(fen).unwrap())
+            .collect();
+
+        let evaluate = |evaluator: &Evaluator, state: &State| evaluator.evaluate_white(state, 0);
+
+        let expected: Vec<_> = states
+            .iter()
+            .map(|state| evaluate(&Evaluator::default(), state))
+            .collect();
+
+        // Every thread gets the same scores out of the shared cache as a fresh evaluator
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    // States can't be shared between threads, only the evaluator can
+                    let states: Vec<State> = fens
+                        .iter()
+                        .map(|fen| try_from_notation::<_, Fen>(fen).unwrap())
+                        .collect();
+
+                    for _ in 0..100 {
+                        for (state, expected) in states.iter().zip(expected.iter()) {
+                            assert_eq!(evaluate(&evaluator, state), *expected);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Changing a weight doesn't leave the old weight's scores behind
+        let reweighted = evaluator.clone().with_weight("BadPawns", 0.0);
+        let unweighted = Evaluator::default().with_weight("BadPawns", 0.0);
+        assert_eq!(
+            evaluate(&reweighted, &states[1]),
+            evaluate(&unweighted, &states[1])
+        );
+        assert_ne!(evaluate(&reweighted, &states[1]), expected[1]);
+    }
 }
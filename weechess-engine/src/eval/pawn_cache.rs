@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+use weechess_core::BitBoard;
+
+use super::Evaluation;
+
+const SHARD_COUNT: usize = 64;
+const SLOTS_PER_SHARD: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct PawnEntry {
+    white: BitBoard,
+    black: BitBoard,
+    evaluation: Evaluation,
+}
+
+/**
+ * Remembers the score of the pawn structure terms for the pawn structures seen so far,
+ * which change a lot less often than the rest of the position. The cache is shared by
+ * every thread evaluating with the same evaluator, and split into shards that are each
+ * locked on their own. A thread that finds the shard it needs already locked skips the
+ * cache and computes the score itself, so sharing it never makes a search thread wait.
+ */
+pub(super) struct PawnCache {
+    shards: Vec<Mutex<Vec<Option<PawnEntry>>>>,
+}
+
+impl PawnCache {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT)
+                .map(|_| Mutex::new(vec![None; SLOTS_PER_SHARD]))
+                .collect(),
+        }
+    }
+
+    /// Finds the score of the given pawn structure, computing (and remembering) it if needed
+    pub fn get_or_insert_with<F>(&self, white: BitBoard, black: BitBoard, f: F) -> Evaluation
+    where
+        F: FnOnce() -> Evaluation,
+    {
+        let key = Self::key(white, black);
+        let shard = &self.shards[(key >> 32) as usize % SHARD_COUNT];
+        let slot = key as usize % SLOTS_PER_SHARD;
+
+        if let Ok(slots) = shard.try_lock() {
+            if let Some(entry) = slots[slot] {
+                if entry.white == white && entry.black == black {
+                    return entry.evaluation;
+                }
+            }
+        }
+
+        let evaluation = f();
+        if let Ok(mut slots) = shard.try_lock() {
+            slots[slot] = Some(PawnEntry {
+                white,
+                black,
+                evaluation,
+            });
+        }
+
+        evaluation
+    }
+
+    // Pawns only ever stand on the middle six ranks, so their bits need mixing
+    // up before the low and high bits of the key make for good indices
+    fn key(white: BitBoard, black: BitBoard) -> u64 {
+        let white: u64 = white.into();
+        let black: u64 = black.into();
+        (white.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ black.rotate_left(29))
+            .wrapping_mul(0xBF58_476D_1CE4_E5B9)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_pawn_cache() {
+        let cache = PawnCache::new();
+        let computed = Cell::new(0);
+        let compute = |evaluation: i32| {
+            computed.set(computed.get() + 1);
+            Evaluation::from(evaluation)
+        };
+
+        let white = BitBoard::from(0xFF00);
+        let black = BitBoard::from(0x00FF_0000_0000_0000);
+        assert_eq!(
+            cache.get_or_insert_with(white, black, || compute(10)),
+            Evaluation::from(10)
+        );
+        assert_eq!(
+            cache.get_or_insert_with(white, black, || compute(20)),
+            Evaluation::from(10)
+        );
+        assert_eq!(computed.get(), 1);
+
+        // Swapping the colors is a different structure
+        assert_eq!(
+            cache.get_or_insert_with(black, white, || compute(-10)),
+            Evaluation::from(-10)
+        );
+        assert_eq!(computed.get(), 2);
+    }
+}
@@ -1,8 +1,22 @@
 #![feature(generic_const_exprs)]
 #![feature(slice_split_once)]
 
+pub mod adjudication;
+pub mod annotation;
+#[cfg(feature = "async")]
+pub mod async_searcher;
 pub mod book;
+pub mod cache;
+pub mod config;
+pub mod crash;
+pub mod database;
 pub mod eval;
+pub mod mate;
+#[cfg(feature = "search-observer")]
+pub mod observer;
+pub mod profiles;
+#[cfg(all(test, feature = "eval-regression"))]
+mod regression;
 pub mod searcher;
 pub mod uci;
 pub mod version;
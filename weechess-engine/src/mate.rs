@@ -0,0 +1,333 @@
+use std::time::{Duration, Instant};
+
+use weechess_core::{Color, GameStatus, Move, MoveGenerator, State};
+
+// Proof and disproof numbers of solved nodes
+const INFINITY: u32 = u32::MAX;
+
+/// What a mate search found out about a position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MateOutcome {
+    /// The side to move can force a win along this line, which is as short as possible.
+    /// The defending moves along it are the ones that hold out the longest.
+    Mate(Vec<Move>),
+
+    /// The side to move can't force a win within the limit on plies
+    NoMate,
+
+    /// The search ran out of nodes before finding out either way
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct MateSearchResult {
+    pub outcome: MateOutcome,
+    pub nodes: usize,
+    pub elapsed: Duration,
+}
+
+/// Answers whether the side to move can force a win (a checkmate in standard chess, or
+/// whatever the variant counts as one) with a proof-number search. Unlike alpha-beta, it
+/// doesn't need an evaluation, and it spends its time on the lines where the defender has
+/// the fewest replies, which makes it a lot faster at solving composed problems.
+#[derive(Debug, Clone)]
+pub struct MateSearch {
+    max_plies: usize,
+    max_nodes: usize,
+}
+
+impl MateSearch {
+    pub const DEFAULT_MAX_NODES: usize = 1_000_000;
+
+    /// Only looks for wins within this many plies, so a mate in `n` moves takes `2n - 1`
+    pub fn new(max_plies: usize) -> Self {
+        Self {
+            max_plies,
+            max_nodes: Self::DEFAULT_MAX_NODES,
+        }
+    }
+
+    /// Gives up after creating this many nodes, which also bounds the memory the search uses
+    pub fn with_max_nodes(self, max_nodes: usize) -> Self {
+        Self { max_nodes, ..self }
+    }
+
+    /// Searches each odd number of plies in turn, so that the first win found is the shortest
+    pub fn run(&self, state: &State) -> MateSearchResult {
+        self.run_until(state, || false)
+    }
+
+    /// Like `run`, but gives up (with an unknown outcome) as soon as `is_cancelled` says so
+    pub fn run_until<F>(&self, state: &State, mut is_cancelled: F) -> MateSearchResult
+    where
+        F: FnMut() -> bool,
+    {
+        let start_time = Instant::now();
+        let mut nodes = 0;
+        let mut outcome = MateOutcome::NoMate;
+        for plies in (1..=self.max_plies).step_by(2) {
+            let mut tree = Tree::new(state.turn_to_move(), plies);
+            outcome = tree.solve(
+                state,
+                self.max_nodes.saturating_sub(nodes),
+                &mut is_cancelled,
+            );
+            nodes += tree.nodes.len();
+            if outcome != MateOutcome::NoMate {
+                break;
+            }
+        }
+
+        MateSearchResult {
+            outcome,
+            nodes,
+            elapsed: start_time.elapsed(),
+        }
+    }
+}
+
+struct Node {
+    mv: Option<Move>,
+    parent: usize,
+    first_child: usize,
+    child_count: usize,
+    proof: u32,
+    disproof: u32,
+}
+
+impl Node {
+    fn is_solved(&self) -> bool {
+        self.proof == 0 || self.disproof == 0
+    }
+}
+
+struct Tree {
+    attacker: Color,
+    max_plies: usize,
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(attacker: Color, max_plies: usize) -> Self {
+        Self {
+            attacker,
+            max_plies,
+            nodes: vec![],
+        }
+    }
+
+    fn solve(
+        &mut self,
+        state: &State,
+        max_nodes: usize,
+        is_cancelled: &mut dyn FnMut() -> bool,
+    ) -> MateOutcome {
+        let (proof, disproof) = self.initial_numbers(state, 0);
+        self.nodes.push(Node {
+            mv: None,
+            parent: 0,
+            first_child: 0,
+            child_count: 0,
+            proof,
+            disproof,
+        });
+
+        while !self.nodes[0].is_solved() {
+            if self.nodes.len() >= max_nodes || is_cancelled() {
+                return MateOutcome::Unknown;
+            }
+
+            let (leaf, leaf_state, plies) = self.most_proving(state);
+            self.expand(leaf, &leaf_state, plies);
+            self.update_ancestors(leaf, plies);
+        }
+
+        if self.nodes[0].proof != 0 {
+            return MateOutcome::NoMate;
+        }
+
+        let mut line = vec![];
+        let mut index = 0;
+        let mut plies = 0;
+        while self.nodes[index].child_count > 0 {
+            let children = self.proven_children(index);
+            let lengths = children.map(|child| (self.mate_length(child, plies + 1), child));
+            let (_, child) = if plies % 2 == 0 {
+                lengths.min_by_key(|(length, _)| *length).unwrap()
+            } else {
+                lengths.max_by_key(|(length, _)| *length).unwrap()
+            };
+
+            line.extend(self.nodes[child].mv);
+            index = child;
+            plies += 1;
+        }
+
+        MateOutcome::Mate(line)
+    }
+
+    /// Follows the children that would solve their parents the soonest down to a leaf
+    fn most_proving(&self, state: &State) -> (usize, State, usize) {
+        let mut index = 0;
+        let mut state = state.clone();
+        let mut plies = 0;
+        while self.nodes[index].child_count > 0 {
+            let children = self.children(index);
+            index = if plies % 2 == 0 {
+                children.min_by_key(|child| self.nodes[*child].proof)
+            } else {
+                children.min_by_key(|child| self.nodes[*child].disproof)
+            }
+            .unwrap();
+
+            state = State::by_performing_move(&state, &self.nodes[index].mv.unwrap()).unwrap();
+            plies += 1;
+        }
+
+        (index, state, plies)
+    }
+
+    fn expand(&mut self, index: usize, state: &State, plies: usize) {
+        let first_child = self.nodes.len();
+        for result in MoveGenerator::compute_legal_moves(state).moves() {
+            let (proof, disproof) = self.initial_numbers(&result.1, plies + 1);
+            self.nodes.push(Node {
+                mv: Some(result.0),
+                parent: index,
+                first_child: 0,
+                child_count: 0,
+                proof,
+                disproof,
+            });
+        }
+
+        self.nodes[index].first_child = first_child;
+        self.nodes[index].child_count = self.nodes.len() - first_child;
+    }
+
+    fn update_ancestors(&mut self, mut index: usize, mut plies: usize) {
+        loop {
+            let children = self.children(index);
+            let (proof, disproof) = if plies.is_multiple_of(2) {
+                (
+                    children.clone().map(|c| self.nodes[c].proof).min(),
+                    Some(sum(children.map(|c| self.nodes[c].disproof))),
+                )
+            } else {
+                (
+                    Some(sum(children.clone().map(|c| self.nodes[c].proof))),
+                    children.map(|c| self.nodes[c].disproof).min(),
+                )
+            };
+
+            self.nodes[index].proof = proof.unwrap();
+            self.nodes[index].disproof = disproof.unwrap();
+            if index == 0 {
+                break;
+            }
+
+            index = self.nodes[index].parent;
+            plies -= 1;
+        }
+    }
+
+    /// Solves finished games and positions at the limit on plies right away. Otherwise,
+    /// the fewer moves a side has, the easier it should be to prove they lose.
+    fn initial_numbers(&self, state: &State, plies: usize) -> (u32, u32) {
+        let moves = MoveGenerator::compute_legal_moves(state).moves().len();
+        let status = if moves == 0 || state.variant_winner().is_some() {
+            state.game_status()
+        } else {
+            GameStatus::Ongoing
+        };
+
+        match status {
+            GameStatus::Won(winner) if winner == self.attacker => (0, INFINITY),
+            GameStatus::Won(_) | GameStatus::Drawn => (INFINITY, 0),
+            GameStatus::Ongoing if plies >= self.max_plies => (INFINITY, 0),
+            GameStatus::Ongoing if plies.is_multiple_of(2) => (1, moves as u32),
+            GameStatus::Ongoing => (moves as u32, 1),
+        }
+    }
+
+    fn children(&self, index: usize) -> std::ops::Range<usize> {
+        let node = &self.nodes[index];
+        node.first_child..node.first_child + node.child_count
+    }
+
+    fn proven_children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.children(index)
+            .filter(|child| self.nodes[*child].proof == 0)
+    }
+
+    /// How many plies it takes to win from a proven node, with the attacker
+    /// picking the quickest win and the defender the slowest loss
+    fn mate_length(&self, index: usize, plies: usize) -> usize {
+        let lengths = self
+            .proven_children(index)
+            .map(|child| self.mate_length(child, plies + 1));
+
+        let length = if plies.is_multiple_of(2) {
+            lengths.min()
+        } else {
+            lengths.max()
+        };
+
+        length.map_or(0, |length| length + 1)
+    }
+}
+
+fn sum(numbers: impl Iterator<Item = u32>) -> u32 {
+    numbers.fold(0, |total, n| total.saturating_add(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weechess_core::notation::{into_notation, lan::Lan, try_from_notation, Fen};
+
+    fn solve(fen: &str, max_plies: usize) -> MateOutcome {
+        let state = try_from_notation::<_, Fen>(fen).unwrap();
+        MateSearch::new(max_plies).run(&state).outcome
+    }
+
+    fn lan(moves: &[Move]) -> Vec<String> {
+        moves
+            .iter()
+            .map(|mv| into_notation::<_, Lan>(mv).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_mate_search() {
+        // Morphy's problem, where the only way to mate in two is to offer the rook
+        let morphy = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        assert_eq!(solve(morphy, 1), MateOutcome::NoMate);
+        let MateOutcome::Mate(line) = solve(morphy, 3) else {
+            panic!("expected a mate");
+        };
+
+        assert_eq!(line.len(), 3);
+        assert_eq!(lan(&line)[0], "a1a6");
+
+        // A quicker mate is found even with plenty of plies to spare
+        let back_rank = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1";
+        let MateOutcome::Mate(line) = solve(back_rank, 5) else {
+            panic!("expected a mate");
+        };
+
+        assert_eq!(lan(&line), vec!["d1d8"]);
+
+        let state = State::default();
+        assert_eq!(MateSearch::new(3).run(&state).outcome, MateOutcome::NoMate);
+        assert_eq!(
+            MateSearch::new(5).with_max_nodes(100).run(&state).outcome,
+            MateOutcome::Unknown
+        );
+
+        // A cancelled search gives up without an answer
+        let state = try_from_notation::<_, Fen>(morphy).unwrap();
+        let result = MateSearch::new(3).run_until(&state, || true);
+        assert_eq!(result.outcome, MateOutcome::Unknown);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+use weechess_core::{Move, State};
+
+use crate::{eval::Evaluation, searcher::EvaluationKind};
+
+/// Hooks into the nodes of the main search, for gathering statistics or building
+/// visualizations that the searcher doesn't provide itself. Every callback does
+/// nothing by default, so implementations only need the ones they care about.
+///
+/// The observer is shared by all the search threads, so the callbacks for
+/// different lines (and different depths) can arrive interleaved, and they're
+/// called on the search's hot path, so they should be quick. Nodes in the
+/// quiescence search aren't reported.
+pub trait SearchObserver: Send + Sync {
+    /// A position is about to be searched `remaining_depth` plies deep, with the
+    /// given window from the perspective of the side to move
+    fn on_node_enter(
+        &self,
+        state: &State,
+        ply: usize,
+        remaining_depth: usize,
+        alpha: Evaluation,
+        beta: Evaluation,
+    ) {
+        _ = (state, ply, remaining_depth, alpha, beta);
+    }
+
+    /// A position has been searched, with its score from the perspective of the side
+    /// to move. Nodes cut short by the search being stopped don't report leaving.
+    fn on_node_exit(&self, state: &State, ply: usize, evaluation: Evaluation) {
+        _ = (state, ply, evaluation);
+    }
+
+    /// A move was good enough that the rest of the moves in the position were skipped,
+    /// where `move_number` is how many moves were searched up to and including it
+    fn on_cutoff(&self, state: &State, ply: usize, mv: Move, move_number: usize) {
+        _ = (state, ply, mv, move_number);
+    }
+
+    /// The transposition table had an entry for the position searched deep enough to use
+    fn on_transposition_hit(
+        &self,
+        state: &State,
+        ply: usize,
+        evaluation: Evaluation,
+        kind: EvaluationKind,
+    ) {
+        _ = (state, ply, evaluation, kind);
+    }
+}
+
+impl fmt::Debug for dyn SearchObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SearchObserver")
+    }
+}
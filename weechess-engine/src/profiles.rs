@@ -0,0 +1,364 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use weechess_core::{pgn::Pgn, State};
+
+use crate::book::{BookError, BookLearning, OpeningBook};
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(std::io::Error),
+    Book(BookError),
+
+    /// Profile names are used as directory names, so they're kept
+    /// to letters, digits, `-` and `_`
+    InvalidName(String),
+    NotFound(String),
+    AlreadyExists(String),
+    InvalidEcoRange(String),
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Io(e) => write!(f, "unable to access book profile: {}", e),
+            ProfileError::Book(e) => write!(f, "{}", e),
+            ProfileError::InvalidName(name) => write!(f, "invalid profile name: {}", name),
+            ProfileError::NotFound(name) => write!(f, "no book profile named {}", name),
+            ProfileError::AlreadyExists(name) => {
+                write!(f, "a book profile named {} already exists", name)
+            }
+            ProfileError::InvalidEcoRange(range) => write!(f, "invalid ECO range: {}", range),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+/// A range of ECO codes like `B20-B99`, or a single code like `C42`. A shorter
+/// code stands for every code that starts with it, so `C6` covers `C60` to `C69`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcoRange {
+    first: String,
+    last: String,
+}
+
+impl EcoRange {
+    pub fn contains(&self, eco: &str) -> bool {
+        let eco = eco.to_ascii_uppercase();
+        self.first <= eco && eco <= self.last
+    }
+
+    fn bound(code: &str, padding: char) -> Option<String> {
+        let code = code.trim().to_ascii_uppercase();
+        let mut chars = code.chars();
+        let valid = matches!(chars.next(), Some('A'..='E'))
+            && code.len() <= 3
+            && chars.all(|c| c.is_ascii_digit());
+
+        valid.then(|| {
+            let padding: String = std::iter::repeat_n(padding, 3 - code.len()).collect();
+            code + &padding
+        })
+    }
+}
+
+impl FromStr for EcoRange {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        match (Self::bound(first, '0'), Self::bound(last, '9')) {
+            (Some(first), Some(last)) => Ok(Self { first, last }),
+            _ => Err(ProfileError::InvalidEcoRange(s.to_string())),
+        }
+    }
+}
+
+/// Which games to import into a profile. A game has to be in one of the ECO ranges
+/// and played by one of the players (as either color), if there are any of them.
+#[derive(Debug, Clone, Default)]
+pub struct ImportFilter {
+    pub eco: Vec<EcoRange>,
+
+    /// Matched case insensitively against any part of the White and Black tags
+    pub players: Vec<String>,
+}
+
+impl ImportFilter {
+    /// Games without an ECO tag are classified by their moves instead
+    pub fn matches(&self, pgn: &Pgn) -> bool {
+        let tag = |name: &str| {
+            pgn.tags
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let eco = tag("ECO").or_else(|| pgn.opening().map(|opening| opening.eco.as_str()));
+        if !self.eco.is_empty() && !eco.is_some_and(|eco| self.eco.iter().any(|r| r.contains(eco)))
+        {
+            return false;
+        }
+
+        let players: Vec<String> = [tag("White"), tag("Black")]
+            .into_iter()
+            .flatten()
+            .map(str::to_lowercase)
+            .collect();
+
+        self.players.is_empty()
+            || self.players.iter().any(|player| {
+                let player = player.to_lowercase();
+                players.iter().any(|p| p.contains(&player))
+            })
+    }
+}
+
+/// A named opening book, like `aggressive` or `solid`, with its own learned move
+/// weights. Each profile is a directory with the games its book is built from.
+#[derive(Debug, Clone)]
+pub struct BookProfile {
+    name: String,
+    dir: PathBuf,
+}
+
+impl BookProfile {
+    const GAMES_FILE_NAME: &'static str = "games.pgn";
+    const LEARNING_FILE_NAME: &'static str = "learning.cbor";
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn games_path(&self) -> PathBuf {
+        self.dir.join(Self::GAMES_FILE_NAME)
+    }
+
+    pub fn learning_path(&self) -> PathBuf {
+        self.dir.join(Self::LEARNING_FILE_NAME)
+    }
+
+    /// The book built from the profile's games, which is empty until some are imported
+    pub fn book(&self, max_depth: usize) -> Result<OpeningBook, ProfileError> {
+        let path = self.games_path();
+        if !path.exists() {
+            return Ok(OpeningBook::empty());
+        }
+
+        OpeningBook::load(path, max_depth).map_err(ProfileError::Book)
+    }
+
+    pub fn learning(&self) -> Result<BookLearning, ProfileError> {
+        BookLearning::load(self.learning_path()).map_err(ProfileError::Book)
+    }
+
+    /// Adds the games that pass the filter to the profile's book, returning how many
+    /// were imported. Games that can't be parsed, or that don't start from the
+    /// standard starting position, are skipped.
+    pub fn import(&self, contents: &str, filter: &ImportFilter) -> Result<usize, ProfileError> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.games_path())
+            .map_err(ProfileError::Io)?;
+
+        let mut imported = 0;
+        for pgn in Pgn::parse_all(contents).filter_map(Result::ok) {
+            if pgn.initial_state != State::default() || !filter.matches(&pgn) {
+                continue;
+            }
+
+            writeln!(file, "{}", pgn).map_err(ProfileError::Io)?;
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+}
+
+/// Where the user's data is kept, which is `$WEECHESS_DATA_DIR` if it's set, or
+/// else the platform's usual place for it (like `~/.local/share/weechess`)
+pub fn user_data_dir() -> Option<PathBuf> {
+    let env_dir = |name| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    env_dir(BookProfiles::DATA_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .or_else(|| env_dir("XDG_DATA_HOME").map(|dir| Path::new(&dir).join("weechess")))
+        .or_else(|| env_dir("APPDATA").map(|dir| Path::new(&dir).join("weechess")))
+        .or_else(|| env_dir("HOME").map(|dir| Path::new(&dir).join(".local/share/weechess")))
+}
+
+/// The book profiles kept in a directory, one subdirectory per profile
+#[derive(Debug, Clone)]
+pub struct BookProfiles {
+    dir: PathBuf,
+}
+
+impl BookProfiles {
+    /// Overrides where the user's data is kept, see `user_data_dir`
+    pub const DATA_DIR_ENV_VAR: &'static str = "WEECHESS_DATA_DIR";
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The profiles in the user's data directory, see `user_data_dir`
+    pub fn in_user_data_dir() -> Option<Self> {
+        Some(Self::new(user_data_dir()?.join("book-profiles")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The names of all the profiles, in alphabetical order
+    pub fn names(&self) -> Result<Vec<String>, ProfileError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(ProfileError::Io(e)),
+        };
+
+        let mut names = vec![];
+        for entry in entries {
+            let entry = entry.map_err(ProfileError::Io)?;
+            if entry.path().is_dir() {
+                names.extend(entry.file_name().to_str().map(String::from));
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn get(&self, name: &str) -> Result<BookProfile, ProfileError> {
+        let profile = self.profile(name)?;
+        if !profile.dir.is_dir() {
+            return Err(ProfileError::NotFound(name.to_string()));
+        }
+
+        Ok(profile)
+    }
+
+    pub fn create(&self, name: &str) -> Result<BookProfile, ProfileError> {
+        let profile = self.profile(name)?;
+        if profile.dir.exists() {
+            return Err(ProfileError::AlreadyExists(name.to_string()));
+        }
+
+        std::fs::create_dir_all(&profile.dir).map_err(ProfileError::Io)?;
+        Ok(profile)
+    }
+
+    /// Deletes a profile, along with its games and learned weights
+    pub fn remove(&self, name: &str) -> Result<(), ProfileError> {
+        let profile = self.get(name)?;
+        std::fs::remove_dir_all(profile.dir).map_err(ProfileError::Io)
+    }
+
+    fn profile(&self, name: &str) -> Result<BookProfile, ProfileError> {
+        let is_valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if !is_valid {
+            return Err(ProfileError::InvalidName(name.to_string()));
+        }
+
+        Ok(BookProfile {
+            name: name.to_string(),
+            dir: self.dir.join(name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"
+[White "Tal, Mikhail"]
+[Black "Smyslov, Vasily"]
+[ECO "B10"]
+
+1. e4 c6 2. d4 d5 1-0
+
+[White "Petrosian, Tigran"]
+[Black "Spassky, Boris"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1/2-1/2
+
+[White "Fischer, Robert"]
+[Black "Tal, Mikhail"]
+
+1. d4 Nf6 2. c4 g6 0-1
+"#;
+
+    #[test]
+    fn test_eco_range() {
+        let range: EcoRange = "B20-B99".parse().unwrap();
+        assert!(range.contains("B20"));
+        assert!(range.contains("b90"));
+        assert!(!range.contains("C00"));
+
+        let prefix: EcoRange = "C6".parse().unwrap();
+        assert!(prefix.contains("C60") && prefix.contains("C69"));
+        assert!(!prefix.contains("C70"));
+
+        assert!("F00".parse::<EcoRange>().is_err());
+        assert!("B2x".parse::<EcoRange>().is_err());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = std::env::temp_dir().join(format!("weechess-profiles-{}", std::process::id()));
+        let profiles = BookProfiles::new(&dir);
+        assert!(profiles.names().unwrap().is_empty());
+
+        let profile = profiles.create("aggressive").unwrap();
+        profiles.create("solid").unwrap();
+        assert_eq!(profiles.names().unwrap(), vec!["aggressive", "solid"]);
+        assert!(matches!(
+            profiles.create("solid"),
+            Err(ProfileError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            profiles.get("../solid"),
+            Err(ProfileError::InvalidName(_))
+        ));
+
+        // Tal's games, as long as they're in a king's pawn opening. The second game
+        // doesn't have an ECO tag, but it's classified from its moves.
+        assert!(profile
+            .book(10)
+            .unwrap()
+            .lookup(&State::default())
+            .is_none());
+        let filter = ImportFilter {
+            eco: vec!["B".parse().unwrap()],
+            players: vec!["tal".to_string()],
+        };
+        assert_eq!(profile.import(GAMES, &filter).unwrap(), 1);
+
+        let filter = ImportFilter {
+            eco: vec!["C60-C99".parse().unwrap()],
+            players: vec![],
+        };
+        assert_eq!(profile.import(GAMES, &filter).unwrap(), 1);
+
+        let book = profiles.get("aggressive").unwrap().book(10).unwrap();
+        assert_eq!(book.lookup(&State::default()).unwrap().len(), 1);
+
+        profiles.remove("aggressive").unwrap();
+        assert!(matches!(
+            profiles.get("aggressive"),
+            Err(ProfileError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
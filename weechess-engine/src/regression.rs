@@ -0,0 +1,155 @@
+//! Positions with scores and moves that any version of the engine should agree with, at
+//! shallow depths. They're only checked with `cargo test --features eval-regression`,
+//! since searching all of them takes a while (especially without `--release`).
+
+use weechess_core::{
+    notation::{into_notation, lan::Lan, try_from_notation, Fen},
+    State,
+};
+
+use crate::{
+    eval::{Evaluation, Evaluator},
+    searcher::{SearchOptions, SearchResult, Searcher},
+};
+
+const CORPUS: &str = include_str!("regression.tsv");
+
+// Every search uses the same seed and a single thread, so that failures can be reproduced
+const SEED: u64 = 0;
+
+/// What the score of a position has to be, from the perspective of the side to move
+#[derive(Debug)]
+enum ExpectedScore {
+    Any,
+    Mate,
+    Between(Option<i32>, Option<i32>),
+}
+
+impl ExpectedScore {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "-" => Some(Self::Any),
+            "mate" => Some(Self::Mate),
+            _ => {
+                let (min, max) = s.split_once("..")?;
+                let bound = |b: &str| match b {
+                    "" => Some(None),
+                    _ => b.parse().ok().map(Some),
+                };
+
+                Some(Self::Between(bound(min)?, bound(max)?))
+            }
+        }
+    }
+
+    fn matches(&self, evaluation: Evaluation) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Mate => evaluation.mate_in_moves().is_some_and(|moves| moves > 0),
+            Self::Between(min, max) => {
+                let cp = i32::from(evaluation);
+                min.is_none_or(|min| cp >= min) && max.is_none_or(|max| cp <= max)
+            }
+        }
+    }
+}
+
+struct Case<'a> {
+    state: State,
+    score: ExpectedScore,
+
+    /// Any of these moves is accepted as the best one, and any move if there are none
+    best_moves: Vec<&'a str>,
+    depth: usize,
+    description: &'a str,
+}
+
+/// Reads the corpus, which has one position per line as its FEN, the expected score
+/// (`min..max` in centipawns with either bound optional, `mate`, or `-` for any), the
+/// best moves in long algebraic notation (or `-`), the depth to search to and a description
+fn parse_corpus() -> Vec<Case<'static>> {
+    CORPUS
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [fen, score, best_moves, depth, description] = fields[..] else {
+                panic!("expected 5 fields on line {}", number + 1);
+            };
+
+            Case {
+                state: try_from_notation::<_, Fen>(fen)
+                    .unwrap_or_else(|_| panic!("invalid FEN on line {}", number + 1)),
+                score: ExpectedScore::parse(score)
+                    .unwrap_or_else(|| panic!("invalid score on line {}", number + 1)),
+                best_moves: match best_moves {
+                    "-" => vec![],
+                    _ => best_moves.split(' ').collect(),
+                },
+                depth: depth
+                    .parse()
+                    .unwrap_or_else(|_| panic!("invalid depth on line {}", number + 1)),
+                description,
+            }
+        })
+        .collect()
+}
+
+fn search(state: &State, depth: usize) -> SearchResult {
+    let (handle, control, receiver) = Searcher::new().analyze(
+        state.clone(),
+        SEED,
+        Evaluator::default(),
+        SearchOptions {
+            max_depth: Some(depth),
+            max_threads: Some(1),
+            ..Default::default()
+        },
+        None,
+    );
+
+    drop(receiver);
+    _ = control;
+    handle.join().unwrap().1
+}
+
+#[test]
+fn test_corpus() {
+    let cases = parse_corpus();
+    assert!(cases.len() >= 200);
+
+    let mut failures = vec![];
+    for case in cases.iter() {
+        let result = search(&case.state, case.depth);
+        let best_move = result
+            .best_move()
+            .map(|mv| into_notation::<_, Lan>(&mv).to_string());
+
+        let score_ok = case.score.matches(result.evaluation);
+        let move_ok = case.best_moves.is_empty()
+            || best_move
+                .as_deref()
+                .is_some_and(|mv| case.best_moves.contains(&mv));
+
+        if !score_ok || !move_ok {
+            failures.push(format!(
+                "{} ({}): expected {:?} and {:?}, got {} and {}",
+                into_notation::<_, Fen>(&case.state),
+                case.description,
+                case.score,
+                case.best_moves,
+                result.evaluation,
+                best_move.as_deref().unwrap_or("no move")
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} positions failed:\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
@@ -1,38 +1,116 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc, Arc, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, OnceLock, RwLock,
     },
     thread,
+    time::{Duration, Instant},
 };
 
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use weechess_core::{
-    Hash, Move, MoveGenerationBuffer, MoveGenerator, MoveResult, PseudoLegalMove, State,
-    ZobristHasher,
+    AttackGenerator, BitBoard, Hash, Line, Move, MoveGenerationBuffer, MoveGenerator, MoveResult,
+    Piece, PieceIndex, PseudoLegalMove, State, ZobristHasher,
 };
 
-use crate::eval::{self, Evaluation};
+#[cfg(feature = "search-observer")]
+use crate::observer::SearchObserver;
+use crate::{
+    crash,
+    eval::{self, Evaluation},
+    mate::{MateOutcome, MateSearch},
+};
 
 use rayon::prelude::*;
 
-const DEFAULT_TRANSPOSITION_TABLE_SIZE_MB: usize = 1024;
+pub const DEFAULT_TRANSPOSITION_TABLE_SIZE_MB: usize = 1024;
 
 // There's a balance to this right now between lock contention
 // and the amount of work that can be shared between threads. This
-// value seems to be a good balance on my machine right now.
-const DEFAULT_MAX_THREAD_COUNT: usize = 32;
+// value seems to be a good balance on my machine right now, and
+// `weechess calibrate` can find a better one for other machines.
+pub const DEFAULT_MAX_THREAD_COUNT: usize = 32;
+
+// How often to report progress while a single depth is still being searched
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// How often the node count is checked when progress is also reported every so many nodes
+const PROGRESS_NODES_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Once the transposition tables are this full, entries left over from
+// previous iterations are swept out to make room for new ones
+const SWEEP_SATURATION: f32 = 0.9;
+
+// If the tables are still this full after a sweep, they're grown (if allowed)
+const GROWTH_SATURATION: f32 = 0.75;
+
+// A king with at most this many squares to run to is considered boxed in when
+// deciding whether to extend the search to look for a mate
+const MATE_THREAT_MAX_FLIGHT_SQUARES: u32 = 1;
+
+// How many plies into quiescence search quiet checking moves (and the replies
+// to them) are still searched, so that mates just past the horizon are found
+const QUIESCENCE_CHECK_PLIES: usize = 2;
+
+// The number of generations after which an entry is considered stale
+const STALE_GENERATION_AGE: u8 = 2;
+
+// How many nodes the proof-number search gets to find a mate before the alpha-beta search
+// takes over, which is plenty for most composed problems
+const MATE_SEARCH_NODES: usize = 200_000;
+
+// How many nodes each thread searches between adding them to the shared count
+// and checking whether the search should stop
+const NODE_CHECK_INTERVAL: usize = 10000;
+
+// Reports a node-level event to the search's observer, if there is one. Without the
+// `search-observer` feature this expands to nothing, so it costs nothing in the search.
+macro_rules! observe {
+    ($observer:expr, $callback:ident($($arg:expr),* $(,)?)) => {
+        #[cfg(feature = "search-observer")]
+        if let Some(observer) = &$observer.observer {
+            observer.$callback($($arg),*);
+        }
+    };
+}
+
+// The most random noise added to move ordering estimates in the helper threads
+const MAX_ORDERING_JITTER: i32 = 10;
+
+// Below this rate of cutoffs on the first move, the move ordering is struggling
+// enough that the helper threads get less noise added to it
+const ORDERING_JITTER_MIN_RATE: f32 = 0.85;
 
 type RandomNumberGenerator = ChaCha8Rng;
 
 #[derive(Debug)]
 pub enum StatusEvent {
-    BestMove {
-        line: Vec<Move>,
+    /// The principal variation changed, either because a depth finished
+    /// or because the search was stopped partway through one. The line is
+    /// boxed since it's far bigger than any of the other events.
+    PvUpdate {
+        depth: u32,
+        line: Box<Line>,
+        evaluation: eval::Evaluation,
+    },
+    /// One of the lines after the best one, when searching for more than one. The
+    /// rank counts from the best line, so the second best line has a rank of 2.
+    MultiPvUpdate {
+        depth: u32,
+        rank: usize,
+        line: Box<Line>,
         evaluation: eval::Evaluation,
     },
+    /// Every thread finished searching to this depth
+    IterationComplete {
+        depth: u32,
+        stats: SearchStats,
+    },
     Progress {
         depth: u32,
         nodes_searched: usize,
@@ -42,16 +120,620 @@ pub enum StatusEvent {
         message: String,
         kind: WarningKind,
     },
+    /// A move in the root position finished being searched at this depth. Only
+    /// the first move searched (and any move that improves on the best one so far)
+    /// gets an exact score, the others are upper bounds.
+    RootMove {
+        depth: u32,
+        number: usize,
+        mv: Move,
+        evaluation: eval::Evaluation,
+        kind: EvaluationKind,
+    },
+    /// A copy of the tables taken while searching this depth, when asked for with
+    /// `SearchOptions::snapshot_interval`. The search carries on as it was.
+    Snapshot {
+        depth: u32,
+        snapshot: Box<SearchArtifactSnapshot>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchStats {
+    pub nodes_searched: usize,
+    pub transposition_saturation: f32,
+
+    /// How well the moves were ordered during the last iteration
+    pub ordering: OrderingStats,
+
+    /// What happened at the nodes searched during the last iteration
+    pub nodes: NodeStats,
+}
+
+/// Counts of how often the transposition table was useful, and how often
+/// the search looked further down a line than it was asked to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NodeStats {
+    pub transposition_probes: usize,
+
+    /// The probes that found an entry searched deep enough to be used
+    pub transposition_hits: usize,
+
+    /// How many moves were searched deeper because they looked forcing
+    pub extensions: usize,
+
+    /// How many times a line was scored as a draw for repeating a position
+    pub repetition_draws: usize,
+}
+
+impl NodeStats {
+    /// The fraction of probes that were hits, if there were any
+    pub fn transposition_hit_rate(&self) -> Option<f32> {
+        if self.transposition_probes > 0 {
+            Some(self.transposition_hits as f32 / self.transposition_probes as f32)
+        } else {
+            None
+        }
+    }
+
+    fn merge(&mut self, other: &NodeStats) {
+        self.transposition_probes += other.transposition_probes;
+        self.transposition_hits += other.transposition_hits;
+        self.extensions += other.extensions;
+        self.repetition_draws += other.repetition_draws;
+    }
+}
+
+/// Counts how often the first move searched was good enough for a beta cutoff,
+/// which is how often the move ordering got it right
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrderingStats {
+    pub cutoffs: usize,
+    pub first_move_cutoffs: usize,
+}
+
+impl OrderingStats {
+    /// The fraction of cutoffs that happened on the first move, if there were any
+    pub fn first_move_rate(&self) -> Option<f32> {
+        if self.cutoffs > 0 {
+            Some(self.first_move_cutoffs as f32 / self.cutoffs as f32)
+        } else {
+            None
+        }
+    }
+
+    fn merge(&mut self, other: &OrderingStats) {
+        self.cutoffs += other.cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+    }
+}
+
+/// The move ordering state of a single search thread
+struct MoveOrdering {
+    /// The range of random noise added to move estimates, so that the lazy SMP
+    /// threads don't all search the same moves in the same order
+    jitter: i32,
+    stats: OrderingStats,
+
+    /// How the root moves did in the previous depth, which orders them in this one
+    root_moves: Vec<RootMoveScore>,
+
+    /// How the root moves did in this depth, in the order they were searched
+    searched_root_moves: Vec<RootMoveScore>,
+}
+
+/// How a root move did in a search of the root position
+#[derive(Debug, Clone, Copy)]
+struct RootMoveScore {
+    mv: Move,
+    evaluation: Evaluation,
+
+    /// Moves that fail low all score the same, but the ones that took more nodes
+    /// to refute tend to be the better ones
+    nodes_searched: usize,
+}
+
+impl MoveOrdering {
+    /// The noise sends the helper threads down different paths than the main
+    /// thread, but it also makes their move ordering worse. The main thread (and
+    /// so a single-threaded search) doesn't get any, and the helper threads get
+    /// less of it when the ordering is already struggling.
+    fn for_thread(index: usize, previous: &OrderingStats) -> Self {
+        let jitter = match previous.first_move_rate() {
+            _ if index == 0 => 0,
+            Some(rate) if rate < ORDERING_JITTER_MIN_RATE => MAX_ORDERING_JITTER / 2,
+            _ => MAX_ORDERING_JITTER,
+        };
+
+        Self {
+            jitter,
+            stats: OrderingStats::default(),
+            root_moves: vec![],
+            searched_root_moves: vec![],
+        }
+    }
+
+    fn with_root_moves(self, root_moves: &[RootMoveScore]) -> Self {
+        Self {
+            root_moves: root_moves.to_vec(),
+            ..self
+        }
+    }
+}
+
+/// Independent streams of random numbers for the parts of a search, all derived from
+/// the seed of the search. Each stream only depends on the seed and what it's for, so
+/// a search is reproducible with the same seed and thread count no matter the order
+/// the threads happen to draw their numbers in.
+#[derive(Debug, Clone, Copy)]
+struct RandomStreams {
+    seed: u64,
+}
+
+impl RandomStreams {
+    fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// The stream used to seed a new hasher
+    fn hasher(&self) -> RandomNumberGenerator {
+        self.stream(0)
+    }
+
+    /// The stream of a single search thread at a single depth
+    fn thread(&self, depth: usize, index: usize) -> RandomNumberGenerator {
+        self.stream(((depth as u64) << 32 | index as u64) + 1)
+    }
+
+    fn stream(&self, stream: u64) -> RandomNumberGenerator {
+        let mut rng = RandomNumberGenerator::seed_from_u64(self.seed);
+        rng.set_stream(stream);
+        rng
+    }
 }
 
 #[derive(Debug)]
 pub enum WarningKind {
     TranspositionTableSaturated,
+
+    /// The proof-number search ruled out a mate in as many moves as the search was
+    /// asked to look for, so the search is only looking for the best move
+    NoMate,
 }
 
 #[derive(Debug)]
 pub enum ControlEvent {
     Stop,
+
+    /// Stops the search because the time for it ran out
+    TimeUp,
+}
+
+/// Why a search came to an end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Every depth up to the limit was searched
+    DepthReached,
+
+    /// A forced mate was found, so there was no point in searching deeper
+    MateFound,
+
+    /// The search was told to stop
+    Stopped,
+
+    /// The time for the search ran out
+    TimeUp,
+
+    /// The search used up its budget of nodes
+    NodeLimit,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::DepthReached => write!(f, "depth reached"),
+            StopReason::MateFound => write!(f, "mate found"),
+            StopReason::Stopped => write!(f, "stopped"),
+            StopReason::TimeUp => write!(f, "time up"),
+            StopReason::NodeLimit => write!(f, "node limit reached"),
+        }
+    }
+}
+
+/// The outcome of a whole search
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub reason: StopReason,
+
+    /// The deepest depth that every thread finished searching
+    pub depth: u32,
+
+    /// The best line found, which is empty if the search was stopped before finding one
+    pub line: Line,
+    pub evaluation: eval::Evaluation,
+
+    /// The next best lines with their scores, best first, when searching for more than one
+    /// line. They're from the deepest depth that all of them were searched to.
+    pub alternative_lines: Vec<(eval::Evaluation, Line)>,
+    pub stats: SearchStats,
+    pub elapsed: Duration,
+}
+
+impl SearchResult {
+    pub fn best_move(&self) -> Option<Move> {
+        self.line.first().copied()
+    }
+}
+
+/// When to search promotions to rooks and bishops. A queen can do everything they can
+/// (unlike a knight), so they're only worth it in the rare positions where promoting to
+/// a queen would stalemate the opponent, but they still add to the branching wherever
+/// pawns are about to promote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnderpromotionPolicy {
+    /// Searched like any other move
+    All,
+
+    /// Only searched after all the other moves failed to produce a cutoff, and only
+    /// when they give check in the quiescence search
+    #[default]
+    Deferred,
+}
+
+impl UnderpromotionPolicy {
+    /// Whether the move is a promotion that isn't searched until every other move has been
+    fn defers(&self, game_state: &State, mv: &Move) -> bool {
+        *self == UnderpromotionPolicy::Deferred
+            && game_state.variant().has_royal_king()
+            && matches!(mv.promotion(), Some(Piece::Rook | Piece::Bishop))
+    }
+}
+
+impl FromStr for UnderpromotionPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" => Ok(UnderpromotionPolicy::All),
+            "deferred" => Ok(UnderpromotionPolicy::Deferred),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for UnderpromotionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnderpromotionPolicy::All => write!(f, "all"),
+            UnderpromotionPolicy::Deferred => write!(f, "deferred"),
+        }
+    }
+}
+
+/// Riskier ways of cutting the search short, which give up a little accuracy to get
+/// deeper. They're all off by default, since what they're worth depends on the rest of
+/// the engine. `weechess spsa` tunes them against each other with self-play.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PruningOptions {
+    /// Searches quiet moves that come late in the move order less deeply
+    pub late_move_reduction: Option<LateMoveReduction>,
+
+    /// Skips quiet moves one ply before the horizon when the static evaluation of the
+    /// position is still this far below what the side to move can already get
+    pub futility_margin: Option<Evaluation>,
+
+    /// Searches each depth within this far of the previous depth's score first, and only
+    /// searches it again without the window if the score turns out to be outside of it
+    pub aspiration_window: Option<Evaluation>,
+}
+
+/// Reduces the depth of the `n`th move searched in a node with `d` plies left by
+/// `base + ln(d) * ln(n) / divisor` plies. A move that does better than expected at
+/// the reduced depth is searched again to the full depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LateMoveReduction {
+    pub base: f32,
+    pub divisor: f32,
+}
+
+impl LateMoveReduction {
+    /// The first few moves of a node are the likeliest to be best, so they're never reduced
+    const MIN_MOVE_NUMBER: usize = 4;
+
+    /// Nodes this close to the horizon don't have enough depth left to take away
+    const MIN_REMAINING_DEPTH: usize = 3;
+
+    fn reduction(&self, remaining_depth: usize, move_number: usize) -> usize {
+        if remaining_depth < Self::MIN_REMAINING_DEPTH || move_number < Self::MIN_MOVE_NUMBER {
+            return 0;
+        }
+
+        let reduction = self.base
+            + (remaining_depth as f32).ln() * (move_number as f32).ln()
+                / self.divisor.max(f32::EPSILON);
+
+        // The move is still searched at least one ply deep
+        (reduction.max(0.0) as usize).min(remaining_depth - 2)
+    }
+}
+
+/// Limits and behaviours for a single search
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// The maximum depth to search to, or unlimited if not set
+    pub max_depth: Option<usize>,
+
+    /// Makes the engine undervalue its own pieces by this fraction (between 0 and 1)
+    /// to weaken its play. The handicap applies to the side to move in the searched position.
+    pub handicap: Option<f32>,
+
+    /// Only search deep enough to find a forced mate in this many moves. This
+    /// can be combined with `max_depth`, in which case the shallower limit is used.
+    pub mate: Option<usize>,
+
+    /// Lets the transposition tables grow up to this many megabytes if they fill up
+    /// during the search. New tables start out no bigger than this either. By default,
+    /// they start at `DEFAULT_TRANSPOSITION_TABLE_SIZE_MB` and never grow.
+    pub max_table_size_mb: Option<usize>,
+
+    /// How often to report progress while a depth is still being searched, which
+    /// is once a second by default. Depths that finish sooner don't report any.
+    pub progress_interval: Option<Duration>,
+
+    /// Also reports progress whenever this many more nodes have been searched within a
+    /// depth. The threads only add to the node count every `NODE_CHECK_INTERVAL` nodes,
+    /// so reports can come that many nodes late.
+    pub progress_nodes: Option<usize>,
+
+    /// How many threads to search every depth with. By default, the first few depths are
+    /// searched with just one and the rest with up to `DEFAULT_MAX_THREAD_COUNT`. The best
+    /// number depends on the machine, see `weechess calibrate`.
+    pub max_threads: Option<usize>,
+
+    /// Sends a `StatusEvent::Snapshot` of the tables this often while searching, like
+    /// for saving them in case the search doesn't get to finish
+    pub snapshot_interval: Option<Duration>,
+
+    /// Keeps only this many of the deepest entries in snapshots of the tables, so that
+    /// they can be written out without writing out all of the tables. By default, every
+    /// entry is kept.
+    pub max_snapshot_entries: Option<usize>,
+
+    /// Stops the search after roughly this many nodes, no matter how long they take.
+    /// Unlike a time limit, this doesn't depend on how fast (or busy) the machine is.
+    pub max_nodes: Option<usize>,
+
+    /// When to search promotions to rooks and bishops, see `UnderpromotionPolicy`
+    pub underpromotions: UnderpromotionPolicy,
+
+    /// How many of the best lines to find, instead of just the best one. The lines
+    /// after the first are reported with `StatusEvent::MultiPvUpdate`.
+    pub multi_pv: Option<usize>,
+
+    /// How much of the search to cut short, see `PruningOptions`
+    pub pruning: PruningOptions,
+
+    /// Gets told about the nodes of the search as they're searched, see `SearchObserver`
+    #[cfg(feature = "search-observer")]
+    pub observer: Option<Arc<dyn SearchObserver>>,
+}
+
+impl SearchOptions {
+    fn depth_limit(&self) -> Option<usize> {
+        let mate_depth = self.mate.map(|moves| (moves * 2).saturating_sub(1).max(1));
+        match (self.max_depth, mate_depth) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// What the engine thinks of one position along a line
+#[derive(Debug, Clone)]
+pub struct LinePly {
+    /// The move that led to this position, or nothing for the position the line starts from
+    pub mv: Option<Move>,
+
+    /// The score of the position, from white's perspective so that the
+    /// scores along the line can be compared with each other
+    pub evaluation: eval::Evaluation,
+
+    /// What the engine would play here, unless the game is over (or nothing was searched)
+    pub best_move: Option<Move>,
+}
+
+/// A move in a line that can't be played in the position it comes up in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalMoveError {
+    /// How far into the line the move is, starting from 0
+    pub ply: usize,
+    pub mv: Move,
+}
+
+impl Display for IllegalMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "illegal move at ply {}: {}", self.ply, self.mv)
+    }
+}
+
+impl std::error::Error for IllegalMoveError {}
+
+/// A move found in the transposition tables, see [`SearchArtifact::hash_line`]
+#[derive(Debug, Clone, Copy)]
+pub struct HashLinePly {
+    pub mv: Move,
+
+    /// The stored score of the position the move is played from, from the
+    /// perspective of the side playing it
+    pub evaluation: eval::Evaluation,
+
+    /// Whether the score is exact or only a bound. A move stored with a lower
+    /// bound is the refutation that cut the search of its position short.
+    pub kind: EvaluationKind,
+
+    /// How many plies the position was searched to
+    pub depth: usize,
+}
+
+/// What every node of a search needs, none of which changes during the search
+struct SearchContext<'a> {
+    evaluator: &'a eval::Evaluator,
+    options: &'a SearchOptions,
+    token: &'a CancellationToken,
+    hasher: &'a ZobristHasher,
+    state_history: &'a StateHistory,
+    transpositions: &'a TranspositionTableAccess,
+}
+
+/// What a single search thread keeps track of while it searches
+struct SearchThread {
+    rng: RandomNumberGenerator,
+    ordering: MoveOrdering,
+    nodes_searched: usize,
+    node_stats: NodeStats,
+
+    /// Buffers for the moves of the nodes being searched, which are handed back once
+    /// a node is done with them so that every node doesn't need to allocate its own
+    move_buffers: Vec<Vec<PseudoLegalMove>>,
+}
+
+impl SearchThread {
+    fn new(rng: RandomNumberGenerator, ordering: MoveOrdering) -> Self {
+        Self {
+            rng,
+            ordering,
+            nodes_searched: 0,
+            node_stats: NodeStats::default(),
+            move_buffers: vec![],
+        }
+    }
+
+    fn take_move_buffer(&mut self) -> Vec<PseudoLegalMove> {
+        self.move_buffers.pop().unwrap_or_default()
+    }
+
+    fn return_move_buffer(&mut self, buffer: Vec<PseudoLegalMove>) {
+        self.move_buffers.push(buffer);
+    }
+
+    /// Counts a new node, and returns whether the search should stop. To avoid spending
+    /// a lot of time waiting for atomic operations, the nodes are only counted (and the
+    /// token checked) every so often.
+    fn enter_node(&mut self, token: &CancellationToken) -> bool {
+        self.nodes_searched += 1;
+        self.nodes_searched.is_multiple_of(NODE_CHECK_INTERVAL) && token.checkpoint()
+    }
+}
+
+/// What each of the threads searching the root starts with
+struct ThreadData {
+    game_state: State,
+    rng: RandomNumberGenerator,
+    best_move: Option<Move>,
+    search_depth: usize,
+    ordering: MoveOrdering,
+    reports_root_moves: bool,
+}
+
+type ThreadResult = (Evaluation, Option<Move>, MoveOrdering, NodeStats);
+
+enum ThreadMessage {
+    RootMove(StatusEvent),
+    Finished(Result<Vec<ThreadResult>, SearchInterrupt>),
+}
+
+/// Where a node is in the search tree, and how much deeper it's searched
+#[derive(Debug, Clone, Copy)]
+struct SearchNode {
+    max_depth: usize,
+    current_depth: usize,
+    current_extension: usize,
+    kind: NodeKind,
+}
+
+impl SearchNode {
+    fn root(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            current_depth: 0,
+            current_extension: 0,
+            kind: NodeKind::Pv,
+        }
+    }
+
+    /// The number of moves actually made to get here. Extensions push the current depth
+    /// further than that, but this is what matters when scoring mates.
+    fn ply(&self) -> usize {
+        self.current_depth - self.current_extension
+    }
+
+    fn remaining_depth(&self) -> usize {
+        self.max_depth - self.current_depth
+    }
+
+    /// The node after the move with the given index, searched `extension` plies deeper
+    fn child(&self, move_index: usize, extension: usize) -> Self {
+        Self {
+            max_depth: self.max_depth + extension,
+            current_depth: self.current_depth + 1 + extension,
+            current_extension: self.current_extension + extension,
+            kind: self.kind.child(move_index),
+        }
+    }
+
+    /// The same node, searched `reduction` plies less deep
+    fn reduced(&self, reduction: usize) -> Self {
+        Self {
+            max_depth: self.max_depth - reduction,
+            ..*self
+        }
+    }
+}
+
+/// The root position one thread searches, and how
+struct RootSearch<'a> {
+    game_state: &'a State,
+    max_depth: usize,
+
+    /// Searched first, ahead of every other move
+    prioritized_move: Option<Move>,
+
+    /// Left out of the search, see `Searcher::analyze_root`
+    excluded_moves: &'a [Move],
+
+    /// Scores outside of this window are only bounds
+    window: (Evaluation, Evaluation),
+}
+
+impl RootSearch<'_> {
+    /// Nothing can do better than mating on the next move, or worse than being mated now
+    fn full_window() -> (Evaluation, Evaluation) {
+        (
+            -eval::Evaluation::mate_in_ply(0),
+            eval::Evaluation::mate_in_ply(1),
+        )
+    }
+
+    /// A window around the best score of the previous depth, if there's an aspiration window
+    /// and a score to put it around. Mates are left alone, since their scores jump around.
+    fn aspiration_window(
+        options: &SearchOptions,
+        ordering: &MoveOrdering,
+        excluded_moves: &[Move],
+    ) -> (Evaluation, Evaluation) {
+        let (min, max) = Self::full_window();
+        let guess = ordering
+            .root_moves
+            .iter()
+            .map(|score| score.evaluation)
+            .max()
+            .filter(|evaluation| !evaluation.is_terminal() && excluded_moves.is_empty());
+
+        match (options.pruning.aspiration_window, guess) {
+            (Some(width), Some(guess)) => ((guess - width).max(min), (guess + width).min(max)),
+            _ => (min, max),
+        }
+    }
 }
 
 pub struct Searcher;
@@ -61,36 +743,78 @@ impl Searcher {
         Self
     }
 
+    /// Does the one-time setup a search would otherwise do when it starts, like building
+    /// the attack tables, starting the worker threads and allocating the transposition
+    /// tables, so that the first search doesn't lose any of its time to it. The given
+    /// artifact is reused if there is one, and new tables are only allocated otherwise.
+    pub fn warm_up<R: Rng>(&self, artifact: Option<SearchArtifact>, rng: &mut R) -> SearchArtifact {
+        AttackGenerator::initialize_tables();
+        _ = rayon::current_num_threads();
+        artifact.unwrap_or_else(|| SearchArtifact::new(rng))
+    }
+
     pub fn analyze(
         &self,
         state: State,
         rng_seed: u64,
         evaluator: eval::Evaluator,
-        max_depth: Option<usize>,
+        options: SearchOptions,
         previous_artifact: Option<SearchArtifact>,
     ) -> (
-        thread::JoinHandle<SearchArtifact>,
+        thread::JoinHandle<(SearchArtifact, SearchResult)>,
         mpsc::Sender<ControlEvent>,
         mpsc::Receiver<StatusEvent>,
     ) {
-        let rng = RandomNumberGenerator::seed_from_u64(rng_seed);
+        let (control, controller) = mpsc::channel();
+        let (search_handle, receiver) = self.analyze_with_control(
+            state,
+            rng_seed,
+            evaluator,
+            options,
+            previous_artifact,
+            (control.clone(), controller),
+        );
+
+        (search_handle, control, receiver)
+    }
+
+    /// Like `analyze`, but takes its control events from a channel the caller made
+    /// beforehand, so that they can be sent to it before the search has started
+    pub fn analyze_with_control(
+        &self,
+        state: State,
+        rng_seed: u64,
+        evaluator: eval::Evaluator,
+        options: SearchOptions,
+        previous_artifact: Option<SearchArtifact>,
+        control: (mpsc::Sender<ControlEvent>, mpsc::Receiver<ControlEvent>),
+    ) -> (
+        thread::JoinHandle<(SearchArtifact, SearchResult)>,
+        mpsc::Receiver<StatusEvent>,
+    ) {
+        crash::record_search(&state, rng_seed);
+
+        let streams = RandomStreams::new(rng_seed);
+        let evaluator = match options.handicap {
+            Some(handicap) => evaluator.with_handicap(state.turn_to_move(), handicap),
+            None => evaluator,
+        };
+
         let (tx1, rx1) = mpsc::channel();
-        let (tx2, rx2) = mpsc::channel();
-        let tx3 = tx2.clone();
+        let (tx3, rx2) = control;
         let control_handle = thread::spawn(move || {
             let sink = tx1;
             let controller = rx2;
 
             let (signal_token, listen_token) = CancellationToken::new();
             let search_handle = thread::spawn(move || {
-                let new_artifact = Self::analyze_iterative(
+                let outcome = Self::analyze_iterative(
                     state,
                     &evaluator,
-                    rng,
-                    max_depth,
+                    streams,
+                    &options,
                     listen_token,
                     previous_artifact,
-                    None,
                     &mut |event| {
                         // This can error if the receiver drops their end. That's ok
                         _ = sink.send(event);
@@ -101,214 +825,327 @@ impl Searcher {
                 tx3.send(ControlEvent::Stop).unwrap();
 
                 // Finally, return the new artifact so it can be passed into the next search iteration
-                new_artifact
+                outcome
             });
 
-            loop {
-                match controller.recv() {
-                    Ok(ControlEvent::Stop) => break,
-                    Err(mpsc::RecvError) => break,
-                }
-            }
+            let reason = match controller.recv() {
+                Ok(ControlEvent::TimeUp) => StopReason::TimeUp,
+                Ok(ControlEvent::Stop) | Err(mpsc::RecvError) => StopReason::Stopped,
+            };
 
-            signal_token.cancel();
+            signal_token.cancel(reason);
             search_handle.join().unwrap()
         });
 
         let _ = state;
-        (control_handle, tx2, rx1)
+        (control_handle, rx1)
     }
 
     fn analyze_iterative<F>(
         game_state: State,
         evaluator: &eval::Evaluator,
-        rng: RandomNumberGenerator,
-        max_depth: Option<usize>,
+        streams: RandomStreams,
+        options: &SearchOptions,
         token: CancellationToken,
         previous_artifact: Option<SearchArtifact>,
-        max_thread_count: Option<usize>,
         f: &mut F,
-    ) -> SearchArtifact
+    ) -> (SearchArtifact, SearchResult)
     where
         F: FnMut(StatusEvent),
     {
-        let max_depth = max_depth.unwrap_or(usize::MAX);
-        let mut rng = rng;
-
-        let (hasher, transpositions, mut state_history) = previous_artifact
-            .map(|a| (a.hasher, a.transpositions, a.state_history))
-            .unwrap_or_else(|| {
-                let hasher = ZobristHasher::with(&mut rng);
-                let state_history = StateHistory::new();
-                let transpositions = {
-                    const TABLE_COUNT: usize = 128;
-                    let tables = (0..TABLE_COUNT)
-                        .map(|_| {
-                            TranspositionTable::with_memory(
-                                DEFAULT_TRANSPOSITION_TABLE_SIZE_MB * 1024 * 1024 / TABLE_COUNT,
-                            )
-                        })
-                        .collect();
+        let start_time = Instant::now();
+        let max_depth = options.depth_limit().unwrap_or(usize::MAX);
+        let max_nodes = options.max_nodes;
+        let max_table_memory = options.max_table_size_mb.map(|mb| mb * 1024 * 1024);
 
-                    TranspositionTableAccess::with_tables(tables)
-                };
-
-                (hasher, transpositions, state_history)
-            });
+        let SearchArtifact {
+            hasher,
+            transpositions,
+            mut state_history,
+            root_moves,
+        } = previous_artifact.unwrap_or_else(|| {
+            let size_mb = options
+                .max_table_size_mb
+                .map_or(DEFAULT_TRANSPOSITION_TABLE_SIZE_MB, |mb| {
+                    mb.min(DEFAULT_TRANSPOSITION_TABLE_SIZE_MB)
+                });
+
+            SearchArtifact::with_table_size(&mut streams.hasher(), size_mb)
+        });
 
         let game_state_hash = hasher.hash(&game_state);
-        let mut nodes_searched = 0;
+
+        // A previous search of the same position can already order the first depth
+        let mut root_moves = root_moves
+            .filter(|(hash, _)| *hash == game_state_hash)
+            .map(|(_, moves)| moves)
+            .unwrap_or_default();
+        let token = match max_nodes {
+            Some(max_nodes) => token.with_node_limit(max_nodes),
+            None => token,
+        };
+
         let mut best_eval = eval::Evaluation::NEG_INF;
         let mut best_mv = None;
+        let mut best_line = Line::new();
+        let mut completed_depth = 0;
+        let mut ordering_stats = OrderingStats::default();
+        let mut node_stats = NodeStats::default();
+        let mut reason = StopReason::DepthReached;
+        let mut alternative_lines = vec![];
+
+        // There's no point in looking for more lines than there are moves
+        let multi_pv = options.multi_pv.unwrap_or(1).clamp(
+            1,
+            MoveGenerator::compute_legal_moves(&game_state)
+                .moves()
+                .len()
+                .max(1),
+        );
 
         // Mark that we've seen this state - this will help us avoid draws by repetition in winning states
         state_history.increment(game_state_hash);
 
-        for depth in 0..max_depth {
-            // Don't bother doing multiple threads if we're only searching a few moves
-            // as the OS overhead will likely outweigh the benefits of parallelism
-            let thread_count = max_thread_count.unwrap_or_else(|| {
-                if depth < 3 {
-                    1
-                } else {
-                    usize::min(rayon::max_num_threads(), DEFAULT_MAX_THREAD_COUNT)
-                }
-            });
+        let ctx = SearchContext {
+            evaluator,
+            options,
+            token: &token,
+            hasher: &hasher,
+            state_history: &state_history,
+            transpositions: &transpositions,
+        };
 
-            struct ThreadData {
-                rng: ChaCha8Rng,
-                game_state: State,
-                best_move: Option<Move>,
-                search_depth: usize,
-            }
+        // Proof-number searches solve most composed problems a lot quicker, and tell for
+        // sure when there isn't a mate in as many moves as asked for, so they go first
+        let proven_mate = options
+            .mate
+            .filter(|moves| *moves > 0 && multi_pv == 1)
+            .and_then(|moves| {
+                let result = MateSearch::new(moves * 2 - 1)
+                    .with_max_nodes(MATE_SEARCH_NODES)
+                    .run_until(&game_state, || token.is_cancelled());
+
+                match result.outcome {
+                    MateOutcome::Mate(line) if !line.is_empty() => Some(line),
+                    MateOutcome::NoMate => {
+                        f(StatusEvent::Warning {
+                            kind: WarningKind::NoMate,
+                            message: format!("no mate in {}", moves),
+                        });
 
-            // This is a variation of lazy SMP. We rely on the non-determanistic
-            // nature of move ordering and the transposition table to introduce parallelism
-            let thread_data: Vec<_> = (0..thread_count)
-                .map(|i| ThreadData {
-                    rng: ChaCha8Rng::seed_from_u64(rng.gen()),
-                    game_state: game_state.clone(),
-                    search_depth: {
-                        // We want a variety of search depths across the threads
-                        let stop_short = i % 2;
-                        depth.saturating_sub(stop_short) + 1
-                    },
-                    best_move: if i == 0 {
-                        // Only the first thread needs to search the best move,
-                        // otherwise we're just doing duplicate work at the top
-                        best_mv
-                    } else {
                         None
-                    },
-                })
-                .collect();
+                    }
+                    _ => None,
+                }
+            });
 
-            let results: Result<Vec<_>, SearchInterrupt> = {
-                thread_data
-                    .into_par_iter()
-                    .map(|data| {
-                        let game_state = data.game_state;
-                        let best_move = data.best_move;
-                        let search_depth = data.search_depth;
-                        let mut rng = data.rng;
-                        let mut nodes_searched = 0;
-                        let mut move_buffer = Vec::new();
-
-                        let result: Result<Evaluation, SearchInterrupt> = Self::analyze_recursive(
-                            &game_state,
-                            &evaluator,
-                            &token,
-                            &hasher,
-                            &state_history,
-                            &transpositions,
-                            search_depth,
-                            0,
-                            0,
-                            -eval::Evaluation::mate_in_ply(0),
-                            eval::Evaluation::mate_in_ply(0),
-                            best_move,
-                            &mut rng,
-                            &mut move_buffer,
-                            &mut nodes_searched,
-                        );
-
-                        result.map(|eval| (eval, nodes_searched))
-                    })
-                    .collect()
-            };
+        // A proven mate doesn't need searching, it's as good as it gets
+        let max_depth = match proven_mate {
+            Some(line) => {
+                best_eval = eval::Evaluation::mate_in_ply(line.len());
+                best_line = line.into_iter().collect();
+                completed_depth = best_line.len() as u32;
+                reason = StopReason::MateFound;
+                f(StatusEvent::PvUpdate {
+                    depth: completed_depth,
+                    evaluation: best_eval,
+                    line: Box::new(best_line.clone()),
+                });
 
-            match results {
-                Ok(evaluations) => {
-                    // Tally up the nodes searched across all threads
-                    nodes_searched += evaluations.iter().map(|(_, n)| n).sum::<usize>();
+                0
+            }
+            None => max_depth,
+        };
 
-                    // Find the best evaluation across all threads
-                    best_eval = *evaluations.iter().map(|(e, _)| e).max().unwrap();
+        let mut next_snapshot = options
+            .snapshot_interval
+            .map(|interval| Instant::now() + interval);
 
-                    f(StatusEvent::Progress {
-                        depth: (depth + 1) as u32,
-                        nodes_searched,
-                        transposition_saturation: transpositions.saturation(),
-                    });
+        'iterations: for depth in 0..max_depth {
+            crash::record_depth(depth + 1);
 
-                    let line: Vec<Move> = transpositions
-                        .iter_moves(&hasher, &game_state, depth)
-                        .map(|r| r.0)
-                        .collect();
+            // Entries that aren't touched again in this iteration will slowly age out
+            transpositions.next_generation();
 
-                    best_mv = line.first().copied();
+            // Don't bother doing multiple threads if we're only searching a few moves
+            // as the OS overhead will likely outweigh the benefits of parallelism
+            let thread_count = options.max_threads.unwrap_or_else(|| {
+                if depth < 3 {
+                    1
+                } else {
+                    usize::min(rayon::max_num_threads(), DEFAULT_MAX_THREAD_COUNT)
+                }
+            });
 
-                    assert!(!line.is_empty());
+            // Each line after the best one is searched with the first moves of the lines
+            // before it left out, by all the threads in turn
+            let mut excluded_moves: Vec<Move> = vec![];
+            let mut depth_lines = vec![];
+            for rank in 1..=multi_pv {
+                // This is a variation of lazy SMP. We rely on the non-determanistic
+                // nature of move ordering and the transposition table to introduce parallelism
+                let thread_data: Vec<_> = (0..thread_count)
+                    .map(|i| ThreadData {
+                        game_state: game_state.clone(),
+                        rng: streams.thread(depth, i),
+                        search_depth: {
+                            // We want a variety of search depths across the threads
+                            let stop_short = i % 2;
+                            depth.saturating_sub(stop_short) + 1
+                        },
+                        best_move: if i == 0 && excluded_moves.is_empty() {
+                            // Only the first thread needs to search the best move,
+                            // otherwise we're just doing duplicate work at the top
+                            best_mv
+                        } else {
+                            None
+                        },
+                        ordering: MoveOrdering::for_thread(i, &ordering_stats)
+                            .with_root_moves(&root_moves),
+                        // The helper threads would only report the same moves again, with
+                        // scores that don't line up with the main thread's
+                        reports_root_moves: i == 0,
+                    })
+                    .collect();
+
+                let results = Self::analyze_threads(
+                    &ctx,
+                    thread_data,
+                    &excluded_moves,
+                    depth,
+                    &mut next_snapshot,
+                    f,
+                );
 
-                    // Make sure that the line we're returning is actually valid
-                    debug_assert!({
-                        let mut game_state = game_state.clone();
-                        for mv in line.iter() {
-                            game_state = State::by_performing_move(&game_state, mv)
-                                .expect(&format!("invalid move: {}", mv));
+                match results {
+                    Ok(evaluations) if !excluded_moves.is_empty() => {
+                        for (_, _, ordering, nodes) in evaluations.iter() {
+                            ordering_stats.merge(&ordering.stats);
+                            node_stats.merge(nodes);
                         }
 
-                        true
-                    });
+                        let Some((evaluation, line)) =
+                            Self::alternative_line(&ctx, &game_state, &evaluations, depth)
+                        else {
+                            break;
+                        };
+
+                        f(StatusEvent::MultiPvUpdate {
+                            depth: (depth + 1) as u32,
+                            rank,
+                            evaluation,
+                            line: Box::new(line.clone()),
+                        });
+
+                        excluded_moves.push(line[0]);
+                        depth_lines.push((evaluation, line));
+                    }
+                    Ok(evaluations) => {
+                        // Tally up the cutoffs across all threads
+                        ordering_stats = OrderingStats::default();
+                        node_stats = NodeStats::default();
+                        for (_, _, ordering, nodes) in evaluations.iter() {
+                            ordering_stats.merge(&ordering.stats);
+                            node_stats.merge(nodes);
+                        }
 
-                    f(StatusEvent::BestMove {
-                        evaluation: best_eval,
-                        line,
-                    });
+                        // The main thread searched every root move to the full depth, unless
+                        // the root was already in the tables from a deeper search
+                        if !evaluations[0].2.searched_root_moves.is_empty() {
+                            root_moves = evaluations[0].2.searched_root_moves.clone();
+                        }
 
-                    // The best line in this position will lead to a forced mate
-                    if best_eval >= eval::Evaluation::POS_INF {
-                        // TODO: If this mate came from a quiessence search line
-                        // then there may be a better mate with depth greater than
-                        // the current search depth but less than this quiessence
-                        // search went. We should probably search for better mates
-                        // somehow, but for now we'll just end search and use the
-                        // forced mate line
-                        break;
-                    }
-                }
-                Err(SearchInterrupt) => {
-                    if let Some(x) = transpositions.find(game_state_hash) {
-                        if x.evaluation > best_eval {
-                            f(StatusEvent::BestMove {
-                                evaluation: x.evaluation,
-                                line: {
-                                    let line: Vec<Move> = transpositions
-                                        .iter_moves(&hasher, &game_state, depth)
-                                        .map(|r| r.0)
-                                        .collect();
-
-                                    assert!(!line.is_empty());
-
-                                    line
-                                },
-                            });
+                        // Find the best evaluation across all threads
+                        best_eval = *evaluations.iter().map(|(e, _, _, _)| e).max().unwrap();
+
+                        transpositions.relieve_pressure(max_table_memory);
+
+                        let line: Line = transpositions
+                            .iter_moves(&hasher, &game_state, depth)
+                            .map(|r| r.0)
+                            .collect();
+
+                        best_mv = line.first().copied();
+
+                        assert!(!line.is_empty());
+
+                        // Make sure that the line we're returning is actually valid
+                        debug_assert!({
+                            let mut game_state = game_state.clone();
+                            for mv in line.iter() {
+                                game_state = State::by_performing_move(&game_state, mv)
+                                    .unwrap_or_else(|_| panic!("invalid move: {}", mv));
+                            }
+
+                            true
+                        });
+
+                        best_line = line.clone();
+                        excluded_moves.extend(best_mv);
+                        completed_depth = (depth + 1) as u32;
+                        f(StatusEvent::PvUpdate {
+                            depth: (depth + 1) as u32,
+                            evaluation: best_eval,
+                            line: Box::new(line),
+                        });
+
+                        f(StatusEvent::IterationComplete {
+                            depth: (depth + 1) as u32,
+                            stats: SearchStats {
+                                nodes_searched: token.nodes_searched(),
+                                transposition_saturation: transpositions.saturation(),
+                                ordering: ordering_stats,
+                                nodes: node_stats,
+                            },
+                        });
+
+                        // The best line in this position will lead to a forced mate
+                        if best_eval >= eval::Evaluation::POS_INF {
+                            // TODO: If this mate came from a quiessence search line
+                            // then there may be a better mate with depth greater than
+                            // the current search depth but less than this quiessence
+                            // search went. We should probably search for better mates
+                            // somehow, but for now we'll just end search and use the
+                            // forced mate line
+                            reason = StopReason::MateFound;
+                            break 'iterations;
+                        }
+
+                        if max_nodes.is_some_and(|max_nodes| token.nodes_searched() >= max_nodes) {
+                            reason = StopReason::NodeLimit;
+                            break 'iterations;
                         }
                     }
+                    Err(SearchInterrupt) => {
+                        reason = token.reason().unwrap_or(StopReason::Stopped);
+
+                        // The unfinished depth may have already found a better move
+                        // than the last one, which is worth using over nothing
+                        if let Some(x) = transpositions.find(game_state_hash) {
+                            if x.evaluation > best_eval {
+                                let line: Line = transpositions
+                                    .iter_moves(&hasher, &game_state, depth)
+                                    .map(|r| r.0)
+                                    .collect();
+
+                                assert!(!line.is_empty());
+
+                                best_eval = x.evaluation;
+                                best_line = line.clone();
+                                f(StatusEvent::PvUpdate {
+                                    depth: (depth + 1) as u32,
+                                    evaluation: x.evaluation,
+                                    line: Box::new(line),
+                                });
+                            }
+                        }
 
-                    break;
+                        break 'iterations;
+                    }
                 }
             }
+
+            alternative_lines = depth_lines;
         }
 
         if transpositions.saturation() > 0.5 {
@@ -321,70 +1158,274 @@ impl Searcher {
             });
         }
 
-        SearchArtifact {
+        let result = SearchResult {
+            reason,
+            depth: completed_depth,
+            line: best_line,
+            evaluation: best_eval,
+            alternative_lines,
+            stats: SearchStats {
+                nodes_searched: token.nodes_searched(),
+                transposition_saturation: transpositions.saturation(),
+                ordering: ordering_stats,
+                nodes: node_stats,
+            },
+            elapsed: start_time.elapsed(),
+        };
+
+        let artifact = SearchArtifact {
             hasher,
             transpositions,
             state_history,
-        }
+            root_moves: Some((game_state_hash, root_moves)),
+        };
+
+        (artifact, result)
+    }
+
+    /// Searches the root with all of the threads at once, reporting the root moves of the
+    /// main thread (and the progress of the search every so often) until they're done.
+    /// Snapshots of the tables are taken whenever `next_snapshot` comes around.
+    fn analyze_threads<F>(
+        ctx: &SearchContext,
+        thread_data: Vec<ThreadData>,
+        excluded_moves: &[Move],
+        depth: usize,
+        next_snapshot: &mut Option<Instant>,
+        f: &mut F,
+    ) -> Result<Vec<ThreadResult>, SearchInterrupt>
+    where
+        F: FnMut(StatusEvent),
+    {
+        let progress_interval = ctx
+            .options
+            .progress_interval
+            .unwrap_or(DEFAULT_PROGRESS_INTERVAL);
+        let progress_nodes = ctx.options.progress_nodes;
+
+        // Run the search threads in the background so that progress can still be reported
+        // while a long iteration is in progress. The tables are only kept in check between
+        // iterations, since sweeping or growing them locks out every thread probing them.
+        let (messages_tx, messages_rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let results = thread_data
+                    .into_par_iter()
+                    .map(|data| {
+                        let search_depth = data.search_depth;
+                        let mut thread = SearchThread::new(data.rng, data.ordering);
+                        let mut on_root_move = |number, mv, evaluation, kind| {
+                            if data.reports_root_moves {
+                                _ = messages_tx.send(ThreadMessage::RootMove(
+                                    StatusEvent::RootMove {
+                                        depth: search_depth as u32,
+                                        number,
+                                        mv,
+                                        evaluation,
+                                        kind,
+                                    },
+                                ));
+                            }
+                        };
+
+                        let mut root = RootSearch {
+                            game_state: &data.game_state,
+                            max_depth: search_depth,
+                            prioritized_move: data.best_move,
+                            excluded_moves,
+                            window: RootSearch::aspiration_window(
+                                ctx.options,
+                                &thread.ordering,
+                                excluded_moves,
+                            ),
+                        };
+
+                        let mut result =
+                            Self::analyze_root(ctx, &mut thread, &root, &mut on_root_move);
+
+                        // A score at the edge of a narrowed window is only a bound, so the
+                        // root has to be searched again without it to get the real score
+                        if let Ok((evaluation, _)) = result {
+                            let (alpha, beta) = root.window;
+                            if root.window != RootSearch::full_window()
+                                && (evaluation <= alpha || evaluation >= beta)
+                            {
+                                root.window = RootSearch::full_window();
+                                result =
+                                    Self::analyze_root(ctx, &mut thread, &root, &mut on_root_move);
+                            }
+                        }
+
+                        // The nodes since the last check haven't been counted yet
+                        ctx.token
+                            .count_nodes(thread.nodes_searched % NODE_CHECK_INTERVAL);
+
+                        result.map(|(eval, mv)| (eval, mv, thread.ordering, thread.node_stats))
+                    })
+                    .collect();
+
+                _ = messages_tx.send(ThreadMessage::Finished(results));
+            });
+
+            let mut next_progress = Instant::now() + progress_interval;
+            let mut next_progress_nodes =
+                progress_nodes.map(|nodes| ctx.token.nodes_searched() + nodes);
+            loop {
+                // Nobody says when the node count goes up, so it has to be checked
+                let mut timeout = next_progress.saturating_duration_since(Instant::now());
+                if next_progress_nodes.is_some() {
+                    timeout = timeout.min(PROGRESS_NODES_POLL_INTERVAL);
+                }
+
+                if let Some(next_snapshot) = next_snapshot {
+                    timeout = timeout.min(next_snapshot.saturating_duration_since(Instant::now()));
+                }
+
+                let message = messages_rx.recv_timeout(timeout);
+
+                // Taking the snapshot only holds up the threads writing to one table at a
+                // time, and only for as long as it takes to copy the table
+                if next_snapshot.is_some_and(|next| Instant::now() >= next) {
+                    let snapshot = SearchArtifactSnapshot::new(
+                        ctx.hasher,
+                        ctx.transpositions,
+                        ctx.state_history,
+                        ctx.options.max_snapshot_entries,
+                    );
+
+                    f(StatusEvent::Snapshot {
+                        depth: depth as u32,
+                        snapshot: Box::new(snapshot),
+                    });
+
+                    *next_snapshot = ctx
+                        .options
+                        .snapshot_interval
+                        .map(|interval| Instant::now() + interval);
+                }
+
+                match message {
+                    Ok(ThreadMessage::Finished(results)) => break results,
+                    Ok(ThreadMessage::RootMove(event)) => f(event),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        let nodes_searched = ctx.token.nodes_searched();
+                        let is_due = Instant::now() >= next_progress
+                            || next_progress_nodes.is_some_and(|nodes| nodes_searched >= nodes);
+
+                        if !is_due {
+                            continue;
+                        }
+
+                        f(StatusEvent::Progress {
+                            depth: depth as u32,
+                            nodes_searched,
+                            transposition_saturation: ctx.transpositions.saturation(),
+                        });
+
+                        next_progress = Instant::now() + progress_interval;
+                        next_progress_nodes = progress_nodes.map(|nodes| nodes_searched + nodes);
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        panic!("search threads exited without a result")
+                    }
+                }
+            }
+        })
+    }
+
+    /// The best line the threads found with the excluded moves left out, if there were
+    /// any moves left. The root isn't stored without all of its moves, so the line
+    /// continues from the position after the best remaining move.
+    fn alternative_line(
+        ctx: &SearchContext,
+        game_state: &State,
+        evaluations: &[ThreadResult],
+        depth: usize,
+    ) -> Option<(Evaluation, Line)> {
+        let (evaluation, mv) = evaluations
+            .iter()
+            .map(|(e, mv, _, _)| (*e, *mv))
+            .max_by_key(|(e, _)| *e)?;
+
+        let mv = mv?;
+        let next_state = State::by_performing_move(game_state, &mv).unwrap();
+        let line: Line = std::iter::once(mv)
+            .chain(
+                ctx.transpositions
+                    .iter_moves(ctx.hasher, &next_state, depth)
+                    .map(|r| r.0),
+            )
+            .collect();
+
+        Some((evaluation, line))
     }
 
     fn analyze_recursive(
+        ctx: &SearchContext,
+        thread: &mut SearchThread,
         game_state: &State,
-        evaluator: &eval::Evaluator,
-        token: &CancellationToken,
-        hasher: &ZobristHasher,
-        state_history: &StateHistory,
-        transpositions: &TranspositionTableAccess,
-        max_depth: usize,
-        current_depth: usize,
-        current_extension: usize,
+        state_hash: Hash,
+        node: SearchNode,
         alpha: eval::Evaluation,
         beta: eval::Evaluation,
-        prioritized_move: Option<Move>,
-        rng: &mut ChaCha8Rng,
-        move_buffer: &mut Vec<PseudoLegalMove>,
-        nodes_searched: &mut usize,
     ) -> Result<eval::Evaluation, SearchInterrupt> {
         // We're searching a new node here
-        *nodes_searched += 1;
-
-        // To avoid spending a lot of time waiting for atomic operations,
-        // let's avoid checking the cancellation token in the lower leaf nodes
-        if *nodes_searched % 10000 == 0 && token.is_cancelled() {
+        if thread.enter_node(ctx.token) {
             return Err(SearchInterrupt);
         }
 
         let mut alpha = alpha;
         let mut beta = beta;
-
-        // Pre-compute the hash since we use it for checking draws
-        // by repetition and as a key into the transposition table
-        let state_hash = hasher.hash(game_state);
+        let ply = node.ply();
+
+        // Mate distance pruning: even in the best case we can't do better than mating on the
+        // next move or worse than being mated right now. If a shorter mate has already been
+        // found higher up in the tree, there's no point in searching this position any further
+        alpha = alpha.max(-eval::Evaluation::mate_in_ply(ply));
+        beta = beta.min(eval::Evaluation::mate_in_ply(ply + 1));
+        if alpha >= beta {
+            return Ok(alpha);
+        }
 
         // Early check for draws by repetition
-        if current_depth > 0 && state_history.lookup(&state_hash).is_some() {
+        if node.current_depth > 0 && ctx.state_history.lookup(&state_hash).is_some() {
             // We're just going to pretend that a one-fold repitition is a draw for simplicity
+            thread.node_stats.repetition_draws += 1;
             return Ok(eval::Evaluation::EVEN);
         }
 
-        // First thing to do is check the transposition table to see if we've
-        // searched this position to a greater depth than we're about to search now
-        if let Some(entry) = transpositions.find(state_hash) {
-            let remaining_depth = max_depth - current_depth;
-            let remaining_depth_in_transposition = entry.max_depth - entry.depth;
-            if remaining_depth_in_transposition >= remaining_depth {
+        // First thing to do is check the transposition table to see if we've searched
+        // this position to a greater depth than we're about to search now. A score that
+        // counted on a repetition might not hold on the way here, so it's never reused.
+        thread.node_stats.transposition_probes += 1;
+        let entry = ctx
+            .transpositions
+            .find(state_hash)
+            .filter(|entry| !entry.depends_on_history);
+
+        if let Some(entry) = entry {
+            if entry.remaining_depth >= node.remaining_depth() {
+                thread.node_stats.transposition_hits += 1;
+                observe!(
+                    ctx.options,
+                    on_transposition_hit(game_state, ply, entry.evaluation, entry.kind)
+                );
+
                 // We've already searched this position to a greater depth than we're
-                // about to search now, so we can use the existing evaluation
+                // about to search now, so we can use the existing evaluation. Bounds
+                // are only used to narrow the window outside of PV nodes, since cutting
+                // off a PV node early would leave us without an exact score for it
                 match entry.kind {
                     EvaluationKind::Exact => {
                         return Ok(entry.evaluation);
                     }
-                    EvaluationKind::UpperBound => {
+                    EvaluationKind::UpperBound if node.kind != NodeKind::Pv => {
                         beta = beta.min(entry.evaluation);
                     }
-                    EvaluationKind::LowerBound => {
+                    EvaluationKind::LowerBound if node.kind != NodeKind::Pv => {
                         alpha = alpha.max(entry.evaluation);
                     }
+                    _ => {}
                 }
 
                 if alpha >= beta {
@@ -393,50 +1434,50 @@ impl Searcher {
             }
         }
 
+        // Variants can end the game in positions that still have legal moves, like
+        // a king reaching the center in king-of-the-hill
+        if game_state.variant_winner().is_some() {
+            return Ok(ctx
+                .evaluator
+                .evaluate(game_state, game_state.turn_to_move(), ply));
+        }
+
         // We've reached the max depth but stopping here could be dangerous. For example,
         // if we just captured a pawn with our queen, it could look like we're up a pawn
         // here. In reality, we're probably about to lose our queen for that pawn, so
         // we need to exaust all captures in the current position before we evaluate it
-        if current_depth >= max_depth {
-            return Self::quiescence_search(game_state, evaluator, current_depth, alpha, beta);
+        if node.current_depth >= node.max_depth {
+            return Self::quiescence_search(
+                ctx,
+                game_state,
+                ply,
+                QUIESCENCE_CHECK_PLIES,
+                alpha,
+                beta,
+                &mut thread.nodes_searched,
+            );
         }
 
         let mut evaluation_type = EvaluationKind::UpperBound;
         let mut best_move: Option<Move> = None;
 
-        MoveGenerator::compute_psuedo_legal_moves_into(game_state, move_buffer);
-
-        // Sort the moves by the estimated value of the resulting position
-        // so that we can search the most promising moves first - this will
-        // allow us to prune more branches early in alpha-beta search
-        move_buffer.sort_by_cached_key(|mv| {
-            // We don't have the resulting move position yet, so we can only
-            // evaluate the quality of the move at face value
-            let mut estimation = evaluator.estimate(game_state, mv);
-
-            // Add a bit of jiggle to the estimation so that we don't always
-            // search the same moves first. This range needs to be small enough
-            // that we don't waste too much time searching bad moves first but
-            // large enough that we don't always search the same moves first
-            // which would negatively impact the multi-threaded performance.
-            estimation += Evaluation::from(rng.gen_range(-10..=10));
-
-            estimation
-        });
-
-        // If we have a best move from the previous iteration, let's search that first.
-        // We'll end up searching for this move a second time because it's in the move
-        // list twice, but the transposition table will take care of that, and we only
-        // hit this on depth=0 anyways.
-        if let Some(mv) = prioritized_move {
-            move_buffer.push(PseudoLegalMove::new(mv))
-        }
-
-        // Create a shared buffer for the recursive calls to use to avoid excessive allocations
-        let mut next_buffer: Vec<PseudoLegalMove> = Vec::new();
+        let mut move_buffer = thread.take_move_buffer();
+        Self::order_moves(
+            ctx,
+            game_state,
+            None,
+            &mut thread.rng,
+            &thread.ordering,
+            &mut move_buffer,
+        );
 
         // Keep track of where we started this search
-        let previous_nodes_searched = *nodes_searched;
+        let previous_nodes_searched = thread.nodes_searched;
+        let previous_repetition_draws = thread.node_stats.repetition_draws;
+        let mut legal_moves_searched = 0;
+
+        // Only worked out if there's a futile move to compare it against
+        let mut static_evaluation = None;
 
         // Note: Search the moves back to front, ensuring we search the best moves first
         for pseudo_legal_move in move_buffer.iter().rev() {
@@ -447,47 +1488,136 @@ impl Searcher {
                 continue;
             };
 
+            // The hash is used for checking draws by repetition and as a key into the
+            // transposition table. Computing it here lets us start pulling in the
+            // transposition bucket while we do the rest of the work for this move.
+            let new_state_hash = ctx.hasher.hash(&new_state);
+            ctx.transpositions.prefetch(new_state_hash);
+
             // This is a potentially really good move. Let's look a bit deeper than normal (and
             // also make sure we don't get into a situation where we're searching forever)
-            let extension = if current_extension < 16 {
-                Self::calculate_extension_depth(game_state, &mv)
+            let extension = if node.current_extension < 16 {
+                Self::calculate_extension_depth(game_state, &new_state)
             } else {
                 0
             };
 
-            let evaluation = -Self::analyze_recursive(
-                &new_state,
-                evaluator,
-                token,
-                hasher,
-                state_history,
-                transpositions,
-                max_depth + extension,
-                current_depth + 1 + extension,
-                current_extension + extension,
-                -beta,
-                -alpha,
-                None,
-                rng,
-                &mut next_buffer,
-                nodes_searched,
-            )?;
+            if extension > 0 {
+                thread.node_stats.extensions += 1;
+            }
+
+            // Nothing is extended out of check, so this leaves out the moves that escape it
+            let is_quiet = extension == 0
+                && !mv.is_capture()
+                && mv.promotion().is_none()
+                && !new_state.is_check();
+
+            // Only captures are searched after a quiet move this close to the horizon, so it
+            // can't make up for being too far behind. At least one move is always searched,
+            // so that this doesn't look like there weren't any moves at all.
+            if let Some(margin) = ctx.options.pruning.futility_margin {
+                if is_quiet
+                    && node.remaining_depth() == 1
+                    && node.kind != NodeKind::Pv
+                    && legal_moves_searched > 0
+                {
+                    // A move was already searched, so the game isn't over here
+                    let static_evaluation = *static_evaluation.get_or_insert_with(|| {
+                        ctx.evaluator.evaluate_playable_within(
+                            game_state,
+                            game_state.turn_to_move(),
+                            ply,
+                            eval::Evaluation::NEG_INF,
+                            eval::Evaluation::POS_INF,
+                        )
+                    });
+
+                    if static_evaluation + margin <= alpha {
+                        continue;
+                    }
+                }
+            }
+
+            let child = node.child(legal_moves_searched, extension);
+            legal_moves_searched += 1;
+
+            let reduction = match ctx.options.pruning.late_move_reduction {
+                Some(reduction) if is_quiet => {
+                    reduction.reduction(node.remaining_depth(), legal_moves_searched)
+                }
+                _ => 0,
+            };
+
+            observe!(
+                ctx.options,
+                on_node_enter(
+                    &new_state,
+                    ply + 1,
+                    node.remaining_depth() - 1,
+                    -beta,
+                    -alpha
+                )
+            );
+
+            // A reduced move is only checked for being better than the best move so far,
+            // and gets searched to the full depth if it is
+            let reduced_evaluation = if reduction > 0 {
+                Some(-Self::analyze_recursive(
+                    ctx,
+                    thread,
+                    &new_state,
+                    new_state_hash,
+                    child.reduced(reduction),
+                    -alpha - eval::Evaluation::from(1),
+                    -alpha,
+                )?)
+            } else {
+                None
+            };
+
+            let evaluation = match reduced_evaluation {
+                Some(evaluation) if evaluation <= alpha => evaluation,
+                _ => -Self::analyze_recursive(
+                    ctx,
+                    thread,
+                    &new_state,
+                    new_state_hash,
+                    child,
+                    -beta,
+                    -alpha,
+                )?,
+            };
+
+            observe!(ctx.options, on_node_exit(&new_state, ply + 1, -evaluation));
 
             // This move is too good for the opponent, so they will never allow us to reach
             // this position. We can stop searching this position because we know that the
             // opponent will never allow us to reach this position
             if evaluation >= beta {
-                transpositions.insert(
+                thread.ordering.stats.cutoffs += 1;
+                if legal_moves_searched == 1 {
+                    thread.ordering.stats.first_move_cutoffs += 1;
+                }
+
+                observe!(
+                    ctx.options,
+                    on_cutoff(game_state, ply, mv, legal_moves_searched)
+                );
+
+                ctx.transpositions.insert(
                     state_hash,
                     TranspositionEntry {
                         kind: EvaluationKind::LowerBound,
+                        node_kind: node.kind,
                         performed_move: mv,
-                        depth: current_depth,
-                        max_depth,
+                        remaining_depth: node.remaining_depth(),
                         evaluation: beta,
+                        depends_on_history: thread.node_stats.repetition_draws
+                            != previous_repetition_draws,
                     },
                 );
 
+                thread.return_move_buffer(move_buffer);
                 return Ok(beta);
             }
 
@@ -498,22 +1628,31 @@ impl Searcher {
             }
         }
 
+        thread.return_move_buffer(move_buffer);
+
         // We didn't have any legal moves, so this is checkmate or stalemate
-        if previous_nodes_searched == *nodes_searched {
-            let evaluation =
-                evaluator.evaluate(game_state, game_state.turn_to_move(), current_depth);
+        if previous_nodes_searched == thread.nodes_searched {
+            let evaluation = ctx
+                .evaluator
+                .evaluate(game_state, game_state.turn_to_move(), ply);
             return Ok(evaluation);
         }
 
+        // A score that counts on a repetition only holds for the positions that came
+        // before this one, which another path here (or a later search reusing the
+        // tables with a different game history) might not share. The move is still
+        // worth keeping for the principal variation, so only the score is left out.
         if let Some(best_move) = best_move {
-            transpositions.insert(
+            ctx.transpositions.insert(
                 state_hash,
                 TranspositionEntry {
                     kind: evaluation_type,
+                    node_kind: node.kind,
                     performed_move: best_move,
-                    depth: current_depth,
+                    remaining_depth: node.remaining_depth(),
                     evaluation: alpha,
-                    max_depth,
+                    depends_on_history: thread.node_stats.repetition_draws
+                        != previous_repetition_draws,
                 },
             );
         }
@@ -521,134 +1660,574 @@ impl Searcher {
         Ok(alpha)
     }
 
-    /*
-        Performs a recursive search by only looking at captures. Once the position is 'quiet'
-        then we evaluate it and return the evaluation.
-    */
-    fn quiescence_search(
-        game_state: &State,
-        evaluator: &eval::Evaluator,
-        depth: usize,
-        alpha: eval::Evaluation,
-        beta: eval::Evaluation,
-    ) -> Result<eval::Evaluation, SearchInterrupt> {
-        let mut buffer = MoveGenerationBuffer::new();
-        MoveGenerator::compute_legal_moves_into(&game_state, &mut buffer);
-
-        // Don't bother searching further, this is checkmate or stalemate
-        if buffer.legal_moves.is_empty() {
-            return Ok(evaluator.evaluate(game_state, game_state.turn_to_move(), depth));
+    /// Searches each of the moves in the root position in turn, passing the number, move,
+    /// score and kind of score of each one to `on_root_move` as soon as it's searched.
+    /// Otherwise, this searches the root just like `analyze_recursive` would, returning
+    /// the score along with the best move. The excluded moves are left out (which is
+    /// how the lines after the best one are found), in which case nothing is reported
+    /// or stored for the root.
+    fn analyze_root<F>(
+        ctx: &SearchContext,
+        thread: &mut SearchThread,
+        root_search: &RootSearch,
+        on_root_move: &mut F,
+    ) -> Result<(eval::Evaluation, Option<Move>), SearchInterrupt>
+    where
+        F: FnMut(usize, Move, eval::Evaluation, EvaluationKind),
+    {
+        if thread.enter_node(ctx.token) {
+            return Err(SearchInterrupt);
         }
 
-        let is_quiet = buffer.legal_moves.iter().all(|m| !m.0.is_capture());
-        let normal_eval = evaluator.evaluate(game_state, game_state.turn_to_move(), depth);
+        let &RootSearch {
+            game_state,
+            max_depth,
+            prioritized_move,
+            excluded_moves,
+            window: (mut alpha, beta),
+        } = root_search;
 
-        let mut alpha = alpha;
+        let state_hash = ctx.hasher.hash(game_state);
+        let root = SearchNode::root(max_depth);
+
+        observe!(
+            ctx.options,
+            on_node_enter(game_state, 0, max_depth, alpha, beta)
+        );
+
+        let exit = |evaluation, best_move| -> Result<_, SearchInterrupt> {
+            observe!(ctx.options, on_node_exit(game_state, 0, evaluation));
+            Ok((evaluation, best_move))
+        };
 
-        if is_quiet {
-            return Ok(normal_eval);
+        // A previous search might have already gotten deep enough here. The root is always
+        // stored (it's where the principal variation starts), even when its score counted
+        // on a repetition, so it's only reused when the position hasn't come up before in
+        // the game. Otherwise, the stored score may have been found with a different history.
+        let is_repeated = ctx.state_history.count(&state_hash) > 1;
+        let is_excluding = !excluded_moves.is_empty();
+        let mut on_root_move = |number, mv, evaluation, kind| {
+            if !is_excluding {
+                on_root_move(number, mv, evaluation, kind);
+            }
+        };
+
+        thread.node_stats.transposition_probes += 1;
+        let entry = ctx
+            .transpositions
+            .find(state_hash)
+            .filter(|_| !is_repeated && !is_excluding);
+
+        if let Some(entry) = entry {
+            if entry.remaining_depth >= max_depth && entry.kind == EvaluationKind::Exact {
+                thread.node_stats.transposition_hits += 1;
+                observe!(
+                    ctx.options,
+                    on_transposition_hit(game_state, 0, entry.evaluation, entry.kind)
+                );
+
+                return exit(entry.evaluation, Some(entry.performed_move));
+            }
+        }
+
+        if game_state.variant_winner().is_some() {
+            let evaluation = ctx
+                .evaluator
+                .evaluate(game_state, game_state.turn_to_move(), 0);
+            return exit(evaluation, None);
         }
 
-        if normal_eval >= beta {
-            return Ok(beta);
+        let mut best_move: Option<Move> = None;
+        let mut evaluation_type = EvaluationKind::UpperBound;
+
+        let mut move_buffer = thread.take_move_buffer();
+        Self::order_moves(
+            ctx,
+            game_state,
+            None,
+            &mut thread.rng,
+            &thread.ordering,
+            &mut move_buffer,
+        );
+
+        let ordering = &mut thread.ordering;
+        let rng = &mut thread.rng;
+
+        // The moves are searched back to front, so the ones that did best in the previous
+        // depth go at the end. The helper threads add noise to the scores, so that they
+        // don't all search the same moves in the same order.
+        if !ordering.root_moves.is_empty() {
+            move_buffer.sort_by_cached_key(|mv| {
+                ordering
+                    .root_moves
+                    .iter()
+                    .find(|score| score.mv == **mv)
+                    .map(|score| {
+                        let mut evaluation = score.evaluation;
+                        if ordering.jitter > 0 {
+                            evaluation +=
+                                Evaluation::from(rng.gen_range(-ordering.jitter..=ordering.jitter));
+                        }
+
+                        (evaluation, score.nodes_searched)
+                    })
+            });
         }
 
-        if alpha < normal_eval {
-            alpha = normal_eval;
+        if let Some(mv) = prioritized_move {
+            move_buffer.push(PseudoLegalMove::new(mv));
         }
 
-        // Again, sort the moves by the estimated value of the resulting position for better pruning
-        buffer.legal_moves.sort_by_cached_key(|mv| {
-            let moving_piece_value = eval::PIECE_PAWN_WORTHS[mv.0.piece()];
-            let captured_piece_value =
-                mv.0.capture()
-                    .map(|p| eval::PIECE_PAWN_WORTHS[p])
-                    .unwrap_or(0.0);
+        // The scores of the other moves only order the next depth when nothing was left out
+        if !is_excluding {
+            ordering.searched_root_moves.clear();
+        }
 
-            let estimation = captured_piece_value - moving_piece_value;
+        let mut legal_moves_searched = 0;
 
-            // Negative because we are searching from the opponent's perspective,
-            // and converted into an integer that can be used as a key
-            -(estimation * 10.0) as i32
-        });
+        for pseudo_legal_move in move_buffer.iter().rev() {
+            let Some(MoveResult(mv, new_state)) = pseudo_legal_move.try_as_legal_move(game_state)
+            else {
+                continue;
+            };
 
-        for MoveResult(mv, new_state) in buffer.legal_moves.iter() {
-            if !mv.is_capture() {
+            // The prioritized move is in the list twice, but it only needs reporting once
+            if (legal_moves_searched > 0 && Some(mv) == prioritized_move)
+                || excluded_moves.contains(&mv)
+            {
                 continue;
             }
 
-            let evaluation =
-                -Self::quiescence_search(new_state, evaluator, depth + 1, -beta, -alpha)?;
+            let new_state_hash = ctx.hasher.hash(&new_state);
+            ctx.transpositions.prefetch(new_state_hash);
+
+            let extension = Self::calculate_extension_depth(game_state, &new_state);
+            if extension > 0 {
+                thread.node_stats.extensions += 1;
+            }
+
+            let child = root.child(legal_moves_searched, extension);
+            legal_moves_searched += 1;
+
+            observe!(
+                ctx.options,
+                on_node_enter(&new_state, 1, max_depth - 1, -beta, -alpha)
+            );
+
+            let previous_nodes_searched = thread.nodes_searched;
+            let evaluation = -Self::analyze_recursive(
+                ctx,
+                thread,
+                &new_state,
+                new_state_hash,
+                child,
+                -beta,
+                -alpha,
+            )?;
+
+            observe!(ctx.options, on_node_exit(&new_state, 1, -evaluation));
+
+            if !is_excluding {
+                thread.ordering.searched_root_moves.push(RootMoveScore {
+                    mv,
+                    evaluation,
+                    nodes_searched: thread.nodes_searched - previous_nodes_searched,
+                });
+            }
+
+            // Only a mate in one (or beating the aspiration window) is good enough to stop
+            // searching the other moves
             if evaluation >= beta {
-                return Ok(beta);
+                thread.ordering.stats.cutoffs += 1;
+                if legal_moves_searched == 1 {
+                    thread.ordering.stats.first_move_cutoffs += 1;
+                }
+
+                observe!(
+                    ctx.options,
+                    on_cutoff(game_state, 0, mv, legal_moves_searched)
+                );
+                on_root_move(legal_moves_searched, mv, beta, EvaluationKind::LowerBound);
+                if !is_excluding {
+                    ctx.transpositions.insert(
+                        state_hash,
+                        TranspositionEntry {
+                            kind: EvaluationKind::LowerBound,
+                            node_kind: NodeKind::Pv,
+                            performed_move: mv,
+                            remaining_depth: max_depth,
+                            evaluation: beta,
+                            depends_on_history: false,
+                        },
+                    );
+                }
+
+                thread.return_move_buffer(move_buffer);
+                return exit(beta, Some(mv));
             }
 
             if evaluation > alpha {
                 alpha = evaluation;
+                best_move = Some(mv);
+                evaluation_type = EvaluationKind::Exact;
+                on_root_move(legal_moves_searched, mv, evaluation, EvaluationKind::Exact);
+            } else {
+                on_root_move(legal_moves_searched, mv, alpha, EvaluationKind::UpperBound);
             }
         }
 
-        Ok(alpha)
-    }
+        thread.return_move_buffer(move_buffer);
 
-    fn calculate_extension_depth(state: &State, _: &Move) -> usize {
-        if state.is_check() {
-            1
+        // We didn't have any legal moves, so this is checkmate or stalemate
+        if legal_moves_searched == 0 && !is_excluding {
+            let evaluation = ctx
+                .evaluator
+                .evaluate(game_state, game_state.turn_to_move(), 0);
+            return exit(evaluation, None);
+        }
+
+        if let Some(best_move) = best_move.filter(|_| !is_excluding) {
+            ctx.transpositions.insert(
+                state_hash,
+                TranspositionEntry {
+                    kind: evaluation_type,
+                    node_kind: NodeKind::Pv,
+                    performed_move: best_move,
+                    remaining_depth: max_depth,
+                    evaluation: alpha,
+                    depends_on_history: false,
+                },
+            );
+        }
+
+        exit(alpha, best_move)
+    }
+
+    /// Fills the buffer with the pseudo-legal moves in the position, ordered so that
+    /// the most promising moves are at the back, where they'll be searched first
+    fn order_moves(
+        ctx: &SearchContext,
+        game_state: &State,
+        prioritized_move: Option<Move>,
+        rng: &mut RandomNumberGenerator,
+        ordering: &MoveOrdering,
+        move_buffer: &mut Vec<PseudoLegalMove>,
+    ) {
+        MoveGenerator::compute_psuedo_legal_moves_into(game_state, move_buffer);
+
+        // Sort the moves by the estimated value of the resulting position
+        // so that we can search the most promising moves first - this will
+        // allow us to prune more branches early in alpha-beta search
+        move_buffer.sort_by_cached_key(|mv| {
+            // We don't have the resulting move position yet, so we can only
+            // evaluate the quality of the move at face value
+            let mut estimation = ctx.evaluator.estimate(game_state, mv);
+
+            // Add a bit of jiggle to the estimation so that we don't always
+            // search the same moves first. This range needs to be small enough
+            // that we don't waste too much time searching bad moves first but
+            // large enough that we don't always search the same moves first
+            // which would negatively impact the multi-threaded performance.
+            if ordering.jitter > 0 {
+                estimation += Evaluation::from(rng.gen_range(-ordering.jitter..=ordering.jitter));
+            }
+
+            // The moves are searched back to front, so deferred moves go at the very start
+            (
+                !ctx.options.underpromotions.defers(game_state, mv),
+                estimation,
+            )
+        });
+
+        // If we have a best move from the previous iteration, let's search that first.
+        if let Some(mv) = prioritized_move {
+            move_buffer.push(PseudoLegalMove::new(mv))
+        }
+    }
+
+    /*
+        Performs a recursive search by only looking at captures. Once the position is 'quiet'
+        then we evaluate it and return the evaluation. For the first `check_plies` plies,
+        quiet checks and all the replies to them are searched as well.
+    */
+    fn quiescence_search(
+        ctx: &SearchContext,
+        game_state: &State,
+        depth: usize,
+        check_plies: usize,
+        alpha: eval::Evaluation,
+        beta: eval::Evaluation,
+        nodes_searched: &mut usize,
+    ) -> Result<eval::Evaluation, SearchInterrupt> {
+        let mut buffer = MoveGenerationBuffer::new();
+        MoveGenerator::compute_legal_moves_into(&game_state, &mut buffer);
+
+        // Don't bother searching further, this is checkmate or stalemate
+        if buffer.legal_moves.is_empty() {
+            return Ok(ctx
+                .evaluator
+                .evaluate(game_state, game_state.turn_to_move(), depth));
+        }
+
+        // Standing pat isn't an option when escaping a check we searched into, since
+        // the whole point was to find out whether there's any escape at all. Checks
+        // are only given when there's a ply left for the opponent to escape them.
+        let is_evading_check = check_plies > 0 && game_state.is_check();
+        let quiet_checks = &mut buffer.psuedo_legal_moves;
+        let opposing_king = game_state.board().piece_occupancy(PieceIndex::new(
+            game_state.turn_to_move().opposing_color(),
+            Piece::King,
+        ));
+
+        match opposing_king.first_square() {
+            Some(king_square) if check_plies > 1 && !is_evading_check => {
+                MoveGenerator::compute_psuedo_legal_checks_into(
+                    game_state,
+                    king_square,
+                    quiet_checks,
+                );
+            }
+            _ => quiet_checks.clear(),
+        }
+
+        buffer.legal_moves.retain(|MoveResult(mv, new_state)| {
+            if is_evading_check {
+                return true;
+            }
+
+            let is_deferred = ctx.options.underpromotions.defers(game_state, mv);
+
+            (mv.is_capture() || quiet_checks.iter().any(|c| **c == *mv))
+                && (!is_deferred || new_state.is_check())
+        });
+
+        let is_quiet = buffer.legal_moves.is_empty();
+        let mut alpha = alpha;
+
+        if !is_evading_check {
+            // The moves were just generated, so this can't be checkmate or stalemate
+            let normal_eval = ctx.evaluator.evaluate_playable_within(
+                game_state,
+                game_state.turn_to_move(),
+                depth,
+                alpha,
+                beta,
+            );
+
+            if is_quiet {
+                return Ok(normal_eval);
+            }
+
+            if normal_eval >= beta {
+                return Ok(beta);
+            }
+
+            if alpha < normal_eval {
+                alpha = normal_eval;
+            }
+        }
+
+        // Again, sort the moves by the estimated value of the resulting position for better pruning
+        buffer.legal_moves.sort_by_cached_key(|mv| {
+            let moving_piece_value = eval::PIECE_PAWN_WORTHS[mv.0.piece()];
+            let captured_piece_value =
+                mv.0.capture()
+                    .map(|p| eval::PIECE_PAWN_WORTHS[p])
+                    .unwrap_or(0.0);
+
+            let estimation = captured_piece_value - moving_piece_value;
+
+            // Negative because we are searching from the opponent's perspective,
+            // and converted into an integer that can be used as a key
+            -(estimation * 10.0) as i32
+        });
+
+        for MoveResult(_, new_state) in buffer.legal_moves.iter() {
+            // Capture sequences can get long in tactical positions, so these nodes
+            // count towards checking for cancellation just like the main search
+            *nodes_searched += 1;
+            if nodes_searched.is_multiple_of(NODE_CHECK_INTERVAL) && ctx.token.checkpoint() {
+                return Err(SearchInterrupt);
+            }
+
+            let evaluation = -Self::quiescence_search(
+                ctx,
+                new_state,
+                depth + 1,
+                check_plies.saturating_sub(1),
+                -beta,
+                -alpha,
+                nodes_searched,
+            )?;
+            if evaluation >= beta {
+                return Ok(beta);
+            }
+
+            if evaluation > alpha {
+                alpha = evaluation;
+            }
+        }
+
+        Ok(alpha)
+    }
+
+    fn calculate_extension_depth(state: &State, new_state: &State) -> usize {
+        if state.is_check() || Self::is_mate_threat(new_state) {
+            1
         } else {
             0
         }
     }
 
-    pub fn perft<F>(&self, state: &State, depth: usize, mut f: F) -> usize
-    where
-        F: FnMut(&State, &Move, usize, usize) -> (),
-    {
-        let mut buffers: Vec<MoveGenerationBuffer> =
-            std::iter::repeat_with(MoveGenerationBuffer::new)
-                .take(depth)
-                .collect();
+    /// Whether the side to move has a boxed in king with the opponent's heavy pieces
+    /// bearing down on it, which is often the start of a long forced mate
+    fn is_mate_threat(state: &State) -> bool {
+        let board = state.board();
+        let defender = state.turn_to_move();
+        let attacker = defender.opposing_color();
+
+        let Some(king_square) = board
+            .piece_occupancy(PieceIndex::new(defender, Piece::King))
+            .first_square()
+        else {
+            return false;
+        };
+
+        let king_moves = AttackGenerator::compute_king_attacks(king_square);
+        let flight_squares =
+            king_moves & !board.colored_occupancy(defender) & !board.colored_attacks(attacker);
+
+        if flight_squares.count_ones() > MATE_THREAT_MAX_FLIGHT_SQUARES {
+            return false;
+        }
 
-        let mut count = 0;
-        Self::perft_recursive(state, 1, &mut buffers[..], &mut count, &mut f);
-        count
+        let king_zone = king_moves | BitBoard::just(king_square);
+        [Piece::Queen, Piece::Rook].iter().any(|piece| {
+            let piece_index = PieceIndex::new(attacker, *piece);
+            board
+                .piece_occupancy(piece_index)
+                .iter_squares()
+                .any(|square| {
+                    (AttackGenerator::compute(piece_index, square, board.occupancy()) & king_zone)
+                        .any()
+                })
+        })
     }
 
-    fn perft_recursive<F>(
+    /// Plays out a line of moves, searching the starting position and every position along
+    /// the way `depth_after` plies deep. There's one score for each of them, starting with
+    /// the position before the first move. With no depth, the positions are only evaluated
+    /// statically. This blocks until every position has been searched.
+    pub fn evaluate_line(
+        &self,
         state: &State,
-        depth: usize,
-        buffers: &mut [MoveGenerationBuffer],
-        count: &mut usize,
-        f: &mut F,
-    ) where
-        F: FnMut(&State, &Move, usize, usize) -> (),
-    {
-        if let Some((buffer, remaining_buffers)) = buffers.split_first_mut() {
-            MoveGenerator::compute_legal_moves_into(&state, buffer);
-
-            // Quick perf optimization to avoid a function call.
-            if remaining_buffers.is_empty() {
-                *count += buffer.legal_moves.len();
-                return;
+        line: &[Move],
+        depth_after: usize,
+        evaluator: &eval::Evaluator,
+        rng_seed: u64,
+        previous_artifact: Option<SearchArtifact>,
+    ) -> Result<(Vec<LinePly>, Option<SearchArtifact>), IllegalMoveError> {
+        let mut states = vec![state.clone()];
+        for (ply, mv) in line.iter().enumerate() {
+            let state = states.last().unwrap();
+            if !state.is_legal(mv) {
+                return Err(IllegalMoveError { ply, mv: *mv });
             }
 
-            for MoveResult(mv, new_state) in buffer.legal_moves.iter() {
-                let mut c0 = 0;
-                Self::perft_recursive(new_state, depth + 1, remaining_buffers, &mut c0, f);
-                (*f)(new_state, mv, depth, c0);
+            let next_state = State::by_performing_move(state, mv)
+                .map_err(|_| IllegalMoveError { ply, mv: *mv })?;
+
+            states.push(next_state);
+        }
+
+        let streams = RandomStreams::new(rng_seed);
+        let mut artifact = previous_artifact;
+        let mut plies = Vec::with_capacity(states.len());
+        for (i, state) in states.iter().enumerate() {
+            let mv = i.checked_sub(1).map(|ply| line[ply]);
+            let has_moves = !MoveGenerator::compute_legal_moves(state).is_empty();
+            if depth_after == 0 || !has_moves {
+                plies.push(LinePly {
+                    mv,
+                    evaluation: evaluator.evaluate_white(state, 0),
+                    best_move: None,
+                });
 
-                *count += c0;
+                continue;
             }
+
+            // The moves leading up to the position count towards repetitions
+            let mut previous =
+                artifact.unwrap_or_else(|| SearchArtifact::new(&mut streams.hasher()));
+            previous.load_history(&states[..i]);
+
+            let (next_artifact, result) = Self::analyze_iterative(
+                state.clone(),
+                evaluator,
+                streams,
+                &SearchOptions {
+                    max_depth: Some(depth_after),
+                    ..Default::default()
+                },
+                CancellationToken::new().0,
+                Some(previous),
+                &mut |_| {},
+            );
+
+            artifact = Some(next_artifact);
+            plies.push(LinePly {
+                mv,
+                evaluation: result.evaluation.for_color(state.turn_to_move()),
+                best_move: result.best_move(),
+            });
         }
+
+        Ok((plies, artifact))
+    }
+
+    /// Counts the positions reachable in exactly `depth` moves,
+    /// see [`MoveGenerator::perft`]
+    pub fn perft<F>(&self, state: &State, depth: usize, f: F) -> usize
+    where
+        F: FnMut(&State, &Move, usize, usize) -> (),
+    {
+        MoveGenerator::perft(state, depth, f)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum EvaluationKind {
+/// Whether a score is exact, or only a bound on the real score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvaluationKind {
     Exact,
     UpperBound,
     LowerBound,
 }
 
+/**
+ * The role we expect a node to play in the search, assuming the moves are
+ * well ordered. PV nodes are on the principal variation and get an exact
+ * score, cut nodes are expected to fail high on their first move, and all
+ * nodes are expected to have every move searched without raising alpha.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum NodeKind {
+    Pv,
+    Cut,
+    All,
+}
+
+impl NodeKind {
+    fn child(self, move_index: usize) -> Self {
+        match self {
+            NodeKind::Pv if move_index == 0 => NodeKind::Pv,
+            NodeKind::Pv => NodeKind::Cut,
+            NodeKind::Cut => NodeKind::All,
+            NodeKind::All => NodeKind::Cut,
+        }
+    }
+}
+
+#[derive(Debug)]
 struct SearchInterrupt;
 
 /**
@@ -679,14 +2258,73 @@ impl TranspositionTableAccess {
         }
     }
 
+    /// The low bits of the hash pick the bucket within a table, so the table is
+    /// picked from higher bits. Otherwise, every hash routed to a table would
+    /// land in the same small fraction of its buckets.
+    fn table_index(&self, hash: Hash) -> usize {
+        (hash >> 24) as usize % self.tables.len()
+    }
+
     fn insert(&self, hash: Hash, entry: TranspositionEntry) {
-        let index = hash as usize % self.tables.len();
+        let index = self.table_index(hash);
         self.tables[index].write().unwrap().insert(hash, entry);
     }
 
     fn find(&self, hash: Hash) -> Option<TranspositionEntry> {
-        let index = hash as usize % self.tables.len();
-        self.tables[index].read().unwrap().find(hash).copied()
+        let index = self.table_index(hash);
+        self.tables[index].read().unwrap().find(hash)
+    }
+
+    /// Hints to the CPU that we're about to probe the bucket for this hash. This
+    /// skips the prefetch rather than waiting if a writer currently holds the table.
+    fn prefetch(&self, hash: Hash) {
+        let index = self.table_index(hash);
+        if let Ok(table) = self.tables[index].try_read() {
+            table.prefetch(hash);
+        }
+    }
+
+    fn next_generation(&self) {
+        for table in self.tables.iter() {
+            table.write().unwrap().next_generation();
+        }
+    }
+
+    /// Makes room in the tables once they're close to full, first by sweeping out
+    /// stale entries and then, if that isn't enough, by growing them as long as
+    /// they stay within the given memory limit. This locks every table for writing,
+    /// so it's only meant for when nothing is searching them.
+    fn relieve_pressure(&self, max_memory: Option<usize>) {
+        if self.saturation() < SWEEP_SATURATION {
+            return;
+        }
+
+        for table in self.tables.iter() {
+            table.write().unwrap().evict_stale(STALE_GENERATION_AGE);
+        }
+
+        if self.saturation() < GROWTH_SATURATION {
+            return;
+        }
+
+        if let Some(max_memory) = max_memory {
+            let grown_memory: usize = self
+                .tables
+                .iter()
+                .map(|t| t.read().unwrap().grown_memory())
+                .sum();
+
+            if grown_memory <= max_memory {
+                for table in self.tables.iter() {
+                    table.write().unwrap().grow();
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn memory(&self) -> usize {
+        self.tables.iter().map(|t| t.read().unwrap().memory()).sum()
     }
 
     fn entries(&self) -> usize {
@@ -726,6 +2364,12 @@ impl TranspositionTableAccess {
 struct TranspositionTable {
     buckets: Vec<TranspositionBucket>,
     used_slots: usize,
+    generation: u8,
+
+    /// The buckets from before the table last grew, along with the generation it
+    /// grew in. Slots don't keep enough of the hash to move them into a bigger table,
+    /// so these are still probed until their entries would have gone stale anyways.
+    previous_buckets: Option<(Vec<TranspositionBucket>, u8)>,
 }
 
 impl TranspositionTable {
@@ -733,30 +2377,97 @@ impl TranspositionTable {
         Self {
             buckets: vec![TranspositionBucket::empty(); size],
             used_slots: 0,
+            generation: 0,
+            previous_buckets: None,
         }
     }
 
     fn with_memory(size_in_bytes: usize) -> Self {
         let size_of_bucket = std::mem::size_of::<TranspositionBucket>();
         let count = size_in_bytes / size_of_bucket;
-        Self::with_bucket_count(count)
+        Self::with_bucket_count(count.max(1))
+    }
+
+    fn find(&self, hash: Hash) -> Option<TranspositionEntry> {
+        let key = TranspositionSlot::key(hash);
+        let bucket = hash as usize % self.buckets.len();
+        self.buckets[bucket].find(key).or_else(|| {
+            let (previous_buckets, _) = self.previous_buckets.as_ref()?;
+            previous_buckets[hash as usize % previous_buckets.len()].find(key)
+        })
     }
 
-    fn find(&self, hash: Hash) -> Option<&TranspositionEntry> {
+    fn prefetch(&self, hash: Hash) {
         let bucket = hash as usize % self.buckets.len();
-        self.buckets[bucket].find(hash)
+        prefetch(&self.buckets[bucket] as *const TranspositionBucket as *const u8);
     }
 
     fn insert(&mut self, hash: Hash, entry: TranspositionEntry) {
         let index = hash as usize % self.buckets.len();
+        self.insert_into_bucket(index, TranspositionSlot::key(hash), entry);
+    }
+
+    fn insert_into_bucket(&mut self, index: usize, key: u32, entry: TranspositionEntry) {
         if self.buckets[index]
-            .insert_or_replace(hash, entry)
+            .insert_or_replace(key, entry, self.generation)
             .inserted()
         {
             self.used_slots += 1;
         }
     }
 
+    fn next_generation(&mut self) {
+        self.generation = (self.generation + 1) & TranspositionSlot::GENERATION_MASK;
+
+        if let Some((_, grown_in)) = self.previous_buckets {
+            if TranspositionSlot::generation_age(grown_in, self.generation) >= STALE_GENERATION_AGE
+            {
+                self.previous_buckets = None;
+            }
+        }
+    }
+
+    fn evict_stale(&mut self, max_age: u8) {
+        let generation = self.generation;
+        for bucket in self.buckets.iter_mut() {
+            for slot in bucket.slots.iter_mut() {
+                if !slot.is_empty() && slot.age(generation) >= max_age {
+                    *slot = TranspositionSlot::EMPTY;
+                    self.used_slots -= 1;
+                }
+            }
+        }
+    }
+
+    fn grow(&mut self) {
+        let bucket_count = self.buckets.len() * 2;
+        let buckets = std::mem::replace(
+            &mut self.buckets,
+            vec![TranspositionBucket::empty(); bucket_count],
+        );
+
+        self.previous_buckets = Some((buckets, self.generation));
+        self.used_slots = 0;
+    }
+
+    /// The memory taken up by the buckets, including the ones from before the table
+    /// last grew that are still around
+    #[cfg(test)]
+    fn memory(&self) -> usize {
+        let previous_buckets = self
+            .previous_buckets
+            .as_ref()
+            .map_or(0, |(buckets, _)| buckets.len());
+
+        (self.buckets.len() + previous_buckets) * std::mem::size_of::<TranspositionBucket>()
+    }
+
+    /// The memory the table would take up right after growing, when it has twice as many
+    /// buckets and keeps the current ones around (in place of any previous ones)
+    fn grown_memory(&self) -> usize {
+        self.buckets.len() * 3 * std::mem::size_of::<TranspositionBucket>()
+    }
+
     fn entries(&self) -> usize {
         self.used_slots
     }
@@ -766,90 +2477,216 @@ impl TranspositionTable {
     }
 }
 
+/**
+ * A bucket of slots that fits exactly in a single cache line, so a
+ * probe only ever touches one line of memory.
+ */
 #[derive(Copy, Clone, Debug)]
+#[repr(align(64))]
 struct TranspositionBucket {
-    entries: [Option<(Hash, TranspositionEntry)>; TranspositionBucket::BUCKET_SIZE],
+    slots: [TranspositionSlot; TranspositionBucket::BUCKET_SIZE],
 }
 
 impl TranspositionBucket {
-    const BUCKET_SIZE: usize = 8;
+    const BUCKET_SIZE: usize = 5;
 
     fn empty() -> Self {
         Self {
-            entries: [None; Self::BUCKET_SIZE],
+            slots: [TranspositionSlot::EMPTY; Self::BUCKET_SIZE],
         }
     }
 
-    fn find(&self, hash: Hash) -> Option<&TranspositionEntry> {
-        self.entries.iter().find_map(|e| {
-            if let Some(entry) = e {
-                if entry.0 == hash {
-                    return Some(&entry.1);
-                }
-            }
+    fn find(&self, key: u32) -> Option<TranspositionEntry> {
+        self.slots
+            .iter()
+            .find(|slot| !slot.is_empty() && slot.key == key)
+            .map(|slot| slot.entry())
+    }
 
-            None
-        })
+    fn entries(&self) -> impl Iterator<Item = (u32, TranspositionEntry)> + '_ {
+        self.slots
+            .iter()
+            .filter(|slot| !slot.is_empty())
+            .map(|slot| (slot.key, slot.entry()))
     }
 
     fn insert_or_replace(
         &mut self,
-        hash: Hash,
+        key: u32,
         entry: TranspositionEntry,
+        generation: u8,
     ) -> TranspositionInsertionResult {
-        for e in self.entries.iter_mut() {
-            match e {
-                None => {
-                    *e = Some((hash, entry));
-                    return TranspositionInsertionResult::Inserted;
-                }
-                Some((h, _)) if *h == hash => {
-                    *e = Some((hash, entry));
-                    return TranspositionInsertionResult::Swapped;
-                }
-                Some(_) => continue,
+        for slot in self.slots.iter_mut() {
+            if slot.is_empty() {
+                *slot = TranspositionSlot::new(key, &entry, generation);
+                return TranspositionInsertionResult::Inserted;
+            } else if slot.key == key {
+                *slot = TranspositionSlot::new(key, &entry, generation);
+                return TranspositionInsertionResult::Swapped;
             }
         }
 
-        // Collision: for now we'll just replace a random entry
-        let index = (hash ^ (entry.performed_move.as_raw() as u64)) as usize % self.entries.len();
+        // Collision: replace the oldest entry, and the shallowest of those
+        let slot = self
+            .slots
+            .iter_mut()
+            .max_by_key(|slot| {
+                (
+                    slot.age(generation),
+                    std::cmp::Reverse(slot.remaining_depth),
+                )
+            })
+            .unwrap();
 
-        self.entries[index] = Some((hash, entry));
+        *slot = TranspositionSlot::new(key, &entry, generation);
         return TranspositionInsertionResult::Replaced;
     }
 }
 
-enum TranspositionInsertionResult {
-    Inserted,
-    Replaced,
-    Swapped,
+/**
+ * A transposition entry packed into 12 bytes. The low bits of the hash
+ * already pick the table and bucket, so only the high 32 bits are kept as
+ * a key to tell apart the positions that share a bucket.
+ *
+ * The move only uses the low 29 bits of its word, so the evaluation kind
+ * and whether the score depends on the game's history are stored in the
+ * bits above it. Only the remaining depth of the search is kept, since
+ * that's all that matters when deciding whether to reuse it. Moves are
+ * never zero, so a zeroed move word marks an empty slot. The node kind
+ * shares its byte with the generation the entry was written in, which
+ * wraps around.
+ */
+#[derive(Copy, Clone, Debug)]
+struct TranspositionSlot {
+    key: u32,
+    move_and_kind: u32,
+    evaluation: i16,
+    remaining_depth: u8,
+    node_kind_and_generation: u8,
 }
 
-impl TranspositionInsertionResult {
-    fn inserted(&self) -> bool {
-        matches!(self, Self::Inserted)
+impl TranspositionSlot {
+    const EMPTY: Self = Self {
+        key: 0,
+        move_and_kind: 0,
+        evaluation: 0,
+        remaining_depth: 0,
+        node_kind_and_generation: 0,
+    };
+
+    const MOVE_MASK: u32 = (1 << 29) - 1;
+    const KIND_OFFSET: u8 = 29;
+    const KIND_MASK: u32 = 0b11;
+    const HISTORY_FLAG: u32 = 1 << 31;
+    const NODE_KIND_MASK: u8 = 0b11;
+    const GENERATION_OFFSET: u8 = 2;
+    const GENERATION_MASK: u8 = u8::MAX >> Self::GENERATION_OFFSET;
+
+    fn key(hash: Hash) -> u32 {
+        (hash >> 32) as u32
     }
-}
 
-#[derive(Clone, Copy, Debug)]
-struct TranspositionEntry {
-    kind: EvaluationKind,
-    performed_move: Move,
-    depth: usize,
-    max_depth: usize,
-    evaluation: eval::Evaluation,
-}
+    fn generation_age(generation: u8, current_generation: u8) -> u8 {
+        current_generation.wrapping_sub(generation) & Self::GENERATION_MASK
+    }
 
-struct TranspositionTableMoveIterator<'a> {
-    access: &'a TranspositionTableAccess,
-    hasher: &'a ZobristHasher,
-    max_depth: usize,
-    current_index: usize,
-    current_game_state: State,
-}
+    fn new(key: u32, entry: &TranspositionEntry, generation: u8) -> Self {
+        let kind: u32 = match entry.kind {
+            EvaluationKind::Exact => 0,
+            EvaluationKind::UpperBound => 1,
+            EvaluationKind::LowerBound => 2,
+        };
 
-impl Iterator for TranspositionTableMoveIterator<'_> {
-    type Item = MoveResult;
+        debug_assert!(entry.performed_move.as_raw() & !Self::MOVE_MASK == 0);
+
+        Self {
+            key,
+            move_and_kind: (entry.performed_move.as_raw() & Self::MOVE_MASK)
+                | (kind << Self::KIND_OFFSET)
+                | if entry.depends_on_history {
+                    Self::HISTORY_FLAG
+                } else {
+                    0
+                },
+            evaluation: i32::from(entry.evaluation).clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            remaining_depth: entry.remaining_depth.min(u8::MAX as usize) as u8,
+            node_kind_and_generation: match entry.node_kind {
+                NodeKind::Pv => 0,
+                NodeKind::Cut => 1,
+                NodeKind::All => 2,
+            } | ((generation & Self::GENERATION_MASK)
+                << Self::GENERATION_OFFSET),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.move_and_kind == 0
+    }
+
+    fn generation(&self) -> u8 {
+        self.node_kind_and_generation >> Self::GENERATION_OFFSET
+    }
+
+    fn age(&self, current_generation: u8) -> u8 {
+        Self::generation_age(self.generation(), current_generation)
+    }
+
+    fn entry(&self) -> TranspositionEntry {
+        TranspositionEntry {
+            kind: match (self.move_and_kind >> Self::KIND_OFFSET) & Self::KIND_MASK {
+                0 => EvaluationKind::Exact,
+                1 => EvaluationKind::UpperBound,
+                _ => EvaluationKind::LowerBound,
+            },
+            performed_move: Move::from_raw(self.move_and_kind & Self::MOVE_MASK),
+            evaluation: Evaluation::from(self.evaluation as i32),
+            node_kind: match self.node_kind_and_generation & Self::NODE_KIND_MASK {
+                0 => NodeKind::Pv,
+                1 => NodeKind::Cut,
+                _ => NodeKind::All,
+            },
+            remaining_depth: self.remaining_depth as usize,
+            depends_on_history: self.move_and_kind & Self::HISTORY_FLAG != 0,
+        }
+    }
+}
+
+enum TranspositionInsertionResult {
+    Inserted,
+    Replaced,
+    Swapped,
+}
+
+impl TranspositionInsertionResult {
+    fn inserted(&self) -> bool {
+        matches!(self, Self::Inserted)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct TranspositionEntry {
+    kind: EvaluationKind,
+    node_kind: NodeKind,
+    performed_move: Move,
+    remaining_depth: usize,
+    evaluation: eval::Evaluation,
+
+    /// Whether the score counted on a repetition, which only holds for the positions
+    /// that came before it. Only the move of such an entry is worth reusing.
+    #[serde(default)]
+    depends_on_history: bool,
+}
+
+struct TranspositionTableMoveIterator<'a> {
+    access: &'a TranspositionTableAccess,
+    hasher: &'a ZobristHasher,
+    max_depth: usize,
+    current_index: usize,
+    current_game_state: State,
+}
+
+impl Iterator for TranspositionTableMoveIterator<'_> {
+    type Item = MoveResult;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_index > self.max_depth {
@@ -861,11 +2698,14 @@ impl Iterator for TranspositionTableMoveIterator<'_> {
             return None;
         };
 
-        let Ok(next_game_state) =
-            State::by_performing_move(&self.current_game_state, &entry.performed_move)
-        else {
-            return None;
-        };
+        // Only part of the hash is checked, so the entry could belong to a different
+        // position. Make sure the move is actually legal before following it.
+        let MoveResult(_, next_game_state) =
+            MoveGenerator::compute_legal_moves(&self.current_game_state)
+                .moves()
+                .iter()
+                .find(|r| r.0 == entry.performed_move)
+                .cloned()?;
 
         self.current_index += 1;
         self.current_game_state = next_game_state.clone();
@@ -874,22 +2714,41 @@ impl Iterator for TranspositionTableMoveIterator<'_> {
     }
 }
 
-#[derive(Clone)]
-struct StateHistory {
+/// The number of times each position has been reached in the game, by hash, which
+/// the search uses to steer away from (or towards) draws by repetition
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StateHistory {
     states: HashMap<Hash, usize>,
 }
 
 impl StateHistory {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             states: HashMap::new(),
         }
     }
 
-    fn increment(&mut self, hash: Hash) {
+    pub fn increment(&mut self, hash: Hash) {
         *self.states.entry(hash).or_insert(0) += 1;
     }
 
+    /// The number of times the position with this hash has been reached
+    pub fn count(&self, hash: &Hash) -> usize {
+        self.states.get(hash).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Hash, usize)> + '_ {
+        self.states.iter().map(|(hash, count)| (*hash, *count))
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
     fn lookup(&self, hash: &Hash) -> Option<&usize> {
         self.states.get(hash)
     }
@@ -899,81 +2758,1099 @@ pub struct SearchArtifact {
     hasher: ZobristHasher,
     transpositions: TranspositionTableAccess,
     state_history: StateHistory,
+
+    /// How the root moves did in the last depth that was completed, and the position
+    /// they were searched in. These aren't kept in snapshots of the artifact.
+    root_moves: Option<(Hash, Vec<RootMoveScore>)>,
+}
+
+impl SearchArtifact {
+    /// Creates empty tables for a search to start from, with a randomly seeded hasher
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        Self::with_table_size(rng, DEFAULT_TRANSPOSITION_TABLE_SIZE_MB)
+    }
+
+    /// Like `new`, but with tables taking up about this many megabytes in total
+    pub fn with_table_size<R: Rng>(rng: &mut R, size_mb: usize) -> Self {
+        const TABLE_COUNT: usize = 128;
+        let tables = (0..TABLE_COUNT)
+            .map(|_| TranspositionTable::with_memory(size_mb * 1024 * 1024 / TABLE_COUNT))
+            .collect();
+
+        Self {
+            hasher: ZobristHasher::with(rng),
+            transpositions: TranspositionTableAccess::with_tables(tables),
+            state_history: StateHistory::new(),
+            root_moves: None,
+        }
+    }
+
+    /// The hasher used for the positions in the tables and the state history
+    pub fn hasher(&self) -> &ZobristHasher {
+        &self.hasher
+    }
+
+    pub fn state_history(&self) -> &StateHistory {
+        &self.state_history
+    }
+
+    /// The number of times a position has been reached, as far as the search knows
+    pub fn repetitions(&self, state: &State) -> usize {
+        self.state_history.count(&self.hasher.hash(state))
+    }
+
+    /// Replaces the state history with the positions of a game leading up to (but not
+    /// including) the position about to be searched, which the search records itself
+    pub fn load_history<'a, I>(&mut self, states: I)
+    where
+        I: IntoIterator<Item = &'a State>,
+    {
+        self.state_history = StateHistory::new();
+        for state in states {
+            self.state_history.increment(self.hasher.hash(state));
+        }
+    }
+
+    /// Follows the moves stored in the tables from any position, for as long as
+    /// they're legal and don't lead back to a position already on the line
+    pub fn hash_line(&self, state: &State) -> Vec<HashLinePly> {
+        let mut line = vec![];
+        let mut seen = HashSet::new();
+        let mut state = state.clone();
+        while line.len() < Line::CAPACITY {
+            let hash = self.hasher.hash(&state);
+            if !seen.insert(hash) {
+                break;
+            }
+
+            let Some(entry) = self.transpositions.find(hash) else {
+                break;
+            };
+
+            // Only part of the hash is checked, so the entry could belong to a different position
+            if !state.is_legal(&entry.performed_move) {
+                break;
+            }
+
+            let Ok(next_state) = State::by_performing_move(&state, &entry.performed_move) else {
+                break;
+            };
+
+            line.push(HashLinePly {
+                mv: entry.performed_move,
+                evaluation: entry.evaluation,
+                kind: entry.kind,
+                depth: entry.remaining_depth,
+            });
+
+            state = next_state;
+        }
+
+        line
+    }
+
+    /// A serializable copy of the tables, keeping only the deepest `max_entries`
+    /// entries if there's a limit
+    pub fn snapshot(&self, max_entries: Option<usize>) -> SearchArtifactSnapshot {
+        SearchArtifactSnapshot::new(
+            &self.hasher,
+            &self.transpositions,
+            &self.state_history,
+            max_entries,
+        )
+    }
+
+    /// The best move found for a position in an earlier search, if it's still in the tables
+    pub fn best_move(&self, state: &State) -> Option<Move> {
+        let entry = self.transpositions.find(self.hasher.hash(state))?;
+        state
+            .is_legal(&entry.performed_move)
+            .then_some(entry.performed_move)
+    }
+}
+
+/**
+ * A serializable copy of a search artifact. Only the occupied slots of the
+ * transposition tables are stored, so a snapshot is much smaller than the
+ * tables themselves. It can be turned back into an artifact to search from.
+ */
+#[derive(Serialize, Deserialize)]
+pub struct SearchArtifactSnapshot {
+    hasher: ZobristHasher,
+    tables: Vec<TranspositionTableSnapshot>,
+    state_history: StateHistory,
+}
+
+/**
+ * Slots only keep part of the hash, so each entry is stored along with
+ * the bucket it was found in to be able to put it back in the same place.
+ */
+#[derive(Serialize, Deserialize)]
+struct TranspositionTableSnapshot {
+    bucket_count: usize,
+    entries: Vec<(usize, u32, TranspositionEntry)>,
+}
+
+impl SearchArtifactSnapshot {
+    /// Copies the tables, keeping only the deepest `max_entries` entries (split evenly
+    /// between the tables) if there's a limit
+    fn new(
+        hasher: &ZobristHasher,
+        transpositions: &TranspositionTableAccess,
+        state_history: &StateHistory,
+        max_entries: Option<usize>,
+    ) -> Self {
+        let max_table_entries = max_entries.map(|max| max / transpositions.tables.len());
+        Self {
+            hasher: hasher.clone(),
+            tables: transpositions
+                .tables
+                .iter()
+                .map(|table| {
+                    let table = table.read().unwrap();
+                    let mut entries: Vec<_> = table
+                        .buckets
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(index, bucket)| {
+                            bucket
+                                .entries()
+                                .map(move |(key, entry)| (index, key, entry))
+                        })
+                        .collect();
+
+                    if let Some(max) = max_table_entries.filter(|max| entries.len() > *max) {
+                        entries
+                            .sort_by_key(|(_, _, entry)| std::cmp::Reverse(entry.remaining_depth));
+                        entries.truncate(max);
+                    }
+
+                    TranspositionTableSnapshot {
+                        bucket_count: table.buckets.len(),
+                        entries,
+                    }
+                })
+                .collect(),
+            state_history: state_history.clone(),
+        }
+    }
+
+    /// The number of table entries that were kept
+    pub fn entries(&self) -> usize {
+        self.tables.iter().map(|table| table.entries.len()).sum()
+    }
+}
+
+impl std::fmt::Debug for SearchArtifactSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchArtifactSnapshot")
+            .field("entries", &self.entries())
+            .finish()
+    }
+}
+
+impl From<&SearchArtifact> for SearchArtifactSnapshot {
+    fn from(artifact: &SearchArtifact) -> Self {
+        artifact.snapshot(None)
+    }
+}
+
+impl From<SearchArtifactSnapshot> for SearchArtifact {
+    fn from(snapshot: SearchArtifactSnapshot) -> Self {
+        let tables = snapshot
+            .tables
+            .into_iter()
+            .map(|snapshot| {
+                let mut table = TranspositionTable::with_bucket_count(snapshot.bucket_count);
+                for (index, key, entry) in snapshot.entries {
+                    if index < table.buckets.len() {
+                        table.insert_into_bucket(index, key, entry);
+                    }
+                }
+
+                table
+            })
+            .collect();
+
+        Self {
+            hasher: snapshot.hasher,
+            transpositions: TranspositionTableAccess::with_tables(tables),
+            state_history: snapshot.state_history,
+            root_moves: None,
+        }
+    }
+}
+
+impl Serialize for SearchArtifact {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SearchArtifactSnapshot::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchArtifact {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SearchArtifactSnapshot::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[inline]
+fn prefetch(ptr: *const u8) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = ptr;
 }
 
 #[derive(Clone)]
 struct CancellationToken {
     cancelled: Arc<AtomicBool>,
+    reason: Arc<OnceLock<StopReason>>,
+
+    /// The nodes searched by every thread so far, across all depths. The threads add
+    /// their own counts every `NODE_CHECK_INTERVAL` nodes, and the rest when they finish.
+    nodes_searched: Arc<AtomicU64>,
+
+    /// Cancels the search once this many nodes have been searched
+    max_nodes: Option<usize>,
 }
 
 impl CancellationToken {
     fn new() -> (Self, Self) {
         let token = Self {
             cancelled: Arc::new(AtomicBool::new(false)),
+            reason: Arc::new(OnceLock::new()),
+            nodes_searched: Arc::new(AtomicU64::new(0)),
+            max_nodes: None,
+        };
+
+        (token.clone(), token)
+    }
+
+    /// Makes the search stop once it's searched the given number of nodes
+    fn with_node_limit(self, max_nodes: usize) -> Self {
+        Self {
+            max_nodes: Some(max_nodes),
+            ..self
+        }
+    }
+
+    fn count_nodes(&self, nodes: usize) {
+        self.nodes_searched
+            .fetch_add(nodes as u64, Ordering::Relaxed);
+    }
+
+    fn nodes_searched(&self) -> usize {
+        self.nodes_searched.load(Ordering::Relaxed) as usize
+    }
+
+    /// Counts the `NODE_CHECK_INTERVAL` nodes a thread searched since its last
+    /// checkpoint, and returns whether to stop searching
+    fn checkpoint(&self) -> bool {
+        self.count_nodes(NODE_CHECK_INTERVAL);
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.nodes_searched() >= max_nodes)
+        {
+            self.cancel(StopReason::NodeLimit);
+        }
+
+        self.is_cancelled()
+    }
+
+    /// Cancels the search, unless it was already cancelled for another reason
+    fn cancel(&self, reason: StopReason) {
+        _ = self.reason.set(reason);
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn reason(&self) -> Option<StopReason> {
+        self.reason.get().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weechess_core::{
+        notation::{self, into_notation, lan::Lan, Fen},
+        Color, MoveQuery, Piece, PieceIndex, Square,
+    };
+
+    fn evaluate(
+        game_state: State,
+        seed: u64,
+        depth: usize,
+        prev_artifact: Option<SearchArtifact>,
+    ) -> (eval::Evaluation, Line) {
+        let cancel_token = CancellationToken::new().0;
+        let evaluator = eval::Evaluator::default();
+        let mut result = None;
+        _ = Searcher::analyze_iterative(
+            game_state,
+            &evaluator,
+            RandomStreams::new(seed),
+            &SearchOptions {
+                max_depth: Some(depth),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            cancel_token,
+            prev_artifact,
+            &mut |e| match e {
+                StatusEvent::PvUpdate {
+                    line, evaluation, ..
+                } => {
+                    println!(
+                        "Best line: ({}) {}",
+                        into_notation::<_, Lan>(&&line[..]),
+                        evaluation
+                    );
+                    result = Some((evaluation, *line));
+                }
+                _ => {}
+            },
+        );
+
+        result.unwrap()
+    }
+
+    /// Owns everything a `SearchContext` borrows, for searching parts of the tree directly
+    struct TestContext {
+        evaluator: eval::Evaluator,
+        options: SearchOptions,
+        token: CancellationToken,
+        hasher: ZobristHasher,
+        state_history: StateHistory,
+        transpositions: TranspositionTableAccess,
+    }
+
+    impl TestContext {
+        fn new(token: CancellationToken, underpromotions: UnderpromotionPolicy) -> Self {
+            Self {
+                evaluator: eval::Evaluator::default(),
+                options: SearchOptions {
+                    underpromotions,
+                    ..Default::default()
+                },
+                token,
+                hasher: ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(0)),
+                state_history: StateHistory::new(),
+                transpositions: TranspositionTableAccess::small(),
+            }
+        }
+
+        fn context(&self) -> SearchContext<'_> {
+            SearchContext {
+                evaluator: &self.evaluator,
+                options: &self.options,
+                token: &self.token,
+                hasher: &self.hasher,
+                state_history: &self.state_history,
+                transpositions: &self.transpositions,
+            }
+        }
+    }
+
+    /// Runs a quiescence search from the root of the position with the full window
+    fn quiescence_search(
+        state: &State,
+        token: &CancellationToken,
+        check_plies: usize,
+        underpromotions: UnderpromotionPolicy,
+        nodes_searched: &mut usize,
+    ) -> Result<eval::Evaluation, SearchInterrupt> {
+        let test_context = TestContext::new(token.clone(), underpromotions);
+        Searcher::quiescence_search(
+            &test_context.context(),
+            state,
+            0,
+            check_plies,
+            -eval::Evaluation::mate_in_ply(0),
+            eval::Evaluation::mate_in_ply(0),
+            nodes_searched,
+        )
+    }
+
+    #[test]
+    fn test_termination() {
+        let searcher = Searcher::new();
+        let evaluator = eval::Evaluator::default();
+        let state = State::default();
+        let (handle, tx, _) = searcher.analyze(state, 0, evaluator, SearchOptions::default(), None);
+        tx.send(ControlEvent::Stop).unwrap();
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_search_result() {
+        let search = |state: &str, depth, max_nodes, token, artifact| {
+            Searcher::analyze_iterative(
+                notation::try_from_notation::<_, Fen>(state).unwrap(),
+                &eval::Evaluator::default(),
+                RandomStreams::new(0),
+                &SearchOptions {
+                    max_depth: Some(depth),
+                    max_nodes,
+                    max_threads: Some(1),
+                    ..Default::default()
+                },
+                token,
+                artifact,
+                &mut |_| {},
+            )
         };
 
-        (token.clone(), token)
-    }
+        let (artifact, result) = search(Fen::DEFAULT, 3, None, CancellationToken::new().0, None);
+        assert_eq!(result.reason, StopReason::DepthReached);
+        assert_eq!(result.depth, 3);
+        assert!(result.best_move().is_some());
+        assert!(result.stats.nodes_searched > 0);
+
+        // Ra8 is mate, so there's no need to go any deeper
+        let (artifact, result) = search(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            10,
+            None,
+            CancellationToken::new().0,
+            Some(artifact),
+        );
+        assert_eq!(result.reason, StopReason::MateFound);
+        assert!(result.depth < 10);
+        assert_eq!(result.best_move().unwrap().destination(), Square::A8);
+
+        // Only the first reason to stop counts. The token is only checked every so
+        // often, so the shallow depths still get searched.
+        let (token, canceller) = CancellationToken::new();
+        canceller.cancel(StopReason::TimeUp);
+        canceller.cancel(StopReason::Stopped);
+        let (_, result) = search(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            20,
+            None,
+            token,
+            Some(artifact),
+        );
+        assert_eq!(result.reason, StopReason::TimeUp);
+        assert!(result.depth < 20);
+
+        // Running out of nodes stops the search part way through a depth
+        let (_, result) = search(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            20,
+            Some(100_000),
+            CancellationToken::new().0,
+            None,
+        );
+        assert_eq!(result.reason, StopReason::NodeLimit);
+        assert!(result.depth < 20);
+        assert!(result.best_move().is_some());
+
+        // The nodes of the unfinished depth still count, and the search stops
+        // at the first check after running out
+        assert!(result.stats.nodes_searched >= 100_000);
+        assert!(result.stats.nodes_searched < 100_000 + NODE_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn test_status_events() {
+        // Each depth's best line comes out before the depth is reported as complete
+        let mut events = vec![];
+        let mut last_line = None;
+        let mut nodes_searched = 0;
+        let (_, result) = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |e| match e {
+                StatusEvent::PvUpdate {
+                    depth,
+                    line,
+                    evaluation,
+                } => {
+                    events.push(("pv", depth));
+                    last_line = Some((*line, evaluation));
+                }
+                StatusEvent::IterationComplete { depth, stats } => {
+                    events.push(("complete", depth));
+                    assert!(stats.nodes_searched >= nodes_searched);
+                    nodes_searched = stats.nodes_searched;
+                }
+                _ => {}
+            },
+        );
+
+        assert_eq!(
+            events,
+            [
+                ("pv", 1),
+                ("complete", 1),
+                ("pv", 2),
+                ("complete", 2),
+                ("pv", 3),
+                ("complete", 3),
+            ]
+        );
+
+        let (line, evaluation) = last_line.unwrap();
+        assert_eq!(&line[..], &result.line[..]);
+        assert_eq!(evaluation, result.evaluation);
+        assert_eq!(nodes_searched, result.stats.nodes_searched);
+    }
+
+    #[test]
+    fn test_root_move_ordering() {
+        let state = State::default();
+        let hasher = ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(0));
+        let search = |root_moves, depth| {
+            let artifact = SearchArtifact {
+                hasher: hasher.clone(),
+                transpositions: TranspositionTableAccess::small(),
+                state_history: StateHistory::new(),
+                root_moves,
+            };
+
+            let mut first_moves = vec![];
+            let (artifact, _) = Searcher::analyze_iterative(
+                state.clone(),
+                &eval::Evaluator::default(),
+                RandomStreams::new(0),
+                &SearchOptions {
+                    max_depth: Some(depth),
+                    max_threads: Some(1),
+                    ..Default::default()
+                },
+                CancellationToken::new().0,
+                Some(artifact),
+                &mut |event| {
+                    if let StatusEvent::RootMove { number: 1, mv, .. } = event {
+                        first_moves.push(mv);
+                    }
+                },
+            );
+
+            (artifact, first_moves)
+        };
+
+        // Every root move gets a score, and they're kept for the next search
+        let (artifact, _) = search(None, 2);
+        let (hash, mut root_moves) = artifact.root_moves.unwrap();
+        assert_eq!(hash, hasher.hash(&state));
+        assert_eq!(root_moves.len(), 20);
+
+        // The first depth is already ordered by the scores from the last search
+        let a3 = state.find_legal(&MoveQuery::try_from_uci("a2a3").unwrap());
+        let a3 = a3.unwrap().0;
+        for score in root_moves.iter_mut().filter(|score| score.mv == a3) {
+            score.evaluation = Evaluation::from(500);
+        }
+
+        let (_, first_moves) = search(Some((hash, root_moves)), 1);
+        assert_eq!(first_moves, vec![a3]);
+    }
+
+    #[test]
+    fn test_evaluate_line() {
+        let state = State::default();
+        let mut line = vec![];
+        let mut position = state.clone();
+        for san in ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"] {
+            let query = notation::try_from_notation::<_, notation::San>(san).unwrap();
+            let result = position.find_legal(&query).unwrap();
+            line.push(result.0);
+            position = result.1;
+        }
+
+        let searcher = Searcher::new();
+        let evaluator = eval::Evaluator::default();
+        let (plies, artifact) = searcher
+            .evaluate_line(&state, &line, 2, &evaluator, 0, None)
+            .unwrap();
+
+        assert_eq!(plies.len(), line.len() + 1);
+        assert_eq!(plies[0].mv, None);
+        assert_eq!(plies[1].mv, Some(line[0]));
+
+        // Nf6 walks into mate, which the search after Bc4 already sees coming
+        let mate = &plies[line.len()];
+        assert!(mate.evaluation >= eval::Evaluation::POS_INF);
+        assert_eq!(mate.best_move, None);
+        assert_eq!(plies[line.len() - 1].best_move, Some(line[line.len() - 1]));
+
+        // Playing the same move twice isn't legal
+        let error = searcher
+            .evaluate_line(&state, &[line[0], line[0]], 0, &evaluator, 0, artifact)
+            .err();
+        assert_eq!(error.map(|e| e.ply), Some(1));
+    }
+
+    #[test]
+    fn test_quiescence_termination() {
+        // Lots of captures are available for both sides here
+        let state = notation::try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+
+        let (token, canceller) = CancellationToken::new();
+        canceller.cancel(StopReason::Stopped);
+
+        let mut nodes_searched = 9999;
+        let result = quiescence_search(
+            &state,
+            &token,
+            0,
+            UnderpromotionPolicy::default(),
+            &mut nodes_searched,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_quiescence_checks() {
+        // Ra8 is a back rank mate, but it's a quiet move
+        let state =
+            notation::try_from_notation::<_, Fen>("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        let (token, _canceller) = CancellationToken::new();
+        let search = |check_plies| {
+            quiescence_search(
+                &state,
+                &token,
+                check_plies,
+                UnderpromotionPolicy::default(),
+                &mut 0,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            search(QUIESCENCE_CHECK_PLIES),
+            eval::Evaluation::mate_in_ply(1)
+        );
+        assert!(search(0).mate_in_moves().is_none());
+    }
+
+    #[test]
+    fn test_underpromotions() {
+        // Both pawns can promote, with or without taking the rook
+        let state =
+            notation::try_from_notation::<_, Fen>("r3k3/1P5P/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let (token, _canceller) = CancellationToken::new();
+        let mut rng = RandomStreams::new(0).thread(0, 0);
+        let ordering = MoveOrdering::for_thread(0, &OrderingStats::default());
+        let mut move_buffer = Vec::new();
+        let order_moves = |underpromotions, rng: &mut _, move_buffer: &mut _| {
+            let test_context = TestContext::new(token.clone(), underpromotions);
+            Searcher::order_moves(
+                &test_context.context(),
+                &state,
+                None,
+                rng,
+                &ordering,
+                move_buffer,
+            );
+        };
+
+        order_moves(UnderpromotionPolicy::Deferred, &mut rng, &mut move_buffer);
+
+        // The rook and bishop promotions are searched last
+        let deferred = move_buffer
+            .iter()
+            .take_while(|mv| matches!(mv.promotion(), Some(Piece::Rook | Piece::Bishop)))
+            .count();
+        assert_eq!(deferred, 6);
+
+        order_moves(UnderpromotionPolicy::All, &mut rng, &mut move_buffer);
+        assert!(!matches!(
+            move_buffer[0].promotion(),
+            Some(Piece::Rook | Piece::Bishop)
+        ));
+
+        // Only the checking underpromotions (bxa8=R) are searched in the quiescence search
+        let search = |underpromotions| {
+            let mut nodes_searched = 0;
+            quiescence_search(&state, &token, 0, underpromotions, &mut nodes_searched).unwrap();
+
+            nodes_searched
+        };
+
+        assert!(search(UnderpromotionPolicy::Deferred) < search(UnderpromotionPolicy::All));
+
+        // The policies are named the same way in the UCI option and the CLI flag
+        for policy in [UnderpromotionPolicy::All, UnderpromotionPolicy::Deferred] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+    }
+
+    #[test]
+    fn test_mate_threat() {
+        assert!(!Searcher::is_mate_threat(&State::default()));
+
+        // The king only has g8 to run to and the rook is bearing down on g7
+        let state =
+            notation::try_from_notation::<_, Fen>("7k/6pp/8/8/8/8/8/6RK b - - 0 1").unwrap();
+        assert!(Searcher::is_mate_threat(&state));
+
+        // The rook is still bearing down on the king, but it has room to run
+        let state =
+            notation::try_from_notation::<_, Fen>("4k3/8/8/8/8/8/8/4R2K b - - 0 1").unwrap();
+        assert!(!Searcher::is_mate_threat(&state));
+    }
+
+    #[test]
+    fn test_mate_search_depth() {
+        let options = SearchOptions {
+            mate: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(options.depth_limit(), Some(5));
+
+        let options = SearchOptions {
+            max_depth: Some(2),
+            mate: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(options.depth_limit(), Some(2));
+    }
+
+    #[test]
+    fn test_proven_mate() {
+        let search = |fen: &str, mate, token| {
+            let mut warnings = vec![];
+            let (_, result) = Searcher::analyze_iterative(
+                notation::try_from_notation::<_, Fen>(fen).unwrap(),
+                &eval::Evaluator::default(),
+                RandomStreams::new(0),
+                &SearchOptions {
+                    mate: Some(mate),
+                    max_threads: Some(1),
+                    ..Default::default()
+                },
+                token,
+                None,
+                &mut |e| {
+                    if let StatusEvent::Warning { kind, .. } = e {
+                        warnings.push(kind);
+                    }
+                },
+            );
+
+            (result, warnings)
+        };
+
+        // Morphy's problem is proven without searching a single alpha-beta node
+        let morphy = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        let (result, _) = search(morphy, 2, CancellationToken::new().0);
+        assert_eq!(result.reason, StopReason::MateFound);
+        assert_eq!(result.line.len(), 3);
+        assert_eq!(result.evaluation, Evaluation::mate_in_ply(3));
+        assert_eq!(result.stats.nodes_searched, 0);
+
+        // Without a mate to prove, the search still looks for the best move
+        let (result, warnings) = search(morphy, 1, CancellationToken::new().0);
+        assert!(matches!(warnings[..], [WarningKind::NoMate]));
+        assert!(result.best_move().is_some());
+
+        // A cancelled search doesn't wait on the proof, or rule out a mate, but the
+        // shallow depths still get searched for a move
+        let (token, canceller) = CancellationToken::new();
+        canceller.cancel(StopReason::Stopped);
+        let (result, warnings) = search(morphy, 2, token);
+        assert!(warnings.is_empty());
+        assert!(result.stats.nodes_searched > 0);
+        assert!(result.best_move().is_some());
+    }
+
+    #[test]
+    fn test_root_move_reports() {
+        let state = State::default();
+        let mut reports: HashMap<u32, Vec<(usize, Move, EvaluationKind)>> = HashMap::new();
+        _ = Searcher::analyze_iterative(
+            state.clone(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |e| {
+                if let StatusEvent::RootMove {
+                    depth,
+                    number,
+                    mv,
+                    kind,
+                    ..
+                } = e
+                {
+                    reports.entry(depth).or_default().push((number, mv, kind));
+                }
+            },
+        );
+
+        // Every legal move is reported once at every depth, in the order they were searched
+        let legal_moves = MoveGenerator::compute_legal_moves(&state);
+        for depth in 1..=3 {
+            let reported = &reports[&depth];
+            assert_eq!(reported.len(), legal_moves.moves().len());
+            assert_eq!(reported[0].2, EvaluationKind::Exact);
+            for (i, (number, mv, _)) in reported.iter().enumerate() {
+                assert_eq!(*number, i + 1);
+                assert!(legal_moves.moves().iter().any(|r| r.0 == *mv));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        let state = State::default();
+        let mut updates: Vec<(u32, usize, Line)> = vec![];
+        let (_, result) = Searcher::analyze_iterative(
+            state.clone(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                multi_pv: Some(3),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |e| {
+                if let StatusEvent::MultiPvUpdate {
+                    depth, rank, line, ..
+                } = e
+                {
+                    updates.push((depth, rank, *line));
+                }
+            },
+        );
+
+        // The alternatives are ranked after the best line, and none of them start with the
+        // same move as it or as each other
+        let best_move = result.best_move().unwrap();
+        assert_eq!(result.alternative_lines.len(), 2);
+        let first_moves: Vec<Move> = result
+            .alternative_lines
+            .iter()
+            .map(|(_, line)| line[0])
+            .collect();
+
+        assert!(!first_moves.contains(&best_move));
+        assert_ne!(first_moves[0], first_moves[1]);
+        assert!(result.alternative_lines[0].0 >= result.alternative_lines[1].0);
+
+        for depth in 1..=3 {
+            let ranks: Vec<usize> = updates
+                .iter()
+                .filter(|(d, _, _)| *d == depth)
+                .map(|(_, rank, _)| *rank)
+                .collect();
+
+            assert_eq!(ranks, vec![2, 3]);
+        }
+    }
+
+    #[cfg(feature = "search-observer")]
+    #[test]
+    fn test_search_observer() {
+        use std::sync::{atomic::AtomicUsize, Mutex};
+
+        #[derive(Default)]
+        struct Counts {
+            entered: AtomicUsize,
+            exited: AtomicUsize,
+            cutoffs: AtomicUsize,
+            deepest_ply: AtomicUsize,
+            root_windows: Mutex<Vec<(Evaluation, Evaluation)>>,
+        }
+
+        impl SearchObserver for Counts {
+            fn on_node_enter(
+                &self,
+                _: &State,
+                ply: usize,
+                _: usize,
+                alpha: Evaluation,
+                beta: Evaluation,
+            ) {
+                if ply == 0 {
+                    self.root_windows.lock().unwrap().push((alpha, beta));
+                }
+
+                self.entered.fetch_add(1, Ordering::Relaxed);
+                self.deepest_ply.fetch_max(ply, Ordering::Relaxed);
+            }
+
+            fn on_node_exit(&self, _: &State, _: usize, _: Evaluation) {
+                self.exited.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn on_cutoff(&self, _: &State, _: usize, _: Move, _: usize) {
+                self.cutoffs.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let counts = Arc::new(Counts::default());
+        _ = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                observer: Some(counts.clone()),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |_| {},
+        );
 
-    fn cancel(&self) {
-        self.cancelled.store(true, Ordering::Relaxed);
+        // Nothing interrupted the search, so every node it entered was also left
+        let entered = counts.entered.load(Ordering::Relaxed);
+        assert!(entered > 0);
+        assert_eq!(entered, counts.exited.load(Ordering::Relaxed));
+        assert!(counts.cutoffs.load(Ordering::Relaxed) > 0);
+        assert_eq!(counts.deepest_ply.load(Ordering::Relaxed), 3);
+
+        // The root is entered once per depth, with the window it's actually searched with
+        let root_windows = counts.root_windows.lock().unwrap();
+        assert_eq!(root_windows.len(), 3);
+        assert!(root_windows.iter().all(|window| *window
+            == (
+                -eval::Evaluation::mate_in_ply(0),
+                eval::Evaluation::mate_in_ply(1)
+            )));
     }
 
-    fn is_cancelled(&self) -> bool {
-        self.cancelled.load(Ordering::Relaxed)
+    #[test]
+    fn test_random_streams() {
+        let draw = |mut rng: RandomNumberGenerator| rng.gen::<u64>();
+        let streams = RandomStreams::new(42);
+
+        // Drawing from one stream doesn't change what any other one produces
+        let mut hasher = streams.hasher();
+        _ = hasher.gen::<u64>();
+        assert_eq!(draw(streams.thread(3, 1)), draw(streams.thread(3, 1)));
+        assert_eq!(
+            draw(streams.thread(3, 1)),
+            draw(RandomStreams::new(42).thread(3, 1))
+        );
+
+        assert_ne!(draw(streams.thread(3, 1)), draw(streams.thread(3, 2)));
+        assert_ne!(draw(streams.thread(3, 1)), draw(streams.thread(4, 1)));
+        assert_ne!(draw(streams.thread(0, 0)), draw(streams.hasher()));
+        assert_ne!(
+            draw(streams.thread(3, 1)),
+            draw(RandomStreams::new(43).thread(3, 1))
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use weechess_core::{
-        notation::{self, into_notation, lan::Lan, Fen},
-        Color, Piece, PieceIndex, Square,
-    };
+    #[test]
+    fn test_progress_nodes() {
+        let state = notation::try_from_notation::<_, Fen>(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
 
-    fn evaluate(
-        game_state: State,
-        rng: RandomNumberGenerator,
-        depth: usize,
-        prev_artifact: Option<SearchArtifact>,
-    ) -> (eval::Evaluation, Vec<Move>) {
-        let cancel_token = CancellationToken::new().0;
-        let evaluator = eval::Evaluator::default();
-        let mut result = None;
+        let progress = |progress_nodes| {
+            let mut reports = vec![];
+            _ = Searcher::analyze_iterative(
+                state.clone(),
+                &eval::Evaluator::default(),
+                RandomStreams::new(0),
+                &SearchOptions {
+                    max_threads: Some(1),
+                    max_nodes: Some(40_000),
+                    progress_interval: Some(Duration::from_secs(3600)),
+                    progress_nodes,
+                    ..Default::default()
+                },
+                CancellationToken::new().0,
+                None,
+                &mut |e| {
+                    if let StatusEvent::Progress { nodes_searched, .. } = e {
+                        reports.push(nodes_searched);
+                    }
+                },
+            );
+
+            reports
+        };
+
+        // Without a node interval, the time interval is too long to report anything
+        assert!(progress(None).is_empty());
+
+        let reports = progress(Some(NODE_CHECK_INTERVAL));
+        assert!(!reports.is_empty());
+        assert!(reports[0] >= NODE_CHECK_INTERVAL);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[1] >= w[0] + NODE_CHECK_INTERVAL));
+    }
+
+    #[test]
+    fn test_ordering_stats() {
+        let mut stats = None;
         _ = Searcher::analyze_iterative(
-            game_state,
-            &evaluator,
-            rng,
-            Some(depth),
-            cancel_token,
-            prev_artifact,
-            Some(1),
-            &mut |e| match e {
-                StatusEvent::BestMove { line, evaluation } => {
-                    println!(
-                        "Best line: ({}) {}",
-                        into_notation::<_, Lan>(&&line[..]),
-                        evaluation
-                    );
-                    result = Some((evaluation, line));
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(4),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |e| {
+                if let StatusEvent::IterationComplete { stats: s, .. } = e {
+                    stats = Some(s);
                 }
-                _ => {}
             },
         );
 
-        result.unwrap()
-    }
+        let ordering = stats.unwrap().ordering;
+        let rate = ordering.first_move_rate().unwrap();
+        assert!(ordering.cutoffs > 0);
+        assert!((0.0..=1.0).contains(&rate));
 
-    #[test]
-    fn test_termination() {
-        let searcher = Searcher::new();
-        let evaluator = eval::Evaluator::default();
-        let state = State::default();
-        let (handle, tx, _) = searcher.analyze(state, 0, evaluator, None, None);
-        tx.send(ControlEvent::Stop).unwrap();
+        // Only the helper threads get any jitter, and less when the ordering is poor
+        let poor = OrderingStats {
+            cutoffs: 10,
+            first_move_cutoffs: 5,
+        };
 
-        handle.join().unwrap();
+        assert_eq!(MoveOrdering::for_thread(0, &ordering).jitter, 0);
+        assert_eq!(
+            MoveOrdering::for_thread(1, &OrderingStats::default()).jitter,
+            MAX_ORDERING_JITTER
+        );
+        assert!(MoveOrdering::for_thread(1, &poor).jitter < MAX_ORDERING_JITTER);
     }
 
     #[test]
@@ -997,8 +3874,7 @@ mod tests {
         )
         .unwrap();
 
-        let rng = ChaCha8Rng::seed_from_u64(0);
-        let (eval, line) = evaluate(state, rng, 4, None);
+        let (eval, line) = evaluate(state, 0, 4, None);
         let best_move = line.first().unwrap();
 
         assert_eq!(best_move.origin(), Square::C4);
@@ -1006,6 +3882,66 @@ mod tests {
         assert!(eval >= eval::Evaluation::mate_in_ply(100));
     }
 
+    #[test]
+    fn test_pruning() {
+        let reduction = LateMoveReduction {
+            base: 0.5,
+            divisor: 2.0,
+        };
+
+        assert_eq!(reduction.reduction(8, 3), 0);
+        assert_eq!(reduction.reduction(2, 20), 0);
+        assert_eq!(reduction.reduction(8, 20), 3);
+        assert_eq!(reduction.reduction(3, 40), 1);
+
+        let search = |fen: &str, depth: usize, pruning: PruningOptions| {
+            let (_, result) = Searcher::analyze_iterative(
+                notation::try_from_notation::<_, Fen>(fen).unwrap(),
+                &eval::Evaluator::default(),
+                RandomStreams::new(0),
+                &SearchOptions {
+                    max_depth: Some(depth),
+                    max_threads: Some(1),
+                    max_table_size_mb: Some(16),
+                    pruning,
+                    ..Default::default()
+                },
+                CancellationToken::new().0,
+                None,
+                &mut |_| {},
+            );
+
+            result
+        };
+
+        let pruning = PruningOptions {
+            late_move_reduction: Some(reduction),
+            futility_margin: Some(eval::Evaluation::ONE_PAWN * 2),
+            aspiration_window: Some(eval::Evaluation::from(50)),
+        };
+
+        // Cutting the search short still finds the mate
+        let mate = "r3k2r/ppp2Npp/1b5n/4p2b/2B1P2q/BQP2P2/P5PP/RN5K w kq - 1 1";
+        let result = search(mate, 4, pruning);
+        assert_eq!(
+            result.best_move(),
+            search(mate, 4, Default::default()).best_move()
+        );
+        assert!(result.evaluation >= eval::Evaluation::mate_in_ply(100));
+
+        // ...and searches less of a quieter position to get just as deep
+        let middlegame = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let full = search(middlegame, 4, Default::default());
+        let pruned = search(middlegame, 4, pruning);
+        assert_eq!(pruned.depth, 4);
+        assert!(
+            pruned.stats.nodes_searched < full.stats.nodes_searched,
+            "{} >= {}",
+            pruned.stats.nodes_searched,
+            full.stats.nodes_searched
+        );
+    }
+
     #[test]
     fn test_transposition_table() {
         let state = State::default();
@@ -1022,9 +3958,10 @@ mod tests {
                 Square::A1,
                 Square::A2,
             ),
-            depth: 0,
-            max_depth: 0,
+            node_kind: NodeKind::Pv,
+            remaining_depth: 0,
             evaluation: eval::Evaluation::ONE_PAWN,
+            depends_on_history: false,
         };
 
         table.insert(state_hash, entry);
@@ -1068,9 +4005,10 @@ mod tests {
                     Square::A1,
                     Square::A2,
                 ),
-                depth: 0,
-                max_depth: 1,
+                node_kind: NodeKind::Pv,
+                remaining_depth: 1,
                 evaluation: eval::Evaluation::ONE_PAWN,
+                depends_on_history: false,
             },
         );
 
@@ -1083,9 +4021,10 @@ mod tests {
                     Square::B1,
                     Square::B2,
                 ),
-                depth: 0,
-                max_depth: 1,
+                node_kind: NodeKind::Pv,
+                remaining_depth: 1,
                 evaluation: eval::Evaluation::ONE_PAWN,
+                depends_on_history: false,
             },
         );
 
@@ -1096,6 +4035,473 @@ mod tests {
         assert!(e1.performed_move != e2.performed_move);
     }
 
+    #[test]
+    fn test_transposition_table_generations() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let hasher = ZobristHasher::with(&mut rng);
+        let state = State::default();
+        let hashes = MoveGenerator::compute_legal_moves(&state)
+            .moves()
+            .iter()
+            .map(|MoveResult(_, s)| hasher.hash(s))
+            .collect::<Vec<_>>();
+
+        let entry = TranspositionEntry {
+            kind: EvaluationKind::Exact,
+            performed_move: Move::by_moving(
+                PieceIndex::new(Color::White, Piece::Pawn),
+                Square::E2,
+                Square::E4,
+            ),
+            node_kind: NodeKind::Pv,
+            remaining_depth: 1,
+            evaluation: eval::Evaluation::ONE_PAWN,
+            depends_on_history: false,
+        };
+
+        let mut table = TranspositionTable::with_bucket_count(1024);
+        table.insert(hashes[0], entry);
+        table.next_generation();
+        table.insert(hashes[1], entry);
+        table.next_generation();
+        table.insert(hashes[2], entry);
+
+        // Only the entry from two generations ago is stale
+        table.evict_stale(STALE_GENERATION_AGE);
+        assert_eq!(table.entries(), 2);
+        assert!(table.find(hashes[0]).is_none());
+        assert!(table.find(hashes[1]).is_some());
+
+        // Growing keeps the old entries around until they'd be stale, and they still
+        // count towards the memory the table takes up until then
+        let bucket_memory = std::mem::size_of::<TranspositionBucket>();
+        assert_eq!(table.grown_memory(), 3072 * bucket_memory);
+        table.grow();
+        assert_eq!(table.max_entries(), 2048 * TranspositionBucket::BUCKET_SIZE);
+        assert_eq!(table.entries(), 0);
+        assert_eq!(table.memory(), 3072 * bucket_memory);
+        assert!(table.find(hashes[2]).is_some());
+
+        table.next_generation();
+        table.next_generation();
+        assert!(table.find(hashes[2]).is_none());
+        assert_eq!(table.memory(), 2048 * bucket_memory);
+    }
+
+    #[test]
+    fn test_transposition_table_growth_limit() {
+        let entry = TranspositionEntry {
+            kind: EvaluationKind::Exact,
+            performed_move: Move::by_moving(
+                PieceIndex::new(Color::White, Piece::Pawn),
+                Square::E2,
+                Square::E4,
+            ),
+            node_kind: NodeKind::Pv,
+            remaining_depth: 1,
+            evaluation: eval::Evaluation::ONE_PAWN,
+            depends_on_history: false,
+        };
+
+        // A single bucket, filled right up
+        let full_table = || {
+            let access =
+                TranspositionTableAccess::with_tables(vec![TranspositionTable::with_bucket_count(
+                    1,
+                )]);
+
+            for key in 1..=TranspositionBucket::BUCKET_SIZE as u64 {
+                access.insert(key << 32, entry);
+            }
+
+            access
+        };
+
+        // Growing doubles the buckets but keeps the old ones around for a while,
+        // so it needs room for three times as many as there are now
+        let bucket_memory = std::mem::size_of::<TranspositionBucket>();
+        let access = full_table();
+        access.relieve_pressure(Some(2 * bucket_memory));
+        assert_eq!(access.memory(), bucket_memory);
+
+        let access = full_table();
+        access.relieve_pressure(Some(3 * bucket_memory));
+        assert_eq!(access.memory(), 3 * bucket_memory);
+        assert_eq!(access.max_entries(), 2 * TranspositionBucket::BUCKET_SIZE);
+    }
+
+    #[test]
+    fn test_transposition_table_size() {
+        let mb = 1024 * 1024;
+        let artifact = SearchArtifact::with_table_size(&mut ChaCha8Rng::seed_from_u64(0), 4);
+        let memory = artifact.transpositions.memory();
+        assert!(memory <= 4 * mb && memory > 3 * mb, "{}", memory);
+
+        // A search without tables to continue from starts with ones that fit its limit
+        let (artifact, _) = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(2),
+                max_threads: Some(1),
+                max_table_size_mb: Some(4),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |_| {},
+        );
+
+        assert!(artifact.transpositions.memory() <= 4 * mb);
+    }
+
+    #[test]
+    fn test_search_snapshots() {
+        // Snapshots are taken between the workers' reports, so a zero interval takes
+        // one every time, and none of them stop the search
+        let mut snapshots = vec![];
+        let (_, result) = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(4),
+                max_threads: Some(1),
+                max_table_size_mb: Some(4),
+                snapshot_interval: Some(Duration::ZERO),
+                max_snapshot_entries: Some(256),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            None,
+            &mut |e| {
+                if let StatusEvent::Snapshot { snapshot, .. } = e {
+                    snapshots.push(snapshot.entries());
+                }
+            },
+        );
+
+        assert_eq!(result.reason, StopReason::DepthReached);
+        assert_eq!(result.depth, 4);
+        assert!(!snapshots.is_empty());
+        assert!(
+            snapshots.iter().all(|entries| *entries <= 256),
+            "{:?}",
+            snapshots
+        );
+    }
+
+    #[test]
+    fn test_transposition_bucket_replacement() {
+        let mut bucket = TranspositionBucket::empty();
+        let entry = |depth| TranspositionEntry {
+            kind: EvaluationKind::Exact,
+            performed_move: Move::by_moving(
+                PieceIndex::new(Color::White, Piece::Pawn),
+                Square::E2,
+                Square::E4,
+            ),
+            node_kind: NodeKind::Pv,
+            remaining_depth: depth,
+            evaluation: eval::Evaluation::ONE_PAWN,
+            depends_on_history: false,
+        };
+
+        // Fill the bucket up with deep entries, except for one old one
+        bucket.insert_or_replace(1, entry(8), 0);
+        for key in 2..=TranspositionBucket::BUCKET_SIZE as u32 {
+            bucket.insert_or_replace(key, entry(8), 1);
+        }
+
+        assert!(!bucket.insert_or_replace(100, entry(1), 1).inserted());
+        assert!(bucket.find(1).is_none());
+        assert!(bucket.find(100).is_some());
+
+        // Among entries of the same age, the shallowest one goes
+        assert!(!bucket.insert_or_replace(101, entry(4), 1).inserted());
+        assert!(bucket.find(100).is_none());
+        assert!(bucket.find(101).is_some());
+    }
+
+    #[test]
+    fn test_node_kinds() {
+        // Only the first move of a PV node continues the principal variation
+        assert_eq!(NodeKind::Pv.child(0), NodeKind::Pv);
+        assert_eq!(NodeKind::Pv.child(1), NodeKind::Cut);
+
+        // Past that, cut and all nodes alternate no matter which move led there
+        assert_eq!(NodeKind::Cut.child(0), NodeKind::All);
+        assert_eq!(NodeKind::Cut.child(5), NodeKind::All);
+        assert_eq!(NodeKind::All.child(0), NodeKind::Cut);
+        assert_eq!(NodeKind::All.child(5), NodeKind::Cut);
+
+        // Extensions push the node deeper without counting as moves towards mates
+        let node = SearchNode::root(4).child(1, 1);
+        assert_eq!(node.kind, NodeKind::Cut);
+        assert_eq!(node.ply(), 1);
+        assert_eq!(node.remaining_depth(), 3);
+
+        // The root is always stored as a PV node
+        let hasher = ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(0));
+        let (artifact, _) = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            Some(SearchArtifact {
+                hasher: hasher.clone(),
+                transpositions: TranspositionTableAccess::small(),
+                state_history: StateHistory::new(),
+                root_moves: None,
+            }),
+            &mut |_| {},
+        );
+
+        let root = artifact.transpositions.find(hasher.hash(&State::default()));
+        assert_eq!(root.map(|entry| entry.node_kind), Some(NodeKind::Pv));
+    }
+
+    #[test]
+    fn test_transposition_slot_packing() {
+        assert_eq!(std::mem::size_of::<TranspositionSlot>(), 12);
+        assert_eq!(std::mem::size_of::<TranspositionBucket>(), 64);
+
+        let performed_move = Move::by_capture_promoting(
+            PieceIndex::new(Color::Black, Piece::Pawn),
+            Square::B2,
+            Square::A1,
+            Piece::Rook,
+            Piece::Queen,
+        );
+
+        for (kind, node_kind, depends_on_history) in [
+            (EvaluationKind::Exact, NodeKind::Pv, false),
+            (EvaluationKind::UpperBound, NodeKind::All, true),
+            (EvaluationKind::LowerBound, NodeKind::Cut, true),
+        ] {
+            let entry = TranspositionEntry {
+                kind,
+                performed_move,
+                node_kind,
+                remaining_depth: 9,
+                evaluation: -Evaluation::mate_in_ply(3),
+                depends_on_history,
+            };
+
+            let slot = TranspositionSlot::new(42, &entry, 37);
+            assert!(!slot.is_empty());
+            assert_eq!(slot.generation(), 37);
+            assert_eq!(slot.age(38), 1);
+
+            let unpacked = slot.entry();
+            assert_eq!(unpacked.kind, kind);
+            assert_eq!(unpacked.performed_move, performed_move);
+            assert_eq!(unpacked.node_kind, node_kind);
+            assert_eq!(unpacked.remaining_depth, 9);
+            assert_eq!(unpacked.evaluation, -Evaluation::mate_in_ply(3));
+            assert_eq!(unpacked.depends_on_history, depends_on_history);
+        }
+    }
+
+    #[test]
+    fn test_transposition_slot_keys() {
+        let entry = |depth| TranspositionEntry {
+            kind: EvaluationKind::Exact,
+            performed_move: Move::by_moving(
+                PieceIndex::new(Color::White, Piece::Pawn),
+                Square::E2,
+                Square::E4,
+            ),
+            node_kind: NodeKind::Pv,
+            remaining_depth: depth,
+            evaluation: eval::Evaluation::ONE_PAWN,
+            depends_on_history: false,
+        };
+
+        // The bucket comes from the low bits of the hash and the key from the high
+        // bits, so the entries that land in the same bucket are still told apart
+        let mut table = TranspositionTable::with_bucket_count(4);
+        let hash = |key: u64, bucket: u64| (key << 32) | bucket;
+        for key in 1..=TranspositionBucket::BUCKET_SIZE {
+            table.insert(hash(key as u64, 1), entry(key));
+        }
+
+        assert_eq!(table.entries(), TranspositionBucket::BUCKET_SIZE);
+        for key in 1..=TranspositionBucket::BUCKET_SIZE {
+            let found = table.find(hash(key as u64, 1)).unwrap();
+            assert_eq!(found.remaining_depth, key);
+        }
+
+        // The same key in another bucket is a different position
+        assert!(table.find(hash(1, 2)).is_none());
+
+        // A full bucket replaces one of its entries to make room
+        table.insert(hash(9, 1), entry(9));
+        assert_eq!(table.entries(), TranspositionBucket::BUCKET_SIZE);
+        assert_eq!(table.find(hash(9, 1)).unwrap().remaining_depth, 9);
+    }
+
+    #[test]
+    fn test_hash_line() {
+        let artifact = SearchArtifact {
+            hasher: ZobristHasher::with(&mut ChaCha8Rng::seed_from_u64(0)),
+            transpositions: TranspositionTableAccess::small(),
+            state_history: StateHistory::new(),
+            root_moves: None,
+        };
+
+        // The knights going out and back again comes back around to the starting position
+        let mut state = State::default();
+        for (i, san) in ["Nf3", "Nf6", "Ng1", "Ng8"].iter().enumerate() {
+            let query = notation::try_from_notation::<_, notation::San>(san).unwrap();
+            let MoveResult(mv, next_state) = state.find_legal(&query).unwrap();
+            artifact.transpositions.insert(
+                artifact.hasher.hash(&state),
+                TranspositionEntry {
+                    kind: EvaluationKind::Exact,
+                    performed_move: mv,
+                    node_kind: NodeKind::Pv,
+                    remaining_depth: 4 - i,
+                    evaluation: eval::Evaluation::EVEN,
+                    depends_on_history: false,
+                },
+            );
+
+            state = next_state;
+        }
+
+        let line = artifact.hash_line(&State::default());
+        assert_eq!(
+            line.iter()
+                .map(|ply| into_notation::<_, Lan>(&ply.mv).to_string())
+                .collect::<Vec<_>>(),
+            vec!["g1f3", "g8f6", "f3g1", "f6g8"]
+        );
+        assert_eq!(line[1].depth, 3);
+
+        // Lines can start from anywhere in the tables
+        let after_nf3 = State::by_performing_move(&State::default(), &line[0].mv).unwrap();
+        assert_eq!(artifact.hash_line(&after_nf3).len(), 4);
+
+        // Nothing was stored for the positions after other moves
+        let after_e4 = State::by_performing_moves(
+            &State::default(),
+            &[MoveQuery::by_moving_from_to(Square::E2, Square::E4)],
+        )
+        .unwrap();
+        assert!(artifact.hash_line(&after_e4).is_empty());
+    }
+
+    #[test]
+    fn test_artifact_round_trip() {
+        let state = State::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let hasher = ZobristHasher::with(&mut rng);
+        let state_hash = hasher.hash(&state);
+
+        let artifact = SearchArtifact {
+            hasher,
+            transpositions: TranspositionTableAccess::small(),
+            state_history: StateHistory::new(),
+            root_moves: None,
+        };
+
+        artifact.transpositions.insert(
+            state_hash,
+            TranspositionEntry {
+                kind: EvaluationKind::LowerBound,
+                performed_move: Move::by_moving(
+                    PieceIndex::new(Color::White, Piece::Pawn),
+                    Square::E2,
+                    Square::E4,
+                ),
+                node_kind: NodeKind::Pv,
+                remaining_depth: 4,
+                evaluation: eval::Evaluation::ONE_PAWN,
+                depends_on_history: false,
+            },
+        );
+
+        let mut buf = Vec::new();
+        ciborium::into_writer(&artifact, &mut buf).unwrap();
+        let restored: SearchArtifact = ciborium::from_reader(&buf[..]).unwrap();
+
+        assert_eq!(restored.hasher, artifact.hasher);
+        assert_eq!(restored.transpositions.entries(), 1);
+        assert_eq!(
+            restored.transpositions.max_entries(),
+            artifact.transpositions.max_entries()
+        );
+
+        assert_eq!(
+            restored.best_move(&state),
+            Some(Move::by_moving(
+                PieceIndex::new(Color::White, Piece::Pawn),
+                Square::E2,
+                Square::E4,
+            ))
+        );
+
+        let entry = restored.transpositions.find(state_hash).unwrap();
+        assert_eq!(entry.kind, EvaluationKind::LowerBound);
+        assert_eq!(entry.remaining_depth, 4);
+        assert_eq!(entry.evaluation, eval::Evaluation::ONE_PAWN);
+    }
+
+    #[test]
+    fn test_load_history() {
+        let mut artifact = SearchArtifact::new(&mut ChaCha8Rng::seed_from_u64(0));
+        let mut states = vec![State::default()];
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let query = notation::try_from_notation::<_, notation::San>(san).unwrap();
+            let MoveResult(_, next) = states.last().unwrap().find_legal(&query).unwrap();
+            states.push(next);
+        }
+
+        // The knights are back where they started, but the current position isn't loaded
+        let current = states.pop().unwrap();
+        artifact.load_history(&states);
+        assert_eq!(artifact.state_history().len(), 4);
+        assert_eq!(artifact.repetitions(&current), 1);
+        assert_eq!(artifact.repetitions(&states[1]), 1);
+
+        // Loading another game replaces the history
+        artifact.load_history(&states[1..2]);
+        assert_eq!(artifact.state_history().len(), 1);
+        assert_eq!(artifact.repetitions(&current), 0);
+        assert_eq!(
+            artifact.state_history().iter().collect::<Vec<_>>(),
+            vec![(artifact.hasher().hash(&states[1]), 1)]
+        );
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let searcher = Searcher::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let state = State::default();
+
+        // An artifact that's already around is kept, along with its history
+        let mut artifact = SearchArtifact::with_table_size(&mut rng, 1);
+        artifact.load_history([&state]);
+        let hash = artifact.hasher().hash(&state);
+
+        let artifact = searcher.warm_up(Some(artifact), &mut rng);
+        assert_eq!(artifact.hasher().hash(&state), hash);
+        assert_eq!(artifact.repetitions(&state), 1);
+
+        // Otherwise new tables are made, with a new hasher
+        let artifact = searcher.warm_up(None, &mut rng);
+        assert_ne!(artifact.hasher().hash(&state), hash);
+        assert_eq!(artifact.state_history().len(), 0);
+    }
+
     #[test]
     fn test_avoid_draws_by_repitition() {
         let game_state =
@@ -1115,9 +4521,10 @@ mod tests {
                 hasher: hasher.clone(),
                 transpositions: TranspositionTableAccess::small(),
                 state_history: StateHistory::new(),
+                root_moves: None,
             };
 
-            let (eval, line) = evaluate(game_state.clone(), rng.clone(), depth, Some(artifact));
+            let (eval, line) = evaluate(game_state.clone(), 0, depth, Some(artifact));
 
             assert!(eval > Evaluation::EVEN);
             assert_eq!(
@@ -1145,9 +4552,10 @@ mod tests {
                     history.increment(hasher.hash(&previous_game_state));
                     history
                 },
+                root_moves: None,
             };
 
-            let (eval, line) = evaluate(game_state.clone(), rng.clone(), depth, Some(artifact));
+            let (eval, line) = evaluate(game_state.clone(), 0, depth, Some(artifact));
             assert!(eval > Evaluation::EVEN);
             assert_ne!(
                 *line.first().unwrap(),
@@ -1159,4 +4567,55 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_repetition_scores_not_reused() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let hasher = ZobristHasher::with(&mut rng);
+        let play = |moves: &[&str]| {
+            let queries: Vec<MoveQuery> = moves
+                .iter()
+                .map(|m| MoveQuery::try_from_uci(m).unwrap())
+                .collect();
+
+            State::by_performing_moves(&State::default(), &queries).unwrap()
+        };
+
+        // The game already went through the position after 1. Nf3 Nf6, so any line
+        // getting back there is a draw, but only because of this game's history
+        let mut state_history = StateHistory::new();
+        state_history.increment(hasher.hash(&play(&["g1f3", "g8f6"])));
+
+        let artifact = SearchArtifact {
+            hasher: hasher.clone(),
+            transpositions: TranspositionTableAccess::small(),
+            state_history,
+            root_moves: None,
+        };
+
+        let (artifact, result) = Searcher::analyze_iterative(
+            State::default(),
+            &eval::Evaluator::default(),
+            RandomStreams::new(0),
+            &SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                ..Default::default()
+            },
+            CancellationToken::new().0,
+            Some(artifact),
+            &mut |_| {},
+        );
+
+        assert!(result.stats.nodes.repetition_draws > 0);
+
+        // After 1. Nf3, the score counted on the repetition, so it's flagged as not holding
+        // for a later search that might not have the same history. The move is still kept,
+        // and other moves are fine.
+        let transpositions = &artifact.transpositions;
+        let find = |moves: &[&str]| transpositions.find(hasher.hash(&play(moves))).unwrap();
+        assert!(find(&["g1f3"]).depends_on_history);
+        assert!(!find(&["e2e4"]).depends_on_history);
+        assert!(!find(&[]).depends_on_history);
+    }
 }
@@ -1,104 +1,479 @@
 use std::{
     io::{stdin, BufRead},
-    sync::mpsc,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    book::OpeningBook,
-    eval::Evaluator,
-    searcher::{self, SearchArtifact, Searcher},
+    book::{self, BookLearning, BookMoveCheck, OpeningBook},
+    cache::{AnalysisCache, CachedAnalysis},
+    crash,
+    eval::{Evaluation, Evaluator},
+    profiles::{BookProfiles, ProfileError},
+    searcher::{
+        self, EvaluationKind, SearchArtifact, SearchOptions, SearchStats, Searcher,
+        UnderpromotionPolicy,
+    },
     version::EngineVersion,
 };
 
 use rand::Rng;
 use weechess_core::{
-    notation::{into_notation, lan::Lan, try_from_notation, Fen},
-    Move, MoveQuery, Piece, Square, State,
+    notation::{
+        into_notation,
+        lan::{Lan, Lan960},
+        try_from_notation, Fen, MoveNotation,
+    },
+    pgn::Pgn,
+    utils::ArrayMap,
+    Color, Line, Move, MoveGenerator, MoveQuery, MoveResult, State, Variant,
 };
 
 const DEFAULT_MAX_SEARCH_TIME: f64 = 4.0;
 
+// Without movestogo, the time on the clock is shared out as if this many moves were left,
+// and no move gets more than this share of it
+const CLOCK_MOVES_TO_GO: usize = 30;
+const CLOCK_MAX_SHARE: f64 = 0.5;
+
+/// Evaluation term weights are exposed as options with this prefix, in percent
+pub const WEIGHT_OPTION_PREFIX: &str = "Weight_";
+pub const MAX_WEIGHT_PERCENT: u32 = 1000;
+
+// Bounds of the ProgressInterval option, in milliseconds
+const MIN_PROGRESS_INTERVAL_MS: u64 = 50;
+const MAX_PROGRESS_INTERVAL_MS: u64 = 60_000;
+
+// Book moves aren't checked with a search unless BookCheckNodes is set
+const MAX_BOOK_CHECK_NODES: usize = 10_000_000;
+
+// How many centipawns worse than the engine's choice a book move can be, by default
+const DEFAULT_BOOK_CHECK_MARGIN: i32 = 100;
+const MAX_BOOK_CHECK_MARGIN: i32 = 1000;
+
+/// Upper bound of the Threads option
+pub const MAX_THREADS: usize = 512;
+
+/// Upper bound of the MultiPV option
+pub const MAX_MULTI_PV: usize = 256;
+
+// Upper bound of the nodestime option, in nodes per millisecond
+const MAX_NODES_TIME: u64 = 100_000;
+
+// Value of the BookProfile option when no profile is selected
+const NO_BOOK_PROFILE: &str = "<none>";
+
+// A search is only considered stuck once it's this far past its deadline, and at least
+// as far past it again as the deadline itself
+const WATCHDOG_MIN_OVERRUN: Duration = Duration::from_secs(5);
+
+// How long a search can go without searching any new nodes, and how many progress
+// intervals that has to span, before it's considered hung
+const WATCHDOG_STALL_TIME: Duration = Duration::from_secs(10);
+const WATCHDOG_STALL_INTERVALS: u32 = 3;
+
+// How long a cancelled search gets to wind down before it's abandoned
+const WATCHDOG_GRACE_TIME: Duration = Duration::from_secs(2);
+const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 // Reference: https://gist.github.com/DOBRO/2592c6dad754ba67e6dcaec8c90165bf
 
-pub struct Client;
+pub struct Client {
+    book: OpeningBook,
+    handicap: Option<f32>,
+    learning: Option<(BookLearning, PathBuf)>,
+    cache: Option<Arc<Mutex<AnalysisCache>>>,
+
+    // Left to the searcher until it's set, which keeps the first depths to one thread
+    threads: Option<usize>,
+    profiles: Option<BookProfiles>,
+    profile: Option<String>,
+}
 
 impl Client {
     pub fn new() -> Self {
-        Self
+        Self::with_book(OpeningBook::try_default().unwrap_or_else(|_| OpeningBook::empty()))
+    }
+
+    pub fn with_book(book: OpeningBook) -> Self {
+        Self {
+            book,
+            handicap: None,
+            learning: None,
+            cache: None,
+            threads: None,
+            profiles: None,
+            profile: None,
+        }
+    }
+
+    /// Makes the engine undervalue its own pieces by a fraction between 0 and 1
+    pub fn with_handicap(self, handicap: Option<f32>) -> Self {
+        Self { handicap, ..self }
+    }
+
+    /// Weights the book moves with what has been learned from previous games, and
+    /// updates the weights in the file whenever a game result is reported
+    pub fn with_book_learning(self, learning: BookLearning, path: impl Into<PathBuf>) -> Self {
+        Self {
+            learning: Some((learning, path.into())),
+            ..self
+        }
+    }
+
+    /// Answers with the cached analysis of positions that have been searched deeply
+    /// enough before, and adds the results of deep enough searches to the cache
+    pub fn with_analysis_cache(self, cache: AnalysisCache) -> Self {
+        Self {
+            cache: Some(Arc::new(Mutex::new(cache))),
+            ..self
+        }
+    }
+
+    /// Sets the default of the Threads option, like to the number found by calibrating
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self {
+            threads: Some(threads.clamp(1, MAX_THREADS)),
+            ..self
+        }
+    }
+
+    /// Offers the book profiles as the BookProfile option, starting with the selected one.
+    /// A profile's book is consulted before the client's own, and it learns on its own.
+    pub fn with_book_profiles(self, profiles: BookProfiles, selected: Option<String>) -> Self {
+        Self {
+            profiles: Some(profiles),
+            profile: selected,
+            ..self
+        }
+    }
+
+    fn load_profile(
+        &self,
+        name: &str,
+    ) -> Result<(OpeningBook, (BookLearning, PathBuf)), ProfileError> {
+        let Some(profiles) = &self.profiles else {
+            return Err(ProfileError::NotFound(name.to_string()));
+        };
+
+        let profile = profiles.get(name)?;
+        let book = profile.book(OpeningBook::DEFAULT_DEPTH)?;
+        Ok((book, (profile.learning()?, profile.learning_path())))
+    }
+
+    /// The analysis cache, if a search of `state` with these settings can use it. The
+    /// cache only knows about standard chess searched for the single best line with the
+    /// default evaluation, so anything that changes what a search finds keeps it from
+    /// being read or written.
+    fn cache_for(
+        &self,
+        state: &State,
+        evaluator: &Evaluator,
+        options: &SearchOptions,
+    ) -> Option<&Arc<Mutex<AnalysisCache>>> {
+        let is_default = state.variant() == Variant::Standard
+            && options.handicap.is_none()
+            && options.mate.is_none()
+            && options.multi_pv.is_none_or(|count| count == 1)
+            && options.underpromotions == UnderpromotionPolicy::default()
+            && evaluator.has_default_weights();
+
+        self.cache.as_ref().filter(|_| is_default)
     }
 
     pub fn exec(&self) -> std::io::Result<()> {
         let mut input = stdin().lock().lines();
         let mut current_search: Option<Search> = None;
         let mut current_position: State = State::default();
+        let mut game_history: Vec<State> = vec![];
+        let mut variant = Variant::Standard;
+        let mut evaluator = Evaluator::default();
+        let mut progress_interval = searcher::DEFAULT_PROGRESS_INTERVAL;
+        let mut book_check_nodes = 0;
+        let mut book_check_margin = DEFAULT_BOOK_CHECK_MARGIN;
+        let mut threads = self.threads;
+        let mut nodes_time = 0;
+        let mut multi_pv = 1;
+        let mut underpromotions = UnderpromotionPolicy::default();
         let mut previous_artifact = None;
+        let mut learning = self.learning.clone();
+        let mut profile_book: Option<OpeningBook> = None;
+        if let Some(name) = &self.profile {
+            match self.load_profile(name) {
+                Ok((book, profile_learning)) => {
+                    profile_book = Some(book);
+                    learning = Some(profile_learning);
+                }
+                Err(e) => println!("info string unable to load book profile: {}", e),
+            }
+        }
+
+        // Book moves can be checked (and played) on the search thread
+        let book_moves_played: Arc<Mutex<Vec<(State, Move)>>> = Arc::default();
         let mut rng = rand::thread_rng();
-        let book = OpeningBook::try_default().unwrap();
+
+        // Whether to send the extra diagnostics a GUI asks for with `debug on`
+        let mut debug = false;
+
+        // Whether the GUI wants castling written as the king taking its rook
+        let mut chess960 = false;
+        let mut notation = MoveNotation::Lan;
         while let Some(Ok(cmd)) = input.next() {
             let parts: Vec<&str> = cmd.split_ascii_whitespace().collect();
             match parts.split_first() {
                 Some((&"go", args)) => {
                     if let Some(search) = current_search.take() {
-                        previous_artifact = Some(search.wait_cancel());
+                        previous_artifact = search.wait_cancel();
                     }
 
-                    let mut search_time: Option<f64> = None;
-                    let mut search_depth: Option<usize> = None;
+                    let limits = GoLimits::parse(args);
+                    let search_depth = limits.depth;
 
-                    // Parse the arguments
-                    let mut iter = args.iter();
-                    while let Some(arg) = iter.next() {
-                        match *arg {
-                            "movetime" => {
-                                if let Some(time) = iter.next() {
-                                    if let Ok(movetime_ms) = i32::from_str_radix(time, 10) {
-                                        search_time = Some(movetime_ms as f64 / 1000.0);
-                                        continue;
-                                    }
-                                }
-                            }
-                            "depth" => {
-                                if let Some(depth) = iter.next() {
-                                    if let Ok(depth) = usize::from_str_radix(depth, 10) {
-                                        search_depth = Some(depth);
-                                        continue;
-                                    }
-                                }
-                            }
-                            _ => {}
+                    // The book only knows about standard chess openings, and
+                    // nothing about how the game got to this position
+                    let moves = if current_position.variant() == Variant::Standard {
+                        let lookup = |book: &OpeningBook| {
+                            let moves = book.lookup_legal(&current_position, |m| {
+                                println!("info string ignoring illegal book move: {}", m);
+                            });
+
+                            book.without_repetitions(&current_position, moves, &game_history)
+                        };
+
+                        match profile_book.as_ref().map(lookup) {
+                            Some(moves) if !moves.is_empty() => moves,
+                            _ => lookup(&self.book),
                         }
+                    } else {
+                        vec![]
+                    };
 
-                        println!("info string unparsable go commands");
-                        break;
+                    if debug && !moves.is_empty() {
+                        println!(
+                            "info string book candidates: {}{}",
+                            moves
+                                .iter()
+                                .map(|m| format_readable_moves(
+                                    &current_position,
+                                    &[*m],
+                                    notation,
+                                    chess960
+                                ))
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                            if learning.is_some() { " (learned)" } else { "" }
+                        );
                     }
 
-                    // TODO: Do we always want to pick a book move?
-                    if let Some(moves) = book.lookup(&current_position) {
-                        let moves = moves.iter().collect::<Vec<_>>();
-                        let m = moves[rng.gen_range(0..moves.len())];
-                        println!("info string book move: {}", m);
-                        println!("bestmove {}", into_notation::<_, Lan>(m));
+                    let book_move = if moves.is_empty() {
+                        None
+                    } else {
+                        match &learning {
+                            Some((learning, _)) => learning
+                                .choose(&current_position, &moves, &mut rng)
+                                .or(Some(moves[0])),
+                            None => Some(moves[rng.gen_range(0..moves.len())]),
+                        }
+                    };
+
+                    if let Some(m) = book_move.filter(|_| book_check_nodes == 0) {
+                        if let Ok(mut played) = book_moves_played.lock() {
+                            played.push((current_position.clone(), m));
+                        }
+
+                        println!(
+                            "info string book move: {}",
+                            format_readable_moves(&current_position, &[m], notation, chess960)
+                        );
+                        println!("bestmove {}", format_moves(&[m], chess960));
 
                         continue;
                     }
 
+                    // Otherwise the book move is checked on the search thread first, within
+                    // the time for the move, and the search only runs if it looks unsound
+                    let book_check = book_move.map(|m| {
+                        let state = current_position.clone();
+                        let evaluator = evaluator.clone();
+                        let rng_seed = rng.gen();
+                        let name = format_readable_moves(&state, &[m], notation, chess960);
+                        let book_moves_played = book_moves_played.clone();
+                        Box::new(move |is_stopped: &mut dyn FnMut() -> bool| {
+                            let check = BookMoveCheck::run(
+                                &state,
+                                &m,
+                                &evaluator,
+                                rng_seed,
+                                book_check_nodes,
+                                is_stopped,
+                            );
+
+                            if !check.is_sound(book_check_margin) {
+                                println!(
+                                    "info string rejecting book move {}, it loses {}cp",
+                                    name,
+                                    check.loss()
+                                );
+
+                                // Without the time to search, the check's move will have to do
+                                return if is_stopped() { check.best_move } else { None };
+                            }
+
+                            if debug {
+                                println!(
+                                    "info string book move {} checks out, it loses {}cp",
+                                    name,
+                                    check.loss()
+                                );
+                            }
+
+                            if let Ok(mut played) = book_moves_played.lock() {
+                                played.push((state, m));
+                            }
+
+                            println!("info string book move: {}", name);
+                            Some(m)
+                        }) as Prelude
+                    });
+
+                    let (max_nodes, search_time) =
+                        limits.budget(current_position.turn_to_move(), nodes_time);
+
+                    let options = SearchOptions {
+                        max_depth: search_depth,
+                        handicap: self.handicap,
+                        mate: limits.mate,
+                        progress_interval: Some(progress_interval),
+                        max_threads: threads,
+                        max_nodes,
+                        multi_pv: Some(multi_pv),
+                        underpromotions,
+                        ..Default::default()
+                    };
+
+                    // Infinite searches wait for the GUI to stop them, even with an answer ready
+                    let cache = self.cache_for(&current_position, &evaluator, &options);
+                    let cached = cache
+                        .filter(|_| !limits.infinite && book_check.is_none())
+                        .and_then(|cache| {
+                            cache.lock().ok()?.probe(&current_position, &game_history)
+                        })
+                        .filter(|analysis| {
+                            search_depth.is_none_or(|depth| analysis.depth as usize >= depth)
+                        });
+
+                    if let Some(analysis) = cached {
+                        println!("info string cached analysis");
+                        println!(
+                            "info depth {} score {} pv {}",
+                            analysis.depth,
+                            format_score(analysis.evaluation),
+                            format_moves(&[analysis.best_move], chess960)
+                        );
+                        println!("bestmove {}", format_moves(&[analysis.best_move], chess960));
+
+                        continue;
+                    }
+
+                    // Let the search know which positions have already come up in
+                    // the game, so that it can see repetitions coming
+                    let mut artifact = previous_artifact
+                        .take()
+                        .unwrap_or_else(|| SearchArtifact::new(&mut rng));
+
+                    artifact.load_history(&game_history);
+
                     let search = Search::spawn(
                         current_position.clone(),
+                        evaluator.clone(),
                         rng.gen(),
-                        search_depth,
+                        options,
                         search_time,
-                        previous_artifact.take(),
+                        Some(artifact),
+                        SearchHooks {
+                            cache: cache.map(|cache| (cache.clone(), game_history.clone())),
+                            prelude: book_check,
+                            infinite: limits.infinite,
+                            debug,
+                            chess960,
+                        },
                     );
 
                     current_search = Some(search);
                 }
+                Some((&"debug", [mode])) => match *mode {
+                    "on" => debug = true,
+                    "off" => debug = false,
+                    _ => println!("info string invalid debug mode: {}", mode),
+                },
                 Some((&"isready", _)) => {
+                    // Get the tables ready now rather than on the first `go`, unless
+                    // a search is running and has already done it
+                    if current_search.is_none() {
+                        previous_artifact =
+                            Some(Searcher::new().warm_up(previous_artifact.take(), &mut rng));
+                    }
+
                     println!("readyok");
                 }
+                Some((&"pv", args)) => {
+                    // Shows the line stored in the tables from the last search, so it
+                    // only works between searches
+                    if current_search
+                        .as_ref()
+                        .is_some_and(|search| !search.is_finished())
+                    {
+                        println!("info string search in progress, stop it first");
+                        continue;
+                    }
+
+                    if let Some(search) = current_search.take() {
+                        previous_artifact = search.wait_cancel();
+                    }
+
+                    let state = match args {
+                        [] | ["current"] => Some(current_position.clone()),
+                        fen => try_from_notation::<State, Fen>(&fen.join(" "))
+                            .ok()
+                            .map(|state| state.with_variant(variant)),
+                    };
+
+                    match (state, &previous_artifact) {
+                        (None, _) => println!("info string invalid fen position"),
+                        (_, None) => println!("info string no tables to show, search first"),
+                        (Some(state), Some(artifact)) => {
+                            let line = artifact.hash_line(&state);
+                            if line.is_empty() {
+                                println!("info string no moves stored for the position");
+                            }
+
+                            let moves: Vec<Move> = line.iter().map(|entry| entry.mv).collect();
+                            let moves = match notation {
+                                MoveNotation::Lan => moves
+                                    .iter()
+                                    .map(|m| format_moves(&[*m], chess960))
+                                    .collect(),
+                                notation => notation.write_line(&state, &moves),
+                            };
+
+                            for (ply, (entry, mv)) in line.iter().zip(moves).enumerate() {
+                                println!(
+                                    "info string ply {} move {} depth {} score {}{}",
+                                    ply + 1,
+                                    mv,
+                                    entry.depth,
+                                    format_score(entry.evaluation),
+                                    format_bound(entry.kind)
+                                );
+                            }
+                        }
+                    }
+                }
                 Some((&"position", args)) => {
                     if let Some(search) = current_search.take() {
-                        previous_artifact = Some(search.wait_cancel());
+                        previous_artifact = search.wait_cancel();
                     }
 
                     let (pos, moves) = args
@@ -109,13 +484,13 @@ impl Client {
                         // Parse the position string
                         match pos.first() {
                             Some(&"startpos") => {
-                                current_position = State::default();
+                                current_position = State::default().with_variant(variant);
                             }
                             Some(&"fen") => {
                                 let fen = pos[1..].join(" ");
                                 match try_from_notation::<State, Fen>(&fen) {
                                     Ok(state) => {
-                                        current_position = state;
+                                        current_position = state.with_variant(variant);
                                     }
                                     Err(..) => {
                                         println!("info string invalid fen position");
@@ -133,31 +508,8 @@ impl Client {
                     {
                         // Apply the moves
                         let move_details: Vec<MoveQuery> = moves
-                            .into_iter()
-                            .filter_map(|m| {
-                                let origin = Square::try_from(&m[0..2]).ok()?;
-                                let destination = Square::try_from(&m[2..4]).ok()?;
-                                let promotion = if let Some(p) = m.chars().nth(4) {
-                                    match p {
-                                        'q' => Some(Piece::Queen),
-                                        'r' => Some(Piece::Rook),
-                                        'b' => Some(Piece::Bishop),
-                                        'n' => Some(Piece::Knight),
-                                        _ => return None,
-                                    }
-                                } else {
-                                    None
-                                };
-
-                                let mut query = MoveQuery::new();
-                                query.set_origin(origin);
-                                query.set_destination(destination);
-                                if let Some(promotion) = promotion {
-                                    query.set_promotion(promotion);
-                                }
-
-                                Some(query)
-                            })
+                            .iter()
+                            .filter_map(|m| MoveQuery::try_from_uci(m))
                             .collect();
 
                         if move_details.len() != moves.len() {
@@ -165,11 +517,28 @@ impl Client {
                             continue;
                         }
 
-                        match State::by_performing_moves(&current_position, &move_details) {
-                            Ok(state) => {
+                        let mut played_moves = vec![];
+                        let mut played_states = vec![];
+                        match move_details.iter().try_fold(
+                            current_position.clone(),
+                            |state, query| {
+                                state.find_legal(query).map(|MoveResult(mv, next)| {
+                                    played_moves.push(mv);
+                                    played_states.push(state);
+                                    next
+                                })
+                            },
+                        ) {
+                            Some(state) => {
+                                crash::record_game(
+                                    into_notation::<_, Fen>(&current_position).to_string(),
+                                    &played_moves,
+                                );
+
                                 current_position = state;
+                                game_history = played_states;
                             }
-                            Err(..) => {
+                            None => {
                                 println!("info string invalid move");
                                 continue;
                             }
@@ -178,20 +547,283 @@ impl Client {
                 }
                 Some((&"stop", _)) => {
                     if let Some(search) = current_search.take() {
-                        previous_artifact = Some(search.wait_cancel());
+                        previous_artifact = search.wait_cancel();
                     }
                 }
                 Some((&"uci", _)) => {
-                    println!("id name {}", EngineVersion::CURRENT);
+                    println!(
+                        "id name {} ({})",
+                        EngineVersion::CURRENT,
+                        EngineVersion::CURRENT.build
+                    );
                     println!("id author {}", EngineVersion::CURRENT.author);
+                    println!(
+                        "info string features: {}",
+                        EngineVersion::CURRENT.build.features().join(" ")
+                    );
+                    println!(
+                        "option name UCI_Variant type combo default {}{}",
+                        Variant::Standard,
+                        Variant::ALL
+                            .iter()
+                            .map(|v| format!(" var {}", v))
+                            .collect::<String>()
+                    );
+
+                    for (name, weight) in Evaluator::default().weights() {
+                        println!(
+                            "option name {}{} type spin default {} min 0 max {}",
+                            WEIGHT_OPTION_PREFIX,
+                            name,
+                            (weight * 100.0).round(),
+                            MAX_WEIGHT_PERCENT
+                        );
+                    }
+
+                    println!(
+                        "option name ProgressInterval type spin default {} min {} max {}",
+                        searcher::DEFAULT_PROGRESS_INTERVAL.as_millis(),
+                        MIN_PROGRESS_INTERVAL_MS,
+                        MAX_PROGRESS_INTERVAL_MS
+                    );
+
+                    println!(
+                        "option name BookCheckNodes type spin default 0 min 0 max {}",
+                        MAX_BOOK_CHECK_NODES
+                    );
+
+                    println!(
+                        "option name BookCheckMargin type spin default {} min 0 max {}",
+                        DEFAULT_BOOK_CHECK_MARGIN, MAX_BOOK_CHECK_MARGIN
+                    );
+
+                    println!(
+                        "option name Threads type spin default {} min 1 max {}",
+                        self.threads.unwrap_or(searcher::DEFAULT_MAX_THREAD_COUNT),
+                        MAX_THREADS
+                    );
+
+                    println!(
+                        "option name nodestime type spin default 0 min 0 max {}",
+                        MAX_NODES_TIME
+                    );
+
+                    println!(
+                        "option name MultiPV type spin default 1 min 1 max {}",
+                        MAX_MULTI_PV
+                    );
+
+                    println!(
+                        "option name Underpromotions type combo default {} var {} var {}",
+                        UnderpromotionPolicy::default(),
+                        UnderpromotionPolicy::All,
+                        UnderpromotionPolicy::Deferred
+                    );
+
+                    // Only for the moves in info strings, the rest are always in LAN
+                    println!("option name Notation type combo default lan var san var lan var peg");
+
+                    if let Some(profiles) = &self.profiles {
+                        println!(
+                            "option name BookProfile type combo default {} var {}{}",
+                            self.profile.as_deref().unwrap_or(NO_BOOK_PROFILE),
+                            NO_BOOK_PROFILE,
+                            profiles
+                                .names()
+                                .unwrap_or_default()
+                                .iter()
+                                .map(|name| format!(" var {}", name))
+                                .collect::<String>()
+                        );
+                    }
+
                     println!("uciok");
                 }
+                Some((&"setoption", args)) => {
+                    let Some((name, value)) = parse_setoption(args) else {
+                        println!("info string invalid setoption command");
+                        continue;
+                    };
+
+                    let (name, value) = (name.as_str(), value.as_str());
+                    if name.eq_ignore_ascii_case("UCI_Variant") {
+                        match value.parse::<Variant>() {
+                            Ok(v) => {
+                                // Positions searched under different rules hash the same
+                                variant = v;
+                                previous_artifact = None;
+                            }
+                            Err(..) => println!("info string unknown variant: {}", value),
+                        }
+                    } else if let Some(term) = name
+                        .strip_prefix(WEIGHT_OPTION_PREFIX)
+                        .filter(|term| evaluator.weights().any(|(name, _)| name == *term))
+                    {
+                        match value.parse::<u32>() {
+                            Ok(percent) if percent <= MAX_WEIGHT_PERCENT => {
+                                // Scores from the old weights can't be trusted anymore
+                                evaluator = evaluator.with_weight(term, percent as f32 / 100.0);
+                                previous_artifact = None;
+                            }
+                            _ => println!("info string invalid weight: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("ProgressInterval") {
+                        match value.parse::<u64>() {
+                            Ok(ms)
+                                if (MIN_PROGRESS_INTERVAL_MS..=MAX_PROGRESS_INTERVAL_MS)
+                                    .contains(&ms) =>
+                            {
+                                progress_interval = Duration::from_millis(ms);
+                            }
+                            _ => println!("info string invalid progress interval: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("BookCheckNodes") {
+                        match value.parse::<usize>() {
+                            Ok(nodes) if nodes <= MAX_BOOK_CHECK_NODES => {
+                                book_check_nodes = nodes;
+                            }
+                            _ => println!("info string invalid book check nodes: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("BookCheckMargin") {
+                        match value.parse::<i32>() {
+                            Ok(cp) if (0..=MAX_BOOK_CHECK_MARGIN).contains(&cp) => {
+                                book_check_margin = cp;
+                            }
+                            _ => println!("info string invalid book check margin: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("UCI_Chess960") {
+                        // Not advertised, since only standard starting positions can be
+                        // played, but GUIs that write castling the Chess960 way can still
+                        // turn it on to get the same castling notation back
+                        match value.parse::<bool>() {
+                            Ok(enabled) => chess960 = enabled,
+                            _ => println!("info string invalid Chess960 setting: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("Threads") {
+                        match value.parse::<usize>() {
+                            Ok(count) if (1..=MAX_THREADS).contains(&count) => {
+                                threads = Some(count)
+                            }
+                            _ => println!("info string invalid thread count: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("MultiPV") {
+                        match value.parse::<usize>() {
+                            Ok(count) if (1..=MAX_MULTI_PV).contains(&count) => multi_pv = count,
+                            _ => println!("info string invalid MultiPV: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("Underpromotions") {
+                        match value.parse::<UnderpromotionPolicy>() {
+                            Ok(policy) => underpromotions = policy,
+                            Err(..) => println!("info string unknown underpromotions: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("Notation") {
+                        match value.parse::<MoveNotation>() {
+                            Ok(n) => notation = n,
+                            Err(..) => println!("info string unknown notation: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("nodestime") {
+                        match value.parse::<u64>() {
+                            Ok(nodes) if nodes <= MAX_NODES_TIME => nodes_time = nodes,
+                            _ => println!("info string invalid nodestime: {}", value),
+                        }
+                    } else if name.eq_ignore_ascii_case("BookProfile") && self.profiles.is_some() {
+                        if value == NO_BOOK_PROFILE {
+                            profile_book = None;
+                            learning = self.learning.clone();
+                        } else {
+                            match self.load_profile(value) {
+                                Ok((book, profile_learning)) => {
+                                    profile_book = Some(book);
+                                    learning = Some(profile_learning);
+                                }
+                                Err(e) => {
+                                    println!("info string unable to load book profile: {}", e)
+                                }
+                            }
+                        }
+                    } else {
+                        println!("info string unknown option: {}", name);
+                    }
+                }
                 Some((&"ucinewgame", _)) => {
                     if let Some(search) = current_search.take() {
                         search.wait_cancel();
                     }
+
+                    if let Ok(mut played) = book_moves_played.lock() {
+                        played.clear();
+                    }
+
+                    game_history.clear();
+                    previous_artifact =
+                        Some(Searcher::new().warm_up(previous_artifact.take(), &mut rng));
+                }
+                Some((&"result", [result])) => {
+                    // Not part of the UCI protocol, but lets a GUI or match runner tell
+                    // us how the game went so that the book can learn from it
+                    let Some(result) = book::parse_result(result) else {
+                        println!("info string invalid result: {}", result);
+                        continue;
+                    };
+
+                    let played = match book_moves_played.lock() {
+                        Ok(mut played) => std::mem::take(&mut *played),
+                        Err(..) => vec![],
+                    };
+
+                    if let Some((learning, path)) = &mut learning {
+                        if learning.learn(&played, result) {
+                            if let Err(e) = learning.save(path) {
+                                println!("info string unable to save book learning: {}", e);
+                            }
+                        }
+                    }
                 }
                 Some((&"quit", _)) => break,
+                Some((&".load", [kind, path, args @ ..])) if *kind == "pgn" => {
+                    if let Some(search) = current_search.take() {
+                        previous_artifact = search.wait_cancel();
+                    }
+
+                    let pgn = match std::fs::read_to_string(path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|contents| Pgn::parse(&contents).map_err(|e| e.to_string()))
+                    {
+                        Ok(pgn) => pgn,
+                        Err(e) => {
+                            eprintln!("Unable to load {}: {}", path, e);
+                            continue;
+                        }
+                    };
+
+                    // Default to the final position of the game
+                    let ply = match args.first().map(|ply| ply.parse::<usize>()) {
+                        Some(Ok(ply)) => ply,
+                        Some(Err(..)) => {
+                            eprintln!("Invalid ply: {}", args[0]);
+                            continue;
+                        }
+                        None => pgn.moves.len(),
+                    };
+
+                    match pgn.state_at(ply) {
+                        Some(Ok(state)) => {
+                            let moves: Vec<Move> = pgn.moves[..ply].iter().map(|m| m.mv).collect();
+                            crash::record_game(
+                                into_notation::<_, Fen>(&pgn.initial_state).to_string(),
+                                &moves,
+                            );
+
+                            game_history =
+                                (0..ply).filter_map(|ply| pgn.state_at(ply)?.ok()).collect();
+
+                            current_position = state;
+                            eprintln!("{}", current_position.pretty());
+                        }
+                        Some(Err(e)) => eprintln!("Unable to load {}: {}", path, e),
+                        None => eprintln!("The game only has {} plies", pgn.moves.len()),
+                    }
+                }
                 Some((&".state", _)) => {
                     eprintln!("{}", current_position.pretty());
                 }
@@ -219,102 +851,872 @@ impl Client {
     }
 }
 
+/// The limits a `go` command puts on the search
+#[derive(Default)]
+struct GoLimits {
+    movetime: Option<f64>,
+    depth: Option<usize>,
+    mate: Option<usize>,
+    nodes: Option<usize>,
+
+    /// Searches until told to stop, whatever the other limits say
+    infinite: bool,
+
+    /// The time each side has left and gets back per move, in seconds
+    time: ArrayMap<Color, Option<f64>>,
+    increment: ArrayMap<Color, f64>,
+    moves_to_go: Option<usize>,
+}
+
+impl GoLimits {
+    /// Parses the arguments of `go`, keeping whatever came before the first one
+    /// that doesn't make sense
+    fn parse(args: &[&str]) -> Self {
+        fn seconds(ms: &str) -> Option<f64> {
+            ms.parse::<i64>().ok().map(|ms| ms.max(0) as f64 / 1000.0)
+        }
+
+        let mut limits = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if *arg == "infinite" {
+                limits.infinite = true;
+                continue;
+            }
+
+            let value = iter.next().copied().unwrap_or_default();
+            let parsed = match *arg {
+                "movetime" => seconds(value).map(|time| limits.movetime = Some(time)),
+                "wtime" => seconds(value).map(|time| limits.time[Color::White] = Some(time)),
+                "btime" => seconds(value).map(|time| limits.time[Color::Black] = Some(time)),
+                "winc" => seconds(value).map(|inc| limits.increment[Color::White] = inc),
+                "binc" => seconds(value).map(|inc| limits.increment[Color::Black] = inc),
+                "movestogo" => value
+                    .parse()
+                    .ok()
+                    .map(|moves| limits.moves_to_go = Some(moves)),
+                "depth" => value.parse().ok().map(|depth| limits.depth = Some(depth)),
+                "nodes" => value.parse().ok().map(|nodes| limits.nodes = Some(nodes)),
+                "mate" => value.parse().ok().map(|moves| limits.mate = Some(moves)),
+                _ => None,
+            };
+
+            if parsed.is_none() {
+                println!("info string unparsable go commands");
+                break;
+            }
+        }
+
+        limits
+    }
+
+    /// The time to spend on the move, either as given or as a share of what's
+    /// left on the clock of the side to move
+    fn search_time(&self, color: Color) -> Option<f64> {
+        self.movetime.or_else(|| {
+            let remaining = self.time[color]?;
+            let moves_to_go = self.moves_to_go.unwrap_or(CLOCK_MOVES_TO_GO).max(1);
+            let time = remaining / moves_to_go as f64 + self.increment[color];
+            Some(time.min(remaining * CLOCK_MAX_SHARE))
+        })
+    }
+
+    /// The nodes and time the search gets. With nodestime set, the time to search is
+    /// spent as nodes instead, and there's no timer at all, so that the search doesn't
+    /// depend on how busy the machine happens to be. Infinite searches get neither.
+    fn budget(&self, color: Color, nodes_time: u64) -> (Option<usize>, Option<f64>) {
+        let search_time = self.search_time(color);
+        match self.nodes {
+            _ if self.infinite => (None, None),
+            Some(nodes) => (Some(nodes), search_time),
+            None if nodes_time > 0 => {
+                let time = search_time.unwrap_or(DEFAULT_MAX_SEARCH_TIME);
+                let nodes = (time * 1000.0 * nodes_time as f64) as usize;
+                (Some(nodes.max(1)), None)
+            }
+            None => (None, search_time),
+        }
+    }
+}
+
+/// Splits the arguments of `setoption` into the name and value of the option. Either
+/// can have spaces in it, so they're told apart by the `name` and `value` keywords.
+/// Options without a value, like buttons, get an empty one.
+fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    let (keyword, args) = args.split_first()?;
+    if *keyword != "name" {
+        return None;
+    }
+
+    let (name, value) = match args.iter().position(|arg| *arg == "value") {
+        Some(index) => (&args[..index], args[index + 1..].join(" ")),
+        None => (args, String::new()),
+    };
+
+    if name.is_empty() {
+        return None;
+    }
+
+    Some((name.join(" "), value))
+}
+
+fn format_score(evaluation: Evaluation) -> String {
+    match evaluation.mate_in_moves() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", evaluation.cp()),
+    }
+}
+
+fn format_bound(kind: EvaluationKind) -> &'static str {
+    match kind {
+        EvaluationKind::Exact => "",
+        EvaluationKind::UpperBound => " upperbound",
+        EvaluationKind::LowerBound => " lowerbound",
+    }
+}
+
+fn print_progress(
+    start_time: std::time::Instant,
+    depth: u32,
+    nodes_searched: usize,
+    transposition_saturation: f32,
+) {
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let nps = if elapsed > 0.0 {
+        nodes_searched as f64 / elapsed
+    } else {
+        0.0
+    };
+
+    println!(
+        "info time {:.0} depth {} nps {:.0} nodes {} hashfull {:.0}",
+        elapsed * 1000f64,
+        depth,
+        nps,
+        nodes_searched,
+        transposition_saturation * 1000.0
+    );
+}
+
+fn print_stats(depth: u32, stats: &SearchStats) {
+    let percent = |rate: Option<f32>| match rate {
+        Some(rate) => format!("{:.1}%", rate * 100.0),
+        None => String::from("n/a"),
+    };
+
+    println!(
+        "info string depth {} tt hits {}/{} ({}) extensions {} repetitions {} first move cutoffs {}",
+        depth,
+        stats.nodes.transposition_hits,
+        stats.nodes.transposition_probes,
+        percent(stats.nodes.transposition_hit_rate()),
+        stats.nodes.extensions,
+        stats.nodes.repetition_draws,
+        percent(stats.ordering.first_move_rate())
+    );
+}
+
+// Writes out moves separated by spaces, with castling written the way the GUI expects
+fn format_moves(moves: &[Move], chess960: bool) -> String {
+    if chess960 {
+        into_notation::<_, Lan960>(&moves).to_string()
+    } else {
+        into_notation::<_, Lan>(&moves).to_string()
+    }
+}
+
+/// Writes a line of moves played from the given position for people to read, like in
+/// `info string`s, in the notation picked with the `Notation` option. Moves meant for
+/// the GUI to parse are always written with `format_moves` instead.
+fn format_readable_moves(
+    state: &State,
+    moves: &[Move],
+    notation: MoveNotation,
+    chess960: bool,
+) -> String {
+    match notation {
+        MoveNotation::Lan => format_moves(moves, chess960),
+        notation => notation.write_line(state, moves).join(" "),
+    }
+}
+
+/// The move to answer with when a search doesn't come up with one. The move an earlier
+/// search found is likely better than just any legal move, if it's still in the tables.
+fn emergency_move(state: &State, previous_artifact: Option<&SearchArtifact>) -> Option<Move> {
+    previous_artifact
+        .and_then(|artifact| artifact.best_move(state))
+        .or_else(|| {
+            MoveGenerator::compute_legal_moves(state)
+                .moves()
+                .first()
+                .map(|r| r.0)
+        })
+}
+
+/// Gets to answer on the search thread before the search starts, which is played instead
+/// of searching if it comes up with a move. It's asked every so often whether the search
+/// has been stopped, and should answer quickly (with a move if it can) once it has.
+type Prelude = Box<dyn FnOnce(&mut dyn FnMut() -> bool) -> Option<Move> + Send>;
+
+/// What a search does around the search itself, for the GUI that asked for it
+struct SearchHooks {
+    /// Where the finished analysis goes, along with the game that led up to the position
+    cache: Option<(Arc<Mutex<AnalysisCache>>, Vec<State>)>,
+    prelude: Option<Prelude>,
+
+    /// Whether the GUI stops the search itself, rather than it running out of time
+    infinite: bool,
+
+    /// Whether to print the extra `info string` lines that `debug on` asks for
+    debug: bool,
+    chess960: bool,
+}
+
 struct Search {
     start_time: std::time::Instant,
-    write_handle: thread::JoinHandle<()>,
-    search_handle: thread::JoinHandle<SearchArtifact>,
+    write_handle: thread::JoinHandle<Option<SearchArtifact>>,
     control: mpsc::Sender<searcher::ControlEvent>,
 }
 
 impl Search {
     pub fn spawn(
         state: State,
+        evaluator: Evaluator,
         rng_seed: u64,
-        depth: Option<usize>,
+        options: SearchOptions,
         search_time: Option<f64>,
         previous_artifact: Option<SearchArtifact>,
+        hooks: SearchHooks,
     ) -> Self {
+        let SearchHooks {
+            cache,
+            prelude,
+            infinite,
+            debug,
+            chess960,
+        } = hooks;
+
         let searcher = Searcher::new();
-        let evaluator = Evaluator::default();
         let start_time = std::time::Instant::now();
-        let (search_handle, control, receiver) =
-            searcher.analyze(state, rng_seed, evaluator, depth, previous_artifact);
+        let searched_state = state.clone();
 
-        {
+        // Have a move ready in case the search is stopped (or fails) before it finds one
+        // of its own, so that we always answer with a move instead of losing on time
+        let emergency_move = emergency_move(&state, previous_artifact.as_ref());
+
+        // Searches limited to a number of nodes search all of them, however long that
+        // takes, unless they've been given a time to stick to as well
+        let max_nodes = options.max_nodes;
+        let max_search_time = match (max_nodes, search_time) {
+            _ if infinite => None,
+            (Some(..), None) => None,
+            (_, search_time) => Some(search_time.unwrap_or(DEFAULT_MAX_SEARCH_TIME)),
+        };
+
+        let mut watchdog = Watchdog::new(
+            max_search_time.map(Duration::from_secs_f64),
+            options
+                .progress_interval
+                .unwrap_or(searcher::DEFAULT_PROGRESS_INTERVAL),
+            start_time,
+        );
+
+        // GUIs expect every line to be numbered once they've asked for more than one
+        let is_multi_pv = options.multi_pv.is_some_and(|count| count > 1);
+        let format_rank = move |rank: usize| match is_multi_pv {
+            true => format!(" multipv {}", rank),
+            false => String::new(),
+        };
+
+        let (control, controller) = mpsc::channel();
+
+        if let (Some(max_nodes), true) = (max_nodes, debug) {
+            println!("info string node limit {}", max_nodes);
+        }
+
+        let timer = max_search_time.map(|max_search_time| {
             // Start a timer to stop the search after a certain amount of time
             let timer_stop = control.clone();
-            let max_search_time = search_time.unwrap_or(DEFAULT_MAX_SEARCH_TIME);
-            _ = thread::spawn(move || loop {
-                if start_time.elapsed().as_secs_f64() >= max_search_time {
-                    _ = timer_stop.send(searcher::ControlEvent::Stop);
-                    break;
-                }
+            if debug {
+                println!(
+                    "info string time limit {:.0}ms{}",
+                    max_search_time * 1000.0,
+                    if search_time.is_none() {
+                        " (default)"
+                    } else {
+                        ""
+                    }
+                );
+            }
 
-                thread::sleep(std::time::Duration::from_millis(100));
+            // The writer hangs up once the search is over, which wakes the timer early
+            let (timer_cancel, timer_cancelled) = mpsc::channel::<()>();
+            let deadline = Duration::from_secs_f64(max_search_time);
+            let timer_handle = thread::spawn(move || {
+                let remaining = deadline.saturating_sub(start_time.elapsed());
+                if let Err(mpsc::RecvTimeoutError::Timeout) =
+                    timer_cancelled.recv_timeout(remaining)
+                {
+                    if debug {
+                        println!(
+                            "info string time is up after {}ms",
+                            start_time.elapsed().as_millis()
+                        );
+                    }
+
+                    _ = timer_stop.send(searcher::ControlEvent::TimeUp);
+                }
             });
-        }
 
+            (timer_cancel, timer_handle)
+        });
+
+        let watchdog_stop = control.clone();
+        let search_control = control.clone();
         let write_handle = thread::spawn(move || {
-            let mut best_line: Vec<Move> = vec![];
-            while let Ok(event) = receiver.recv() {
+            // Stop the timer before the best move goes out, so that nothing it
+            // prints can come after it
+            let stop_timer = move || {
+                if let Some((timer_cancel, timer_handle)) = timer {
+                    drop(timer_cancel);
+                    _ = timer_handle.join();
+                }
+            };
+
+            let mut stop_event = None;
+            let prelude_move = prelude.and_then(|prelude| {
+                prelude(&mut || {
+                    if stop_event.is_none() {
+                        stop_event = controller.try_recv().ok();
+                    }
+
+                    stop_event.is_some()
+                })
+            });
+
+            if prelude_move.is_some() || stop_event.is_some() {
+                stop_timer();
+                match prelude_move.or(emergency_move) {
+                    Some(m) => println!("bestmove {}", format_moves(&[m], chess960)),
+                    None => println!("bestmove 0000"),
+                }
+
+                return previous_artifact;
+            }
+
+            let (search_handle, receiver) = searcher.analyze_with_control(
+                state,
+                rng_seed,
+                evaluator,
+                options,
+                previous_artifact,
+                (search_control, controller),
+            );
+
+            let mut best_line = Line::new();
+            let mut best_evaluation = Evaluation::EVEN;
+            let mut completed: Option<CachedAnalysis> = None;
+            let mut is_abandoned = false;
+            loop {
+                // Waking up regularly, even without any events, lets the watchdog notice
+                // a search that's stuck without sending anything
+                match watchdog.check(Instant::now()) {
+                    WatchdogAction::None => {}
+                    WatchdogAction::Cancel(reason) => {
+                        println!("info string watchdog: {}, cancelling the search", reason);
+                        _ = watchdog_stop.send(searcher::ControlEvent::Stop);
+                    }
+                    WatchdogAction::Abandon => {
+                        println!("info string watchdog: the search didn't stop, abandoning it");
+                        is_abandoned = true;
+                        break;
+                    }
+                }
+
+                let event = match receiver.recv_timeout(WATCHDOG_POLL_INTERVAL) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+
+                watchdog.observe(&event, Instant::now());
                 match event {
-                    searcher::StatusEvent::BestMove { line, evaluation } => {
-                        println!("info score cp {}", evaluation.cp());
-                        println!("info pv {}", into_notation::<_, Lan>(&&line[..]));
-                        best_line = line;
+                    searcher::StatusEvent::PvUpdate {
+                        depth,
+                        line,
+                        evaluation,
+                    } => {
+                        println!(
+                            "info depth {}{} score {} pv {}",
+                            depth,
+                            format_rank(1),
+                            format_score(evaluation),
+                            format_moves(&line, chess960)
+                        );
+                        best_line = *line;
+                        best_evaluation = evaluation;
                     }
-                    searcher::StatusEvent::Progress {
+                    searcher::StatusEvent::MultiPvUpdate {
                         depth,
-                        nodes_searched,
-                        ..
+                        rank,
+                        line,
+                        evaluation,
                     } => {
-                        let elapsed = start_time.elapsed().as_secs_f64();
-                        let nps = if elapsed > 0.0 {
-                            nodes_searched as f64 / elapsed
-                        } else {
-                            0.0
-                        };
-
                         println!(
-                            "info time {:.0} depth {} nps {:.0} nodes {}",
-                            elapsed * 1000f64,
+                            "info depth {}{} score {} pv {}",
                             depth,
-                            nps,
-                            nodes_searched
+                            format_rank(rank),
+                            format_score(evaluation),
+                            format_moves(&line, chess960)
                         );
                     }
+                    searcher::StatusEvent::IterationComplete { depth, stats } => {
+                        completed = best_line.first().map(|mv| CachedAnalysis {
+                            depth,
+                            evaluation: best_evaluation,
+                            best_move: *mv,
+                        });
+
+                        print_progress(
+                            start_time,
+                            depth,
+                            stats.nodes_searched,
+                            stats.transposition_saturation,
+                        );
+
+                        if debug {
+                            print_stats(depth, &stats);
+                        }
+                    }
+                    searcher::StatusEvent::Progress {
+                        depth,
+                        nodes_searched,
+                        transposition_saturation,
+                    } => {
+                        print_progress(start_time, depth, nodes_searched, transposition_saturation);
+                    }
                     searcher::StatusEvent::Warning { message, .. } => {
                         println!("info string {}", message);
                     }
+                    searcher::StatusEvent::RootMove {
+                        depth,
+                        number,
+                        mv,
+                        evaluation,
+                        kind,
+                    } => {
+                        println!(
+                            "info depth {} currmove {} currmovenumber {} score {}{}",
+                            depth,
+                            format_moves(&[mv], chess960),
+                            number,
+                            format_score(evaluation),
+                            format_bound(kind)
+                        );
+                    }
+                    searcher::StatusEvent::Snapshot { .. } => {}
                 }
             }
 
-            if let Some(m) = best_line.first() {
-                println!("bestmove {}{}{}", m.origin(), m.destination(), {
-                    if let Some(p) = m.promotion() {
-                        let c: char = p.into();
-                        String::from(c.to_ascii_lowercase())
-                    } else {
-                        String::from("")
-                    }
-                });
+            stop_timer();
+
+            // The events stop once the search is over, so this won't block for long. An
+            // abandoned search is left to finish (or not) on its own, and its tables with it.
+            let (artifact, result) = match is_abandoned {
+                true => (None, None),
+                false => match search_handle.join() {
+                    Ok((artifact, result)) => (Some(artifact), Some(result)),
+                    Err(..) => (None, None),
+                },
+            };
+
+            if let Some(result) = &result {
+                println!(
+                    "info string search ended ({}) at depth {} after {} nodes",
+                    result.reason, result.depth, result.stats.nodes_searched
+                );
+            }
+
+            match result
+                .and_then(|result| result.best_move())
+                .or(best_line.first().copied())
+                .or(emergency_move)
+            {
+                Some(m) => println!("bestmove {}", format_moves(&[m], chess960)),
+                None => println!("bestmove 0000"),
             }
 
-            ()
+            // Only fully searched depths are worth remembering
+            if let (Some((cache, history)), Some(analysis)) = (cache, completed) {
+                let result = match cache.lock() {
+                    Ok(mut cache) => cache.store(&searched_state, &history, analysis),
+                    Err(..) => Ok(false),
+                };
+
+                if let Err(e) = result {
+                    println!("info string {}", e);
+                }
+            }
+
+            artifact
         });
 
         Self {
             start_time,
-            search_handle,
             write_handle,
             control,
         }
     }
 
-    pub fn wait_cancel(self) -> SearchArtifact {
+    /// Whether the search is over and its best move has been sent
+    pub fn is_finished(&self) -> bool {
+        self.write_handle.is_finished()
+    }
+
+    /// Stops the search and waits for the best move to be written out. There's no
+    /// artifact to return if the search failed, or had to be abandoned.
+    pub fn wait_cancel(self) -> Option<SearchArtifact> {
         _ = self.control.send(searcher::ControlEvent::Stop);
-        let artifact = self.search_handle.join().unwrap();
-        self.write_handle.join().unwrap();
-        artifact
+        self.write_handle.join().unwrap()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum WatchdogAction {
+    None,
+    /// Ask the search to stop, for the given reason
+    Cancel(String),
+    /// Stop waiting for a search that didn't stop when it was asked to. Threads can't be
+    /// killed, so its threads are left running (and holding on to their tables) until they
+    /// notice they've been cancelled, if they ever do. Its results are dropped when they
+    /// come, and the next search starts over with a new artifact.
+    Abandon,
+}
+
+/// Keeps an eye on a search for the writer thread, so that a search that's gone well past
+/// its deadline or stopped searching nodes (like when a thread is deadlocked) gets cancelled,
+/// and given up on if that doesn't work either, instead of leaving the GUI waiting forever
+struct Watchdog {
+    start_time: Instant,
+    deadline: Option<Duration>,
+    stall_time: Duration,
+    last_progress: Instant,
+    nodes_searched: usize,
+    cancelled_at: Option<Instant>,
+}
+
+impl Watchdog {
+    fn new(deadline: Option<Duration>, progress_interval: Duration, now: Instant) -> Self {
+        Self {
+            start_time: now,
+            deadline,
+            stall_time: WATCHDOG_STALL_TIME.max(progress_interval * WATCHDOG_STALL_INTERVALS),
+            last_progress: now,
+            nodes_searched: 0,
+            cancelled_at: None,
+        }
+    }
+
+    /// Progress reports keep coming while the threads are stuck, so only the ones
+    /// with more nodes than before count as progress
+    fn observe(&mut self, event: &searcher::StatusEvent, now: Instant) {
+        let nodes_searched = match event {
+            searcher::StatusEvent::Progress { nodes_searched, .. } => *nodes_searched,
+            searcher::StatusEvent::IterationComplete { stats, .. } => stats.nodes_searched,
+            _ => {
+                self.last_progress = now;
+                return;
+            }
+        };
+
+        if nodes_searched > self.nodes_searched {
+            self.nodes_searched = nodes_searched;
+            self.last_progress = now;
+        }
+    }
+
+    fn check(&mut self, now: Instant) -> WatchdogAction {
+        if let Some(cancelled_at) = self.cancelled_at {
+            return match now.duration_since(cancelled_at) >= WATCHDOG_GRACE_TIME {
+                true => WatchdogAction::Abandon,
+                false => WatchdogAction::None,
+            };
+        }
+
+        let elapsed = now.duration_since(self.start_time);
+        let overrun = self
+            .deadline
+            .map(|deadline| (deadline, elapsed.saturating_sub(deadline)))
+            .filter(|(deadline, overrun)| *overrun >= WATCHDOG_MIN_OVERRUN.max(*deadline));
+
+        let reason = if let Some((_, overrun)) = overrun {
+            format!("the search is {}ms past its deadline", overrun.as_millis())
+        } else if now.duration_since(self.last_progress) >= self.stall_time {
+            format!(
+                "no nodes searched in the last {}ms",
+                now.duration_since(self.last_progress).as_millis()
+            )
+        } else {
+            return WatchdogAction::None;
+        };
+
+        self.cancelled_at = Some(now);
+        WatchdogAction::Cancel(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_setoption() {
+        let parse = |command: &str| {
+            let args: Vec<&str> = command.split_ascii_whitespace().collect();
+            parse_setoption(&args)
+        };
+
+        let option = |name: &str, value: &str| Some((name.to_string(), value.to_string()));
+
+        assert_eq!(parse("name Threads value 4"), option("Threads", "4"));
+        assert_eq!(
+            parse("name BookProfile value my  club   games"),
+            option("BookProfile", "my club games")
+        );
+        assert_eq!(parse("name Clear Hash"), option("Clear Hash", ""));
+        assert_eq!(parse("name Some Option value"), option("Some Option", ""));
+        assert_eq!(parse("name value 4"), None);
+        assert_eq!(parse("Threads value 4"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_emergency_move() {
+        let state = try_from_notation::<_, Fen>("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap();
+        let mv = emergency_move(&state, None).unwrap();
+        assert!(state.is_legal(&mv));
+
+        // An earlier search of the position knows better than the first legal move
+        let (handle, _, _) = Searcher::new().analyze(
+            state.clone(),
+            0,
+            Evaluator::default(),
+            SearchOptions {
+                max_depth: Some(3),
+                max_threads: Some(1),
+                max_table_size_mb: Some(4),
+                ..Default::default()
+            },
+            None,
+        );
+
+        let (artifact, result) = handle.join().unwrap();
+        assert_eq!(emergency_move(&state, Some(&artifact)), result.best_move());
+
+        // ...but not about other positions
+        let other = try_from_notation::<_, Fen>("8/8/8/4k3/8/8/4K3/3Q4 w - - 0 1").unwrap();
+        let mv = emergency_move(&other, Some(&artifact)).unwrap();
+        assert!(other.is_legal(&mv));
+
+        let mated = try_from_notation::<_, Fen>("k6R/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(emergency_move(&mated, Some(&artifact)), None);
+    }
+
+    #[test]
+    fn test_go_nodestime() {
+        let parse = |command: &str| {
+            let args: Vec<&str> = command.split_ascii_whitespace().collect();
+            GoLimits::parse(&args)
+        };
+
+        // A clock with barely any time on it would stop the search almost at once...
+        let limits = parse("wtime 300 btime 60000 winc 0 binc 1000");
+        assert_eq!(limits.budget(Color::White, 0), (None, Some(0.01)));
+        assert_eq!(limits.budget(Color::Black, 0), (None, Some(3.0)));
+
+        // ...but with nodestime its time is searched as nodes, without a timer
+        let (max_nodes, search_time) = limits.budget(Color::White, 1000);
+        assert_eq!((max_nodes, search_time), (Some(10_000), None));
+
+        let state = State::default();
+        let (handle, _, _) = Searcher::new().analyze(
+            state,
+            0,
+            Evaluator::default(),
+            SearchOptions {
+                max_nodes,
+                max_threads: Some(1),
+                max_table_size_mb: Some(4),
+                ..Default::default()
+            },
+            None,
+        );
+
+        let (_, result) = handle.join().unwrap();
+        assert_eq!(result.reason, searcher::StopReason::NodeLimit);
+
+        // An explicit movetime doesn't bring the timer back either
+        let limits = parse("movetime 100 wtime 60000");
+        assert_eq!(limits.budget(Color::White, 500), (Some(50_000), None));
+        assert_eq!(limits.budget(Color::White, 0), (None, Some(0.1)));
+
+        // Infinite searches don't get a budget of either kind
+        let limits = parse("infinite wtime 300");
+        assert!(limits.infinite);
+        assert_eq!(limits.budget(Color::White, 1000), (None, None));
+    }
+
+    #[test]
+    fn test_cache_settings() {
+        let path = std::env::temp_dir().join(format!("weechess-uci-{}.bin", std::process::id()));
+        _ = std::fs::remove_file(&path);
+
+        let state = State::default();
+        let evaluator = Evaluator::default();
+        let client = |handicap| {
+            Client::with_book(OpeningBook::empty())
+                .with_handicap(handicap)
+                .with_analysis_cache(AnalysisCache::open(&path, 1).unwrap())
+        };
+
+        // Searches the starting position the way `go depth 3` would, returning
+        // what the cache has on it afterwards
+        let search = |client: &Client| {
+            let options = SearchOptions {
+                max_depth: Some(3),
+                handicap: client.handicap,
+                max_threads: Some(1),
+                max_table_size_mb: Some(4),
+                ..Default::default()
+            };
+
+            let cache = client.cache_for(&state, &evaluator, &options).cloned();
+            let search = Search::spawn(
+                state.clone(),
+                evaluator.clone(),
+                0,
+                options,
+                None,
+                None,
+                SearchHooks {
+                    cache: cache.map(|cache| (cache, vec![])),
+                    prelude: None,
+                    infinite: false,
+                    debug: false,
+                    chess960: false,
+                },
+            );
+
+            while !search.is_finished() {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            search.wait_cancel();
+            let cache = client.cache.as_ref().unwrap().lock().unwrap();
+            cache.probe(&state, &[])
+        };
+
+        // A handicapped search doesn't write its result to the cache...
+        let handicapped = client(Some(0.5));
+        assert_eq!(search(&handicapped), None);
+
+        // ...or read what an even search wrote there
+        let even = client(None);
+        let analysis = search(&even).unwrap();
+        assert_eq!(analysis.depth, 3);
+
+        let handicapped = client(Some(0.5));
+        let options = SearchOptions {
+            handicap: handicapped.handicap,
+            ..Default::default()
+        };
+
+        assert!(handicapped
+            .cache_for(&state, &evaluator, &options)
+            .is_none());
+        assert!(even
+            .cache_for(&state, &evaluator, &SearchOptions::default())
+            .is_some());
+
+        // Neither does a search with different weights or looking for a mate
+        let weighted = evaluator.clone().with_weight("BadPawns", 0.0);
+        assert!(even
+            .cache_for(&state, &weighted, &SearchOptions::default())
+            .is_none());
+
+        let mate = SearchOptions {
+            mate: Some(3),
+            ..Default::default()
+        };
+
+        assert!(even.cache_for(&state, &evaluator, &mate).is_none());
+
+        _ = std::fs::remove_file(&path);
+    }
+
+    fn progress(nodes_searched: usize) -> searcher::StatusEvent {
+        searcher::StatusEvent::Progress {
+            depth: 1,
+            nodes_searched,
+            transposition_saturation: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_watchdog_deadline() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut watchdog = Watchdog::new(
+            Some(Duration::from_secs(1)),
+            Duration::from_millis(100),
+            start,
+        );
+
+        // Keep the search looking busy, so that only the deadline can set it off
+        let cancel_ms = 1000 + WATCHDOG_MIN_OVERRUN.as_millis() as u64;
+        for ms in (0..cancel_ms).step_by(500) {
+            watchdog.observe(&progress(ms as usize + 1), at(ms));
+            assert_eq!(watchdog.check(at(ms)), WatchdogAction::None, "{}ms", ms);
+        }
+
+        let cancelled = at(cancel_ms);
+        assert!(matches!(
+            watchdog.check(cancelled),
+            WatchdogAction::Cancel(..)
+        ));
+
+        // The search gets a grace period to stop before it's given up on
+        assert_eq!(watchdog.check(cancelled), WatchdogAction::None);
+        assert_eq!(
+            watchdog.check(cancelled + WATCHDOG_GRACE_TIME),
+            WatchdogAction::Abandon
+        );
+    }
+
+    #[test]
+    fn test_watchdog_stall() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let stall_ms = WATCHDOG_STALL_TIME.as_millis() as u64;
+        let mut watchdog = Watchdog::new(None, Duration::from_millis(100), start);
+
+        // Reports without any new nodes don't count as progress
+        watchdog.observe(&progress(1000), at(1000));
+        watchdog.observe(&progress(1000), at(stall_ms));
+        watchdog.observe(&progress(500), at(stall_ms + 500));
+        assert_eq!(watchdog.check(at(stall_ms + 999)), WatchdogAction::None);
+        assert!(matches!(
+            watchdog.check(at(stall_ms + 1000)),
+            WatchdogAction::Cancel(..)
+        ));
+
+        // Long progress intervals get more time between reports before it's a stall
+        let interval = WATCHDOG_STALL_TIME;
+        let mut watchdog = Watchdog::new(None, interval, start);
+        assert_eq!(watchdog.check(at(stall_ms * 2)), WatchdogAction::None);
+        assert!(matches!(
+            watchdog.check(start + interval * WATCHDOG_STALL_INTERVALS),
+            WatchdogAction::Cancel(..)
+        ));
     }
 }
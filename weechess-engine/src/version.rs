@@ -3,6 +3,7 @@ pub struct EngineVersion {
     pub version: usize,
     pub name: &'static str,
     pub author: &'static str,
+    pub build: BuildInfo,
 }
 
 impl EngineVersion {
@@ -10,6 +11,10 @@ impl EngineVersion {
         version: 7,
         name: "parachi",
         author: env!("CARGO_PKG_AUTHORS"),
+        build: BuildInfo {
+            commit: option_env!("WEECHESS_GIT_COMMIT"),
+            profile: env!("WEECHESS_BUILD_PROFILE"),
+        },
     };
 }
 
@@ -18,3 +23,100 @@ impl std::fmt::Display for EngineVersion {
         write!(f, "weechess.{:03}-{}", self.version, self.name)
     }
 }
+
+/// Where a particular binary of the engine came from, to tell builds of the same version apart
+pub struct BuildInfo {
+    /// The git commit the engine was built from, if it was built from a git checkout
+    pub commit: Option<&'static str>,
+
+    /// The cargo profile the engine was built with (ex. `release`)
+    pub profile: &'static str,
+}
+
+impl BuildInfo {
+    /// The optional parts of the engine that made it into this build
+    pub fn features(&self) -> Vec<&'static str> {
+        let mut features = vec![];
+        if cfg!(feature = "embedded-book") {
+            features.push("embedded-book");
+        }
+
+        if cfg!(feature = "runtime-book") {
+            features.push("runtime-book");
+        }
+
+        if cfg!(feature = "search-observer") {
+            features.push("search-observer");
+        }
+
+        if cfg!(target_feature = "popcnt") {
+            features.push("popcnt");
+        }
+
+        if cfg!(target_feature = "bmi2") {
+            features.push("bmi2");
+        }
+
+        if cfg!(debug_assertions) {
+            features.push("debug-assertions");
+        }
+
+        features
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.commit.unwrap_or("unknown"), self.profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_version() {
+        let version = EngineVersion::CURRENT;
+        assert_eq!(
+            version.to_string(),
+            format!("weechess.{:03}-{}", version.version, version.name)
+        );
+
+        // The build script fills these in from cargo and git
+        let build = version.build;
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+
+        assert_eq!(build.profile, profile);
+        if let Some(commit) = build.commit {
+            assert!(!commit.is_empty());
+            assert!(commit.chars().all(|c| c.is_ascii_hexdigit()), "{}", commit);
+        }
+
+        assert_eq!(
+            build.features().contains(&"debug-assertions"),
+            cfg!(debug_assertions)
+        );
+    }
+
+    #[test]
+    fn test_build_info() {
+        let build = BuildInfo {
+            commit: Some("4e0ddfc"),
+            profile: "release",
+        };
+
+        assert_eq!(build.to_string(), "4e0ddfc release");
+
+        let build = BuildInfo {
+            commit: None,
+            profile: "debug",
+        };
+
+        assert_eq!(build.to_string(), "unknown debug");
+    }
+}
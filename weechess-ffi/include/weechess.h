@@ -0,0 +1,39 @@
+#ifndef WEECHESS_H
+#define WEECHESS_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define WEECHESS_OK 0
+#define WEECHESS_ERROR_NULL -1
+#define WEECHESS_ERROR_INVALID_FEN -2
+#define WEECHESS_ERROR_INVALID_COLOR -3
+#define WEECHESS_ERROR_PANIC -4
+
+#define WEECHESS_WHITE 0
+#define WEECHESS_BLACK 1
+
+typedef struct Engine WeechessEngine;
+
+WeechessEngine *weechess_engine_new(void);
+void weechess_engine_free(WeechessEngine *engine);
+
+int weechess_engine_set_position(WeechessEngine *engine, const char *fen);
+int weechess_engine_search(WeechessEngine *engine, uint32_t max_depth, uint64_t max_time_ms);
+
+/* Fills 64 counts, indexed from a1 (0) to h8 (63) */
+int weechess_engine_attack_counts(const WeechessEngine *engine, int color, uint8_t *counts);
+
+/* Returned strings must be released with weechess_string_free */
+char *weechess_engine_best_move(const WeechessEngine *engine);
+char *weechess_engine_pv(const WeechessEngine *engine);
+void weechess_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
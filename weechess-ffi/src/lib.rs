@@ -0,0 +1,390 @@
+// The `ArrayMap` bounds on core's types need this in every crate that uses them
+#![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
+
+//! A C API for embedding the engine in other programs without going
+//! through a UCI subprocess. See `include/weechess.h` for the C declarations.
+//!
+//! Every function takes the engine handle returned by `weechess_engine_new`.
+//! Strings returned by the engine are owned by the caller and need to be
+//! released with `weechess_string_free`. Panics never cross into the caller:
+//! they're reported as `WEECHESS_ERROR_PANIC` (or null) instead.
+
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use weechess_core::{
+    notation::{into_notation, lan::Lan, try_from_notation, Fen},
+    Color, Line, Square, State,
+};
+use weechess_engine::{
+    eval::Evaluator,
+    searcher::{ControlEvent, SearchArtifact, SearchOptions, Searcher},
+};
+
+pub const WEECHESS_OK: c_int = 0;
+pub const WEECHESS_ERROR_NULL: c_int = -1;
+pub const WEECHESS_ERROR_INVALID_FEN: c_int = -2;
+pub const WEECHESS_ERROR_INVALID_COLOR: c_int = -3;
+pub const WEECHESS_ERROR_PANIC: c_int = -4;
+
+pub const WEECHESS_WHITE: c_int = 0;
+pub const WEECHESS_BLACK: c_int = 1;
+
+/// Search time used when neither a depth nor a time limit is given
+const DEFAULT_SEARCH_TIME: Duration = Duration::from_secs(4);
+
+pub struct Engine {
+    state: State,
+    artifact: Option<SearchArtifact>,
+    line: Line,
+}
+
+impl Engine {
+    fn new() -> Self {
+        Self {
+            state: State::default(),
+            artifact: None,
+            line: Line::new(),
+        }
+    }
+
+    /// Returns whether the search finished, rather than panicking
+    fn search(&mut self, max_depth: Option<usize>, max_time: Option<Duration>) -> bool {
+        let max_time = match (max_depth, max_time) {
+            (None, None) => Some(DEFAULT_SEARCH_TIME),
+            (_, max_time) => max_time,
+        };
+
+        let searcher = Searcher::new();
+        let (search_handle, control, receiver) = searcher.analyze(
+            self.state.clone(),
+            rand::random(),
+            Evaluator::default(),
+            SearchOptions {
+                max_depth,
+                ..Default::default()
+            },
+            self.artifact.take(),
+        );
+
+        // The timer is cancelled if the search ends first, so that it's
+        // never left behind once the search returns
+        let timer = max_time.map(|max_time| {
+            let (timer_cancel, timer_cancelled) = mpsc::channel::<()>();
+            let control = control.clone();
+            let timer_handle = thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = timer_cancelled.recv_timeout(max_time)
+                {
+                    _ = control.send(ControlEvent::TimeUp);
+                }
+            });
+
+            (timer_cancel, timer_handle)
+        });
+
+        // Everything needed is in the result at the end
+        drop(receiver);
+
+        let joined = search_handle.join();
+        if let Some((timer_cancel, timer_handle)) = timer {
+            drop(timer_cancel);
+            _ = timer_handle.join();
+        }
+
+        match joined {
+            Ok((artifact, result)) => {
+                self.line = result.line;
+                self.artifact = Some(artifact);
+                true
+            }
+            Err(..) => false,
+        }
+    }
+}
+
+/// Runs `f`, or returns `fallback` if it panics
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+/// Creates a new engine set to the starting position
+#[no_mangle]
+pub extern "C" fn weechess_engine_new() -> *mut Engine {
+    Box::into_raw(Box::new(Engine::new()))
+}
+
+/// Destroys an engine created with `weechess_engine_new`
+///
+/// # Safety
+///
+/// `engine` must be null or a pointer returned by `weechess_engine_new`
+/// that hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_free(engine: *mut Engine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Sets the position to search from a FEN string
+///
+/// # Safety
+///
+/// `engine` must be a valid engine handle and `fen` must be null or
+/// a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_set_position(
+    engine: *mut Engine,
+    fen: *const c_char,
+) -> c_int {
+    let Some(engine) = engine.as_mut() else {
+        return WEECHESS_ERROR_NULL;
+    };
+
+    if fen.is_null() {
+        return WEECHESS_ERROR_NULL;
+    }
+
+    let Ok(fen) = CStr::from_ptr(fen).to_str() else {
+        return WEECHESS_ERROR_INVALID_FEN;
+    };
+
+    guard(WEECHESS_ERROR_PANIC, || {
+        match try_from_notation::<_, Fen>(fen) {
+            Ok(state) => {
+                engine.state = state;
+                engine.line.clear();
+                WEECHESS_OK
+            }
+            Err(..) => WEECHESS_ERROR_INVALID_FEN,
+        }
+    })
+}
+
+/// Searches the current position, blocking until the search is complete.
+/// A `max_depth` or `max_time_ms` of zero means there is no limit of that
+/// kind; if both are zero, a default time limit is used. If the search
+/// panics, there's no best move or line until the next search.
+///
+/// # Safety
+///
+/// `engine` must be a valid engine handle.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_search(
+    engine: *mut Engine,
+    max_depth: u32,
+    max_time_ms: u64,
+) -> c_int {
+    let Some(engine) = engine.as_mut() else {
+        return WEECHESS_ERROR_NULL;
+    };
+
+    let max_depth = (max_depth > 0).then_some(max_depth as usize);
+    let max_time = (max_time_ms > 0).then(|| Duration::from_millis(max_time_ms));
+    match guard(false, || engine.search(max_depth, max_time)) {
+        true => WEECHESS_OK,
+        false => {
+            engine.line.clear();
+            WEECHESS_ERROR_PANIC
+        }
+    }
+}
+
+/// Returns the best move from the last search in long algebraic notation
+/// (ex. `e2e4`), or null if there is none.
+///
+/// # Safety
+///
+/// `engine` must be a valid engine handle.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_best_move(engine: *const Engine) -> *mut c_char {
+    let Some(engine) = engine.as_ref() else {
+        return ptr::null_mut();
+    };
+
+    guard(ptr::null_mut(), || match engine.line.first() {
+        Some(mv) => into_c_string(into_notation::<_, Lan>(mv).to_string()),
+        None => ptr::null_mut(),
+    })
+}
+
+/// Returns the principal variation from the last search as space-separated
+/// moves in long algebraic notation, or null if there is none.
+///
+/// # Safety
+///
+/// `engine` must be a valid engine handle.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_pv(engine: *const Engine) -> *mut c_char {
+    let Some(engine) = engine.as_ref() else {
+        return ptr::null_mut();
+    };
+
+    if engine.line.is_empty() {
+        return ptr::null_mut();
+    }
+
+    guard(ptr::null_mut(), || {
+        into_c_string(into_notation::<_, Lan>(&&engine.line[..]).to_string())
+    })
+}
+
+/// Fills `counts` with the number of pieces of the given color attacking (or
+/// defending) each square of the current position, indexed from a1 (0) to h8 (63)
+///
+/// # Safety
+///
+/// `engine` must be a valid engine handle and `counts` must be null or
+/// point to space for 64 values.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_engine_attack_counts(
+    engine: *const Engine,
+    color: c_int,
+    counts: *mut u8,
+) -> c_int {
+    let Some(engine) = engine.as_ref() else {
+        return WEECHESS_ERROR_NULL;
+    };
+
+    if counts.is_null() {
+        return WEECHESS_ERROR_NULL;
+    }
+
+    let color = match color {
+        WEECHESS_WHITE => Color::White,
+        WEECHESS_BLACK => Color::Black,
+        _ => return WEECHESS_ERROR_INVALID_COLOR,
+    };
+
+    let counts = std::slice::from_raw_parts_mut(counts, Square::ALL.len());
+    guard(WEECHESS_ERROR_PANIC, || {
+        let map = engine.state.board().attack_count_map(color);
+        for square in Square::ALL {
+            counts[square.index()] = map[*square];
+        }
+
+        WEECHESS_OK
+    })
+}
+
+/// Releases a string returned by the engine
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by one of the engine functions
+/// that hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn weechess_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    CString::new(s)
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        unsafe {
+            let engine = weechess_engine_new();
+
+            let fen = CString::new("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+            assert_eq!(
+                weechess_engine_set_position(engine, fen.as_ptr()),
+                WEECHESS_OK
+            );
+
+            assert_eq!(weechess_engine_search(engine, 3, 0), WEECHESS_OK);
+
+            let best_move = weechess_engine_best_move(engine);
+            assert_eq!(CStr::from_ptr(best_move).to_str().unwrap(), "h1h8");
+            weechess_string_free(best_move);
+
+            let pv = weechess_engine_pv(engine);
+            assert!(CStr::from_ptr(pv).to_str().unwrap().starts_with("h1h8"));
+            weechess_string_free(pv);
+
+            weechess_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn test_search_limits() {
+        unsafe {
+            let engine = weechess_engine_new();
+
+            // The timer doesn't hold up a search that's done before its time is up
+            let start_time = std::time::Instant::now();
+            assert_eq!(weechess_engine_search(engine, 1, 60_000), WEECHESS_OK);
+            assert!(start_time.elapsed() < Duration::from_secs(30));
+
+            // A timed search still comes up with a move
+            assert_eq!(weechess_engine_search(engine, 0, 200), WEECHESS_OK);
+            let best_move = weechess_engine_best_move(engine);
+            assert!(!best_move.is_null());
+            weechess_string_free(best_move);
+
+            weechess_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(guard(WEECHESS_ERROR_PANIC, || WEECHESS_OK), WEECHESS_OK);
+        assert_eq!(
+            guard(WEECHESS_ERROR_PANIC, || -> c_int { panic!("oops") }),
+            WEECHESS_ERROR_PANIC
+        );
+    }
+
+    #[test]
+    fn test_attack_counts() {
+        unsafe {
+            let engine = weechess_engine_new();
+            let mut counts = [0u8; 64];
+            assert_eq!(
+                weechess_engine_attack_counts(engine, WEECHESS_WHITE, counts.as_mut_ptr()),
+                WEECHESS_OK
+            );
+
+            // The b1 knight, c1 bishop, d1 queen and e1 king all cover d2 in the starting position
+            assert_eq!(counts[Square::D2.index()], 4);
+            assert_eq!(counts[Square::E4.index()], 0);
+            assert_eq!(
+                weechess_engine_attack_counts(engine, 2, counts.as_mut_ptr()),
+                WEECHESS_ERROR_INVALID_COLOR
+            );
+
+            weechess_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn test_invalid_fen() {
+        unsafe {
+            let engine = weechess_engine_new();
+            let fen = CString::new("not a fen").unwrap();
+            assert_eq!(
+                weechess_engine_set_position(engine, fen.as_ptr()),
+                WEECHESS_ERROR_INVALID_FEN
+            );
+
+            assert!(weechess_engine_best_move(engine).is_null());
+            weechess_engine_free(engine);
+        }
+    }
+}